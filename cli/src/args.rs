@@ -0,0 +1,73 @@
+//! Parsers for the argument formats clap does not know: public keys, wallet
+//! lists and the account metas of `add-ix`.
+
+use solana_sdk::{instruction::AccountMeta, pubkey::Pubkey};
+
+use crate::error::CliError;
+
+pub fn parse_pubkey(arg: &str) -> Result<Pubkey, String> {
+    arg.parse().map_err(|_| format!("`{arg}` is not a base58 public key"))
+}
+
+/// A comma-separated list of wallet indexes, e.g. `0,1,2`
+pub fn parse_wallets(arg: &str) -> Result<Vec<u8>, String> {
+    arg.split(',')
+        .map(|index| index.trim().parse().map_err(|_| format!("`{index}` is not a wallet index (0-255)")))
+        .collect()
+}
+
+/// One account of a stored instruction, `PUBKEY[:w][:s]`: writable with `w`,
+/// a signer with `s`
+pub fn parse_account_meta(arg: &str) -> Result<AccountMeta, String> {
+    let mut parts = arg.split(':');
+    let pubkey = parse_pubkey(parts.next().unwrap_or_default())?;
+    let (mut is_writable, mut is_signer) = (false, false);
+    for flag in parts {
+        match flag {
+            "w" => is_writable = true,
+            "s" => is_signer = true,
+            _ => return Err(format!("unknown account flag `{flag}` in `{arg}`, expected `w` or `s`")),
+        }
+    }
+    Ok(AccountMeta { pubkey, is_signer, is_writable })
+}
+
+/// Pairs each of `wallets` with its instruction count: one count for every
+/// wallet, or a count per wallet in the same order
+pub fn instructions_per_wallet(wallets: &[u8], counts: &[u8]) -> Result<Vec<(u8, u8)>, CliError> {
+    match counts {
+        [count] => Ok(wallets.iter().map(|&wallet| (wallet, *count)).collect()),
+        _ if counts.len() == wallets.len() => Ok(wallets.iter().copied().zip(counts.iter().copied()).collect()),
+        _ => Err(CliError::Usage(format!(
+            "--instructions takes one count or one per wallet, got {} for {} wallets",
+            counts.len(),
+            wallets.len(),
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wallet_lists_and_instruction_counts_pair_up() {
+        assert_eq!(parse_wallets("0,1, 2").unwrap(), vec![0, 1, 2]);
+        assert!(parse_wallets("0,256").is_err());
+        assert!(parse_wallets("").is_err());
+
+        assert_eq!(instructions_per_wallet(&[0, 1, 2], &[2]).unwrap(), vec![(0, 2), (1, 2), (2, 2)]);
+        assert_eq!(instructions_per_wallet(&[4, 7], &[1, 3]).unwrap(), vec![(4, 1), (7, 3)]);
+        assert!(instructions_per_wallet(&[0, 1, 2], &[1, 1]).is_err());
+    }
+
+    #[test]
+    fn account_metas_carry_their_flags() {
+        let key = Pubkey::new_unique();
+        assert_eq!(parse_account_meta(&key.to_string()).unwrap(), AccountMeta::new_readonly(key, false));
+        assert_eq!(parse_account_meta(&format!("{key}:w")).unwrap(), AccountMeta::new(key, false));
+        assert_eq!(parse_account_meta(&format!("{key}:s:w")).unwrap(), AccountMeta::new(key, true));
+        assert!(parse_account_meta(&format!("{key}:x")).is_err());
+        assert!(parse_account_meta("not-a-key:w").is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! The Solana CLI's config file, for the RPC url and keypair operators
+//! already use with `solana`.
+//!
+//! `solana config set` writes `~/.config/solana/cli/config.yml`, a flat YAML
+//! map. Only `json_rpc_url`, `keypair_path` and `commitment` are read, so the
+//! file is parsed line by line rather than as full YAML.
+
+use std::{fs, io, path::PathBuf};
+
+/// Where `solana` keeps its config unless told otherwise
+pub fn default_config_path() -> Option<PathBuf> {
+    home_dir().map(|home| home.join(".config/solana/cli/config.yml"))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(PathBuf::from)
+}
+
+/// The settings the CLI takes from the Solana config, with its defaults for
+/// any the file leaves out
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CliConfig {
+    pub json_rpc_url: String,
+    pub keypair_path: PathBuf,
+    pub commitment: String,
+}
+
+impl Default for CliConfig {
+    fn default() -> Self {
+        Self {
+            json_rpc_url: "https://api.mainnet-beta.solana.com".to_string(),
+            keypair_path: home_dir().unwrap_or_default().join(".config/solana/id.json"),
+            commitment: "confirmed".to_string(),
+        }
+    }
+}
+
+impl CliConfig {
+    /// Reads the config at `path`. A missing file gives the defaults, as it
+    /// does for `solana` itself.
+    pub fn load(path: &PathBuf) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Reads the settings out of a config file's contents, ignoring
+    /// everything else in it
+    pub fn parse(contents: &str) -> Self {
+        let mut config = Self::default();
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            // Values may be quoted, as `solana config set` writes them
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            if value.is_empty() {
+                continue;
+            }
+            match key.trim() {
+                "json_rpc_url" => config.json_rpc_url = value.to_string(),
+                "keypair_path" => config.keypair_path = PathBuf::from(value),
+                "commitment" => config.commitment = value.to_string(),
+                _ => {},
+            }
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_read_from_the_solana_config() {
+        let contents = "---\n\
+            json_rpc_url: \"https://api.devnet.solana.com\"\n\
+            websocket_url: \"\"\n\
+            keypair_path: /home/operator/.config/solana/bot.json\n\
+            address_labels:\n  \"11111111111111111111111111111111\": System Program\n";
+        let config = CliConfig::parse(contents);
+        assert_eq!(config.json_rpc_url, "https://api.devnet.solana.com");
+        assert_eq!(config.keypair_path, PathBuf::from("/home/operator/.config/solana/bot.json"));
+        // Left out, so the default
+        assert_eq!(config.commitment, "confirmed");
+    }
+}
@@ -0,0 +1,33 @@
+use std::{io, path::PathBuf};
+
+use solana_client::client_error::ClientError;
+use solana_sdk::program_error::ProgramError;
+use thiserror::Error;
+
+use bundle_manager_client::FetchError;
+
+/// Why a command did not go through
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("Could not read {path}: {error}")]
+    Read { path: PathBuf, error: io::Error },
+    #[error("Could not read keypair {path}: {error}")]
+    Keypair { path: PathBuf, error: String },
+    #[error("Invalid commitment `{0}` in the Solana config")]
+    Commitment(String),
+    #[error("{0}")]
+    Usage(String),
+    /// The RPC request itself failed. Boxed: `ClientError` is large.
+    #[error("RPC request failed: {0}")]
+    Rpc(Box<ClientError>),
+    #[error(transparent)]
+    Fetch(#[from] FetchError),
+    #[error("Program error: {0}")]
+    Program(#[from] ProgramError),
+}
+
+impl From<ClientError> for CliError {
+    fn from(err: ClientError) -> Self {
+        CliError::Rpc(Box::new(err))
+    }
+}
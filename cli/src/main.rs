@@ -0,0 +1,490 @@
+//! `bundle-bots`, the operator's command line for bundle managers.
+//!
+//! Every command acts on the manager of the keypair in the Solana CLI config
+//! (`solana config set --keypair`) at `--manager-index`, against the config's
+//! RPC url, and sends its transaction signed by that keypair alone. Bundles
+//! are created and executed by the same keypair, so a manager with approval
+//! `authorities`, or records that need a keypair wallet to sign, are out of
+//! its reach; build those transactions with the client SDK instead.
+//!
+//! ```text
+//! bundle-bots init-manager
+//! bundle-bots create-bundle --wallets 0,1,2 --instructions 1
+//! bundle-bots add-ix --bundle <BUNDLE> --wallet 0 --program <PROGRAM> --data-file ix.bin --account <KEY>:w
+//! bundle-bots execute --bundle <BUNDLE>
+//! bundle-bots stats
+//! bundle-bots sweep --wallets 0,1,2 --mint <MINT>
+//! ```
+
+mod args;
+mod config;
+mod error;
+
+use std::{fs, path::PathBuf, process::exit};
+
+use clap::{value_parser, Arg, ArgMatches, Command};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+
+use bundle_manager_client::{
+    bundle_manager::{
+        BundleInstructionRecord, BundleManager, CreateIfMissing, InstructionOptions, ManagerConfig,
+        ASSOCIATED_TOKEN_PROGRAM_ID,
+    },
+    add_instruction, create_bundle, execute_bundle, initialize, pda, rpc, set_manager_status, sweep_wallets,
+    with_compute_budget,
+};
+
+use crate::{
+    args::{instructions_per_wallet, parse_account_meta, parse_pubkey, parse_wallets},
+    config::{default_config_path, CliConfig},
+    error::CliError,
+};
+
+fn main() {
+    if let Err(err) = run(&cli().get_matches()) {
+        eprintln!("Error: {err}");
+        exit(1);
+    }
+}
+
+fn cli() -> Command<'static> {
+    let bundle = || {
+        Arg::new("bundle").long("bundle").value_name("BUNDLE").required(true).value_parser(parse_pubkey)
+            .help("The bundle account")
+    };
+    Command::new("bundle-bots")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Operates bundle managers: create, fill, execute and sweep bundles")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("config").long("config").short('C').global(true).value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("Solana CLI config to read the RPC url and keypair from [default: the solana CLI's]"),
+        )
+        .arg(
+            Arg::new("url").long("url").short('u').global(true).value_name("URL")
+                .help("RPC url, overriding the config's json_rpc_url"),
+        )
+        .arg(
+            Arg::new("keypair").long("keypair").short('k').global(true).value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("Manager authority keypair, overriding the config's keypair_path"),
+        )
+        .arg(
+            Arg::new("manager-index").long("manager-index").global(true).value_name("INDEX").default_value("0")
+                .value_parser(value_parser!(u16))
+                .help("Which of the authority's managers to operate"),
+        )
+        .subcommand(Command::new("init-manager").about("Create the manager with the default config"))
+        .subcommand(
+            Command::new("create-bundle")
+                .about("Create a bundle over some of the manager's wallets and print its address")
+                .arg(
+                    Arg::new("wallets").long("wallets").value_name("INDEXES").required(true)
+                        .value_parser(parse_wallets)
+                        .help("Comma-separated wallet indexes, e.g. 0,1,2"),
+                )
+                .arg(
+                    Arg::new("instructions").long("instructions").value_name("COUNTS").default_value("1")
+                        .value_parser(parse_wallets)
+                        .help("Instructions each wallet runs: one count for all, or one per wallet"),
+                )
+                .arg(
+                    Arg::new("category").long("category").value_name("CATEGORY").default_value("0")
+                        .value_parser(value_parser!(u8)),
+                )
+                .arg(
+                    Arg::new("nonce").long("nonce").value_name("NONCE").value_parser(value_parser!(u64))
+                        .help("Client nonce of the bundle, to retry a create [default: the manager's bundle_seed]"),
+                ),
+        )
+        .subcommand(
+            Command::new("add-ix")
+                .about("Add an instruction to a bundle, run by one of its wallets")
+                .arg(bundle())
+                .arg(
+                    Arg::new("wallet").long("wallet").value_name("INDEX").required(true)
+                        .value_parser(value_parser!(u8)),
+                )
+                .arg(
+                    Arg::new("program").long("program").value_name("PROGRAM").required(true)
+                        .value_parser(parse_pubkey),
+                )
+                .arg(
+                    Arg::new("data-file").long("data-file").value_name("PATH").required(true)
+                        .value_parser(value_parser!(PathBuf))
+                        .help("File holding the raw instruction data"),
+                )
+                .arg(
+                    Arg::new("account").long("account").value_name("PUBKEY[:w][:s]").multiple_occurrences(true)
+                        .value_parser(parse_account_meta)
+                        .help("An account of the instruction, in order; `w` marks it writable, `s` a signer"),
+                )
+                .arg(Arg::new("compress").long("compress").help("Store the data run-length encoded if smaller")),
+        )
+        .subcommand(
+            Command::new("execute")
+                .about("Execute a bundle's instructions")
+                .arg(bundle())
+                .arg(
+                    Arg::new("max-compute-units").long("max-compute-units").value_name("UNITS")
+                        .default_value("200000").value_parser(value_parser!(u32)),
+                )
+                .arg(
+                    Arg::new("recent-fee").long("recent-fee").value_name("MICRO_LAMPORTS").default_value("0")
+                        .value_parser(value_parser!(u64))
+                        .help("Recent priority fee per compute unit the price is derived from"),
+                )
+                .arg(Arg::new("log").long("log").help("Append the execution to the manager's execution log")),
+        )
+        .subcommand(Command::new("pause").about("Pause the manager"))
+        .subcommand(Command::new("resume").about("Resume a paused manager"))
+        .subcommand(Command::new("stats").about("Print the manager's running totals"))
+        .subcommand(
+            Command::new("sweep")
+                .about("Move what the manager's wallets hold to a treasury")
+                .arg(
+                    Arg::new("wallets").long("wallets").value_name("INDEXES").required(true)
+                        .value_parser(parse_wallets),
+                )
+                .arg(
+                    Arg::new("treasury").long("treasury").value_name("TREASURY").value_parser(parse_pubkey)
+                        .help("Receives the funds [default: the manager authority]"),
+                )
+                .arg(
+                    Arg::new("mint").long("mint").value_name("MINT").multiple_occurrences(true)
+                        .value_parser(parse_pubkey)
+                        .help("A token to sweep as well, from the wallets' associated token accounts"),
+                ),
+        )
+}
+
+/// What every command runs against
+struct Context {
+    rpc: RpcClient,
+    authority: Keypair,
+    manager_index: u16,
+    manager: Pubkey,
+}
+
+impl Context {
+    fn new(matches: &ArgMatches) -> Result<Self, CliError> {
+        let config = match matches.get_one::<PathBuf>("config").cloned().or_else(default_config_path) {
+            Some(path) => CliConfig::load(&path).map_err(|error| CliError::Read { path, error })?,
+            None => CliConfig::default(),
+        };
+        let url = matches.get_one::<String>("url").cloned().unwrap_or(config.json_rpc_url);
+        let commitment =
+            config.commitment.parse::<CommitmentConfig>().map_err(|_| CliError::Commitment(config.commitment))?;
+        let keypair_path = matches.get_one::<PathBuf>("keypair").cloned().unwrap_or(config.keypair_path);
+        let authority = read_keypair_file(&keypair_path)
+            .map_err(|error| CliError::Keypair { path: keypair_path, error: error.to_string() })?;
+
+        let manager_index = *matches.get_one::<u16>("manager-index").expect("defaulted");
+        Ok(Self {
+            rpc: RpcClient::new_with_commitment(url, commitment),
+            manager: pda::manager_address(&authority.pubkey(), manager_index),
+            authority,
+            manager_index,
+        })
+    }
+
+    fn send(&self, instructions: &[Instruction]) -> Result<Signature, CliError> {
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.authority.pubkey()),
+            &[&self.authority],
+            blockhash,
+        );
+        let signature = self.rpc.send_and_confirm_transaction(&transaction)?;
+        println!("Signature: {signature}");
+        Ok(signature)
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<(), CliError> {
+    let (command, args) = matches.subcommand().expect("a subcommand is required");
+    let context = Context::new(matches)?;
+    match command {
+        "init-manager" => init_manager(&context),
+        "create-bundle" => run_create_bundle(&context, args),
+        "add-ix" => add_ix(&context, args),
+        "execute" => execute(&context, args),
+        "pause" => set_paused(&context, true),
+        "resume" => set_paused(&context, false),
+        "stats" => stats(&context),
+        "sweep" => sweep(&context, args),
+        _ => unreachable!("clap only accepts the subcommands above"),
+    }
+}
+
+fn init_manager(context: &Context) -> Result<(), CliError> {
+    let authority = context.authority.pubkey();
+    context.send(&[initialize(&authority, context.manager_index, ManagerConfig::default())])?;
+    println!("Manager: {}", context.manager);
+    Ok(())
+}
+
+fn run_create_bundle(context: &Context, args: &ArgMatches) -> Result<(), CliError> {
+    let wallets = args.get_one::<Vec<u8>>("wallets").expect("required");
+    let counts = args.get_one::<Vec<u8>>("instructions").expect("defaulted");
+    let category = *args.get_one::<u8>("category").expect("defaulted");
+    let client_nonce = match args.get_one::<u64>("nonce") {
+        Some(&nonce) => nonce,
+        None => u64::from(rpc::fetch_manager(&context.rpc, &context.manager)?.bundle_seed),
+    };
+
+    let authority = context.authority.pubkey();
+    let wallets = instructions_per_wallet(wallets, counts)?;
+    context.send(&[create_bundle(&context.manager, client_nonce, &authority, &wallets, category, None, None)])?;
+    println!("Bundle: {}", pda::bundle_address(&context.manager, &authority, client_nonce));
+    Ok(())
+}
+
+fn add_ix(context: &Context, args: &ArgMatches) -> Result<(), CliError> {
+    let bundle = *args.get_one::<Pubkey>("bundle").expect("required");
+    let wallet_index = *args.get_one::<u8>("wallet").expect("required");
+    let program_id = *args.get_one::<Pubkey>("program").expect("required");
+    let path = args.get_one::<PathBuf>("data-file").expect("required");
+    let data = fs::read(path).map_err(|error| CliError::Read { path: path.clone(), error })?;
+    let accounts = args.get_many::<AccountMeta>("account").unwrap_or_default().cloned().collect();
+    let options = InstructionOptions { compress: args.is_present("compress"), ..Default::default() };
+
+    // Records take consecutive indexes, so the next one is the bundle's
+    let next_record_index = rpc::fetch_bundle(&context.rpc, &bundle)?.next_record_index;
+    let record = pda::record_address(&bundle, wallet_index, next_record_index);
+    let instruction = Instruction { program_id, accounts, data };
+    let authority = context.authority.pubkey();
+    let add = add_instruction(&context.manager, &bundle, &record, &authority, wallet_index, &instruction, options);
+    context.send(&[add])?;
+    println!("Record: {record}");
+    Ok(())
+}
+
+fn execute(context: &Context, args: &ArgMatches) -> Result<(), CliError> {
+    let bundle_address = *args.get_one::<Pubkey>("bundle").expect("required");
+    let max_compute_units = *args.get_one::<u32>("max-compute-units").expect("defaulted");
+    let recent_fee = *args.get_one::<u64>("recent-fee").expect("defaulted");
+
+    let manager = rpc::fetch_manager(&context.rpc, &context.manager)?;
+    let bundle = rpc::fetch_bundle(&context.rpc, &bundle_address)?;
+    let records = rpc::fetch_bundle_records(&context.rpc, &bundle_address, &bundle)?;
+    let funded = bundle.funding.as_ref().map_or(0, |funding| funding.wallet_count);
+    let remaining = execute_remaining_accounts(
+        &context.manager,
+        &manager,
+        &records,
+        funded,
+        bundle.on_failure.as_ref().map(|(program, _)| program),
+    );
+
+    let authority = context.authority.pubkey();
+    let log = args.is_present("log");
+    let execute =
+        execute_bundle(&context.manager, &bundle_address, &authority, log, &remaining, max_compute_units, recent_fee);
+    context.send(&with_compute_budget(&manager.config, execute)?)?;
+    Ok(())
+}
+
+/// The accounts ExecuteBundle takes after its fixed ones: the records in the
+/// order they were added, then everything their instructions, the funding of
+/// the first `funded` wallets and the `on_failure` hook reference, the fee
+/// collector and the wallet registry. Wallet PDAs are signed for by the
+/// program, so no account but the authority is passed as a signer.
+fn execute_remaining_accounts(
+    manager_address: &Pubkey,
+    manager: &BundleManager,
+    records: &[(Pubkey, BundleInstructionRecord)],
+    funded: u8,
+    on_failure: Option<&Pubkey>,
+) -> Vec<AccountMeta> {
+    let mut accounts: Vec<AccountMeta> = records.iter().map(|(address, _)| AccountMeta::new(*address, false)).collect();
+    for (_, record) in records {
+        merge_account(&mut accounts, AccountMeta::new_readonly(record.program_id, false));
+        for meta in &record.accounts {
+            let meta = AccountMeta { pubkey: meta.pubkey, is_signer: false, is_writable: meta.is_writable };
+            merge_account(&mut accounts, meta);
+        }
+        match &record.create_if_missing {
+            Some(CreateIfMissing::SystemAccount { address, .. }) => {
+                merge_account(&mut accounts, AccountMeta::new(*address, false));
+            },
+            Some(create @ CreateIfMissing::AssociatedTokenAccount { wallet, mint, token_program }) => {
+                merge_account(&mut accounts, AccountMeta::new(create.address(), false));
+                for key in [wallet, mint, token_program, &ASSOCIATED_TOKEN_PROGRAM_ID] {
+                    merge_account(&mut accounts, AccountMeta::new_readonly(*key, false));
+                }
+            },
+            None => {},
+        }
+    }
+    for wallet_index in 0..funded {
+        merge_account(&mut accounts, AccountMeta::new(pda::wallet_address(manager_address, wallet_index), false));
+    }
+    if let Some(program) = on_failure {
+        merge_account(&mut accounts, AccountMeta::new_readonly(*program, false));
+    }
+    if manager.fee_lamports > 0 {
+        merge_account(&mut accounts, AccountMeta::new(manager.fee_collector, false));
+    }
+    if manager.wallet_registry {
+        merge_account(&mut accounts, AccountMeta::new_readonly(pda::wallet_registry_address(manager_address), false));
+    }
+    accounts
+}
+
+// Adds `meta` once, keeping the widest privileges any duplicate asked for
+fn merge_account(accounts: &mut Vec<AccountMeta>, meta: AccountMeta) {
+    match accounts.iter_mut().find(|existing| existing.pubkey == meta.pubkey) {
+        Some(existing) => {
+            existing.is_signer |= meta.is_signer;
+            existing.is_writable |= meta.is_writable;
+        },
+        None => accounts.push(meta),
+    }
+}
+
+fn set_paused(context: &Context, is_paused: bool) -> Result<(), CliError> {
+    context.send(&[set_manager_status(&context.manager, &context.authority.pubkey(), is_paused)])?;
+    Ok(())
+}
+
+fn stats(context: &Context) -> Result<(), CliError> {
+    let stats = rpc::fetch_manager_stats(&context.rpc, &context.manager)?;
+    println!("Manager:                {}", context.manager);
+    println!("Paused:                 {}", stats.is_paused);
+    println!("Active bundles:         {}", stats.active_bundles);
+    println!("Bundles executed:       {}", stats.bundles_executed);
+    println!("Bundles failed:         {} ({:.1}%)", stats.bundles_failed, stats.failure_rate() * 100.0);
+    println!("Consecutive failures:   {}", stats.consecutive_failures);
+    println!("Instructions executed:  {}", stats.instructions_executed);
+    println!("Compute units:          {}", stats.compute_units);
+    println!("Priority fees paid:     {} lamports", stats.priority_fees_paid);
+    if let Some(units) = stats.compute_units_per_instruction() {
+        println!("Units per instruction:  {units}");
+    }
+    Ok(())
+}
+
+fn sweep(context: &Context, args: &ArgMatches) -> Result<(), CliError> {
+    let authority = context.authority.pubkey();
+    let wallets = args.get_one::<Vec<u8>>("wallets").expect("required");
+    let treasury = args.get_one::<Pubkey>("treasury").copied().unwrap_or(authority);
+    // Each mint is swept under the token program that owns it
+    let mints = args.get_many::<Pubkey>("mint").unwrap_or_default()
+        .map(|mint| Ok((*mint, rpc::fetch_account(&context.rpc, mint)?.owner)))
+        .collect::<Result<Vec<_>, CliError>>()?;
+
+    let manager = rpc::fetch_manager(&context.rpc, &context.manager)?;
+    let registry = match manager.wallet_registry {
+        true => Some(rpc::fetch_wallet_registry(&context.rpc, &pda::wallet_registry_address(&context.manager))?),
+        false => None,
+    };
+    context.send(&[sweep_wallets(&context.manager, &authority, &treasury, wallets, &mints, registry.as_ref())])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bundle_manager_client::bundle_manager::{AccountType, InstructionAccountMeta};
+
+    #[test]
+    fn commands_parse() {
+        cli().debug_assert();
+        let key = Pubkey::new_unique();
+        let matches = cli()
+            .try_get_matches_from([
+                "bundle-bots", "add-ix", "--bundle", &key.to_string(), "--wallet", "2", "--program",
+                &key.to_string(), "--data-file", "ix.bin", "--account", &format!("{key}:w"), "--manager-index", "3",
+            ])
+            .unwrap();
+        assert_eq!(matches.get_one::<u16>("manager-index"), Some(&3));
+        let (_, args) = matches.subcommand().unwrap();
+        let accounts: Vec<_> = args.get_many::<AccountMeta>("account").unwrap().collect();
+        assert_eq!(accounts, [&AccountMeta::new(key, false)]);
+        assert!(cli().try_get_matches_from(["bundle-bots", "create-bundle", "--wallets", "0,x"]).is_err());
+    }
+
+    #[test]
+    fn execution_passes_every_account_once_without_signers() {
+        let manager_address = Pubkey::new_unique();
+        let manager = BundleManager { fee_lamports: 10, wallet_registry: true, ..manager() };
+        let wallet = pda::wallet_address(&manager_address, 0);
+        let (program, target) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let record = |accounts: Vec<InstructionAccountMeta>| BundleInstructionRecord {
+            account_type: AccountType::InstructionRecord,
+            version: BundleInstructionRecord::VERSION,
+            bundle: Pubkey::new_unique(),
+            wallet_index: 0,
+            program_id: program,
+            instruction_data: vec![],
+            accounts,
+            executed: false,
+            compressed: false,
+            reserved: 0,
+            create_if_missing: None,
+            last_error: None,
+        };
+        let meta = |pubkey, is_signer| InstructionAccountMeta { pubkey, is_signer, is_writable: true };
+        let records = [
+            (Pubkey::new_unique(), record(vec![meta(wallet, true)])),
+            (Pubkey::new_unique(), record(vec![meta(target, false)])),
+        ];
+
+        let accounts = execute_remaining_accounts(&manager_address, &manager, &records, 1, None);
+        assert_eq!(accounts, [
+            AccountMeta::new(records[0].0, false),
+            AccountMeta::new(records[1].0, false),
+            AccountMeta::new_readonly(program, false),
+            AccountMeta::new(wallet, false),
+            AccountMeta::new(target, false),
+            AccountMeta::new(manager.fee_collector, false),
+            AccountMeta::new_readonly(pda::wallet_registry_address(&manager_address), false),
+        ]);
+    }
+
+    fn manager() -> BundleManager {
+        BundleManager {
+            account_type: AccountType::Manager,
+            version: BundleManager::VERSION,
+            authority: Pubkey::new_unique(),
+            config: ManagerConfig::default(),
+            active_bundles: 0,
+            total_bundles_executed: 0,
+            total_bundles_failed: 0,
+            is_paused: false,
+            bundle_seed: 0,
+            manager_index: 0,
+            bump: 255,
+            consecutive_failures: 0,
+            seed_authority: Pubkey::new_unique(),
+            fee_lamports: 0,
+            fee_collector: Pubkey::new_unique(),
+            tip_account: Pubkey::new_unique(),
+            authorities: vec![],
+            threshold: 0,
+            pending_authority: None,
+            wallet_registry: false,
+            lookup_table: None,
+            delegates: vec![],
+            total_instructions_executed: 0,
+            total_compute_units: 0,
+            total_priority_fees_paid: 0,
+            throttle_slot: 0,
+            bundles_in_slot: 0,
+            last_execution_slot: 0,
+            allowed_programs: vec![],
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Decoding of fetched program accounts, of the address lookup tables
+//! managers link, and of the nonce accounts bots sign against.
+//!
+//! Each decoder checks that the account belongs to the program before
+//! reading its data, so an address that does not hold the expected account
+//! fails with `WrongOwner` rather than a confusing decode error.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use borsh::BorshDeserialize;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    account::Account,
+    address_lookup_table::{self, state::AddressLookupTable, AddressLookupTableAccount},
+    nonce::state::{State as NonceState, Versions as NonceVersions},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+};
+
+use bundle_manager::{
+    client::current_layout, id, AccountType, Bundle, BundleError, BundleInstructionRecord, BundleManager, ExecutionLog,
+    ExecutionLogEntry, Vault, WalletRegistry,
+};
+
+use crate::{
+    nonce::NonceAccount,
+    pda::{execution_log_address, record_address, vault_address, wallet_registry_address},
+    FetchError,
+};
+
+fn decode<T>(
+    address: &Pubkey,
+    account: &Account,
+    unpack: impl FnOnce(&[u8]) -> Result<T, ProgramError>,
+) -> Result<T, FetchError> {
+    if account.owner != id() {
+        return Err(FetchError::WrongOwner { address: *address, owner: account.owner });
+    }
+    unpack(&account.data).map_err(|error| FetchError::Decode { address: *address, error })
+}
+
+/// Decodes the manager at `address` at any version the program has written,
+/// upgrading an older one in memory as MigrateManager would
+pub fn decode_manager(address: &Pubkey, account: &Account) -> Result<BundleManager, FetchError> {
+    decode(address, account, bundle_manager::client::decode_manager)
+}
+
+// `data` of the manager's registry, vault or log at `address` at its current
+// layout. Data from before the account's version byte holds the manager a
+// byte earlier; only the right reading derives `address`.
+fn current_data<'a>(
+    address: &Pubkey,
+    data: &'a [u8],
+    derive: fn(&Pubkey) -> Pubkey,
+) -> Result<Cow<'a, [u8]>, ProgramError> {
+    let manager = [data.get(2..34), data.get(1..33)].into_iter()
+        .flatten()
+        .filter_map(|bytes| Pubkey::try_from(bytes).ok())
+        .find(|manager| derive(manager) == *address);
+    match manager {
+        Some(manager) => current_layout(data, &manager),
+        // Left for unpack to reject
+        None => Ok(Cow::Borrowed(data)),
+    }
+}
+
+/// Decodes the bundle at `address`, see `Bundle::unpack`
+pub fn decode_bundle(address: &Pubkey, account: &Account) -> Result<Bundle, FetchError> {
+    decode(address, account, Bundle::unpack)
+}
+
+/// Decodes the instruction record at `address`; see `decode_bundle_records`
+/// for records from before their version byte
+pub fn decode_record(address: &Pubkey, account: &Account) -> Result<BundleInstructionRecord, FetchError> {
+    decode(address, account, BundleInstructionRecord::unpack)
+}
+
+/// The `getProgramAccounts` request for every instruction record of the
+/// bundle at `bundle_address`: accounts starting with the record type, its
+/// current version and the bundle. Records from before their version byte
+/// are only found once MigrateState has upgraded them.
+pub fn bundle_records_config(bundle_address: &Pubkey) -> RpcProgramAccountsConfig {
+    let mut prefix = vec![AccountType::InstructionRecord as u8, BundleInstructionRecord::VERSION];
+    prefix.extend_from_slice(bundle_address.as_ref());
+    RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, prefix))]),
+        account_config: RpcAccountInfoConfig { encoding: Some(UiAccountEncoding::Base64), ..Default::default() },
+        ..Default::default()
+    }
+}
+
+/// Decodes the records `bundle_records_config` found for `bundle` and puts
+/// them in the order they were added, the order ExecuteBundle takes them in
+/// (it runs them wallet by wallet). That order is only in the record addresses, which are derived from the
+/// bundle's `next_record_index` at the time. Records from before their
+/// version byte are upgraded in memory as MigrateState would.
+pub fn decode_bundle_records(
+    bundle_address: &Pubkey,
+    bundle: &Bundle,
+    accounts: Vec<(Pubkey, Account)>,
+) -> Result<Vec<(Pubkey, BundleInstructionRecord)>, FetchError> {
+    let mut record_indexes = HashMap::new();
+    for wallet_index in &bundle.wallet_indexes {
+        for record_index in 0..bundle.next_record_index {
+            record_indexes.insert(record_address(bundle_address, *wallet_index, record_index), record_index);
+        }
+    }
+    let mut records = accounts.into_iter()
+        .map(|(address, account)| {
+            let record = decode(&address, &account, |data| {
+                BundleInstructionRecord::unpack(&current_layout(data, bundle_address)?)
+            })?;
+            match record_indexes.get(&address) {
+                Some(&record_index) if record.bundle == *bundle_address => Ok((record_index, address, record)),
+                _ => Err(FetchError::Decode { address, error: BundleError::InstructionBundleMismatch.into() }),
+            }
+        })
+        .collect::<Result<Vec<_>, FetchError>>()?;
+    records.sort_by_key(|(record_index, ..)| *record_index);
+    Ok(records.into_iter().map(|(_, address, record)| (address, record)).collect())
+}
+
+/// Decodes the wallet registry at `address`, upgrading one from before its
+/// version byte in memory as MigrateState would
+pub fn decode_wallet_registry(address: &Pubkey, account: &Account) -> Result<WalletRegistry, FetchError> {
+    decode(address, account, |data| WalletRegistry::unpack(&current_data(address, data, wallet_registry_address)?))
+}
+
+/// Decodes the manager vault at `address`, upgrading one from before its
+/// version byte in memory as MigrateState would
+pub fn decode_vault(address: &Pubkey, account: &Account) -> Result<Vault, FetchError> {
+    decode(address, account, |data| Vault::unpack(&current_data(address, data, vault_address)?))
+}
+
+/// Decodes the address lookup table at `address`, e.g. a manager's
+/// `lookup_table`, with every address it holds
+pub fn decode_lookup_table(address: &Pubkey, account: &Account) -> Result<AddressLookupTableAccount, FetchError> {
+    if account.owner != address_lookup_table::program::id() {
+        return Err(FetchError::WrongOwner { address: *address, owner: account.owner });
+    }
+    let table = AddressLookupTable::deserialize(&account.data)
+        .map_err(|_| FetchError::Decode { address: *address, error: ProgramError::InvalidAccountData })?;
+    Ok(AddressLookupTableAccount { key: *address, addresses: table.addresses.to_vec() })
+}
+
+/// Decodes the nonce account at `address`, failing on one that is not
+/// initialized
+pub fn decode_nonce(address: &Pubkey, account: &Account) -> Result<NonceAccount, FetchError> {
+    if account.owner != system_program::id() {
+        return Err(FetchError::WrongOwner { address: *address, owner: account.owner });
+    }
+    let invalid = || FetchError::Decode { address: *address, error: ProgramError::InvalidAccountData };
+    let versions: NonceVersions = bincode::deserialize(&account.data).map_err(|_| invalid())?;
+    match versions.state() {
+        NonceState::Initialized(data) => {
+            Ok(NonceAccount { address: *address, authority: data.authority, blockhash: data.blockhash() })
+        },
+        NonceState::Uninitialized => {
+            Err(FetchError::Decode { address: *address, error: ProgramError::UninitializedAccount })
+        },
+    }
+}
+
+/// A manager's execution log: its header and the entries it still holds
+#[derive(Debug)]
+pub struct ExecutionLogAccount {
+    pub log: ExecutionLog,
+    /// Oldest first; once the ring buffer wraps, only the last `max_entries`
+    pub entries: Vec<ExecutionLogEntry>,
+}
+
+/// Decodes the execution log at `address` with its entries, upgrading one
+/// from before its version byte in memory as MigrateState would
+pub fn decode_execution_log(address: &Pubkey, account: &Account) -> Result<ExecutionLogAccount, FetchError> {
+    decode(address, account, |data| {
+        let data = &current_data(address, data, execution_log_address)?;
+        let log = ExecutionLog::unpack(data)?;
+        let held = log.total_appended.min(log.max_entries as u64) as u32;
+        // Before the buffer wraps the oldest entry is slot 0, after it the slot written next
+        let oldest = if log.total_appended > log.max_entries as u64 { log.next_index } else { 0 };
+        let entries = (0..held)
+            .map(|offset| {
+                let slot = (oldest + offset) % log.max_entries;
+                let entry_data = data.get(ExecutionLog::entry_offset(slot)..).ok_or(ProgramError::InvalidAccountData)?;
+                ExecutionLogEntry::deserialize(&mut &entry_data[..]).map_err(|_| ProgramError::InvalidAccountData)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(ExecutionLogAccount { log, entries })
+    })
+}
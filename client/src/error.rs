@@ -0,0 +1,36 @@
+use solana_client::client_error::ClientError;
+use solana_sdk::{program_error::ProgramError, pubkey::Pubkey};
+use thiserror::Error;
+
+use bundle_manager::BundleError;
+
+/// Why an account could not be fetched and decoded
+#[derive(Error, Debug)]
+pub enum FetchError {
+    /// The RPC request itself failed. Boxed: `ClientError` is large.
+    #[error("RPC request failed: {0}")]
+    Rpc(Box<ClientError>),
+    #[error("Account {0} does not exist")]
+    AccountNotFound(Pubkey),
+    #[error("Account {address} is owned by {owner}, not the program expected to own it")]
+    WrongOwner { address: Pubkey, owner: Pubkey },
+    #[error("Account {address} does not decode: {error}")]
+    Decode { address: Pubkey, error: ProgramError },
+}
+
+impl FetchError {
+    /// The program error behind a `Decode` failure, e.g.
+    /// `InvalidAccountType` for an account read as the wrong type
+    pub fn bundle_error(&self) -> Option<BundleError> {
+        match self {
+            FetchError::Decode { error, .. } => BundleError::from_program_error(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ClientError> for FetchError {
+    fn from(err: ClientError) -> Self {
+        FetchError::Rpc(Box::new(err))
+    }
+}
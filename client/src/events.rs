@@ -0,0 +1,135 @@
+//! The program's `BundleEvent`s, read back from transaction logs.
+//!
+//! Every event is one `Program data:` line logged while this program runs;
+//! `parse_events` attributes each line to the program on top of the invoke
+//! stack, so events of other programs in the same transaction, and of this
+//! program under a different id, are left out. `subscribe` streams them over
+//! a websocket as transactions mentioning the program land, and
+//! `rpc::fetch_transaction_events` reads them from a confirmed transaction.
+//!
+//! A failed transaction rolls its events back along with everything else;
+//! its logs still hold them, so check `TransactionEvents::failed`.
+//!
+//! ```ignore
+//! let (_subscription, events) = events::subscribe(ws_url, CommitmentConfig::confirmed())?;
+//! for transaction in events.filter(|transaction| !transaction.failed) {
+//!     for event in transaction.events { println!("{event:?}"); }
+//! }
+//! ```
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::BorshDeserialize;
+use solana_client::{
+    pubsub_client::{PubsubClient, PubsubClientError, PubsubLogsClientSubscription},
+    rpc_config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter},
+    rpc_response::RpcLogsResponse,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+
+use bundle_manager::{events::BundleEvent, id};
+
+/// The events one transaction emitted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionEvents {
+    /// Base58 transaction signature
+    pub signature: String,
+    /// The transaction failed, so none of its events took effect
+    pub failed: bool,
+    pub events: Vec<BundleEvent>,
+}
+
+impl From<RpcLogsResponse> for TransactionEvents {
+    fn from(response: RpcLogsResponse) -> Self {
+        Self {
+            events: parse_events(&response.logs),
+            failed: response.err.is_some(),
+            signature: response.signature,
+        }
+    }
+}
+
+/// The events this program logged in `logs`, a transaction's log messages,
+/// in order. Lines that do not decode, such as those of a truncated log,
+/// are skipped.
+pub fn parse_events(logs: &[String]) -> Vec<BundleEvent> {
+    let program_id = id();
+    let mut invoked: Vec<Pubkey> = Vec::new();
+    let mut events = Vec::new();
+    for line in logs {
+        let Some(rest) = line.strip_prefix("Program ") else {
+            continue;
+        };
+        if let Some(data) = rest.strip_prefix("data: ") {
+            if invoked.last() != Some(&program_id) {
+                continue;
+            }
+            // Events are logged as a single field
+            let event = STANDARD.decode(data.trim()).ok()
+                .and_then(|bytes| BundleEvent::try_from_slice(&bytes).ok());
+            events.extend(event);
+            continue;
+        }
+
+        let mut words = rest.split_whitespace();
+        let (Some(program), Some(action)) = (words.next(), words.next()) else {
+            continue;
+        };
+        match action {
+            "invoke" => invoked.extend(program.parse::<Pubkey>().ok()),
+            "success" | "failed:" => {
+                invoked.pop();
+            },
+            _ => {},
+        }
+    }
+    events
+}
+
+/// Subscribes over the websocket at `url` to the logs of every transaction
+/// mentioning the program, at `commitment`, yielding the events of each.
+/// The subscription ends when the returned client is dropped or shut down.
+/// The error is boxed: `PubsubClientError` is large.
+pub fn subscribe(
+    url: &str,
+    commitment: CommitmentConfig,
+) -> Result<(PubsubLogsClientSubscription, impl Iterator<Item = TransactionEvents>), Box<PubsubClientError>> {
+    let (subscription, receiver) = PubsubClient::logs_subscribe(
+        url,
+        RpcTransactionLogsFilter::Mentions(vec![id().to_string()]),
+        RpcTransactionLogsConfig { commitment: Some(commitment) },
+    )?;
+    Ok((subscription, receiver.into_iter().map(|response| TransactionEvents::from(response.value))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshSerialize;
+    use bundle_manager::events::ManagerStatusChangedEvent;
+
+    #[test]
+    fn only_this_programs_data_lines_are_events() {
+        let event = BundleEvent::ManagerStatusChanged(ManagerStatusChangedEvent {
+            manager: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            is_paused: true,
+            automatic: false,
+        });
+        let data = format!("Program data: {}", STANDARD.encode(event.try_to_vec().unwrap()));
+        let other = Pubkey::new_unique();
+        let logs: Vec<String> = [
+            format!("Program {other} invoke [1]"),
+            data.clone(),
+            format!("Program {} invoke [2]", id()),
+            "Program log: Manager paused".to_string(),
+            data.clone(),
+            "Program data: bm90IGFuIGV2ZW50".to_string(),
+            format!("Program {} consumed 3000 of 190000 compute units", id()),
+            format!("Program {} success", id()),
+            data,
+            format!("Program {other} success"),
+        ]
+        .into();
+        assert_eq!(parse_events(&logs), vec![event]);
+    }
+}
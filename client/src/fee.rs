@@ -0,0 +1,140 @@
+//! Priority fees from recent network prices.
+//!
+//! ExecuteBundle takes the recent per-compute-unit price as
+//! `recent_fee_micro_lamports` and pays that times the manager's
+//! `priority_fee_multiplier`, capped at its `max_compute_unit_price` (see
+//! `compute_unit_price`); both can be tuned on chain with
+//! UpdateManagerConfig. `FeeOracle` picks the recent price: a percentile of
+//! what `getRecentPrioritizationFees` reports for the accounts the bundle
+//! writes, raised on every resubmission of a bundle that did not land.
+//!
+//! ```ignore
+//! let oracle = FeeOracle::default();
+//! for attempt in 0..3 {
+//!     let recent_fee = oracle.recent_fee(&rpc, &writable_accounts(&[&execute]), attempt)?;
+//!     let execute = execute_bundle(&manager, &bundle, &authority, false, &remaining, max_cu, recent_fee);
+//!     // ... with_compute_budget, sign and send; stop once it lands
+//! }
+//! ```
+
+use solana_client::{rpc_client::RpcClient, rpc_response::RpcPrioritizationFee};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+use crate::FetchError;
+
+/// Most accounts `getRecentPrioritizationFees` accepts in one request
+pub const MAX_FEE_ACCOUNTS: usize = 128;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeeOracle {
+    /// Percentile, 0 to 100, of the sampled slot prices to start from
+    pub percentile: u8,
+    /// Lowest price to start from, so escalation has something to raise
+    /// when recent slots paid nothing
+    pub min_fee: u64,
+    /// Percent added to the price on every resubmission, compounding
+    pub escalation_percent: u16,
+    /// Highest price to return however many attempts were made; 0 leaves it
+    /// uncapped, though the manager's own cap still applies on chain
+    pub max_fee: u64,
+}
+
+impl Default for FeeOracle {
+    /// The 75th percentile, at least 1 micro-lamport, raised by half on every
+    /// resubmission and uncapped
+    fn default() -> Self {
+        Self { percentile: 75, min_fee: 1, escalation_percent: 50, max_fee: 0 }
+    }
+}
+
+impl FeeOracle {
+    /// The price for submission `attempt` of a bundle, the first being 0,
+    /// given the slot prices `samples`
+    pub fn price(&self, samples: &[RpcPrioritizationFee], attempt: u32) -> u64 {
+        let fees: Vec<u64> = samples.iter().map(|sample| sample.prioritization_fee).collect();
+        let base = percentile(&fees, self.percentile).max(self.min_fee);
+        // Rounded up, so even a 1 micro-lamport price rises
+        let escalated = (0..attempt).fold(base, |fee, _| {
+            fee.saturating_add(fee.saturating_mul(self.escalation_percent as u64).div_ceil(100))
+        });
+        match self.max_fee {
+            0 => escalated,
+            cap => escalated.min(cap),
+        }
+    }
+
+    /// Samples recent prices for `accounts`, at most `MAX_FEE_ACCOUNTS` of
+    /// them, and returns the price for submission `attempt`
+    pub fn recent_fee(&self, rpc: &RpcClient, accounts: &[Pubkey], attempt: u32) -> Result<u64, FetchError> {
+        let accounts = &accounts[..accounts.len().min(MAX_FEE_ACCOUNTS)];
+        Ok(self.price(&rpc.get_recent_prioritization_fees(accounts)?, attempt))
+    }
+
+    /// `recent_fee` over the async client
+    pub async fn recent_fee_nonblocking(
+        &self,
+        rpc: &solana_client::nonblocking::rpc_client::RpcClient,
+        accounts: &[Pubkey],
+        attempt: u32,
+    ) -> Result<u64, FetchError> {
+        let accounts = &accounts[..accounts.len().min(MAX_FEE_ACCOUNTS)];
+        Ok(self.price(&rpc.get_recent_prioritization_fees(accounts).await?, attempt))
+    }
+}
+
+/// The nearest-rank `percentile` of `fees`, or 0 for no fees
+pub fn percentile(fees: &[u64], percentile: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    let mut fees = fees.to_vec();
+    fees.sort_unstable();
+    let rank = (fees.len() * percentile.min(100) as usize).div_ceil(100);
+    fees[rank.saturating_sub(1)]
+}
+
+/// The distinct writable accounts of `instructions`, the ones whose recent
+/// prices the bundle competes with
+pub fn writable_accounts(instructions: &[&Instruction]) -> Vec<Pubkey> {
+    let mut accounts = Vec::new();
+    for meta in instructions.iter().flat_map(|instruction| &instruction.accounts) {
+        if meta.is_writable && !accounts.contains(&meta.pubkey) {
+            accounts.push(meta.pubkey);
+        }
+    }
+    accounts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(fees: &[u64]) -> Vec<RpcPrioritizationFee> {
+        fees.iter()
+            .enumerate()
+            .map(|(slot, &prioritization_fee)| RpcPrioritizationFee { slot: slot as u64, prioritization_fee })
+            .collect()
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let fees = [50, 0, 10, 40, 20, 30, 0, 0, 100, 60];
+        assert_eq!(percentile(&fees, 0), 0);
+        assert_eq!(percentile(&fees, 50), 20);
+        assert_eq!(percentile(&fees, 75), 50);
+        assert_eq!(percentile(&fees, 100), 100);
+        assert_eq!(percentile(&[], 75), 0);
+    }
+
+    #[test]
+    fn prices_escalate_per_attempt_up_to_the_cap() {
+        let oracle = FeeOracle { max_fee: 3_000, ..FeeOracle::default() };
+        let recent = samples(&[1_000; 4]);
+        let prices: Vec<u64> = (0..5).map(|attempt| oracle.price(&recent, attempt)).collect();
+        assert_eq!(prices, vec![1_000, 1_500, 2_250, 3_000, 3_000]);
+
+        // Quiet slots still leave a price to escalate from
+        assert_eq!(FeeOracle::default().price(&samples(&[0, 0]), 0), 1);
+        assert_eq!(FeeOracle::default().price(&[], 2), 3);
+    }
+}
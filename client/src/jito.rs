@@ -0,0 +1,305 @@
+//! Submission of signed transactions as Jito bundles.
+//!
+//! A Jito bundle is up to `MAX_BUNDLE_TRANSACTIONS` transactions the block
+//! engine lands atomically and in order, or not at all, and only with a tip:
+//! a transfer to one of Jito's tip accounts. `assemble_bundle` appends the
+//! tip as a transaction of its own, so the bot's transactions can be signed
+//! before the tip is chosen. A bundle whose last ExecuteBundle already pays
+//! the manager's `tip_account` (see `with_jito_tip`) can be sent as it is.
+//! Bundles hold versioned transactions, so v0 transactions that use the
+//! manager's lookup table (see `lookup_table`) go in as they are and legacy
+//! ones with `.into()`.
+//!
+//! `JitoClient` sends bundles to the block engine's JSON-RPC endpoint and
+//! polls their status until they land or are dropped, resending with
+//! exponential backoff. Every resend carries the same transactions, so a
+//! bundle whose blockhash expires in the meantime cannot land and ends
+//! `Dropped`; rebuild it with a fresh blockhash, or sign it against durable
+//! nonces in the first place, see `nonce`.
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+use solana_sdk::{
+    hash::Hash,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::Signer,
+    system_instruction,
+    transaction::{Transaction, VersionedTransaction},
+};
+use thiserror::Error;
+
+/// Most transactions the block engine accepts in one bundle, the tip's included
+pub const MAX_BUNDLE_TRANSACTIONS: usize = 5;
+
+/// Path of the bundle JSON-RPC endpoint below the block engine URL
+pub const BUNDLES_PATH: &str = "/api/v1/bundles";
+
+#[derive(Clone, Debug)]
+pub struct JitoConfig {
+    /// Block engine base URL, e.g. `https://mainnet.block-engine.jito.wtf`
+    pub block_engine_url: String,
+    /// Receives the tip; one of the accounts the block engine's `getTipAccounts` lists
+    pub tip_account: Pubkey,
+    pub tip_lamports: u64,
+    /// Time between status polls of a sent bundle
+    pub poll_interval: Duration,
+    /// Polls after which a bundle that is still pending counts as dropped
+    pub max_polls: u32,
+    /// Sends of one bundle, the first included, before giving up
+    pub max_attempts: u32,
+    /// Wait before the first resend, doubled before every further one
+    pub initial_backoff: Duration,
+}
+
+impl JitoConfig {
+    /// Polls every 500 ms for up to 30 s per attempt and makes three
+    /// attempts, backing off 1 s and then 2 s
+    pub fn new(block_engine_url: impl Into<String>, tip_account: Pubkey, tip_lamports: u64) -> Self {
+        Self {
+            block_engine_url: block_engine_url.into(),
+            tip_account,
+            tip_lamports,
+            poll_interval: Duration::from_millis(500),
+            max_polls: 60,
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum JitoError {
+    #[error("A bundle holds at most {MAX_BUNDLE_TRANSACTIONS} transactions with its tip, got {0}")]
+    TooManyTransactions(usize),
+    #[error("Transaction {0} of the bundle is not fully signed")]
+    UnsignedTransaction(usize),
+    #[error("Transaction {index} does not serialize: {error}")]
+    Serialize { index: usize, error: bincode::Error },
+    #[error("Block engine request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Block engine error {code}: {message}")]
+    Rpc { code: i64, message: String },
+    #[error("Unexpected block engine response: {0}")]
+    UnexpectedResponse(Value),
+    #[error("Bundle {bundle_id} was dropped after {attempts} attempts")]
+    Dropped { bundle_id: String, attempts: u32 },
+}
+
+/// Where a sent bundle stands, as `getInflightBundleStatuses` reports it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JitoBundleStatus {
+    Pending,
+    Landed { slot: u64 },
+    /// Failed, or unknown to the block engine; it will not land
+    Dropped,
+}
+
+impl JitoBundleStatus {
+    /// Reads the first status of a `getInflightBundleStatuses` result
+    fn from_inflight(result: &Value) -> Result<Self, JitoError> {
+        let entry = result.get("value").and_then(|value| value.get(0));
+        let unexpected = || JitoError::UnexpectedResponse(result.clone());
+        match entry.and_then(|entry| entry.get("status")).and_then(Value::as_str) {
+            Some("Pending") => Ok(JitoBundleStatus::Pending),
+            Some("Landed") => {
+                let slot = entry.and_then(|entry| entry.get("landed_slot")).and_then(Value::as_u64);
+                slot.map(|slot| JitoBundleStatus::Landed { slot }).ok_or_else(unexpected)
+            },
+            Some("Failed" | "Invalid") => Ok(JitoBundleStatus::Dropped),
+            _ => Err(unexpected()),
+        }
+    }
+}
+
+/// A bundle the block engine reported landed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LandedBundle {
+    pub bundle_id: String,
+    pub slot: u64,
+}
+
+/// The tip transfer from `payer` to the configured tip account
+pub fn tip_instruction(payer: &Pubkey, config: &JitoConfig) -> Instruction {
+    system_instruction::transfer(payer, &config.tip_account, config.tip_lamports)
+}
+
+// Every signature the message requires is present. Whether they verify is
+// left to the block engine.
+fn is_signed(transaction: &VersionedTransaction) -> bool {
+    transaction.signatures.len() == transaction.message.header().num_required_signatures as usize
+        && transaction.signatures.iter().all(|signature| *signature != Signature::default())
+}
+
+/// `transactions`, each already signed, followed by a tip transaction that
+/// `payer` signs for `recent_blockhash`
+pub fn assemble_bundle(
+    mut transactions: Vec<VersionedTransaction>,
+    payer: &impl Signer,
+    config: &JitoConfig,
+    recent_blockhash: Hash,
+) -> Result<Vec<VersionedTransaction>, JitoError> {
+    if transactions.len() + 1 > MAX_BUNDLE_TRANSACTIONS {
+        return Err(JitoError::TooManyTransactions(transactions.len() + 1));
+    }
+    if let Some(index) = transactions.iter().position(|transaction| !is_signed(transaction)) {
+        return Err(JitoError::UnsignedTransaction(index));
+    }
+    let payer_key = payer.pubkey();
+    let tip = Transaction::new_signed_with_payer(
+        &[tip_instruction(&payer_key, config)],
+        Some(&payer_key),
+        &[payer],
+        recent_blockhash,
+    );
+    transactions.push(tip.into());
+    Ok(transactions)
+}
+
+/// Sends bundles to a block engine and follows them until they land
+pub struct JitoClient {
+    http: reqwest::Client,
+    config: JitoConfig,
+}
+
+impl JitoClient {
+    pub fn new(config: JitoConfig) -> Self {
+        Self { http: reqwest::Client::new(), config }
+    }
+
+    pub fn config(&self) -> &JitoConfig {
+        &self.config
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, JitoError> {
+        let url = format!("{}{}", self.config.block_engine_url.trim_end_matches('/'), BUNDLES_PATH);
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let mut response: Value =
+            self.http.post(url).json(&request).send().await?.error_for_status()?.json().await?;
+        if let Some(error) = response.get("error") {
+            return Err(JitoError::Rpc {
+                code: error.get("code").and_then(Value::as_i64).unwrap_or_default(),
+                message: error.get("message").and_then(Value::as_str).unwrap_or_default().to_string(),
+            });
+        }
+        match response.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => Err(JitoError::UnexpectedResponse(response)),
+        }
+    }
+
+    /// Sends `bundle` once, returning the bundle id the block engine assigns
+    pub async fn send_bundle(&self, bundle: &[VersionedTransaction]) -> Result<String, JitoError> {
+        let encoded = bundle.iter()
+            .enumerate()
+            .map(|(index, transaction)| {
+                let bytes = bincode::serialize(transaction).map_err(|error| JitoError::Serialize { index, error })?;
+                Ok(STANDARD.encode(bytes))
+            })
+            .collect::<Result<Vec<_>, JitoError>>()?;
+        let result = self.call("sendBundle", json!([encoded, { "encoding": "base64" }])).await?;
+        match result.as_str() {
+            Some(bundle_id) => Ok(bundle_id.to_string()),
+            None => Err(JitoError::UnexpectedResponse(result)),
+        }
+    }
+
+    pub async fn bundle_status(&self, bundle_id: &str) -> Result<JitoBundleStatus, JitoError> {
+        let result = self.call("getInflightBundleStatuses", json!([[bundle_id]])).await?;
+        JitoBundleStatus::from_inflight(&result)
+    }
+
+    // Polls until the bundle lands or is dropped. A poll that fails counts
+    // as pending, so one rate-limited request does not abandon the bundle.
+    async fn wait_for(&self, bundle_id: &str) -> JitoBundleStatus {
+        for _ in 0..self.config.max_polls {
+            tokio::time::sleep(self.config.poll_interval).await;
+            match self.bundle_status(bundle_id).await {
+                Ok(JitoBundleStatus::Pending) | Err(_) => continue,
+                Ok(status) => return status,
+            }
+        }
+        JitoBundleStatus::Dropped
+    }
+
+    /// Sends `bundle` and waits for it to land, resending it after a drop
+    /// or a failed send until `max_attempts` sends have been made. Errors
+    /// the block engine returns for the bundle itself are not retried.
+    pub async fn send_and_confirm(&self, bundle: &[VersionedTransaction]) -> Result<LandedBundle, JitoError> {
+        let mut backoff = self.config.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            let err = match self.send_bundle(bundle).await {
+                Ok(bundle_id) => match self.wait_for(&bundle_id).await {
+                    JitoBundleStatus::Landed { slot } => return Ok(LandedBundle { bundle_id, slot }),
+                    _ => JitoError::Dropped { bundle_id, attempts: attempt },
+                },
+                Err(err @ JitoError::Http(_)) => err,
+                Err(err) => return Err(err),
+            };
+            if attempt >= self.config.max_attempts {
+                return Err(err);
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Keypair;
+
+    fn config() -> JitoConfig {
+        JitoConfig::new("https://block-engine.test", Pubkey::new_unique(), 10_000)
+    }
+
+    fn signed_transfer(payer: &Keypair) -> VersionedTransaction {
+        let transfer = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1);
+        Transaction::new_signed_with_payer(&[transfer], Some(&payer.pubkey()), &[payer], Hash::default()).into()
+    }
+
+    #[test]
+    fn bundles_end_with_the_tip() {
+        let payer = Keypair::new();
+        let config = config();
+        let bundle = assemble_bundle(vec![signed_transfer(&payer)], &payer, &config, Hash::default()).unwrap();
+        assert_eq!(bundle.len(), 2);
+        let tip = &bundle[1];
+        assert!(is_signed(tip));
+        assert_eq!(tip.message.instructions().len(), 1);
+        assert!(tip.message.static_account_keys().contains(&config.tip_account));
+
+        let full = vec![signed_transfer(&payer); MAX_BUNDLE_TRANSACTIONS];
+        assert!(matches!(
+            assemble_bundle(full, &payer, &config, Hash::default()),
+            Err(JitoError::TooManyTransactions(6))
+        ));
+
+        let transfer = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1);
+        let unsigned = Transaction::new_with_payer(&[transfer], Some(&payer.pubkey())).into();
+        assert!(matches!(
+            assemble_bundle(vec![signed_transfer(&payer), unsigned], &payer, &config, Hash::default()),
+            Err(JitoError::UnsignedTransaction(1))
+        ));
+    }
+
+    #[test]
+    fn inflight_statuses_decode() {
+        let status = |entry: Value| JitoBundleStatus::from_inflight(&json!({ "context": { "slot": 1 }, "value": [entry] }));
+        assert_eq!(status(json!({ "status": "Pending" })).unwrap(), JitoBundleStatus::Pending);
+        assert_eq!(
+            status(json!({ "status": "Landed", "landed_slot": 42 })).unwrap(),
+            JitoBundleStatus::Landed { slot: 42 }
+        );
+        assert_eq!(status(json!({ "status": "Failed" })).unwrap(), JitoBundleStatus::Dropped);
+        assert_eq!(status(json!({ "status": "Invalid" })).unwrap(), JitoBundleStatus::Dropped);
+        assert!(status(json!({ "status": "Landed" })).is_err());
+        assert!(JitoBundleStatus::from_inflight(&json!({ "value": [] })).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+//! Off-chain SDK for the bundle-manager program.
+//!
+//! Bots build instructions with the program's own builders, re-exported here
+//! from `bundle_manager::client`, derive account addresses with `pda`, and
+//! read program accounts with the helpers in `rpc` (blocking `RpcClient`)
+//! or `nonblocking` (async `RpcClient`). Both check that an account exists
+//! and is owned by the program before decoding it, see `accounts`. `fee`
+//! prices ExecuteBundle from recent network fees, `simulation` runs a
+//! bundle's stored instructions through `simulateTransaction` before it is
+//! executed, `lookup_table` builds v0 transactions over the manager's
+//! address lookup table, `nonce` signs them against durable nonces for
+//! later submission, `stats` reads a manager's running totals,
+//! `scheduler` waits for scheduled bundles to become executable, `swaps`
+//! turns Jupiter swap routes into bundle instructions, `events` reads the
+//! program's events back from transaction logs, `watch` streams a bundle's
+//! status changes over a websocket, and `jito` submits the signed
+//! transactions as Jito bundles.
+//!
+//! ```ignore
+//! let rpc = RpcClient::new(url);
+//! let manager = pda::manager_address(&authority, 0);
+//! let create = create_bundle(&manager, nonce, &authority, &[(0, 2)], 0, None, None);
+//! // ... send `create`, add instructions, then:
+//! let bundle = rpc::fetch_bundle(&rpc, &pda::bundle_address(&manager, &authority, nonce))?;
+//! ```
+
+pub mod accounts;
+mod error;
+pub mod events;
+pub mod fee;
+pub mod jito;
+pub mod lookup_table;
+pub mod nonblocking;
+pub mod nonce;
+pub mod pda;
+pub mod rpc;
+pub mod scheduler;
+pub mod simulation;
+pub mod stats;
+pub mod swaps;
+pub mod watch;
+
+pub use bundle_manager;
+pub use bundle_manager::client::*;
+pub use bundle_manager::id;
+pub use error::FetchError;
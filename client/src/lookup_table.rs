@@ -0,0 +1,203 @@
+//! Address lookup tables and v0 transactions for bundle execution.
+//!
+//! A legacy transaction lists every account it touches in full, and an
+//! ExecuteBundle over many wallets soon outgrows the transaction size limit.
+//! A v0 transaction refers to accounts held in an address lookup table by a
+//! one-byte index instead. The manager authority creates one table holding
+//! the accounts every execution touches (`shared_addresses`) and records it
+//! with LinkLookupTable, so every bot reads the same table off the manager.
+//!
+//! ```ignore
+//! let (create, table) = create_lookup_table(&authority, &authority, rpc.get_slot()?);
+//! let addresses = shared_addresses(&manager, &manager_state, registry.as_ref());
+//! let extend = extend_lookup_table(&table, &authority, &authority, &addresses);
+//! // ... send `create`, then `extend` and `link_lookup_table`; the table is
+//! // usable from the slot after its last extension
+//! let tables = [rpc::fetch_lookup_table(&rpc, &table)?];
+//! let transaction = v0_transaction(&authority, &[execute], &tables, &[&authority_keypair], blockhash)?;
+//! ```
+
+use solana_sdk::{
+    address_lookup_table::{self, AddressLookupTableAccount},
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, CompileError, VersionedMessage},
+    pubkey::Pubkey,
+    signer::{signers::Signers, SignerError},
+    system_program, sysvar,
+    transaction::VersionedTransaction,
+};
+use thiserror::Error;
+
+use bundle_manager::{id, BundleManager, WalletRegistry, COMPUTE_BUDGET_PROGRAM_ID};
+
+use crate::pda::{execution_log_address, wallet_registry_address};
+
+/// Addresses one ExtendLookupTable instruction adds, small enough for its
+/// transaction to stay under the size limit
+pub const MAX_EXTEND_ADDRESSES: usize = 20;
+
+#[derive(Error, Debug)]
+pub enum V0TransactionError {
+    #[error("Instructions do not compile to a v0 message: {0}")]
+    Compile(#[from] CompileError),
+    #[error("Transaction could not be signed: {0}")]
+    Sign(#[from] SignerError),
+}
+
+/// CreateLookupTable for a table `authority` controls, and the table's
+/// address. `recent_slot` must be a recent finalized slot.
+pub fn create_lookup_table(authority: &Pubkey, payer: &Pubkey, recent_slot: u64) -> (Instruction, Pubkey) {
+    address_lookup_table::instruction::create_lookup_table(*authority, *payer, recent_slot)
+}
+
+/// ExtendLookupTable instructions adding `addresses` to `table`, at most
+/// `MAX_EXTEND_ADDRESSES` per instruction, each meant for a transaction of
+/// its own
+pub fn extend_lookup_table(
+    table: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    addresses: &[Pubkey],
+) -> Vec<Instruction> {
+    addresses.chunks(MAX_EXTEND_ADDRESSES)
+        .map(|chunk| {
+            address_lookup_table::instruction::extend_lookup_table(*table, *authority, Some(*payer), chunk.to_vec())
+        })
+        .collect()
+}
+
+/// The accounts every execution of `manager`'s bundles touches: the
+/// programs and sysvar ExecuteBundle passes, the manager, its execution log,
+/// tip and fee accounts, and, once it has a registry, the registry and
+/// every registered wallet
+pub fn shared_addresses(
+    manager_address: &Pubkey,
+    manager: &BundleManager,
+    registry: Option<&WalletRegistry>,
+) -> Vec<Pubkey> {
+    let mut addresses = vec![
+        id(),
+        system_program::id(),
+        COMPUTE_BUDGET_PROGRAM_ID,
+        sysvar::slot_hashes::id(),
+        *manager_address,
+        execution_log_address(manager_address),
+        manager.tip_account,
+        manager.fee_collector,
+    ];
+    if let Some(registry) = registry {
+        addresses.push(wallet_registry_address(manager_address));
+        addresses.extend(registry.wallets.iter().map(|wallet| wallet.address));
+    }
+    let mut distinct = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        if !distinct.contains(&address) {
+            distinct.push(address);
+        }
+    }
+    distinct
+}
+
+/// The distinct `addresses` that `table` does not hold yet, in their order
+pub fn missing_addresses(table: &AddressLookupTableAccount, addresses: &[Pubkey]) -> Vec<Pubkey> {
+    let mut missing = Vec::new();
+    for address in addresses {
+        if !table.addresses.contains(address) && !missing.contains(address) {
+            missing.push(*address);
+        }
+    }
+    missing
+}
+
+/// A v0 transaction paid by `payer` running `instructions`, looking up
+/// whatever accounts it can in `tables` and signed by `signers`, which must
+/// include the payer
+pub fn v0_transaction<T: Signers + ?Sized>(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    tables: &[AddressLookupTableAccount],
+    signers: &T,
+    recent_blockhash: Hash,
+) -> Result<VersionedTransaction, V0TransactionError> {
+    let message = v0::Message::try_compile(payer, instructions, tables, recent_blockhash)?;
+    Ok(VersionedTransaction::try_new(VersionedMessage::V0(message), signers)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bundle_manager::{client, RegisteredWallet};
+    use solana_sdk::{signature::Keypair, signer::Signer};
+
+    fn manager() -> BundleManager {
+        BundleManager {
+            account_type: bundle_manager::AccountType::Manager,
+            version: BundleManager::VERSION,
+            authority: Pubkey::new_unique(),
+            config: Default::default(),
+            active_bundles: 0,
+            total_bundles_executed: 0,
+            total_bundles_failed: 0,
+            is_paused: false,
+            bundle_seed: 0,
+            manager_index: 0,
+            bump: 255,
+            consecutive_failures: 0,
+            seed_authority: Pubkey::new_unique(),
+            fee_lamports: 0,
+            fee_collector: Pubkey::new_unique(),
+            tip_account: Pubkey::new_unique(),
+            authorities: Vec::new(),
+            threshold: 1,
+            pending_authority: None,
+            wallet_registry: true,
+            lookup_table: None,
+            delegates: Vec::new(),
+            total_instructions_executed: 0,
+            total_compute_units: 0,
+            total_priority_fees_paid: 0,
+            throttle_slot: 0,
+            bundles_in_slot: 0,
+            last_execution_slot: 0,
+            allowed_programs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn execution_accounts_move_into_the_table() {
+        let authority = Keypair::new();
+        let manager_address = Pubkey::new_unique();
+        let manager = manager();
+        let registry = WalletRegistry {
+            account_type: bundle_manager::AccountType::WalletRegistry,
+            version: WalletRegistry::VERSION,
+            manager: manager_address,
+            bump: 255,
+            wallets: (0..20)
+                .map(|index| RegisteredWallet { wallet_index: index, address: Pubkey::new_unique(), is_pda: true })
+                .collect(),
+        };
+        let addresses = shared_addresses(&manager_address, &manager, Some(&registry));
+        assert_eq!(addresses.len(), 8 + 1 + 20);
+        let table = Pubkey::new_unique();
+        assert_eq!(extend_lookup_table(&table, &authority.pubkey(), &authority.pubkey(), &addresses).len(), 2);
+
+        let table = AddressLookupTableAccount { key: Pubkey::new_unique(), addresses: addresses[..10].to_vec() };
+        assert_eq!(missing_addresses(&table, &addresses), addresses[10..].to_vec());
+
+        let bundle = Pubkey::new_unique();
+        let wallets: Vec<_> = registry.wallets.iter()
+            .map(|wallet| solana_sdk::instruction::AccountMeta::new(wallet.address, false))
+            .collect();
+        let execute =
+            client::execute_bundle(&manager_address, &bundle, &authority.pubkey(), true, &wallets, 200_000, 0);
+        let table = AddressLookupTableAccount { key: Pubkey::new_unique(), addresses };
+        let transaction = v0_transaction(&authority.pubkey(), &[execute], &[table], &[&authority], Hash::default())
+            .unwrap();
+        let VersionedMessage::V0(message) = &transaction.message else { panic!("not a v0 message") };
+        // The payer, the bundle and the invoked program stay in the message
+        assert_eq!(message.account_keys.len(), 3);
+        assert_eq!(transaction.verify_with_results(), vec![true]);
+    }
+}
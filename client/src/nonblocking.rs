@@ -0,0 +1,105 @@
+//! Account fetches over the async `RpcClient`, at the client's commitment.
+//! `rpc` has the same functions for the blocking client.
+
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
+use solana_sdk::{
+    account::Account, address_lookup_table::AddressLookupTableAccount, pubkey::Pubkey, signature::Signature,
+};
+
+use bundle_manager::{Bundle, BundleInstructionRecord, BundleManager, Vault, WalletRegistry};
+
+use crate::{
+    accounts::{
+        bundle_records_config, decode_bundle, decode_bundle_records, decode_execution_log, decode_lookup_table,
+        decode_manager, decode_nonce, decode_record, decode_vault, decode_wallet_registry, ExecutionLogAccount,
+    },
+    events::{parse_events, TransactionEvents},
+    nonce::NonceAccount,
+    stats::BundleManagerStats,
+    FetchError,
+};
+
+/// The account at `address`, or `AccountNotFound`
+pub async fn fetch_account(rpc: &RpcClient, address: &Pubkey) -> Result<Account, FetchError> {
+    rpc.get_account_with_commitment(address, rpc.commitment()).await?
+        .value
+        .ok_or(FetchError::AccountNotFound(*address))
+}
+
+pub async fn fetch_manager(rpc: &RpcClient, address: &Pubkey) -> Result<BundleManager, FetchError> {
+    decode_manager(address, &fetch_account(rpc, address).await?)
+}
+
+/// The running totals of the manager at `address`, see `BundleManagerStats`
+pub async fn fetch_manager_stats(rpc: &RpcClient, address: &Pubkey) -> Result<BundleManagerStats, FetchError> {
+    Ok(BundleManagerStats::from(&fetch_manager(rpc, address).await?))
+}
+
+pub async fn fetch_bundle(rpc: &RpcClient, address: &Pubkey) -> Result<Bundle, FetchError> {
+    decode_bundle(address, &fetch_account(rpc, address).await?)
+}
+
+pub async fn fetch_record(rpc: &RpcClient, address: &Pubkey) -> Result<BundleInstructionRecord, FetchError> {
+    decode_record(address, &fetch_account(rpc, address).await?)
+}
+
+/// Every instruction record of `bundle`, at `bundle_address`, in the order
+/// ExecuteBundle takes them, see `decode_bundle_records`
+pub async fn fetch_bundle_records(
+    rpc: &RpcClient,
+    bundle_address: &Pubkey,
+    bundle: &Bundle,
+) -> Result<Vec<(Pubkey, BundleInstructionRecord)>, FetchError> {
+    let config = bundle_records_config(bundle_address);
+    let accounts = rpc.get_program_accounts_with_config(&bundle_manager::id(), config).await?;
+    decode_bundle_records(bundle_address, bundle, accounts)
+}
+
+/// The records at `addresses`, in their order, in a single request
+pub async fn fetch_records(rpc: &RpcClient, addresses: &[Pubkey]) -> Result<Vec<BundleInstructionRecord>, FetchError> {
+    let accounts = rpc.get_multiple_accounts_with_commitment(addresses, rpc.commitment()).await?.value;
+    addresses.iter()
+        .zip(accounts)
+        .map(|(address, account)| decode_record(address, &account.ok_or(FetchError::AccountNotFound(*address))?))
+        .collect()
+}
+
+pub async fn fetch_execution_log(rpc: &RpcClient, address: &Pubkey) -> Result<ExecutionLogAccount, FetchError> {
+    decode_execution_log(address, &fetch_account(rpc, address).await?)
+}
+
+pub async fn fetch_wallet_registry(rpc: &RpcClient, address: &Pubkey) -> Result<WalletRegistry, FetchError> {
+    decode_wallet_registry(address, &fetch_account(rpc, address).await?)
+}
+
+/// The manager vault at `address`, see `pda::vault_address`
+pub async fn fetch_vault(rpc: &RpcClient, address: &Pubkey) -> Result<Vault, FetchError> {
+    decode_vault(address, &fetch_account(rpc, address).await?)
+}
+
+/// The address lookup table at `address`, e.g. a manager's `lookup_table`
+pub async fn fetch_lookup_table(rpc: &RpcClient, address: &Pubkey) -> Result<AddressLookupTableAccount, FetchError> {
+    decode_lookup_table(address, &fetch_account(rpc, address).await?)
+}
+
+/// The nonce account at `address`, e.g. a wallet's `nonce::nonce_address`
+pub async fn fetch_nonce(rpc: &RpcClient, address: &Pubkey) -> Result<NonceAccount, FetchError> {
+    decode_nonce(address, &fetch_account(rpc, address).await?)
+}
+
+/// The events of the transaction `signature`, at the client's commitment,
+/// which `getTransaction` needs to be at least confirmed
+pub async fn fetch_transaction_events(rpc: &RpcClient, signature: &Signature) -> Result<TransactionEvents, FetchError> {
+    let config = RpcTransactionConfig {
+        commitment: Some(rpc.commitment()),
+        max_supported_transaction_version: Some(0),
+        ..Default::default()
+    };
+    let meta = rpc.get_transaction_with_config(signature, config).await?.transaction.meta;
+    let logs: Option<Vec<String>> = meta.as_ref().and_then(|meta| meta.log_messages.clone().into());
+    Ok(TransactionEvents {
+        signature: signature.to_string(),
+        failed: meta.is_some_and(|meta| meta.err.is_some()),
+        events: parse_events(&logs.unwrap_or_default()),
+    })
+}
@@ -0,0 +1,161 @@
+//! Durable nonces, for bundles signed now and submitted later.
+//!
+//! A transaction built against a recent blockhash stops landing about a
+//! minute after that blockhash, too soon for a bundle pre-signed ahead of,
+//! say, a token launch. A transaction whose first instruction advances a
+//! nonce account may instead use the nonce's stored value as its blockhash,
+//! and stays valid until that nonce is advanced. Every transaction of a Jito
+//! bundle needs a nonce account of its own, so the bot keeps one per wallet,
+//! at an address derived from its authority and the wallet index
+//! (`nonce_address`). The tip transaction `jito::assemble_bundle` appends is
+//! signed at submission, against a fresh blockhash.
+//!
+//! `PendingBundle` keeps the signed transactions together with the nonces
+//! they were built against; once any of those nonces has moved on, the
+//! bundle can no longer land and has to be signed again.
+//!
+//! ```ignore
+//! let lamports = rpc.get_minimum_balance_for_rent_exemption(NONCE_ACCOUNT_LEN)?;
+//! // ... once: send create_nonce_accounts(&payer, &authority, &[0, 1], lamports)
+//! let nonce = rpc::fetch_nonce(&rpc, &nonce_address(&authority, 0))?;
+//! let transaction = durable_transaction(&authority, &nonce, &[execute], &tables, &[&authority_keypair])?;
+//! let pending = PendingBundle { bundle, transactions: vec![transaction], nonces: vec![nonce] };
+//! // ... later, before sending:
+//! let current = [rpc::fetch_nonce(&rpc, &pending.nonces[0].address)?];
+//! assert!(pending.is_current(&current));
+//! ```
+
+use solana_sdk::{
+    address_lookup_table::AddressLookupTableAccount,
+    hash::Hash,
+    instruction::Instruction,
+    nonce,
+    pubkey::Pubkey,
+    signer::signers::Signers,
+    system_instruction, system_program,
+    transaction::VersionedTransaction,
+};
+
+use crate::lookup_table::{v0_transaction, V0TransactionError};
+
+/// Size of a nonce account; fund it with the rent exemption for this many bytes
+pub const NONCE_ACCOUNT_LEN: usize = nonce::State::size();
+
+/// An initialized nonce account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NonceAccount {
+    pub address: Pubkey,
+    /// Signs the instruction that advances the nonce
+    pub authority: Pubkey,
+    /// The stored nonce, used as the blockhash of transactions built against it
+    pub blockhash: Hash,
+}
+
+/// Seed of the nonce account of wallet `wallet_index`
+pub fn nonce_seed(wallet_index: u8) -> String {
+    format!("nonce-{wallet_index}")
+}
+
+/// Address of the nonce account `authority` keeps for wallet `wallet_index`
+pub fn nonce_address(authority: &Pubkey, wallet_index: u8) -> Pubkey {
+    Pubkey::create_with_seed(authority, &nonce_seed(wallet_index), &system_program::id())
+        .expect("nonce seeds are short")
+}
+
+/// Instructions creating and initializing a nonce account for each of
+/// `wallet_indexes`, funded with `lamports` each by `payer`. `authority` is
+/// the base of every address and so signs too.
+pub fn create_nonce_accounts(
+    payer: &Pubkey,
+    authority: &Pubkey,
+    wallet_indexes: &[u8],
+    lamports: u64,
+) -> Vec<Instruction> {
+    wallet_indexes.iter()
+        .flat_map(|&wallet_index| {
+            system_instruction::create_nonce_account_with_seed(
+                payer,
+                &nonce_address(authority, wallet_index),
+                authority,
+                &nonce_seed(wallet_index),
+                authority,
+                lamports,
+            )
+        })
+        .collect()
+}
+
+/// AdvanceNonceAccount for the nonce account of wallet `wallet_index`, which
+/// invalidates every transaction signed against its current value
+pub fn advance_nonce(authority: &Pubkey, wallet_index: u8) -> Instruction {
+    system_instruction::advance_nonce_account(&nonce_address(authority, wallet_index), authority)
+}
+
+/// A v0 transaction paid by `payer` running `instructions` against `nonce`:
+/// the nonce is advanced first and its stored value is the blockhash.
+/// `signers` must include the payer and the nonce authority. Leave the nonce
+/// account out of `tables`, the runtime only finds it among the message's
+/// own accounts.
+pub fn durable_transaction<T: Signers + ?Sized>(
+    payer: &Pubkey,
+    nonce: &NonceAccount,
+    instructions: &[Instruction],
+    tables: &[AddressLookupTableAccount],
+    signers: &T,
+) -> Result<VersionedTransaction, V0TransactionError> {
+    let mut durable = Vec::with_capacity(instructions.len() + 1);
+    durable.push(system_instruction::advance_nonce_account(&nonce.address, &nonce.authority));
+    durable.extend_from_slice(instructions);
+    v0_transaction(payer, &durable, tables, signers, nonce.blockhash)
+}
+
+/// A bundle's pre-signed transactions and the nonces they were signed against
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingBundle {
+    /// The bundle account the transactions execute
+    pub bundle: Pubkey,
+    pub transactions: Vec<VersionedTransaction>,
+    pub nonces: Vec<NonceAccount>,
+}
+
+impl PendingBundle {
+    /// Whether the transactions can still land: every nonce in `current`,
+    /// freshly fetched, still holds the value it was signed against. A nonce
+    /// missing from `current` counts as moved on.
+    pub fn is_current(&self, current: &[NonceAccount]) -> bool {
+        self.nonces.iter().all(|nonce| current.contains(nonce))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{message::VersionedMessage, signature::Keypair, signer::Signer};
+
+    #[test]
+    fn transactions_advance_their_nonce_first() {
+        let authority = Keypair::new();
+        let nonce = NonceAccount {
+            address: nonce_address(&authority.pubkey(), 3),
+            authority: authority.pubkey(),
+            blockhash: Hash::new_unique(),
+        };
+        assert_eq!(create_nonce_accounts(&authority.pubkey(), &authority.pubkey(), &[3, 4], 1).len(), 4);
+
+        let transfer = system_instruction::transfer(&authority.pubkey(), &Pubkey::new_unique(), 1);
+        let transaction = durable_transaction(&authority.pubkey(), &nonce, &[transfer], &[], &[&authority]).unwrap();
+        let VersionedMessage::V0(message) = &transaction.message else { panic!("not a v0 message") };
+        assert_eq!(message.recent_blockhash, nonce.blockhash);
+        assert_eq!(message.instructions.len(), 2);
+        let advance = &message.instructions[0];
+        assert_eq!(message.account_keys[advance.program_id_index as usize], system_program::id());
+        assert_eq!(message.account_keys[advance.accounts[0] as usize], nonce.address);
+        assert_eq!(transaction.verify_with_results(), vec![true]);
+
+        let pending =
+            PendingBundle { bundle: Pubkey::new_unique(), transactions: vec![transaction], nonces: vec![nonce] };
+        assert!(pending.is_current(&[nonce]));
+        assert!(!pending.is_current(&[NonceAccount { blockhash: Hash::new_unique(), ..nonce }]));
+        assert!(!pending.is_current(&[]));
+    }
+}
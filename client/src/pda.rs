@@ -0,0 +1,45 @@
+//! Addresses of the program's accounts, derived for the deployed program id.
+//! The bumps are dropped; the `bundle_manager::find_*` helpers return them.
+
+use solana_sdk::pubkey::Pubkey;
+
+use bundle_manager::{
+    find_bundle_address, find_execution_log_address, find_instruction_record_address, find_manager_address,
+    find_vault_address, find_wallet_address, find_wallet_registry_address, id,
+};
+
+/// The manager of `authority` at `manager_index`
+pub fn manager_address(authority: &Pubkey, manager_index: u16) -> Pubkey {
+    find_manager_address(&id(), authority, manager_index).0
+}
+
+/// The bundle `authority` creates in `manager` with `client_nonce`
+pub fn bundle_address(manager: &Pubkey, authority: &Pubkey, client_nonce: u64) -> Pubkey {
+    find_bundle_address(&id(), manager, authority, client_nonce).0
+}
+
+/// The record AddInstruction creates for `wallet_index` while the bundle's
+/// `next_record_index` is `record_index`
+pub fn record_address(bundle: &Pubkey, wallet_index: u8, record_index: u16) -> Pubkey {
+    find_instruction_record_address(&id(), bundle, wallet_index, record_index).0
+}
+
+/// The wallet PDA at `wallet_index`, shared by every bundle of `manager`
+pub fn wallet_address(manager: &Pubkey, wallet_index: u8) -> Pubkey {
+    find_wallet_address(&id(), manager, wallet_index).0
+}
+
+/// The wallet registry of `manager`
+pub fn wallet_registry_address(manager: &Pubkey) -> Pubkey {
+    find_wallet_registry_address(&id(), manager).0
+}
+
+/// The vault of `manager`
+pub fn vault_address(manager: &Pubkey) -> Pubkey {
+    find_vault_address(&id(), manager).0
+}
+
+/// The execution log of `manager`
+pub fn execution_log_address(manager: &Pubkey) -> Pubkey {
+    find_execution_log_address(&id(), manager).0
+}
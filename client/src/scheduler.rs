@@ -0,0 +1,167 @@
+//! Firing scheduled bundles once they become executable.
+//!
+//! A bundle created through `with_execute_after` cannot execute before its
+//! `execute_after` timestamp by the cluster's clock, which can drift from the
+//! bot's own. `Scheduler` tracks such bundles over the async `RpcClient`,
+//! reads their state together with the Clock sysvar on every poll, and hands
+//! each bundle to a callback as soon as it is eligible. Bundles that stop
+//! waiting some other way, executed, cancelled or closed, are dropped.
+//!
+//! ```ignore
+//! let mut scheduler = Scheduler::new(&rpc, Duration::from_secs(1));
+//! scheduler.schedule(bundle);
+//! scheduler.run(|address, bundle| async move { send_execute(address, bundle).await }).await?;
+//! ```
+
+use std::{future::Future, time::Duration};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{clock::Clock, program_error::ProgramError, pubkey::Pubkey, sysvar};
+
+use bundle_manager::{Bundle, BundleStatus};
+
+use crate::{accounts::decode_bundle, nonblocking::fetch_account, FetchError};
+
+/// Most accounts one `getMultipleAccounts` request returns
+const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+
+/// The cluster's Unix timestamp, the one ExecuteBundle compares
+/// `execute_after` against
+pub async fn cluster_time(rpc: &RpcClient) -> Result<i64, FetchError> {
+    let address = sysvar::clock::id();
+    let account = fetch_account(rpc, &address).await?;
+    let clock: Clock = bincode::deserialize(&account.data)
+        .map_err(|_| FetchError::Decode { address, error: ProgramError::InvalidAccountData })?;
+    Ok(clock.unix_timestamp)
+}
+
+/// Whether `bundle` can execute at `now`: not yet run, every planned
+/// instruction added and its scheduled time reached
+pub fn is_eligible(bundle: &Bundle, now: i64) -> bool {
+    bundle.status == BundleStatus::Created
+        && bundle.added_instructions() == bundle.total_instructions()
+        && bundle.execute_after <= now
+}
+
+/// Bundles waiting for their scheduled time
+pub struct Scheduler<'a> {
+    rpc: &'a RpcClient,
+    pending: Vec<Pubkey>,
+    /// Time between polls while bundles are pending
+    pub poll_interval: Duration,
+}
+
+impl<'a> Scheduler<'a> {
+    pub fn new(rpc: &'a RpcClient, poll_interval: Duration) -> Self {
+        Self { rpc, pending: Vec::new(), poll_interval }
+    }
+
+    /// Tracks `bundle` until it is eligible or stops waiting
+    pub fn schedule(&mut self, bundle: Pubkey) {
+        if !self.pending.contains(&bundle) {
+            self.pending.push(bundle);
+        }
+    }
+
+    pub fn pending(&self) -> &[Pubkey] {
+        &self.pending
+    }
+
+    /// Reads every pending bundle once and returns the ones now eligible,
+    /// which are no longer tracked. A bundle still being built or scheduled
+    /// for later stays pending; one that is gone or no longer Created is
+    /// dropped.
+    pub async fn poll(&mut self) -> Result<Vec<(Pubkey, Bundle)>, FetchError> {
+        if self.pending.is_empty() {
+            return Ok(Vec::new());
+        }
+        let now = cluster_time(self.rpc).await?;
+        let mut eligible = Vec::new();
+        let mut still_pending = Vec::new();
+        for addresses in self.pending.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+            let accounts = self.rpc.get_multiple_accounts_with_commitment(addresses, self.rpc.commitment()).await?;
+            for (address, account) in addresses.iter().zip(accounts.value) {
+                let Some(account) = account else { continue };
+                let bundle = decode_bundle(address, &account)?;
+                if is_eligible(&bundle, now) {
+                    eligible.push((*address, bundle));
+                } else if bundle.status == BundleStatus::Created {
+                    still_pending.push(*address);
+                }
+            }
+        }
+        self.pending = still_pending;
+        Ok(eligible)
+    }
+
+    /// Polls every `poll_interval` until nothing is pending, awaiting `fire`
+    /// for each bundle as it becomes eligible. Stops at the first error a
+    /// poll or `fire` returns; bundles not yet fired stay pending.
+    pub async fn run<F, Fut, E>(&mut self, mut fire: F) -> Result<(), E>
+    where
+        F: FnMut(Pubkey, Bundle) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+        E: From<FetchError>,
+    {
+        while !self.pending.is_empty() {
+            for (address, bundle) in self.poll().await? {
+                fire(address, bundle).await?;
+            }
+            if !self.pending.is_empty() {
+                tokio::time::sleep(self.poll_interval).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bundle_manager::{AccountType, EXECUTED_MASK_LEN};
+
+    fn bundle(instructions_added: u8, execute_after: i64) -> Bundle {
+        Bundle {
+            account_type: AccountType::Bundle,
+            version: Bundle::VERSION,
+            manager: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            bundle_id: 0,
+            created_at: 1_000,
+            execution_started_at: 0,
+            execution_completed_at: 0,
+            wallet_count: 1,
+            category: 0,
+            wallet_indexes: vec![0],
+            instructions_per_wallet: vec![2],
+            instructions_added: vec![instructions_added],
+            status: BundleStatus::Created,
+            priority_fee: 0,
+            on_failure: None,
+            funding: None,
+            expected_net_lamports: 0,
+            last_attempt_slot: 0,
+            last_executed_index: None,
+            executed_mask: [0; EXECUTED_MASK_LEN],
+            next_record_index: instructions_added as u16,
+            seeds: None,
+            compute_unit_limit: 0,
+            execute_after,
+            max_retries: None,
+            retry_count: 0,
+            expires_at: 0,
+            spend_limits: None,
+        }
+    }
+
+    #[test]
+    fn bundles_are_eligible_once_built_and_due() {
+        assert!(is_eligible(&bundle(2, 0), 1_000));
+        assert!(is_eligible(&bundle(2, 2_000), 2_000));
+        assert!(!is_eligible(&bundle(2, 2_000), 1_999));
+        assert!(!is_eligible(&bundle(1, 0), 2_000), "an instruction is still missing");
+
+        let executed = Bundle { status: BundleStatus::Executed, ..bundle(2, 0) };
+        assert!(!is_eligible(&executed, 2_000));
+    }
+}
@@ -0,0 +1,407 @@
+//! Simulating a bundle's stored instructions before paying to execute it.
+//!
+//! ExecuteBundle with `dry_run` set (`simulate_bundle` among the builders)
+//! checks the bundle's structure on chain without invoking anything. What it
+//! cannot tell is whether the instructions themselves succeed. `simulate`
+//! reads the bundle's records back from the chain and runs each wallet's
+//! instructions, in execution order, through `simulateTransaction` with
+//! signature checks off, so the wallet PDAs sign as ExecuteBundle would make
+//! them. The bundle authority pays, and creates every `create_if_missing`
+//! account right before the instruction that needs it.
+//!
+//! Each wallet's simulation starts from the current chain state, so an
+//! instruction relying on what another wallet's instructions do first can
+//! fail here and still succeed in the bundle. Compute units are those of the
+//! instructions alone; ExecuteBundle adds its own overhead on top.
+//!
+//! ```ignore
+//! let simulation = simulation::simulate(&rpc, &bundle)?;
+//! if let Some((position, error)) = &simulation.failure {
+//!     eprintln!("{error}: {:#?}", simulation.instructions[*position].logs);
+//! }
+//! ```
+
+use solana_client::{
+    rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig, rpc_response::RpcSimulateTransactionResult,
+};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program, sysvar,
+    transaction::{Transaction, TransactionError},
+};
+
+use bundle_manager::{
+    client::compute_budget_instructions, Bundle, BundleInstructionRecord, CreateIfMissing,
+    ASSOCIATED_TOKEN_PROGRAM_ID, MAX_COMPUTE_UNIT_LIMIT,
+};
+
+use crate::{rpc, FetchError};
+
+/// How one stored instruction fared
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstructionSimulation {
+    pub record: Pubkey,
+    pub wallet_index: u8,
+    /// Units the instruction consumed, creating its `create_if_missing`
+    /// account included; `None` when it did not run
+    pub compute_units: Option<u64>,
+    /// The program logs of the instruction and its inner instructions
+    pub logs: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BundleSimulation {
+    /// Every record still to execute, in execution order
+    pub instructions: Vec<InstructionSimulation>,
+    /// Position in `instructions` of the first to fail, and the error. An
+    /// error of a wallet's transaction as a whole, e.g. an authority short
+    /// of lamports, is put on the wallet's first instruction.
+    pub failure: Option<(usize, TransactionError)>,
+}
+
+impl BundleSimulation {
+    /// Units all the instructions that ran consumed
+    pub fn compute_units(&self) -> u64 {
+        self.instructions.iter().filter_map(|instruction| instruction.compute_units).sum()
+    }
+}
+
+/// One wallet's instructions as a transaction to simulate
+struct WalletTransaction {
+    transaction: Transaction,
+    /// For each instruction of the transaction, the position of the record
+    /// it belongs to, `None` for the compute budget
+    positions: Vec<Option<usize>>,
+}
+
+// What ExecuteBundle does for `spec` before invoking the instruction, paid by
+// the bundle authority
+fn create_instruction(spec: &CreateIfMissing, authority: &Pubkey, rent: &Rent) -> Instruction {
+    match spec {
+        CreateIfMissing::SystemAccount { address, space, owner } => {
+            let lamports = rent.minimum_balance(*space as usize);
+            system_instruction::create_account(authority, address, lamports, *space, owner)
+        },
+        CreateIfMissing::AssociatedTokenAccount { wallet, mint, token_program } => Instruction {
+            program_id: ASSOCIATED_TOKEN_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(*authority, true),
+                AccountMeta::new(spec.address(), false),
+                AccountMeta::new_readonly(*wallet, false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(*token_program, false),
+            ],
+            data: vec![1],
+        },
+    }
+}
+
+// `records` in the order ExecuteBundle runs them: wallet by wallet, as the
+// bundle lists its `wallet_indexes`, each wallet's in the order they were added
+fn in_execution_order(
+    bundle: &Bundle,
+    mut records: Vec<(Pubkey, BundleInstructionRecord)>,
+) -> Vec<(Pubkey, BundleInstructionRecord)> {
+    records.sort_by_key(|(_, record)| bundle.wallet_indexes.iter().position(|wallet| *wallet == record.wallet_index));
+    records
+}
+
+/// One transaction per wallet, in order of each wallet's first record,
+/// running the wallet's `records` that have not executed yet
+fn wallet_transactions(
+    bundle: &Bundle,
+    records: &[(Pubkey, BundleInstructionRecord)],
+    rent: &Rent,
+) -> Result<Vec<WalletTransaction>, FetchError> {
+    let mut wallets: Vec<u8> = Vec::new();
+    for (_, record) in records.iter().filter(|(_, record)| !record.executed) {
+        if !wallets.contains(&record.wallet_index) {
+            wallets.push(record.wallet_index);
+        }
+    }
+    let [set_limit, _] = compute_budget_instructions(MAX_COMPUTE_UNIT_LIMIT, 0);
+    wallets.into_iter()
+        .map(|wallet_index| {
+            let mut instructions = vec![set_limit.clone()];
+            let mut positions = vec![None];
+            let pending = records.iter().filter(|(_, record)| !record.executed);
+            for (position, (address, record)) in pending.enumerate() {
+                if record.wallet_index != wallet_index {
+                    continue;
+                }
+                if let Some(spec) = &record.create_if_missing {
+                    instructions.push(create_instruction(spec, &bundle.authority, rent));
+                    positions.push(Some(position));
+                }
+                let instruction =
+                    record.instruction().map_err(|error| FetchError::Decode { address: *address, error })?;
+                instructions.push(instruction);
+                positions.push(Some(position));
+            }
+            let message = Message::new(&instructions, Some(&bundle.authority));
+            Ok(WalletTransaction { transaction: Transaction::new_unsigned(message), positions })
+        })
+        .collect()
+}
+
+/// Splits simulation logs by top-level instruction: each one's lines and
+/// the units it consumed, if its program reported them
+fn split_logs(logs: &[String]) -> Vec<(Vec<String>, Option<u64>)> {
+    let mut instructions: Vec<(Vec<String>, Option<u64>)> = Vec::new();
+    let mut depth = 0;
+    for line in logs {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["Program", _, "invoke", level] => {
+                depth = level.trim_matches(['[', ']']).parse().unwrap_or(depth + 1);
+                if depth == 1 {
+                    instructions.push((Vec::new(), None));
+                }
+            },
+            ["Program", _, "consumed", units, "of", _, "compute", "units"] if depth == 1 => {
+                if let Some((_, consumed)) = instructions.last_mut() {
+                    *consumed = units.parse().ok();
+                }
+            },
+            _ => {},
+        }
+        if let Some((lines, _)) = instructions.last_mut() {
+            lines.push(line.clone());
+        }
+        if matches!(words.as_slice(), ["Program", _, "success"] | ["Program", _, "failed:", ..]) {
+            depth -= 1;
+        }
+    }
+    instructions
+}
+
+/// Folds each wallet's simulation result into the bundle's
+fn collect(
+    records: &[(Pubkey, BundleInstructionRecord)],
+    results: Vec<(WalletTransaction, RpcSimulateTransactionResult)>,
+) -> BundleSimulation {
+    let mut instructions: Vec<InstructionSimulation> = records.iter()
+        .filter(|(_, record)| !record.executed)
+        .map(|(address, record)| InstructionSimulation {
+            record: *address,
+            wallet_index: record.wallet_index,
+            compute_units: None,
+            logs: Vec::new(),
+        })
+        .collect();
+    let mut failure: Option<(usize, TransactionError)> = None;
+    for (wallet, result) in results {
+        let logs = split_logs(result.logs.as_deref().unwrap_or_default());
+        for (position, (lines, units)) in wallet.positions.iter().zip(logs) {
+            let Some(instruction) = position.map(|position| &mut instructions[position]) else { continue };
+            instruction.logs.extend(lines);
+            if let Some(units) = units {
+                instruction.compute_units = Some(instruction.compute_units.unwrap_or_default() + units);
+            }
+        }
+        let Some(error) = result.err else { continue };
+        let position = match error {
+            TransactionError::InstructionError(index, _) => wallet.positions.get(index as usize).copied().flatten(),
+            _ => None,
+        };
+        let position = position.or_else(|| wallet.positions.iter().copied().flatten().next()).unwrap_or_default();
+        if failure.as_ref().is_none_or(|(first, _)| position < *first) {
+            failure = Some((position, error));
+        }
+    }
+    BundleSimulation { instructions, failure }
+}
+
+fn simulation_config() -> RpcSimulateTransactionConfig {
+    RpcSimulateTransactionConfig { sig_verify: false, replace_recent_blockhash: true, ..Default::default() }
+}
+
+fn decode_rent(data: &[u8]) -> Result<Rent, FetchError> {
+    bincode::deserialize(data)
+        .map_err(|_| FetchError::Decode { address: sysvar::rent::id(), error: ProgramError::InvalidAccountData })
+}
+
+/// Simulates the instructions of the bundle at `bundle_address` that have
+/// not executed yet
+pub fn simulate(rpc: &RpcClient, bundle_address: &Pubkey) -> Result<BundleSimulation, FetchError> {
+    let bundle = rpc::fetch_bundle(rpc, bundle_address)?;
+    let records = in_execution_order(&bundle, rpc::fetch_bundle_records(rpc, bundle_address, &bundle)?);
+    let rent = decode_rent(&rpc::fetch_account(rpc, &sysvar::rent::id())?.data)?;
+    let results = wallet_transactions(&bundle, &records, &rent)?
+        .into_iter()
+        .map(|wallet| {
+            let result = rpc.simulate_transaction_with_config(&wallet.transaction, simulation_config())?.value;
+            Ok((wallet, result))
+        })
+        .collect::<Result<_, FetchError>>()?;
+    Ok(collect(&records, results))
+}
+
+/// `simulate` over the async client
+pub async fn simulate_nonblocking(
+    rpc: &solana_client::nonblocking::rpc_client::RpcClient,
+    bundle_address: &Pubkey,
+) -> Result<BundleSimulation, FetchError> {
+    use crate::nonblocking;
+
+    let bundle = nonblocking::fetch_bundle(rpc, bundle_address).await?;
+    let records = in_execution_order(&bundle, nonblocking::fetch_bundle_records(rpc, bundle_address, &bundle).await?);
+    let rent = decode_rent(&nonblocking::fetch_account(rpc, &sysvar::rent::id()).await?.data)?;
+    let mut results = Vec::new();
+    for wallet in wallet_transactions(&bundle, &records, &rent)? {
+        let result = rpc.simulate_transaction_with_config(&wallet.transaction, simulation_config()).await?.value;
+        results.push((wallet, result));
+    }
+    Ok(collect(&records, results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bundle_manager::{AccountType, BundleStatus, InstructionAccountMeta, SPL_TOKEN_PROGRAM_ID, EXECUTED_MASK_LEN};
+    use solana_sdk::instruction::InstructionError;
+
+    fn record(wallet_index: u8, create: bool) -> (Pubkey, BundleInstructionRecord) {
+        let wallet = Pubkey::new_unique();
+        let record = BundleInstructionRecord {
+            account_type: AccountType::InstructionRecord,
+            version: BundleInstructionRecord::VERSION,
+            bundle: Pubkey::new_unique(),
+            wallet_index,
+            program_id: Pubkey::new_unique(),
+            instruction_data: vec![wallet_index],
+            accounts: vec![InstructionAccountMeta { pubkey: wallet, is_signer: true, is_writable: true }],
+            executed: false,
+            compressed: false,
+            reserved: 0,
+            create_if_missing: create.then(|| CreateIfMissing::AssociatedTokenAccount {
+                wallet,
+                mint: Pubkey::new_unique(),
+                token_program: SPL_TOKEN_PROGRAM_ID,
+            }),
+            last_error: None,
+        };
+        (Pubkey::new_unique(), record)
+    }
+
+    fn bundle() -> Bundle {
+        Bundle {
+            account_type: AccountType::Bundle,
+            version: Bundle::VERSION,
+            manager: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            bundle_id: 0,
+            created_at: 0,
+            execution_started_at: 0,
+            execution_completed_at: 0,
+            wallet_count: 2,
+            category: 0,
+            wallet_indexes: vec![0, 1],
+            instructions_per_wallet: vec![2, 1],
+            instructions_added: vec![2, 1],
+            status: BundleStatus::Created,
+            priority_fee: 0,
+            on_failure: None,
+            funding: None,
+            expected_net_lamports: 0,
+            last_attempt_slot: 0,
+            last_executed_index: None,
+            executed_mask: [0; EXECUTED_MASK_LEN],
+            next_record_index: 3,
+            seeds: None,
+            compute_unit_limit: 0,
+            execute_after: 0,
+            max_retries: None,
+            retry_count: 0,
+            expires_at: 0,
+            spend_limits: None,
+        }
+    }
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn logs_split_by_top_level_instruction() {
+        let logs = logs(&[
+            "Program ComputeBudget111111111111111111111111111111 invoke [1]",
+            "Program ComputeBudget111111111111111111111111111111 success",
+            "Program Swap111 invoke [1]",
+            "Program log: Instruction: Route",
+            "Program Token111 invoke [2]",
+            "Program Token111 consumed 4645 of 1380000 compute units",
+            "Program Token111 success",
+            "Program Swap111 consumed 52000 of 1399850 compute units",
+            "Program Swap111 success",
+            "Program Swap111 invoke [1]",
+            "Program Swap111 consumed 900 of 1347850 compute units",
+            "Program Swap111 failed: custom program error: 0x1771",
+        ]);
+        let split = split_logs(&logs);
+        assert_eq!(split.iter().map(|(_, units)| *units).collect::<Vec<_>>(), vec![None, Some(52_000), Some(900)]);
+        assert_eq!(split[1].0.len(), 7);
+        assert_eq!(split[2].0.last().unwrap(), "Program Swap111 failed: custom program error: 0x1771");
+    }
+
+    #[test]
+    fn failures_map_back_to_records() {
+        let bundle = bundle();
+        let records = vec![record(0, false), record(1, true), record(0, false)];
+        let wallets = wallet_transactions(&bundle, &records, &Rent::default()).unwrap();
+        assert_eq!(wallets[0].positions, vec![None, Some(0), Some(2)]);
+        // Wallet 1's token account is created right before its instruction
+        assert_eq!(wallets[1].positions, vec![None, Some(1), Some(1)]);
+        assert_eq!(wallets[1].transaction.message.account_keys[0], bundle.authority);
+
+        let result = |err, lines: &[&str]| RpcSimulateTransactionResult {
+            err,
+            logs: Some(logs(lines)),
+            accounts: None,
+            units_consumed: None,
+            return_data: None,
+            inner_instructions: None,
+        };
+        let ok = result(None, &[
+            "Program ComputeBudget111 invoke [1]",
+            "Program ComputeBudget111 success",
+            "Program A invoke [1]",
+            "Program A consumed 100 of 200 compute units",
+            "Program A success",
+            "Program B invoke [1]",
+            "Program B consumed 50 of 200 compute units",
+            "Program B success",
+        ]);
+        let error = TransactionError::InstructionError(2, InstructionError::Custom(1));
+        let failed = result(Some(error.clone()), &[
+            "Program ComputeBudget111 invoke [1]",
+            "Program ComputeBudget111 success",
+            "Program ATA invoke [1]",
+            "Program ATA consumed 20 of 200 compute units",
+            "Program ATA success",
+            "Program C invoke [1]",
+            "Program C consumed 10 of 200 compute units",
+            "Program C failed: custom program error: 0x1",
+        ]);
+        let simulation = collect(&records, wallets.into_iter().zip([ok, failed]).collect());
+        let units: Vec<_> = simulation.instructions.iter().map(|instruction| instruction.compute_units).collect();
+        assert_eq!(units, vec![Some(100), Some(30), Some(50)]);
+        assert_eq!(simulation.compute_units(), 180);
+        assert_eq!(simulation.failure, Some((1, error)));
+    }
+
+    #[test]
+    fn records_run_wallet_by_wallet() {
+        let bundle = Bundle { wallet_indexes: vec![1, 0], ..bundle() };
+        let records = vec![record(0, false), record(1, false), record(0, false)];
+        let addresses: Vec<Pubkey> = records.iter().map(|(address, _)| *address).collect();
+        let ordered: Vec<Pubkey> =
+            in_execution_order(&bundle, records).into_iter().map(|(address, _)| address).collect();
+        assert_eq!(ordered, vec![addresses[1], addresses[0], addresses[2]]);
+    }
+}
@@ -0,0 +1,89 @@
+//! A manager's running totals, read off its account.
+//!
+//! ExecuteBundle adds to the manager's bundle counters and its execution
+//! totals on every call; `BundleManagerStats` puts them together with the
+//! rates a dashboard or bot wants from them. ResetStats clears only the
+//! bundle counters, so the execution totals cover the manager's whole life.
+//!
+//! ```ignore
+//! let stats = rpc::fetch_manager_stats(&rpc, &manager)?;
+//! println!("{} executed, {:.1}% failed", stats.bundles_executed, stats.failure_rate() * 100.0);
+//! ```
+
+use bundle_manager::BundleManager;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BundleManagerStats {
+    /// Bundles created and not yet executed, failed or cancelled
+    pub active_bundles: u16,
+    /// Bundles that ran every instruction, since the last ResetStats
+    pub bundles_executed: u32,
+    /// Bundles that failed, since the last ResetStats
+    pub bundles_failed: u32,
+    /// Failures since the last bundle that executed
+    pub consecutive_failures: u16,
+    /// Stored instructions ExecuteBundle has run
+    pub instructions_executed: u64,
+    /// Compute units ExecuteBundle calls have used
+    pub compute_units: u64,
+    /// Priority fees, in lamports, ExecuteBundle calls have requested
+    pub priority_fees_paid: u64,
+    pub is_paused: bool,
+}
+
+impl BundleManagerStats {
+    /// Share of finished bundles that failed, 0 before any finished
+    pub fn failure_rate(&self) -> f64 {
+        let finished = u64::from(self.bundles_executed) + u64::from(self.bundles_failed);
+        if finished == 0 {
+            return 0.0;
+        }
+        self.bundles_failed as f64 / finished as f64
+    }
+
+    /// Compute units used per instruction run, if any has run
+    pub fn compute_units_per_instruction(&self) -> Option<u64> {
+        self.compute_units.checked_div(self.instructions_executed)
+    }
+
+    /// Priority fee, in lamports, per instruction run, if any has run
+    pub fn priority_fee_per_instruction(&self) -> Option<u64> {
+        self.priority_fees_paid.checked_div(self.instructions_executed)
+    }
+}
+
+impl From<&BundleManager> for BundleManagerStats {
+    fn from(manager: &BundleManager) -> Self {
+        Self {
+            active_bundles: manager.active_bundles,
+            bundles_executed: manager.total_bundles_executed,
+            bundles_failed: manager.total_bundles_failed,
+            consecutive_failures: manager.consecutive_failures,
+            instructions_executed: manager.total_instructions_executed,
+            compute_units: manager.total_compute_units,
+            priority_fees_paid: manager.total_priority_fees_paid,
+            is_paused: manager.is_paused,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_are_empty_until_something_ran() {
+        let mut stats = BundleManagerStats::default();
+        assert_eq!(stats.failure_rate(), 0.0);
+        assert_eq!(stats.compute_units_per_instruction(), None);
+
+        stats.bundles_executed = 3;
+        stats.bundles_failed = 1;
+        stats.instructions_executed = 8;
+        stats.compute_units = 12_000;
+        stats.priority_fees_paid = 800;
+        assert_eq!(stats.failure_rate(), 0.25);
+        assert_eq!(stats.compute_units_per_instruction(), Some(1_500));
+        assert_eq!(stats.priority_fee_per_instruction(), Some(100));
+    }
+}
@@ -0,0 +1,371 @@
+//! DEX swaps run from a manager's wallets, routed by Jupiter.
+//!
+//! `JupiterClient` fetches a quote from Jupiter's swap API and the
+//! instructions that execute it for one wallet, whatever AMMs (Raydium,
+//! Orca, ...) the route goes through. `route_items` turns those instructions
+//! into the `BatchItem`s a bundle stores for the wallet's index. The wallet
+//! signs inside ExecuteBundle, so ask for the instructions with the wallet's
+//! own address (`pda::wallet_address`, or its registered address) as the
+//! user. Compute budget instructions are left out: they cannot run through
+//! CPI, and ExecuteBundle's transaction sets its own budget.
+//!
+//! A bundle plans at most `MAX_BUNDLE_INSTRUCTIONS` instructions, and fewer
+//! per wallet, so `plan_bundles` packs routes into as few bundles as their
+//! size allows. A route that does not fit in one bundle is split across
+//! consecutive ones; those bundles run one after the other, not atomically.
+//!
+//! ```ignore
+//! let jupiter = JupiterClient::new(JUPITER_API_URL);
+//! let quote = jupiter.quote(&QuoteRequest { input_mint, output_mint, amount, slippage_bps: 50 }).await?;
+//! let swap = jupiter.swap_instructions(&quote, &pda::wallet_address(&manager, 3)).await?;
+//! for (nonce, planned) in plan_bundles(vec![route_items(3, &swap)], MAX_WALLET_INSTRUCTIONS).iter().enumerate() {
+//!     let create = create_bundle(&manager, nonce as u64, &authority, &planned.wallets(), 0, None, None);
+//!     let bundle = pda::bundle_address(&manager, &authority, nonce as u64);
+//!     let adds = planned.add_instructions(&manager, &bundle, &authority, 0, 1);
+//!     // ... send `create` and `adds`, then execute over `swap.lookup_tables`
+//! }
+//! ```
+
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use thiserror::Error;
+
+use bundle_manager::{
+    client::{batch_add_instruction, batch_item},
+    BatchItem, InstructionOptions, COMPUTE_BUDGET_PROGRAM_ID, MAX_BATCH_ITEMS, MAX_BUNDLE_INSTRUCTIONS,
+    MAX_WALLETS_PER_BUNDLE,
+};
+
+use crate::pda::record_address;
+
+/// Jupiter's public swap API
+pub const JUPITER_API_URL: &str = "https://quote-api.jup.ag/v6";
+
+/// Most instructions one wallet can plan in a bundle, CreateBundle counting
+/// them in a `u8`
+pub const MAX_WALLET_INSTRUCTIONS: usize = u8::MAX as usize;
+
+#[derive(Error, Debug)]
+pub enum SwapError {
+    #[error("Jupiter request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Jupiter error: {0}")]
+    Api(String),
+    #[error("Unexpected Jupiter response: {0}")]
+    UnexpectedResponse(Value),
+}
+
+/// What to swap: `amount` of `input_mint`, in its base units, for as much
+/// `output_mint` as the route gives
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuoteRequest {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub amount: u64,
+    /// Slippage the swap tolerates, in basis points of the quoted output
+    pub slippage_bps: u16,
+}
+
+/// A quoted route, kept whole so it can be handed back to Jupiter
+#[derive(Clone, Debug)]
+pub struct Quote {
+    pub in_amount: u64,
+    pub out_amount: u64,
+    /// Least output the swap accepts once slippage is taken off
+    pub min_out_amount: u64,
+    pub response: Value,
+}
+
+impl Quote {
+    fn from_response(response: Value) -> Result<Self, SwapError> {
+        // Jupiter sends amounts as strings, they can exceed JSON's safe integers
+        let amount = |field: &str| response.get(field).and_then(Value::as_str).and_then(|amount| amount.parse().ok());
+        match (amount("inAmount"), amount("outAmount"), amount("otherAmountThreshold")) {
+            (Some(in_amount), Some(out_amount), Some(min_out_amount)) => {
+                Ok(Self { in_amount, out_amount, min_out_amount, response })
+            },
+            _ => Err(SwapError::UnexpectedResponse(response)),
+        }
+    }
+}
+
+/// The instructions that execute a quote, in the order they run
+#[derive(Clone, Debug)]
+pub struct SwapInstructions {
+    /// Token accounts to create and SOL to wrap before the swap
+    pub setup: Vec<Instruction>,
+    pub swap: Instruction,
+    /// Unwraps SOL after the swap
+    pub cleanup: Option<Instruction>,
+    /// Lookup tables the route's accounts live in; ExecuteBundle usually
+    /// needs them to fit in its transaction (see `lookup_table`)
+    pub lookup_tables: Vec<Pubkey>,
+}
+
+impl SwapInstructions {
+    fn from_response(response: &Value) -> Result<Self, SwapError> {
+        let unexpected = || SwapError::UnexpectedResponse(response.clone());
+        let instructions = |field: &str| -> Result<Vec<Instruction>, SwapError> {
+            let Some(values) = response.get(field).and_then(Value::as_array) else { return Ok(Vec::new()) };
+            values.iter().map(|value| parse_instruction(value).ok_or_else(unexpected)).collect()
+        };
+        let setup = instructions("setupInstructions")?
+            .into_iter()
+            .filter(|instruction| instruction.program_id != COMPUTE_BUDGET_PROGRAM_ID)
+            .collect();
+        let swap = response.get("swapInstruction").and_then(parse_instruction).ok_or_else(unexpected)?;
+        let cleanup = match response.get("cleanupInstruction") {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(parse_instruction(value).ok_or_else(unexpected)?),
+        };
+        let lookup_tables = response.get("addressLookupTableAddresses")
+            .and_then(Value::as_array)
+            .map(|addresses| addresses.iter().map(parse_pubkey).collect::<Option<Vec<_>>>())
+            .unwrap_or(Some(Vec::new()))
+            .ok_or_else(unexpected)?;
+        Ok(Self { setup, swap, cleanup, lookup_tables })
+    }
+
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.setup.iter().chain([&self.swap]).chain(&self.cleanup)
+    }
+}
+
+fn parse_pubkey(value: &Value) -> Option<Pubkey> {
+    Pubkey::from_str(value.as_str()?).ok()
+}
+
+// An instruction as the swap API encodes it, with base64 data
+fn parse_instruction(value: &Value) -> Option<Instruction> {
+    let accounts = value.get("accounts")?
+        .as_array()?
+        .iter()
+        .map(|meta| {
+            Some(AccountMeta {
+                pubkey: parse_pubkey(meta.get("pubkey")?)?,
+                is_signer: meta.get("isSigner")?.as_bool()?,
+                is_writable: meta.get("isWritable")?.as_bool()?,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Instruction {
+        program_id: parse_pubkey(value.get("programId")?)?,
+        accounts,
+        data: STANDARD.decode(value.get("data")?.as_str()?).ok()?,
+    })
+}
+
+/// Quotes and swap instructions from a Jupiter swap API
+pub struct JupiterClient {
+    http: reqwest::Client,
+    api_url: String,
+}
+
+impl JupiterClient {
+    /// `api_url` is the API's base, e.g. `JUPITER_API_URL`
+    pub fn new(api_url: impl Into<String>) -> Self {
+        Self { http: reqwest::Client::new(), api_url: api_url.into() }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.api_url.trim_end_matches('/'), path)
+    }
+
+    // Error bodies come with a failure status, so the body is read first
+    async fn read(response: reqwest::Response) -> Result<Value, SwapError> {
+        let status = response.status();
+        let body: Value = response.json().await?;
+        match body.get("error") {
+            Some(error) => Err(SwapError::Api(error.as_str().map_or_else(|| error.to_string(), str::to_string))),
+            None if !status.is_success() => Err(SwapError::UnexpectedResponse(body)),
+            None => Ok(body),
+        }
+    }
+
+    pub async fn quote(&self, request: &QuoteRequest) -> Result<Quote, SwapError> {
+        let query = [
+            ("inputMint", request.input_mint.to_string()),
+            ("outputMint", request.output_mint.to_string()),
+            ("amount", request.amount.to_string()),
+            ("slippageBps", request.slippage_bps.to_string()),
+        ];
+        let response = self.http.get(self.url("quote")).query(&query).send().await?;
+        Quote::from_response(Self::read(response).await?)
+    }
+
+    /// The instructions that execute `quote` for `wallet`, which pays and
+    /// signs for the swap
+    pub async fn swap_instructions(&self, quote: &Quote, wallet: &Pubkey) -> Result<SwapInstructions, SwapError> {
+        let request = json!({
+            "quoteResponse": quote.response,
+            "userPublicKey": wallet.to_string(),
+            "wrapAndUnwrapSol": true,
+        });
+        let response = self.http.post(self.url("swap-instructions")).json(&request).send().await?;
+        SwapInstructions::from_response(&Self::read(response).await?)
+    }
+}
+
+/// The items storing `swap`'s instructions for `wallet_index`, in order
+pub fn route_items(wallet_index: u8, swap: &SwapInstructions) -> Vec<BatchItem> {
+    swap.instructions()
+        .map(|instruction| batch_item(wallet_index, instruction, InstructionOptions::default()))
+        .collect()
+}
+
+/// The instructions one bundle runs
+#[derive(Clone, Debug, Default)]
+pub struct PlannedBundle {
+    pub items: Vec<BatchItem>,
+}
+
+impl PlannedBundle {
+    /// CreateBundle's `wallets`: each wallet the items run for, in order of
+    /// first use, with its number of items
+    pub fn wallets(&self) -> Vec<(u8, u8)> {
+        let mut wallets: Vec<(u8, u8)> = Vec::new();
+        for item in &self.items {
+            match wallets.iter_mut().find(|(index, _)| *index == item.wallet_index) {
+                Some((_, count)) => *count += 1,
+                None => wallets.push((item.wallet_index, 1)),
+            }
+        }
+        wallets
+    }
+
+    fn fits(&self, item: &BatchItem, max_instructions: usize) -> bool {
+        let wallets = self.wallets();
+        let count = wallets.iter().find(|(index, _)| *index == item.wallet_index).map(|&(_, count)| count);
+        self.items.len() < max_instructions
+            && match count {
+                Some(count) => (count as usize) < MAX_WALLET_INSTRUCTIONS,
+                None => wallets.len() < MAX_WALLETS_PER_BUNDLE as usize,
+            }
+    }
+
+    /// BatchAddInstructions storing the items, `batch_size` of them per
+    /// instruction, into the bundle whose `next_record_index` is
+    /// `next_record_index`. Each goes in a transaction of its own; swap
+    /// instructions reference so many accounts that a batch of one is often
+    /// all a transaction holds.
+    pub fn add_instructions(
+        &self,
+        manager: &Pubkey,
+        bundle: &Pubkey,
+        authority: &Pubkey,
+        next_record_index: u16,
+        batch_size: usize,
+    ) -> Vec<Instruction> {
+        let mut record_index = next_record_index;
+        self.items.chunks(batch_size.clamp(1, MAX_BATCH_ITEMS))
+            .map(|batch| {
+                let items = batch.iter()
+                    .map(|item| {
+                        let record = record_address(bundle, item.wallet_index, record_index);
+                        record_index += 1;
+                        (record, item.clone())
+                    })
+                    .collect();
+                batch_add_instruction(manager, bundle, authority, items)
+            })
+            .collect()
+    }
+}
+
+/// Packs `routes` into bundles of at most `max_instructions` instructions,
+/// in order. A route that fits in no more than one bundle is never split,
+/// so it runs atomically; a route too large for one starts a bundle of its
+/// own and continues in the next ones.
+pub fn plan_bundles(routes: Vec<Vec<BatchItem>>, max_instructions: usize) -> Vec<PlannedBundle> {
+    let max_instructions = max_instructions.clamp(1, MAX_BUNDLE_INSTRUCTIONS);
+    let mut bundles: Vec<PlannedBundle> = Vec::new();
+    for route in routes {
+        let fits_whole = bundles.last().is_some_and(|bundle| {
+            let mut candidate = bundle.clone();
+            route.iter().all(|item| {
+                let fits = candidate.fits(item, max_instructions);
+                candidate.items.push(item.clone());
+                fits
+            })
+        });
+        if !fits_whole {
+            bundles.push(PlannedBundle::default());
+        }
+        for item in route {
+            if !bundles.last().unwrap().fits(&item, max_instructions) {
+                bundles.push(PlannedBundle::default());
+            }
+            bundles.last_mut().unwrap().items.push(item);
+        }
+    }
+    bundles.retain(|bundle| !bundle.items.is_empty());
+    bundles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(wallet_index: u8, len: usize) -> Vec<BatchItem> {
+        let instruction = Instruction::new_with_bytes(Pubkey::new_unique(), &[1, 2, 3], Vec::new());
+        vec![batch_item(wallet_index, &instruction, InstructionOptions::default()); len]
+    }
+
+    #[test]
+    fn swap_instructions_decode_without_the_compute_budget() {
+        let (program, wallet, table) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let instruction = |program: Pubkey| {
+            json!({
+                "programId": program.to_string(),
+                "accounts": [{ "pubkey": wallet.to_string(), "isSigner": true, "isWritable": true }],
+                "data": STANDARD.encode([9, 8, 7]),
+            })
+        };
+        let response = json!({
+            "computeBudgetInstructions": [instruction(COMPUTE_BUDGET_PROGRAM_ID)],
+            "setupInstructions": [instruction(program), instruction(COMPUTE_BUDGET_PROGRAM_ID)],
+            "swapInstruction": instruction(program),
+            "cleanupInstruction": null,
+            "addressLookupTableAddresses": [table.to_string()],
+        });
+        let swap = SwapInstructions::from_response(&response).unwrap();
+        assert_eq!(swap.instructions().count(), 2);
+        assert_eq!(swap.swap.accounts, vec![AccountMeta::new(wallet, true)]);
+        assert_eq!(swap.swap.data, vec![9, 8, 7]);
+        assert_eq!(swap.lookup_tables, vec![table]);
+
+        let items = route_items(4, &swap);
+        assert!(items.iter().all(|item| item.wallet_index == 4 && item.program_id == program));
+        assert!(SwapInstructions::from_response(&json!({ "setupInstructions": [] })).is_err());
+
+        let quote = json!({ "inAmount": "1000", "outAmount": "18446744073709551615", "otherAmountThreshold": "990" });
+        let quote = Quote::from_response(quote).unwrap();
+        assert_eq!((quote.in_amount, quote.out_amount, quote.min_out_amount), (1_000, u64::MAX, 990));
+    }
+
+    #[test]
+    fn routes_split_only_when_they_outgrow_a_bundle() {
+        let sizes = |bundles: &[PlannedBundle]| bundles.iter().map(|bundle| bundle.wallets()).collect::<Vec<_>>();
+        let bundles = plan_bundles(vec![route(0, 3), route(1, 3), route(0, 2)], 7);
+        assert_eq!(sizes(&bundles), vec![vec![(0, 3), (1, 3)], vec![(0, 2)]]);
+
+        let bundles = plan_bundles(vec![route(0, 2), route(1, 12), route(2, 1)], 5);
+        assert_eq!(sizes(&bundles), vec![vec![(0, 2)], vec![(1, 5)], vec![(1, 5)], vec![(1, 2), (2, 1)]]);
+
+        // A wallet counts its instructions in a u8
+        let bundles = plan_bundles(vec![route(0, 300)], MAX_BUNDLE_INSTRUCTIONS);
+        assert_eq!(sizes(&bundles), vec![vec![(0, 255)], vec![(0, 45)]]);
+
+        let (manager, bundle, authority) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let adds = bundles[1].add_instructions(&manager, &bundle, &authority, 10, 20);
+        assert_eq!(adds.len(), 6);
+        assert_eq!(adds[0].accounts[4].pubkey, record_address(&bundle, 0, 10));
+        assert_eq!(adds[5].accounts[4].pubkey, record_address(&bundle, 0, 50));
+    }
+}
@@ -0,0 +1,184 @@
+//! Pushed bundle status updates, for reacting to a bundle the moment it
+//! lands instead of polling `getAccountInfo`.
+//!
+//! `BundleWatcher::watch` subscribes over the websocket to the bundle
+//! account and to the logs of transactions mentioning it, and merges both
+//! into one stream. Account notifications carry the bundle as written,
+//! including PartiallyExecuted progress; log notifications carry the
+//! BundleExecuted and BundleFailed events of transactions that succeeded.
+//! CancelBundle and CloseBundle close the account, which shows up as
+//! `Closed`. Updates can arrive from both sides for the same change.
+//!
+//! ```ignore
+//! let watcher = BundleWatcher::connect(ws_url).await?;
+//! let bundle = nonblocking::fetch_bundle(&rpc, &address).await?;
+//! let mut updates = watcher.watch(address, &bundle, CommitmentConfig::confirmed()).await?;
+//! while let Some(update) = updates.next().await {
+//!     if update.is_final() { break; }
+//! }
+//! ```
+
+use futures::stream::{self, BoxStream, StreamExt};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient,
+    pubsub_client::PubsubClientError,
+    rpc_config::{RpcAccountInfoConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter},
+};
+use solana_sdk::{account::Account, commitment_config::CommitmentConfig, pubkey::Pubkey};
+
+use bundle_manager::{
+    events::{BundleEvent, BundleExecutedEvent, BundleFailedEvent},
+    Bundle, BundleStatus,
+};
+
+use crate::{accounts::decode_bundle, events::TransactionEvents};
+
+/// One change to a watched bundle
+#[derive(Debug)]
+pub enum BundleStatusUpdate {
+    /// The bundle account was written at `slot`; boxed, as `Bundle` is large
+    Changed { slot: u64, bundle: Box<Bundle> },
+    /// The bundle account was closed at `slot`, by CancelBundle, CloseBundle
+    /// or GarbageCollect
+    Closed { slot: u64 },
+    /// A transaction ran the bundle's last instruction
+    Executed { signature: String, event: BundleExecutedEvent },
+    /// A transaction left the bundle Failed
+    Failed { signature: String, event: BundleFailedEvent },
+}
+
+impl BundleStatusUpdate {
+    /// The bundle's status after this update, `None` once it is closed
+    pub fn status(&self) -> Option<BundleStatus> {
+        match self {
+            BundleStatusUpdate::Changed { bundle, .. } => Some(bundle.status),
+            BundleStatusUpdate::Closed { .. } => None,
+            BundleStatusUpdate::Executed { .. } => Some(BundleStatus::Executed),
+            BundleStatusUpdate::Failed { .. } => Some(BundleStatus::Failed),
+        }
+    }
+
+    /// Whether the bundle ended: Executed, Failed or closed. A Failed bundle
+    /// can still be retried, see RetryBundle.
+    pub fn is_final(&self) -> bool {
+        matches!(self.status(), None | Some(BundleStatus::Executed | BundleStatus::Failed))
+    }
+}
+
+/// A websocket connection watching bundles. Every stream `watch` returns
+/// borrows it, and ends when it is dropped.
+pub struct BundleWatcher {
+    client: PubsubClient,
+}
+
+impl BundleWatcher {
+    /// Connects to the websocket endpoint at `url`. The error is boxed:
+    /// `PubsubClientError` is large.
+    pub async fn connect(url: &str) -> Result<Self, Box<PubsubClientError>> {
+        Ok(Self { client: PubsubClient::new(url).await? })
+    }
+
+    /// Updates of the bundle at `address`, whose current state is `bundle`,
+    /// at `commitment`
+    pub async fn watch(
+        &self,
+        address: Pubkey,
+        bundle: &Bundle,
+        commitment: CommitmentConfig,
+    ) -> Result<BoxStream<'_, BundleStatusUpdate>, Box<PubsubClientError>> {
+        let account_config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(commitment),
+            ..Default::default()
+        };
+        let (accounts, _) = self.client.account_subscribe(&address, Some(account_config)).await?;
+        let (logs, _) = self.client
+            .logs_subscribe(
+                RpcTransactionLogsFilter::Mentions(vec![address.to_string()]),
+                RpcTransactionLogsConfig { commitment: Some(commitment) },
+            )
+            .await?;
+
+        let accounts = accounts.filter_map(move |response| {
+            let update = account_update(&address, response.context.slot, response.value.decode());
+            async move { update }
+        });
+        let (manager, bundle_id) = (bundle.manager, bundle.bundle_id);
+        let logs = logs.flat_map(move |response| {
+            stream::iter(event_updates(&manager, bundle_id, TransactionEvents::from(response.value)))
+        });
+        Ok(stream::select(accounts, logs).boxed())
+    }
+}
+
+// The update an account notification stands for; `None` for data that does
+// not decode as a bundle
+fn account_update(address: &Pubkey, slot: u64, account: Option<Account>) -> Option<BundleStatusUpdate> {
+    let account = account?;
+    if account.lamports == 0 || account.owner != bundle_manager::id() {
+        return Some(BundleStatusUpdate::Closed { slot });
+    }
+    let bundle = decode_bundle(address, &account).ok()?;
+    Some(BundleStatusUpdate::Changed { slot, bundle: Box::new(bundle) })
+}
+
+// The updates a transaction's events hold for bundle `bundle_id` of
+// `manager`; a batch emits events for several bundles
+fn event_updates(manager: &Pubkey, bundle_id: u32, transaction: TransactionEvents) -> Vec<BundleStatusUpdate> {
+    if transaction.failed {
+        return Vec::new();
+    }
+    let signature = transaction.signature;
+    transaction.events.into_iter()
+        .filter_map(|event| match event {
+            BundleEvent::BundleExecuted(event) if event.manager == *manager && event.bundle_id == bundle_id => {
+                Some(BundleStatusUpdate::Executed { signature: signature.clone(), event })
+            },
+            BundleEvent::BundleFailed(event) if event.manager == *manager && event.bundle_id == bundle_id => {
+                Some(BundleStatusUpdate::Failed { signature: signature.clone(), event })
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed(manager: Pubkey, bundle_id: u32) -> BundleEvent {
+        BundleEvent::BundleFailed(BundleFailedEvent {
+            manager,
+            bundle_id,
+            authority: Pubkey::new_unique(),
+            wallet_count: 1,
+            execution_started_at: 1,
+            execution_completed_at: 2,
+            error: 0,
+            compute_units: 0,
+        })
+    }
+
+    #[test]
+    fn closed_accounts_and_other_bundles_events() {
+        let address = Pubkey::new_unique();
+        let closed = Account { lamports: 0, owner: solana_sdk::system_program::id(), ..Default::default() };
+        let update = account_update(&address, 9, Some(closed)).unwrap();
+        assert!(matches!(update, BundleStatusUpdate::Closed { slot: 9 }));
+        assert!(update.is_final());
+        let garbage = Account { lamports: 1, data: vec![9; 8], owner: bundle_manager::id(), ..Default::default() };
+        assert!(account_update(&address, 9, Some(garbage)).is_none());
+
+        let manager = Pubkey::new_unique();
+        let transaction = TransactionEvents {
+            signature: "sig".to_string(),
+            failed: false,
+            events: vec![failed(manager, 3), failed(manager, 4), failed(Pubkey::new_unique(), 4)],
+        };
+        let updates = event_updates(&manager, 4, transaction.clone());
+        assert_eq!(updates.len(), 1);
+        assert!(matches!(&updates[0], BundleStatusUpdate::Failed { event, .. } if event.bundle_id == 4));
+        assert!(event_updates(&manager, 4, TransactionEvents { failed: true, ..transaction }).is_empty());
+    }
+}
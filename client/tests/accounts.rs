@@ -0,0 +1,198 @@
+//! The account decoders check ownership before decoding, read execution log
+//! entries in the order they were appended, put a bundle's records in
+//! execution order and read nonce accounts.
+
+use borsh::BorshSerialize;
+use bundle_manager::{
+    AccountType, Bundle, BundleError, BundleInstructionRecord, BundleStatus, ExecutionLog, ExecutionLogEntry, Vault,
+    EXECUTED_MASK_LEN,
+};
+use bundle_manager_client::{
+    accounts::{
+        decode_bundle, decode_bundle_records, decode_execution_log, decode_manager, decode_nonce, decode_vault,
+    },
+    nonce::{nonce_address, NonceAccount},
+    pda::{execution_log_address, record_address, vault_address},
+    FetchError,
+};
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    nonce::state::{Data, DurableNonce, State, Versions},
+    pubkey::Pubkey,
+    system_program,
+};
+
+fn program_account(data: Vec<u8>) -> Account {
+    Account { lamports: 1, data, owner: bundle_manager::id(), executable: false, rent_epoch: 0 }
+}
+
+fn execution_log(max_entries: u32, bundle_ids: &[u32]) -> Vec<u8> {
+    let total_appended = bundle_ids.len() as u64;
+    let log = ExecutionLog {
+        account_type: AccountType::ExecutionLog,
+        version: ExecutionLog::VERSION,
+        manager: Pubkey::new_unique(),
+        max_entries,
+        next_index: (total_appended % max_entries as u64) as u32,
+        total_appended,
+    };
+    let mut data = log.try_to_vec().unwrap();
+    data.resize(ExecutionLog::space(max_entries), 0);
+    for (appended, bundle_id) in bundle_ids.iter().enumerate() {
+        let entry = ExecutionLogEntry {
+            executor: Pubkey::new_unique(),
+            bundle_id: *bundle_id,
+            slot: appended as u64,
+            status: BundleStatus::Executed,
+            compute_units: 0,
+        };
+        let offset = ExecutionLog::entry_offset(appended as u32 % max_entries);
+        entry.serialize(&mut &mut data[offset..]).unwrap();
+    }
+    data
+}
+
+#[test]
+fn accounts_of_other_owners_or_types_are_rejected() {
+    let address = Pubkey::new_unique();
+    let mut account = program_account(execution_log(2, &[]));
+
+    let err = decode_manager(&address, &account).unwrap_err();
+    assert!(matches!(err, FetchError::Decode { address: decoded, .. } if decoded == address));
+    assert_eq!(err.bundle_error(), Some(BundleError::InvalidAccountType));
+
+    let owner = Pubkey::new_unique();
+    account.owner = owner;
+    let err = decode_bundle(&address, &account).unwrap_err();
+    assert!(matches!(err, FetchError::WrongOwner { owner: decoded, .. } if decoded == owner));
+    assert_eq!(err.bundle_error(), None);
+}
+
+#[test]
+fn execution_log_entries_are_read_oldest_first() {
+    let address = Pubkey::new_unique();
+    let bundle_ids = |ids: &[u32], max_entries| {
+        let log = decode_execution_log(&address, &program_account(execution_log(max_entries, ids))).unwrap();
+        log.entries.iter().map(|entry| entry.bundle_id).collect::<Vec<_>>()
+    };
+
+    assert_eq!(bundle_ids(&[], 3), Vec::<u32>::new());
+    assert_eq!(bundle_ids(&[1, 2], 3), vec![1, 2]);
+    // The fourth and fifth entries overwrote the first two
+    assert_eq!(bundle_ids(&[1, 2, 3, 4, 5], 3), vec![3, 4, 5]);
+}
+
+#[test]
+fn accounts_from_before_their_version_byte_are_upgraded_in_memory() {
+    let manager = Pubkey::new_unique();
+    let vault = Vault {
+        account_type: AccountType::Vault,
+        version: Vault::VERSION,
+        manager,
+        bump: 255,
+        total_deposited: 5,
+        total_withdrawn: 2,
+    };
+    let mut data = vault.try_to_vec().unwrap();
+    data.remove(1);
+    let decoded = decode_vault(&vault_address(&manager), &program_account(data.clone())).unwrap();
+    assert_eq!((decoded.manager, decoded.total_deposited, decoded.total_withdrawn), (manager, 5, 2));
+    // Read at an address it does not derive, the data is not the manager's vault
+    let err = decode_vault(&Pubkey::new_unique(), &program_account(data)).unwrap_err();
+    assert!(matches!(err, FetchError::Decode { .. }));
+
+    let mut data = execution_log(3, &[1, 2, 3, 4]);
+    let manager = Pubkey::try_from(&data[2..34]).unwrap();
+    data.remove(1);
+    let log = decode_execution_log(&execution_log_address(&manager), &program_account(data)).unwrap();
+    assert_eq!(log.log.version, ExecutionLog::VERSION);
+    assert_eq!(log.entries.iter().map(|entry| entry.bundle_id).collect::<Vec<_>>(), vec![2, 3, 4]);
+}
+
+#[test]
+fn bundle_records_come_back_in_the_order_they_were_added() {
+    let bundle_address = Pubkey::new_unique();
+    let bundle = Bundle {
+        account_type: AccountType::Bundle,
+        version: Bundle::VERSION,
+        manager: Pubkey::new_unique(),
+        authority: Pubkey::new_unique(),
+        bundle_id: 0,
+        created_at: 0,
+        execution_started_at: 0,
+        execution_completed_at: 0,
+        wallet_count: 2,
+        category: 0,
+        wallet_indexes: vec![4, 9],
+        instructions_per_wallet: vec![2, 1],
+        instructions_added: vec![2, 1],
+        status: BundleStatus::Created,
+        priority_fee: 0,
+        on_failure: None,
+        funding: None,
+        expected_net_lamports: 0,
+        last_attempt_slot: 0,
+        last_executed_index: None,
+        executed_mask: [0; EXECUTED_MASK_LEN],
+        // Record 1 was removed again
+        next_record_index: 4,
+        seeds: None,
+        compute_unit_limit: 0,
+        execute_after: 0,
+        max_retries: None,
+        retry_count: 0,
+        expires_at: 0,
+        spend_limits: None,
+    };
+    let record = |bundle: Pubkey, wallet_index: u8, record_index: u16| {
+        let record = BundleInstructionRecord {
+            account_type: AccountType::InstructionRecord,
+            version: BundleInstructionRecord::VERSION,
+            bundle,
+            wallet_index,
+            program_id: Pubkey::new_unique(),
+            instruction_data: vec![record_index as u8],
+            accounts: Vec::new(),
+            executed: false,
+            compressed: false,
+            reserved: 0,
+            create_if_missing: None,
+            last_error: None,
+        };
+        (record_address(&bundle_address, wallet_index, record_index), program_account(record.try_to_vec().unwrap()))
+    };
+
+    let accounts = vec![record(bundle_address, 4, 3), record(bundle_address, 9, 0), record(bundle_address, 4, 2)];
+    let records = decode_bundle_records(&bundle_address, &bundle, accounts).unwrap();
+    let order: Vec<_> = records.iter().map(|(_, record)| (record.wallet_index, record.instruction_data[0])).collect();
+    assert_eq!(order, vec![(9, 0), (4, 2), (4, 3)]);
+    assert_eq!(records[0].0, record_address(&bundle_address, 9, 0));
+
+    // A record claiming the bundle from an address it does not derive
+    let (_, stray) = record(bundle_address, 4, 0);
+    let err = decode_bundle_records(&bundle_address, &bundle, vec![(Pubkey::new_unique(), stray)]).unwrap_err();
+    assert_eq!(err.bundle_error(), Some(BundleError::InstructionBundleMismatch));
+}
+
+#[test]
+fn nonce_accounts_hold_their_durable_blockhash() {
+    let authority = Pubkey::new_unique();
+    let address = nonce_address(&authority, 0);
+    let durable_nonce = DurableNonce::from_blockhash(&Hash::new_unique());
+    let initialized = Versions::new(State::Initialized(Data::new(authority, durable_nonce, 5_000)));
+    let mut account = Account {
+        lamports: 1,
+        data: bincode::serialize(&initialized).unwrap(),
+        owner: system_program::id(),
+        executable: false,
+        rent_epoch: 0,
+    };
+    let nonce = decode_nonce(&address, &account).unwrap();
+    assert_eq!(nonce, NonceAccount { address, authority, blockhash: *durable_nonce.as_hash() });
+
+    account.data = bincode::serialize(&Versions::new(State::Uninitialized)).unwrap();
+    assert!(matches!(decode_nonce(&address, &account), Err(FetchError::Decode { .. })));
+    account.owner = bundle_manager::id();
+    assert!(matches!(decode_nonce(&address, &account), Err(FetchError::WrongOwner { .. })));
+}
@@ -0,0 +1,154 @@
+//! The program's IDL describes what the builders actually encode: every
+//! instruction's discriminant, fixed accounts and arguments, and every error.
+
+use bundle_manager::{
+    BundleError, CreateIfMissing, FundWallets, InstructionOptions, ManagerConfig, SpendLimits, TokenSpendCap,
+};
+use bundle_manager_client::{
+    add_instruction, create_bundle, execute_bundle, initialize_with_approvals, set_manager_status, with_execute_after,
+    with_max_retries, with_spend_limits,
+};
+use serde_json::Value;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, system_instruction};
+
+const IDL: &str = include_str!("../../programs/bundle-manager/idl/bundle_manager.json");
+
+// Splits `len` bytes off the front of `data`
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if data.len() < len {
+        return Err(format!("data ends {} bytes short", len - data.len()));
+    }
+    let (head, rest) = data.split_at(len);
+    *data = rest;
+    Ok(head)
+}
+
+// A Borsh length prefix
+fn take_len(data: &mut &[u8]) -> Result<usize, String> {
+    Ok(u32::from_le_bytes(take(data, 4)?.try_into().unwrap()) as usize)
+}
+
+fn consume_fields(idl: &Value, fields: &Value, data: &mut &[u8]) -> Result<(), String> {
+    fields.as_array().unwrap().iter().try_for_each(|field| consume(idl, &field["type"], data))
+}
+
+// Walks `data` as the IDL type `ty` describes, failing on anything it does
+// not cover or on data too short for it
+fn consume(idl: &Value, ty: &Value, data: &mut &[u8]) -> Result<(), String> {
+    match ty {
+        Value::String(name) => match name.as_str() {
+            "u8" | "bool" => take(data, 1).map(drop),
+            "u16" => take(data, 2).map(drop),
+            "u32" => take(data, 4).map(drop),
+            "u64" | "i64" => take(data, 8).map(drop),
+            "publicKey" => take(data, 32).map(drop),
+            "bytes" => {
+                let len = take_len(data)?;
+                take(data, len).map(drop)
+            },
+            other => Err(format!("unknown type {other}")),
+        },
+        Value::Object(map) => {
+            if let Some(inner) = map.get("vec") {
+                let len = take_len(data)?;
+                (0..len).try_for_each(|_| consume(idl, inner, data))
+            } else if let Some(inner) = map.get("option") {
+                match take(data, 1)?[0] {
+                    0 => Ok(()),
+                    1 => consume(idl, inner, data),
+                    tag => Err(format!("bad option tag {tag}")),
+                }
+            } else if let Some(Value::Array(items)) = map.get("tuple") {
+                items.iter().try_for_each(|item| consume(idl, item, data))
+            } else if let Some(Value::String(name)) = map.get("defined") {
+                let defined = idl["types"].as_array().unwrap().iter()
+                    .find(|defined| defined["name"] == name.as_str())
+                    .ok_or(format!("type {name} is not defined"))?;
+                match defined["type"]["kind"].as_str() {
+                    Some("struct") => consume_fields(idl, &defined["type"]["fields"], data),
+                    Some("enum") => {
+                        let tag = take(data, 1)?[0] as usize;
+                        let variant = &defined["type"]["variants"][tag];
+                        if variant.is_null() {
+                            return Err(format!("{name} has no variant {tag}"));
+                        }
+                        consume_fields(idl, &variant["fields"], data)
+                    },
+                    kind => Err(format!("{name} has kind {kind:?}")),
+                }
+            } else {
+                Err(format!("unknown type {ty}"))
+            }
+        },
+        _ => Err(format!("unknown type {ty}")),
+    }
+}
+
+#[test]
+fn idl_matches_the_builders() {
+    let idl: Value = serde_json::from_str(IDL).unwrap();
+    assert_eq!(idl["metadata"]["address"], bundle_manager::id().to_string());
+
+    let manager = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let bundle = Pubkey::new_unique();
+    let funding = Some(FundWallets { lamports_each: 1, wallet_count: 2 });
+    let create = create_bundle(&manager, 7, &authority, &[(0, 1), (1, 2)], 0, Some((bundle, vec![1, 2])), funding);
+    let options = InstructionOptions {
+        compress: true,
+        create_if_missing: Some(CreateIfMissing::AssociatedTokenAccount {
+            wallet: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            token_program: Pubkey::new_unique(),
+        }),
+    };
+    let limits = SpendLimits {
+        max_lamports_out: Some(5),
+        token_caps: vec![TokenSpendCap { mint: Pubkey::new_unique(), max_amount: 6 }],
+    };
+    let create = with_spend_limits(with_max_retries(with_execute_after(create, 1_700_000_000), 3), limits);
+    let stored = system_instruction::transfer(&authority, &Pubkey::new_unique(), 5);
+    let builders: Vec<(&str, Instruction)> = vec![
+        ("initialize", initialize_with_approvals(&authority, 3, ManagerConfig::default(), &[authority, bundle], 2)),
+        ("createBundle", create),
+        ("addInstruction", add_instruction(&manager, &bundle, &bundle, &authority, 1, &stored, options)),
+        ("executeBundle", execute_bundle(&manager, &bundle, &authority, true, &[], 200_000, 10)),
+        ("setManagerStatus", set_manager_status(&manager, &authority, true)),
+    ];
+
+    let instructions = idl["instructions"].as_array().unwrap();
+    assert_eq!(instructions.len(), builders.len());
+    for ((name, instruction), described) in builders.iter().zip(instructions) {
+        assert_eq!(described["name"], *name);
+        assert_eq!(described["discriminant"]["value"], instruction.data[0], "{name} discriminant");
+
+        let accounts: Vec<&Value> = described["accounts"].as_array().unwrap().iter()
+            .filter(|account| account["isOptional"] != true)
+            .collect();
+        assert_eq!(accounts.len(), instruction.accounts.len(), "{name} accounts");
+        for (account, meta) in accounts.iter().zip(&instruction.accounts) {
+            assert_eq!(account["isMut"], meta.is_writable, "{name} {}", account["name"]);
+            assert_eq!(account["isSigner"], meta.is_signer, "{name} {}", account["name"]);
+        }
+
+        let data = &mut &instruction.data[1..];
+        for arg in described["args"].as_array().unwrap() {
+            consume(&idl, &arg["type"], data).unwrap_or_else(|error| panic!("{name} {}: {error}", arg["name"]));
+        }
+        assert!(data.is_empty(), "{name} encodes more than its IDL args");
+    }
+}
+
+#[test]
+fn idl_lists_every_error() {
+    let idl: Value = serde_json::from_str(IDL).unwrap();
+    let errors = idl["errors"].as_array().unwrap();
+    for error in errors {
+        let code = error["code"].as_u64().unwrap() as u32;
+        let variant = BundleError::from_code(code).unwrap_or_else(|| panic!("no BundleError with code {code}"));
+        assert_eq!(error["name"], format!("{variant:?}"));
+        assert_eq!(error["msg"], variant.to_string());
+    }
+    let last = errors.last().unwrap()["code"].as_u64().unwrap() as u32;
+    assert_eq!(BundleError::from_code(last + 1), None, "errors after {last} are missing from the IDL");
+}
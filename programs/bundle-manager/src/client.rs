@@ -0,0 +1,1162 @@
+//! Instruction builders and account decoders for off-chain callers.
+//!
+//! Each builder returns an `Instruction` for this program with the accounts
+//! in the order the matching `BundleInstruction` variant documents and the
+//! variant Borsh-encoded as its data. PDAs are derived with the `find_*`
+//! helpers where the builder has everything needed to do so; accounts that
+//! depend on on-chain state (instruction records, whose addresses follow the
+//! bundle's `next_record_index`) are passed in.
+//!
+//! The decoders read account data fetched with any RPC client, e.g.
+//! `decode_bundle(&rpc.get_account_data(&bundle)?)`.
+//!
+//! Only compiled with the `client` feature. Bots depending on this crate
+//! should also enable `no-entrypoint`.
+
+use std::borrow::Cow;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+
+use crate::{
+    compute_unit_price, find_associated_token_address, find_bundle_address, find_execution_log_address,
+    find_manager_address, find_vault_address, find_wallet_address, find_wallet_registry_address, id, migration,
+    token_transfer_checked, BatchItem, Bundle, BundleInstruction, BundleManager, BundleStatus, CreateIfMissing,
+    DelegatePermissions, FundWallets, InstructionAccountMeta, InstructionOptions, ManagerConfig, SpendLimits,
+    TokenTransfer, Vault, VaultAsset, WalletRegistry,
+    ASSOCIATED_TOKEN_PROGRAM_ID, COMPUTE_BUDGET_PROGRAM_ID,
+};
+
+fn build(instruction: &BundleInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+    // Serializing into a Vec cannot fail
+    let data = instruction.try_to_vec().expect("BundleInstruction serializes");
+    Instruction::new_with_bytes(id(), &data, accounts)
+}
+
+// The Initialize accounts shared by the initialize builders
+fn initialize_accounts(authority: &Pubkey, manager_index: u16) -> Vec<AccountMeta> {
+    let (manager, _) = find_manager_address(&id(), authority, manager_index);
+    vec![
+        AccountMeta::new(manager, false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]
+}
+
+/// `Initialize` for the manager PDA of `authority` at `manager_index`,
+/// charging no protocol fee
+pub fn initialize(authority: &Pubkey, manager_index: u16, config: ManagerConfig) -> Instruction {
+    initialize_with_fee(authority, manager_index, config, 0, authority)
+}
+
+/// `Initialize` charging `fee_lamports` per executed bundle to `fee_collector`,
+/// with tips going to `authority` until SetTipAccount
+pub fn initialize_with_fee(
+    authority: &Pubkey,
+    manager_index: u16,
+    config: ManagerConfig,
+    fee_lamports: u64,
+    fee_collector: &Pubkey,
+) -> Instruction {
+    initialize_with_tip_account(authority, manager_index, config, fee_lamports, fee_collector, authority)
+}
+
+/// `Initialize` with the protocol fee and the `tip_account` ExecuteBundle
+/// pays `jito_tip_lamports` to
+pub fn initialize_with_tip_account(
+    authority: &Pubkey,
+    manager_index: u16,
+    config: ManagerConfig,
+    fee_lamports: u64,
+    fee_collector: &Pubkey,
+    tip_account: &Pubkey,
+) -> Instruction {
+    build(
+        &BundleInstruction::Initialize {
+            manager_index,
+            config,
+            fee_lamports,
+            fee_collector: *fee_collector,
+            tip_account: *tip_account,
+            authorities: Vec::new(),
+            threshold: 1,
+        },
+        initialize_accounts(authority, manager_index),
+    )
+}
+
+/// `Initialize` requiring `threshold` of `authorities` to sign every
+/// ExecuteBundle, charging no protocol fee
+pub fn initialize_with_approvals(
+    authority: &Pubkey,
+    manager_index: u16,
+    config: ManagerConfig,
+    authorities: &[Pubkey],
+    threshold: u8,
+) -> Instruction {
+    build(
+        &BundleInstruction::Initialize {
+            manager_index,
+            config,
+            fee_lamports: 0,
+            fee_collector: *authority,
+            tip_account: *authority,
+            authorities: authorities.to_vec(),
+            threshold,
+        },
+        initialize_accounts(authority, manager_index),
+    )
+}
+
+/// `CreateBundle` at the address `client_nonce` derives for `authority`;
+/// reuse the nonce when retrying the same bundle. `wallets` pairs each
+/// wallet index with the number of instructions it runs.
+pub fn create_bundle(
+    manager: &Pubkey,
+    client_nonce: u64,
+    authority: &Pubkey,
+    wallets: &[(u8, u8)],
+    category: u8,
+    on_failure: Option<(Pubkey, Vec<u8>)>,
+    funding: Option<FundWallets>,
+) -> Instruction {
+    let (bundle, _) = find_bundle_address(&id(), manager, authority, client_nonce);
+    build(
+        &BundleInstruction::CreateBundle {
+            wallet_indexes: wallets.iter().map(|&(index, _)| index).collect(),
+            instructions_per_wallet: wallets.iter().map(|&(_, count)| count).collect(),
+            category,
+            on_failure,
+            funding,
+            client_nonce,
+            execute_after: None,
+            max_retries: None,
+            spend_limits: None,
+        },
+        vec![
+            AccountMeta::new(*manager, false),
+            AccountMeta::new(bundle, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Converts an instruction the bundle should run into the `BatchItem` that
+/// stores it for `wallet_index`
+pub fn batch_item(wallet_index: u8, instruction: &Instruction, options: InstructionOptions) -> BatchItem {
+    BatchItem {
+        wallet_index,
+        program_id: instruction.program_id,
+        instruction_data: instruction.data.clone(),
+        accounts: instruction.accounts.iter()
+            .map(|meta| InstructionAccountMeta {
+                pubkey: meta.pubkey,
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            })
+            .collect(),
+        options,
+    }
+}
+
+/// `AddInstruction` storing `instruction` in the new `record` PDA, which
+/// `find_instruction_record_address` derives from the bundle's current
+/// `next_record_index`
+pub fn add_instruction(
+    manager: &Pubkey,
+    bundle: &Pubkey,
+    record: &Pubkey,
+    authority: &Pubkey,
+    wallet_index: u8,
+    instruction: &Instruction,
+    options: InstructionOptions,
+) -> Instruction {
+    let item = batch_item(wallet_index, instruction, options);
+    build(
+        &BundleInstruction::AddInstruction {
+            wallet_index: item.wallet_index,
+            program_id: item.program_id,
+            instruction_data: item.instruction_data,
+            accounts: item.accounts,
+            options: item.options,
+        },
+        vec![
+            AccountMeta::new_readonly(*manager, false),
+            AccountMeta::new(*bundle, false),
+            AccountMeta::new(*record, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// `BatchAddInstruction` storing each item in its paired record PDA, derived
+/// with consecutive record indexes from the bundle's `next_record_index`
+pub fn batch_add_instruction(
+    manager: &Pubkey,
+    bundle: &Pubkey,
+    authority: &Pubkey,
+    items: Vec<(Pubkey, BatchItem)>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*manager, false),
+        AccountMeta::new(*bundle, false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(items.iter().map(|(record, _)| AccountMeta::new(*record, false)));
+    let items = items.into_iter().map(|(_, item)| item).collect();
+    build(&BundleInstruction::BatchAddInstruction { items }, accounts)
+}
+
+/// `RemoveInstruction`, refunding the record's rent to `authority`
+pub fn remove_instruction(manager: &Pubkey, bundle: &Pubkey, record: &Pubkey, authority: &Pubkey) -> Instruction {
+    build(
+        &BundleInstruction::RemoveInstruction,
+        vec![
+            AccountMeta::new_readonly(*manager, false),
+            AccountMeta::new(*bundle, false),
+            AccountMeta::new(*record, false),
+            AccountMeta::new(*authority, true),
+        ],
+    )
+}
+
+// The ExecuteBundle accounts shared by execute_bundle and simulate_bundle
+fn execute_accounts(
+    manager: &Pubkey,
+    bundle: &Pubkey,
+    authority: &Pubkey,
+    log: bool,
+    remaining_accounts: &[AccountMeta],
+) -> Vec<AccountMeta> {
+    let execution_log = if log {
+        AccountMeta::new(find_execution_log_address(&id(), manager).0, false)
+    } else {
+        AccountMeta::new_readonly(id(), false)
+    };
+    let mut accounts = vec![
+        AccountMeta::new(*manager, false),
+        AccountMeta::new(*bundle, false),
+        // Unused by the program; slot hashes stands in for the deprecated
+        // recent blockhashes sysvar
+        AccountMeta::new_readonly(sysvar::slot_hashes::id(), false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        execution_log,
+    ];
+    accounts.extend_from_slice(remaining_accounts);
+    accounts
+}
+
+/// The SetComputeUnitLimit and SetComputeUnitPrice instructions a client
+/// puts in front of ExecuteBundle
+pub fn compute_budget_instructions(max_compute_units: u32, price_micro_lamports: u64) -> [Instruction; 2] {
+    let mut set_limit = vec![2];
+    set_limit.extend_from_slice(&max_compute_units.to_le_bytes());
+    let mut set_price = vec![3];
+    set_price.extend_from_slice(&price_micro_lamports.to_le_bytes());
+
+    [
+        Instruction { program_id: COMPUTE_BUDGET_PROGRAM_ID, accounts: vec![], data: set_limit },
+        Instruction { program_id: COMPUTE_BUDGET_PROGRAM_ID, accounts: vec![], data: set_price },
+    ]
+}
+
+/// `ExecuteBundle`, logging to the manager's execution log when `log` is set.
+///
+/// `remaining_accounts` are the bundle's instruction records in execution
+/// order, followed by every account and program their instructions, the
+/// funding phase and the on_failure hook reference, the manager's fee
+/// collector and tip account when they are paid, and its wallet registry
+/// once it has one. `with_compute_budget` prepends the compute budget
+/// instructions for the priority fee.
+pub fn execute_bundle(
+    manager: &Pubkey,
+    bundle: &Pubkey,
+    authority: &Pubkey,
+    log: bool,
+    remaining_accounts: &[AccountMeta],
+    max_compute_units: u32,
+    recent_fee_micro_lamports: u64,
+) -> Instruction {
+    build(
+        &BundleInstruction::ExecuteBundle {
+            max_compute_units,
+            recent_fee_micro_lamports,
+            dry_run: false,
+            jito_tip_lamports: 0,
+            start_index: 0,
+            count: 0,
+        },
+        execute_accounts(manager, bundle, authority, log, remaining_accounts),
+    )
+}
+
+/// `RetryBundle` of a Failed bundle: the same accounts as `execute_bundle`,
+/// whose records already executed are skipped
+pub fn retry_bundle(
+    manager: &Pubkey,
+    bundle: &Pubkey,
+    authority: &Pubkey,
+    log: bool,
+    remaining_accounts: &[AccountMeta],
+    max_compute_units: u32,
+    recent_fee_micro_lamports: u64,
+) -> Instruction {
+    build(
+        &BundleInstruction::RetryBundle { max_compute_units, recent_fee_micro_lamports, jito_tip_lamports: 0 },
+        execute_accounts(manager, bundle, authority, log, remaining_accounts),
+    )
+}
+
+/// `ExecuteBundle` with `dry_run` set: the same accounts as `execute_bundle`,
+/// checked without invoking anything
+pub fn simulate_bundle(
+    manager: &Pubkey,
+    bundle: &Pubkey,
+    authority: &Pubkey,
+    log: bool,
+    remaining_accounts: &[AccountMeta],
+    max_compute_units: u32,
+    recent_fee_micro_lamports: u64,
+) -> Instruction {
+    build(
+        &BundleInstruction::ExecuteBundle {
+            max_compute_units,
+            recent_fee_micro_lamports,
+            dry_run: true,
+            jito_tip_lamports: 0,
+            start_index: 0,
+            count: 0,
+        },
+        execute_accounts(manager, bundle, authority, log, remaining_accounts),
+    )
+}
+
+/// `ExecuteBundleBatch` for `bundles`, which share `authority`
+///
+/// `remaining_accounts` are the records of each bundle in turn, in
+/// execution order, followed by every account and program any of their
+/// instructions reference, the fee collector and tip account when they are
+/// paid, and the manager's wallet registry once it has one.
+pub fn execute_bundle_batch(
+    manager: &Pubkey,
+    bundles: &[Pubkey],
+    authority: &Pubkey,
+    log: bool,
+    remaining_accounts: &[AccountMeta],
+    max_compute_units: u32,
+    recent_fee_micro_lamports: u64,
+) -> Instruction {
+    let execution_log = if log {
+        AccountMeta::new(find_execution_log_address(&id(), manager).0, false)
+    } else {
+        AccountMeta::new_readonly(id(), false)
+    };
+    let mut accounts = vec![
+        AccountMeta::new(*manager, false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        execution_log,
+    ];
+    accounts.extend(bundles.iter().map(|bundle| AccountMeta::new(*bundle, false)));
+    accounts.extend_from_slice(remaining_accounts);
+    build(
+        &BundleInstruction::ExecuteBundleBatch {
+            bundle_count: bundles.len().try_into().expect("at most 255 bundles"),
+            max_compute_units,
+            recent_fee_micro_lamports,
+            jito_tip_lamports: 0,
+        },
+        accounts,
+    )
+}
+
+/// Sets `jito_tip_lamports` on an `execute_bundle`, `simulate_bundle`,
+/// `retry_bundle` or `execute_bundle_batch` instruction and appends the
+/// manager's `tip_account`, which is paid the tip. Panics on any other
+/// instruction.
+pub fn with_jito_tip(mut execute: Instruction, tip_account: &Pubkey, jito_tip_lamports: u64) -> Instruction {
+    let mut decoded = BundleInstruction::try_from_slice(&execute.data).expect("BundleInstruction decodes");
+    match &mut decoded {
+        BundleInstruction::ExecuteBundle { jito_tip_lamports: tip, .. }
+        | BundleInstruction::RetryBundle { jito_tip_lamports: tip, .. }
+        | BundleInstruction::ExecuteBundleBatch { jito_tip_lamports: tip, .. } => *tip = jito_tip_lamports,
+        other => panic!("with_jito_tip needs ExecuteBundle, RetryBundle or ExecuteBundleBatch, got {other:?}"),
+    }
+    execute.data = decoded.try_to_vec().expect("BundleInstruction serializes");
+    execute.accounts.push(AccountMeta::new(*tip_account, false));
+    execute
+}
+
+/// Schedules a `create_bundle` instruction's bundle to become executable at
+/// the Unix timestamp `execute_after`. Panics on any other instruction.
+pub fn with_execute_after(mut create: Instruction, execute_after: i64) -> Instruction {
+    let mut decoded = BundleInstruction::try_from_slice(&create.data).expect("BundleInstruction decodes");
+    match &mut decoded {
+        BundleInstruction::CreateBundle { execute_after: scheduled, .. } => *scheduled = Some(execute_after),
+        other => panic!("with_execute_after needs CreateBundle, got {other:?}"),
+    }
+    create.data = decoded.try_to_vec().expect("BundleInstruction serializes");
+    create
+}
+
+/// Caps how often a `create_bundle` instruction's bundle may run again once
+/// it has failed. Panics on any other instruction.
+pub fn with_max_retries(mut create: Instruction, max_retries: u8) -> Instruction {
+    let mut decoded = BundleInstruction::try_from_slice(&create.data).expect("BundleInstruction decodes");
+    match &mut decoded {
+        BundleInstruction::CreateBundle { max_retries: cap, .. } => *cap = Some(max_retries),
+        other => panic!("with_max_retries needs CreateBundle, got {other:?}"),
+    }
+    create.data = decoded.try_to_vec().expect("BundleInstruction serializes");
+    create
+}
+
+/// Caps what each ExecuteBundle call of a `create_bundle` instruction's
+/// bundle may take out of its wallets. Panics on any other instruction.
+pub fn with_spend_limits(mut create: Instruction, spend_limits: SpendLimits) -> Instruction {
+    let mut decoded = BundleInstruction::try_from_slice(&create.data).expect("BundleInstruction decodes");
+    match &mut decoded {
+        BundleInstruction::CreateBundle { spend_limits: limits, .. } => *limits = Some(spend_limits),
+        other => panic!("with_spend_limits needs CreateBundle, got {other:?}"),
+    }
+    create.data = decoded.try_to_vec().expect("BundleInstruction serializes");
+    create
+}
+
+/// Has `delegate` create a `create_bundle` instruction's bundle for its
+/// `authority`, the manager authority, signing and paying rent in its
+/// place. Panics on any other instruction.
+pub fn with_create_delegate(mut create: Instruction, delegate: &Pubkey) -> Instruction {
+    let decoded = BundleInstruction::try_from_slice(&create.data).expect("BundleInstruction decodes");
+    if !matches!(decoded, BundleInstruction::CreateBundle { .. }) {
+        panic!("with_create_delegate needs CreateBundle, got {decoded:?}");
+    }
+    create.accounts[2] = AccountMeta::new_readonly(create.accounts[2].pubkey, false);
+    create.accounts.push(AccountMeta::new(*delegate, true));
+    create
+}
+
+/// An `execute_bundle`, `simulate_bundle`, `retry_bundle` or
+/// `execute_bundle_batch` instruction behind its `compute_budget_instructions`: the limit is its
+/// `max_compute_units` and the price the one ExecuteBundle derives from its
+/// `recent_fee_micro_lamports` with the manager's `priority_fee_multiplier`,
+/// so the transaction pays the `priority_fee` the bundle records. `config`
+/// is the manager's current config. Panics on any other instruction.
+pub fn with_compute_budget(config: &ManagerConfig, execute: Instruction) -> Result<Vec<Instruction>, ProgramError> {
+    let decoded = BundleInstruction::try_from_slice(&execute.data).expect("BundleInstruction decodes");
+    let (max_compute_units, recent_fee_micro_lamports) = match decoded {
+        BundleInstruction::ExecuteBundle { max_compute_units, recent_fee_micro_lamports, .. }
+        | BundleInstruction::RetryBundle { max_compute_units, recent_fee_micro_lamports, .. }
+        | BundleInstruction::ExecuteBundleBatch { max_compute_units, recent_fee_micro_lamports, .. } => {
+            (max_compute_units, recent_fee_micro_lamports)
+        },
+        other => panic!("with_compute_budget needs ExecuteBundle, RetryBundle or ExecuteBundleBatch, got {other:?}"),
+    };
+    let price = compute_unit_price(config, recent_fee_micro_lamports)?;
+    let mut instructions = compute_budget_instructions(max_compute_units, price).to_vec();
+    instructions.push(execute);
+    Ok(instructions)
+}
+
+/// Limits an `execute_bundle` or `simulate_bundle` instruction to the
+/// `count` records from `start_index`. Panics on any other instruction.
+pub fn with_slice(mut execute: Instruction, start_index: u8, count: u8) -> Instruction {
+    let mut decoded = BundleInstruction::try_from_slice(&execute.data).expect("BundleInstruction decodes");
+    match &mut decoded {
+        BundleInstruction::ExecuteBundle { start_index: start, count: len, .. } => {
+            *start = start_index;
+            *len = count;
+        },
+        other => panic!("with_slice needs ExecuteBundle, got {other:?}"),
+    }
+    execute.data = decoded.try_to_vec().expect("BundleInstruction serializes");
+    execute
+}
+
+/// `SetManagerStatus`
+pub fn set_manager_status(manager: &Pubkey, authority: &Pubkey, is_paused: bool) -> Instruction {
+    build(
+        &BundleInstruction::SetManagerStatus { is_paused },
+        vec![
+            AccountMeta::new(*manager, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// `SetAuthority`, proposing `new_authority` for the manager
+pub fn set_authority(manager: &Pubkey, authority: &Pubkey, new_authority: Pubkey) -> Instruction {
+    build(
+        &BundleInstruction::SetAuthority { new_authority },
+        vec![
+            AccountMeta::new(*manager, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// `AcceptAuthority`, signed by the proposed authority
+pub fn accept_authority(manager: &Pubkey, new_authority: &Pubkey) -> Instruction {
+    build(
+        &BundleInstruction::AcceptAuthority,
+        vec![
+            AccountMeta::new(*manager, false),
+            AccountMeta::new_readonly(*new_authority, true),
+        ],
+    )
+}
+
+/// `MigrateManager`, also upgrading each of `bundles`
+pub fn migrate_manager(manager: &Pubkey, authority: &Pubkey, bundles: &[Pubkey]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*manager, false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(bundles.iter().map(|bundle| AccountMeta::new(*bundle, false)));
+    build(&BundleInstruction::MigrateManager, accounts)
+}
+
+/// `MigrateState`, upgrading each of `accounts`: the manager's wallet
+/// registry, vault, execution log or instruction records. `bundles` are the
+/// bundles those records belong to.
+pub fn migrate_state(manager: &Pubkey, authority: &Pubkey, accounts: &[Pubkey], bundles: &[Pubkey]) -> Instruction {
+    let mut metas = vec![
+        AccountMeta::new_readonly(*manager, false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    metas.extend(accounts.iter().map(|account| AccountMeta::new(*account, false)));
+    metas.extend(bundles.iter().map(|bundle| AccountMeta::new_readonly(*bundle, false)));
+    build(&BundleInstruction::MigrateState, metas)
+}
+
+/// `SetFee`, charging `fee_lamports` per executed bundle to `fee_collector`
+pub fn set_fee(manager: &Pubkey, authority: &Pubkey, fee_lamports: u64, fee_collector: &Pubkey) -> Instruction {
+    build(
+        &BundleInstruction::SetFee { fee_lamports, fee_collector: *fee_collector },
+        vec![
+            AccountMeta::new(*manager, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// `SetTipAccount`, paying future tips to `tip_account`
+pub fn set_tip_account(manager: &Pubkey, authority: &Pubkey, tip_account: &Pubkey) -> Instruction {
+    build(
+        &BundleInstruction::SetTipAccount { tip_account: *tip_account },
+        vec![
+            AccountMeta::new(*manager, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// `UpdateManagerConfig`, replacing the manager's config with `config`
+pub fn update_manager_config(manager: &Pubkey, authority: &Pubkey, config: ManagerConfig) -> Instruction {
+    build(
+        &BundleInstruction::UpdateManagerConfig { config },
+        vec![
+            AccountMeta::new(*manager, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// `LinkLookupTable`, sharing `lookup_table` with the manager's bots, or
+/// unlinking the current table with None
+pub fn link_lookup_table(manager: &Pubkey, authority: &Pubkey, lookup_table: Option<&Pubkey>) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*manager, false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    accounts.extend(lookup_table.map(|table| AccountMeta::new_readonly(*table, false)));
+    build(&BundleInstruction::LinkLookupTable { lookup_table: lookup_table.copied() }, accounts)
+}
+
+/// `AddDelegate`, letting `delegate` act for the manager authority with
+/// `permissions`
+pub fn add_delegate(
+    manager: &Pubkey,
+    authority: &Pubkey,
+    delegate: &Pubkey,
+    permissions: DelegatePermissions,
+) -> Instruction {
+    build(
+        &BundleInstruction::AddDelegate { delegate: *delegate, permissions },
+        vec![
+            AccountMeta::new(*manager, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// `RemoveDelegate`, revoking `delegate`
+pub fn remove_delegate(manager: &Pubkey, authority: &Pubkey, delegate: &Pubkey) -> Instruction {
+    build(
+        &BundleInstruction::RemoveDelegate { delegate: *delegate },
+        vec![
+            AccountMeta::new(*manager, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// `AddAllowedProgram`, letting the manager's bundles invoke `program_id`
+pub fn add_allowed_program(manager: &Pubkey, authority: &Pubkey, program_id: &Pubkey) -> Instruction {
+    build(
+        &BundleInstruction::AddAllowedProgram { program_id: *program_id },
+        vec![
+            AccountMeta::new(*manager, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// `RemoveAllowedProgram`, taking `program_id` off the manager's allowlist
+pub fn remove_allowed_program(manager: &Pubkey, authority: &Pubkey, program_id: &Pubkey) -> Instruction {
+    build(
+        &BundleInstruction::RemoveAllowedProgram { program_id: *program_id },
+        vec![
+            AccountMeta::new(*manager, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// `ResetStats`, recounting `active_bundles` over `active_bundles`
+pub fn reset_stats(manager: &Pubkey, authority: &Pubkey, active_bundles: &[Pubkey]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*manager, false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+    accounts.extend(active_bundles.iter().map(|bundle| AccountMeta::new_readonly(*bundle, false)));
+    build(&BundleInstruction::ResetStats, accounts)
+}
+
+/// `InitializeExecutionLog` for the manager's log PDA
+pub fn initialize_execution_log(manager: &Pubkey, authority: &Pubkey, max_entries: u32) -> Instruction {
+    let (log, _) = find_execution_log_address(&id(), manager);
+    build(
+        &BundleInstruction::InitializeExecutionLog { max_entries },
+        vec![
+            AccountMeta::new_readonly(*manager, false),
+            AccountMeta::new(log, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// `GetManagersStats`, at most `MAX_STATS_MANAGERS` managers
+pub fn get_managers_stats(managers: &[Pubkey]) -> Instruction {
+    build(
+        &BundleInstruction::GetManagersStats,
+        managers.iter().map(|manager| AccountMeta::new_readonly(*manager, false)).collect(),
+    )
+}
+
+/// `GetExecutionState`
+pub fn get_execution_state(bundle: &Pubkey) -> Instruction {
+    build(
+        &BundleInstruction::GetExecutionState,
+        vec![AccountMeta::new_readonly(*bundle, false)],
+    )
+}
+
+/// `EstimateCost` of an ExecuteBundle call with the given compute budget,
+/// recent priority price and tip
+pub fn estimate_cost(
+    manager: &Pubkey,
+    bundle: &Pubkey,
+    max_compute_units: u32,
+    recent_fee_micro_lamports: u64,
+    jito_tip_lamports: u64,
+) -> Instruction {
+    build(
+        &BundleInstruction::EstimateCost { max_compute_units, recent_fee_micro_lamports, jito_tip_lamports },
+        vec![
+            AccountMeta::new_readonly(*manager, false),
+            AccountMeta::new_readonly(*bundle, false),
+        ],
+    )
+}
+
+/// `CloseBundle`, passing every record added to the bundle
+pub fn close_bundle(
+    manager: &Pubkey,
+    bundle: &Pubkey,
+    authority: &Pubkey,
+    destination: &Pubkey,
+    records: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*manager, false),
+        AccountMeta::new(*bundle, false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new(*destination, false),
+    ];
+    accounts.extend(records.iter().map(|record| AccountMeta::new(*record, false)));
+    build(&BundleInstruction::CloseBundle, accounts)
+}
+
+/// `ExpireBundle`; pass the bundle's on_failure program if it has one
+pub fn expire_bundle(manager: &Pubkey, bundle: &Pubkey, on_failure_program: Option<&Pubkey>) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*manager, false),
+        AccountMeta::new(*bundle, false),
+    ];
+    accounts.extend(on_failure_program.map(|program| AccountMeta::new_readonly(*program, false)));
+    build(&BundleInstruction::ExpireBundle, accounts)
+}
+
+/// `FailBundle`, signed by `caller`; pass the bundle's on_failure program if
+/// it has one
+pub fn fail_bundle(
+    manager: &Pubkey,
+    bundle: &Pubkey,
+    caller: &Pubkey,
+    on_failure_program: Option<&Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*manager, false),
+        AccountMeta::new(*bundle, false),
+        AccountMeta::new_readonly(*caller, true),
+    ];
+    accounts.extend(on_failure_program.map(|program| AccountMeta::new_readonly(*program, false)));
+    build(&BundleInstruction::FailBundle, accounts)
+}
+
+/// `CancelBundle`, signed by `caller`, the bundle or manager authority;
+/// `bundle_authority` receives the rent and `records` are every record the
+/// bundle holds
+pub fn cancel_bundle(
+    manager: &Pubkey,
+    bundle: &Pubkey,
+    caller: &Pubkey,
+    bundle_authority: &Pubkey,
+    records: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*manager, false),
+        AccountMeta::new(*bundle, false),
+        AccountMeta::new_readonly(*caller, true),
+        AccountMeta::new(*bundle_authority, false),
+    ];
+    accounts.extend(records.iter().map(|record| AccountMeta::new(*record, false)));
+    build(&BundleInstruction::CancelBundle, accounts)
+}
+
+/// `RegisterWallet`: maps `wallet_index` to its wallet PDA, or to the
+/// keypair wallet `wallet` when given
+pub fn register_wallet(manager: &Pubkey, authority: &Pubkey, wallet_index: u8, wallet: Option<Pubkey>) -> Instruction {
+    let (registry, _) = find_wallet_registry_address(&id(), manager);
+    build(
+        &BundleInstruction::RegisterWallet { wallet_index, wallet },
+        vec![
+            AccountMeta::new(*manager, false),
+            AccountMeta::new(registry, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// `RemoveWallet`, refunding the freed rent to `authority`
+pub fn remove_wallet(manager: &Pubkey, authority: &Pubkey, wallet_index: u8) -> Instruction {
+    let (registry, _) = find_wallet_registry_address(&id(), manager);
+    build(
+        &BundleInstruction::RemoveWallet { wallet_index },
+        vec![
+            AccountMeta::new_readonly(*manager, false),
+            AccountMeta::new(registry, false),
+            AccountMeta::new(*authority, true),
+        ],
+    )
+}
+
+// The manager's wallet at `wallet_index` the way TransferTokensBundled
+// resolves it: its registered address, else its wallet PDA
+fn wallet_address(manager: &Pubkey, registry: Option<&WalletRegistry>, wallet_index: u8) -> Pubkey {
+    match registry.and_then(|registry| registry.get(wallet_index)) {
+        Some(wallet) => wallet.address,
+        None => find_wallet_address(&id(), manager, wallet_index).0,
+    }
+}
+
+// Adds `meta` once, keeping the widest privileges any duplicate asked for
+fn merge_account(accounts: &mut Vec<AccountMeta>, meta: AccountMeta) {
+    match accounts.iter_mut().find(|existing| existing.pubkey == meta.pubkey) {
+        Some(existing) => {
+            existing.is_signer |= meta.is_signer;
+            existing.is_writable |= meta.is_writable;
+        },
+        None => accounts.push(meta),
+    }
+}
+
+// Every wallet `transfers` name, keypair sources as signers, each followed
+// by its associated token account for `mint`
+fn token_wallet_accounts(
+    manager: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    transfers: &[TokenTransfer],
+    registry: Option<&WalletRegistry>,
+) -> Vec<AccountMeta> {
+    let mut accounts = Vec::new();
+    for transfer in transfers {
+        for (wallet_index, is_source) in [(transfer.source_wallet, true), (transfer.destination_wallet, false)] {
+            let registered = registry.and_then(|registry| registry.get(wallet_index));
+            let is_keypair = registered.is_some_and(|wallet| !wallet.is_pda);
+            let wallet = wallet_address(manager, registry, wallet_index);
+            merge_account(&mut accounts, AccountMeta::new_readonly(wallet, is_source && is_keypair));
+            let token_account = find_associated_token_address(&wallet, mint, token_program);
+            merge_account(&mut accounts, AccountMeta::new(token_account, false));
+        }
+    }
+    accounts
+}
+
+/// The accounts the records of `token_transfer_items` need passed to
+/// ExecuteBundle: every wallet the transfers name, keypair sources as
+/// signers, their associated token accounts for `mint`, the mint and the
+/// token and associated token account programs. `registry` is the
+/// manager's wallet registry once it has one.
+pub fn token_transfer_accounts(
+    manager: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    transfers: &[TokenTransfer],
+    registry: Option<&WalletRegistry>,
+) -> Vec<AccountMeta> {
+    let mut accounts = token_wallet_accounts(manager, mint, token_program, transfers, registry);
+    accounts.extend([
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+    ]);
+    accounts
+}
+
+/// The `BatchItem`s storing `transfers` in a bundle: for each, a
+/// TransferChecked of `mint` at `decimals` kept for its source wallet,
+/// which creates the destination's associated token account first when
+/// missing. Wallets resolve as in TransferTokensBundled; ExecuteBundle
+/// then needs `token_transfer_accounts`.
+pub fn token_transfer_items(
+    manager: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    decimals: u8,
+    transfers: &[TokenTransfer],
+    registry: Option<&WalletRegistry>,
+) -> Vec<BatchItem> {
+    transfers.iter()
+        .map(|transfer| {
+            let source = wallet_address(manager, registry, transfer.source_wallet);
+            let destination = wallet_address(manager, registry, transfer.destination_wallet);
+            let destination_account = CreateIfMissing::AssociatedTokenAccount {
+                wallet: destination,
+                mint: *mint,
+                token_program: *token_program,
+            };
+            let instruction = token_transfer_checked(
+                token_program,
+                &find_associated_token_address(&source, mint, token_program),
+                mint,
+                &destination_account.address(),
+                &source,
+                transfer.amount,
+                decimals,
+            );
+            let options = InstructionOptions { create_if_missing: Some(destination_account), ..Default::default() };
+            batch_item(transfer.source_wallet, &instruction, options)
+        })
+        .collect()
+}
+
+/// `TransferTokensBundled`, running `transfers` of `mint` at `decimals`
+/// right away. `registry` is the manager's wallet registry once it has one;
+/// a manager with approval authorities also needs them appended as signers.
+pub fn transfer_tokens_bundled(
+    manager: &Pubkey,
+    authority: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    decimals: u8,
+    transfers: &[TokenTransfer],
+    registry: Option<&WalletRegistry>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*manager, false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(*token_program, false),
+        AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(token_wallet_accounts(manager, mint, token_program, transfers, registry));
+    if registry.is_some() {
+        accounts.push(AccountMeta::new_readonly(find_wallet_registry_address(&id(), manager).0, false));
+    }
+    build(&BundleInstruction::TransferTokensBundled { decimals, transfers: transfers.to_vec() }, accounts)
+}
+
+/// `Deposit` of `lamports` into the manager's vault, which the first
+/// deposit creates at the depositor's expense
+pub fn deposit(manager: &Pubkey, depositor: &Pubkey, lamports: u64) -> Instruction {
+    build(&BundleInstruction::Deposit { lamports }, vec![
+        AccountMeta::new_readonly(*manager, false),
+        AccountMeta::new(find_vault_address(&id(), manager).0, false),
+        AccountMeta::new(*depositor, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ])
+}
+
+// The accounts WithdrawToWallet and DistributeEqually take for paying
+// `asset` to `wallet_indexes`
+fn vault_payout_accounts(
+    manager: &Pubkey,
+    authority: &Pubkey,
+    wallet_indexes: &[u8],
+    asset: &VaultAsset,
+    registry: Option<&WalletRegistry>,
+) -> Vec<AccountMeta> {
+    let vault = find_vault_address(&id(), manager).0;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*manager, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if let VaultAsset::Token { mint, token_program, .. } = asset {
+        accounts.extend([
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false),
+            AccountMeta::new(find_associated_token_address(&vault, mint, token_program), false),
+        ]);
+    }
+    for &wallet_index in wallet_indexes {
+        let wallet = wallet_address(manager, registry, wallet_index);
+        match asset {
+            VaultAsset::Sol => merge_account(&mut accounts, AccountMeta::new(wallet, false)),
+            VaultAsset::Token { mint, token_program, .. } => {
+                merge_account(&mut accounts, AccountMeta::new_readonly(wallet, false));
+                let token_account = find_associated_token_address(&wallet, mint, token_program);
+                merge_account(&mut accounts, AccountMeta::new(token_account, false));
+            },
+        }
+    }
+    if registry.is_some() {
+        accounts.push(AccountMeta::new_readonly(find_wallet_registry_address(&id(), manager).0, false));
+    }
+    accounts
+}
+
+/// `WithdrawToWallet`, paying `amount` of `asset` from the manager's vault
+/// to its wallet `wallet_index`. `registry` is the manager's wallet registry
+/// once it has one; a manager with approval authorities also needs them
+/// appended as signers.
+pub fn withdraw_to_wallet(
+    manager: &Pubkey,
+    authority: &Pubkey,
+    wallet_index: u8,
+    amount: u64,
+    asset: VaultAsset,
+    registry: Option<&WalletRegistry>,
+) -> Instruction {
+    let accounts = vault_payout_accounts(manager, authority, &[wallet_index], &asset, registry);
+    build(&BundleInstruction::WithdrawToWallet { wallet_index, amount, asset }, accounts)
+}
+
+/// `DistributeEqually`, splitting `total_amount` of `asset` in the manager's
+/// vault between `wallet_indexes`, with `withdraw_to_wallet`'s `registry`
+pub fn distribute_equally(
+    manager: &Pubkey,
+    authority: &Pubkey,
+    wallet_indexes: &[u8],
+    total_amount: u64,
+    asset: VaultAsset,
+    registry: Option<&WalletRegistry>,
+) -> Instruction {
+    let accounts = vault_payout_accounts(manager, authority, wallet_indexes, &asset, registry);
+    let wallet_indexes = wallet_indexes.to_vec();
+    build(&BundleInstruction::DistributeEqually { wallet_indexes, total_amount, asset }, accounts)
+}
+
+/// Funds the manager's wallets `0..wallet_count` with `lamports_each` from
+/// its vault in one instruction, a `distribute_equally` of SOL. Each wallet
+/// adds an account, so past a few dozen wallets send it in a versioned
+/// transaction over the manager's lookup table.
+pub fn fund_wallets_from_vault(
+    manager: &Pubkey,
+    authority: &Pubkey,
+    wallet_count: u8,
+    lamports_each: u64,
+    registry: Option<&WalletRegistry>,
+) -> Instruction {
+    let wallet_indexes: Vec<u8> = (0..wallet_count).collect();
+    let total_amount = lamports_each.saturating_mul(u64::from(wallet_count));
+    distribute_equally(manager, authority, &wallet_indexes, total_amount, VaultAsset::Sol, registry)
+}
+
+/// `SweepWallets`, draining `wallet_indexes` into `treasury`: their SOL above
+/// rent and their whole balance of each mint in `mints`, given as (mint,
+/// token program owning it). `registry` is the manager's wallet registry
+/// once it has one; keypair wallets and approval authorities also need
+/// appending as signers.
+pub fn sweep_wallets(
+    manager: &Pubkey,
+    authority: &Pubkey,
+    treasury: &Pubkey,
+    wallet_indexes: &[u8],
+    mints: &[(Pubkey, Pubkey)],
+    registry: Option<&WalletRegistry>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*manager, false),
+        AccountMeta::new(*authority, true),
+        AccountMeta::new(*treasury, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    for &wallet_index in wallet_indexes {
+        merge_account(&mut accounts, AccountMeta::new(wallet_address(manager, registry, wallet_index), false));
+    }
+    for (mint, token_program) in mints {
+        accounts.extend([
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new(find_associated_token_address(treasury, mint, token_program), false),
+        ]);
+        for &wallet_index in wallet_indexes {
+            let wallet = wallet_address(manager, registry, wallet_index);
+            let token_account = find_associated_token_address(&wallet, mint, token_program);
+            merge_account(&mut accounts, AccountMeta::new(token_account, false));
+        }
+    }
+    if !mints.is_empty() {
+        accounts.push(AccountMeta::new_readonly(ASSOCIATED_TOKEN_PROGRAM_ID, false));
+    }
+    if registry.is_some() {
+        accounts.push(AccountMeta::new_readonly(find_wallet_registry_address(&id(), manager).0, false));
+    }
+    let mint_keys = mints.iter().map(|(mint, _)| *mint).collect();
+    build(&BundleInstruction::SweepWallets { wallet_indexes: wallet_indexes.to_vec(), mints: mint_keys }, accounts)
+}
+
+/// `sweep_wallets` over every wallet PDA `registry` holds, the usual
+/// clean-up at the end of a run; keypair wallets are left to their owners
+pub fn sweep_registered_wallets(
+    manager: &Pubkey,
+    authority: &Pubkey,
+    treasury: &Pubkey,
+    registry: &WalletRegistry,
+    mints: &[(Pubkey, Pubkey)],
+) -> Instruction {
+    let wallet_indexes: Vec<u8> = registry.wallets.iter()
+        .filter(|wallet| wallet.is_pda)
+        .map(|wallet| wallet.wallet_index)
+        .collect();
+    sweep_wallets(manager, authority, treasury, &wallet_indexes, mints, Some(registry))
+}
+
+/// `GarbageCollect`, signed by `collector`, who is paid the bounty;
+/// `bundle_authority` receives the rest of the rent and `records` are every
+/// record the bundle holds. Pass the bundle's on_failure program if it has one.
+pub fn garbage_collect(
+    manager: &Pubkey,
+    bundle: &Pubkey,
+    collector: &Pubkey,
+    bundle_authority: &Pubkey,
+    records: &[Pubkey],
+    on_failure_program: Option<&Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*manager, false),
+        AccountMeta::new(*bundle, false),
+        AccountMeta::new(*collector, true),
+        AccountMeta::new(*bundle_authority, false),
+    ];
+    accounts.extend(records.iter().map(|record| AccountMeta::new(*record, false)));
+    accounts.extend(on_failure_program.map(|program| AccountMeta::new_readonly(*program, false)));
+    build(&BundleInstruction::GarbageCollect, accounts)
+}
+
+/// Decodes a bundle account's data, see `Bundle::unpack`
+pub fn decode_bundle(data: &[u8]) -> Result<Bundle, ProgramError> {
+    Bundle::unpack(data)
+}
+
+/// Decodes a manager account's data at any version this program has
+/// written, upgrading an older one as MigrateManager would, see
+/// `BundleManager::unpack`
+pub fn decode_manager(data: &[u8]) -> Result<BundleManager, ProgramError> {
+    match migration::upgrade_manager(data) {
+        Ok(Some(upgraded)) => Ok(upgraded),
+        // Data of no older layout fails as unpack explains it
+        _ => BundleManager::unpack(data),
+    }
+}
+
+/// An instruction record's, wallet registry's, vault's or execution log's
+/// data at its current layout, upgrading data from before the account had a
+/// version byte as MigrateState would. `owner` is a record's bundle, or the
+/// manager the other accounts belong to.
+pub fn current_layout<'a>(data: &'a [u8], owner: &Pubkey) -> Result<Cow<'a, [u8]>, ProgramError> {
+    Ok(match migration::upgrade_unversioned(data, owner)? {
+        Some(upgraded) => Cow::Owned(upgraded),
+        None => Cow::Borrowed(data),
+    })
+}
+
+/// Decodes a vault account's data, see `Vault::unpack`
+pub fn decode_vault(data: &[u8]) -> Result<Vault, ProgramError> {
+    Vault::unpack(data)
+}
+
+/// A bundle with the progress figures clients usually derive from it
+#[derive(Debug)]
+pub struct BundleSummary {
+    pub bundle: Bundle,
+    /// The bundle reached Executed
+    pub is_complete: bool,
+    /// Time between the last execution's start and end, when both were recorded
+    pub elapsed_ms: Option<i64>,
+    /// Planned instructions that have not executed yet
+    pub remaining_instructions: u16,
+}
+
+impl BundleSummary {
+    pub fn new(bundle: Bundle) -> Self {
+        let total = bundle.total_instructions();
+        let executed = (0..total).filter(|&index| bundle.is_executed(index)).count() as u16;
+        // Managers with ephemeral_results leave both timestamps at 0
+        let elapsed_ms = (bundle.execution_started_at > 0
+            && bundle.execution_completed_at >= bundle.execution_started_at)
+            .then(|| (bundle.execution_completed_at - bundle.execution_started_at).saturating_mul(1000));
+        Self {
+            is_complete: bundle.status == BundleStatus::Executed,
+            elapsed_ms,
+            remaining_instructions: total - executed,
+            bundle,
+        }
+    }
+
+    /// Decodes a bundle account's data and summarizes it
+    pub fn decode(data: &[u8]) -> Result<Self, ProgramError> {
+        decode_bundle(data).map(Self::new)
+    }
+}
@@ -0,0 +1,197 @@
+//! Errors the program returns, as `ProgramError::Custom` codes.
+
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive as _;
+use solana_program::{decode_error::DecodeError, program_error::ProgramError};
+use thiserror::Error;
+
+/// First custom error code used by `BundleError`.
+///
+/// Error code ranges, as seen in `ProgramError::Custom(n)`:
+/// - `0..BUNDLE_ERROR_BASE`: never produced by this program itself; a code
+///   here came from a CPI target, whose error aborts the transaction with
+///   its own code.
+/// - `BUNDLE_ERROR_BASE..`: `BundleError` variants. Each variant spells out
+///   its code so the numbers clients decode live in one place; codes are
+///   never reused or renumbered, new variants take the next free one.
+///
+/// CPI targets with their own large codes (Anchor programs use 6000+) can
+/// still land in our range. The transaction's logs tell them apart: the
+/// runtime's `Program <id> failed` line names the program that raised it.
+pub const BUNDLE_ERROR_BASE: u32 = 1000;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Error, FromPrimitive)]
+pub enum BundleError {
+    #[error("Bundle manager is paused")]
+    ManagerPaused = 1000,
+    #[error("Too many wallets in bundle")]
+    TooManyWallets = 1001,
+    #[error("wallet_indexes and instructions_per_wallet lengths differ")]
+    InvalidInstructionCount = 1002,
+    #[error("Authority cannot fund the rent for a new account")]
+    InsufficientFundsForRent = 1003,
+    #[error("Manager account is not the canonical manager PDA")]
+    InvalidManagerPda = 1004,
+    #[error("Bundle wallet bookkeeping is inconsistent")]
+    InconsistentBundleState = 1005,
+    #[error("Execution log account does not match the manager")]
+    InvalidExecutionLog = 1006,
+    #[error("Authority cannot cover the wallet funding phase")]
+    InsufficientFundsForFunding = 1007,
+    #[error("Account to create does not match its expected address")]
+    CreatedAccountMismatch = 1008,
+    #[error("Too many managers for one stats call")]
+    TooManyManagers = 1009,
+    #[error("Token program is not a token program or does not own the token account")]
+    InvalidTokenProgram = 1010,
+    #[error("Failed bundle is still in its retry cooldown")]
+    RetryCooldownActive = 1011,
+    #[error("Bundle plans more instructions than MAX_BUNDLE_INSTRUCTIONS")]
+    TooManyInstructions = 1012,
+    #[error("Reclaimed rent must go to the bundle authority")]
+    InvalidRentDestination = 1013,
+    #[error("Bundle does not belong to this manager")]
+    ManagerMismatch = 1014,
+    #[error("Bundle status does not allow this operation")]
+    InvalidStateTransition = 1015,
+    #[error("Wallet index is not part of the bundle")]
+    WalletNotInBundle = 1016,
+    #[error("Wallet already has every instruction planned for it")]
+    WalletInstructionLimitReached = 1017,
+    #[error("Fewer instruction records passed than the bundle plans")]
+    MissingInstructionRecords = 1018,
+    #[error("Instruction record belongs to a different bundle")]
+    InstructionBundleMismatch = 1019,
+    #[error("Manager account is already initialized")]
+    AlreadyInitialized = 1020,
+    #[error("Account growth exceeds the per-instruction realloc limit")]
+    ReallocTooLarge = 1021,
+    #[error("Bundle has expired")]
+    BundleExpired = 1022,
+    #[error("Bundle has not expired yet")]
+    BundleNotExpired = 1023,
+    #[error("Batch is empty or holds more than MAX_BATCH_ITEMS instructions")]
+    BatchTooLarge = 1024,
+    #[error("Manager has used every bundle seed")]
+    SeedExhausted = 1025,
+    #[error("An account a bundled instruction references was not passed")]
+    MissingAccountForInstruction = 1026,
+    #[error("A bundled instruction marks an account signer or writable beyond what the transaction grants")]
+    AccountPrivilegeEscalation = 1027,
+    #[error("Manager has no pending authority to accept")]
+    NoPendingAuthority = 1028,
+    #[error("Account layout version is not supported; older accounts need MigrateManager or MigrateState")]
+    UnsupportedAccountVersion = 1029,
+    #[error("A wallet has fewer instructions than the bundle plans for it")]
+    IncompleteBundle = 1030,
+    #[error("max_compute_units is zero or above MAX_COMPUTE_UNIT_LIMIT")]
+    InvalidComputeBudget = 1031,
+    #[error("Instruction data is longer than the manager's max_instruction_data_len")]
+    InstructionDataTooLarge = 1032,
+    #[error("Instruction references more accounts than the manager's max_accounts_per_instruction")]
+    TooManyInstructionAccounts = 1033,
+    #[error("A counter or lamport amount overflowed")]
+    ArithmeticOverflow = 1034,
+    #[error("Fewer of the manager's approval authorities signed than its threshold")]
+    InsufficientApprovals = 1035,
+    #[error("Approval threshold is 0 or above the number of authorities, or the authorities repeat or exceed MAX_APPROVAL_AUTHORITIES")]
+    InvalidApprovalThreshold = 1036,
+    #[error("ExecuteBundle slice skips or repeats records, or runs past the bundle's last record")]
+    InvalidExecutionSlice = 1037,
+    #[error("Bundle was left mid-execution and failed with FailBundle")]
+    ExecutionAbandoned = 1038,
+    #[error("Only the bundle authority can fail a bundle before its execution times out")]
+    ExecutionNotTimedOut = 1039,
+    #[error("Signer is not the bundle's authority")]
+    Unauthorized = 1040,
+    #[error("Account data belongs to another account type")]
+    InvalidAccountType = 1041,
+    #[error("Bundle account is not the PDA of its recorded seeds")]
+    InvalidBundlePda = 1042,
+    #[error("A bundle in the batch did not finish executing")]
+    BatchBundleNotExecuted = 1043,
+    #[error("Wallet index is not registered in the manager's wallet registry")]
+    WalletNotRegistered = 1044,
+    #[error("Wallet index is already registered")]
+    WalletAlreadyRegistered = 1045,
+    #[error("Instruction signer is neither the registered wallet nor the bundle authority")]
+    UnregisteredWalletSigner = 1046,
+    #[error("Wallet registry is not the manager's registry PDA")]
+    InvalidWalletRegistry = 1047,
+    #[error("Account is not an address lookup table")]
+    InvalidLookupTable = 1048,
+    #[error("Bundle is scheduled for later and cannot execute yet")]
+    BundleNotYetExecutable = 1049,
+    #[error("Manager already holds the most delegates it can")]
+    TooManyDelegates = 1050,
+    #[error("Key is not a delegate of the manager")]
+    DelegateNotFound = 1051,
+    #[error("Bundle has used up its max_retries")]
+    RetryLimitReached = 1052,
+    #[error("Vault holds less than the payout")]
+    InsufficientVaultFunds = 1053,
+    #[error("Manager has reached its max_bundles_per_slot for this slot")]
+    SlotBundleLimitReached = 1054,
+    #[error("Manager is still in its execution cooldown")]
+    ExecutionCooldownActive = 1055,
+    #[error("Instruction invokes a program the manager does not allow")]
+    ProgramNotAllowed = 1056,
+    #[error("Manager's program allowlist is full")]
+    TooManyAllowedPrograms = 1057,
+    #[error("Program is not on the manager's allowlist")]
+    AllowedProgramNotFound = 1058,
+    #[error("Bundle took more out of its wallets than its spend limits allow")]
+    SpendLimitExceeded = 1059,
+    #[error("Spend limits cap more mints than a bundle can hold")]
+    TooManySpendCaps = 1060,
+    #[error("Instruction record is not at its PDA, or records are passed twice or out of the order they were added")]
+    InvalidRecordPda = 1061,
+    #[error("A wallet index appears more than once in the bundle")]
+    DuplicateWallet = 1062,
+}
+
+impl BundleError {
+    /// Whether a `ProgramError::Custom` code falls in the `BundleError` range
+    pub fn is_bundle_error_code(code: u32) -> bool {
+        code >= BUNDLE_ERROR_BASE
+    }
+
+    /// The variant a `ProgramError::Custom` code stands for, or `None` for
+    /// codes no variant uses, such as those of a CPI target
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::from_u32(code)
+    }
+
+    /// The variant behind `error`, if it is a `BundleError`
+    pub fn from_program_error(error: &ProgramError) -> Option<Self> {
+        match error {
+            ProgramError::Custom(code) => Self::from_code(*code),
+            _ => None,
+        }
+    }
+}
+
+/// The `u32` form of `error` kept in `BundleInstructionRecord::last_error`:
+/// a `ProgramError::Custom` code as is, a builtin error as the index the
+/// runtime keeps in the upper 32 bits of its `u64` form. The two overlap
+/// below `BUNDLE_ERROR_BASE`; the failed attempt's log line tells them apart.
+pub fn error_code(error: &ProgramError) -> u32 {
+    match error {
+        ProgramError::Custom(code) => *code,
+        other => (u64::from(other.clone()) >> 32) as u32,
+    }
+}
+
+impl<T> DecodeError<T> for BundleError {
+    fn type_of() -> &'static str {
+        "BundleError"
+    }
+}
+
+const _: () = assert!(BundleError::ManagerPaused as u32 == BUNDLE_ERROR_BASE);
+
+impl From<BundleError> for ProgramError {
+    fn from(e: BundleError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
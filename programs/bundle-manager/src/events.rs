@@ -0,0 +1,131 @@
+//! Structured events for off-chain indexers.
+//!
+//! Every event is logged with `sol_log_data` as a single Borsh-encoded
+//! `BundleEvent`, so its first byte tells indexers which event follows.
+//! Decode `Program data:` log lines of this program with
+//! `BundleEvent::try_from_slice`. As with `BundleInstruction`, variants are
+//! only ever appended.
+//!
+//! The human-readable `msg!` lines are kept alongside for debugging; they are
+//! not a stable format.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{entrypoint::ProgramResult, log::sol_log_data, pubkey::Pubkey};
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundleCreatedEvent {
+    pub manager: Pubkey,
+    pub bundle_id: u32,
+    pub authority: Pubkey,
+    pub wallet_count: u8,
+    pub category: u8,
+    pub created_at: i64,
+}
+
+/// One per record AddInstruction or BatchAddInstruction creates
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InstructionAddedEvent {
+    pub manager: Pubkey,
+    pub bundle: Pubkey,
+    pub bundle_id: u32,
+    pub record: Pubkey,
+    pub record_index: u16,
+    pub wallet_index: u8,
+    /// The program the stored instruction invokes
+    pub program_id: Pubkey,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundleExecutedEvent {
+    pub manager: Pubkey,
+    pub bundle_id: u32,
+    pub authority: Pubkey,
+    pub wallet_count: u8,
+    pub execution_started_at: i64,
+    pub execution_completed_at: i64,
+    /// Lamports, see `Bundle::priority_fee`
+    pub priority_fee: u64,
+    /// The authority's net lamport change the bundle's transfers predicted
+    pub expected_net_lamports: i64,
+    /// The change actually measured across the bundle's instructions; a gap
+    /// beyond `LAMPORT_DELTA_TOLERANCE` is a discrepancy. A bundle resumed
+    /// over several calls only measures its final call.
+    pub measured_net_lamports: i64,
+    /// Lamports paid to the manager's `fee_collector`, see `BundleManager::fee_lamports`
+    pub protocol_fee: u64,
+    /// Lamports this call tipped the manager's `tip_account`; a resumed
+    /// bundle's earlier calls tipped separately
+    pub jito_tip: u64,
+    /// Compute units this call used, as in `ExecutionLogEntry::compute_units`
+    pub compute_units: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundleFailedEvent {
+    pub manager: Pubkey,
+    pub bundle_id: u32,
+    pub authority: Pubkey,
+    pub wallet_count: u8,
+    pub execution_started_at: i64,
+    pub execution_completed_at: i64,
+    /// `u64::from(ProgramError)` of the failure, e.g. a `BundleError` code
+    pub error: u64,
+    /// Compute units the failed call used; 0 for bundles failed by
+    /// FailBundle or ExpireBundle, which run nothing
+    pub compute_units: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManagerStatusChangedEvent {
+    pub manager: Pubkey,
+    pub authority: Pubkey,
+    pub is_paused: bool,
+    /// Set when the auto-pause breaker paused the manager, rather than its authority
+    pub automatic: bool,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManagerAuthorityProposedEvent {
+    pub manager: Pubkey,
+    pub authority: Pubkey,
+    /// `None` when the authority withdrew its proposal
+    pub pending_authority: Option<Pubkey>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManagerAuthorityChangedEvent {
+    pub manager: Pubkey,
+    pub previous_authority: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManagerStatsResetEvent {
+    pub manager: Pubkey,
+    pub authority: Pubkey,
+    pub previous_active_bundles: u16,
+    pub previous_total_bundles_executed: u32,
+    pub previous_total_bundles_failed: u32,
+    /// The recomputed gauge; both totals restart at 0
+    pub active_bundles: u16,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum BundleEvent {
+    BundleCreated(BundleCreatedEvent),
+    BundleExecuted(BundleExecutedEvent),
+    BundleFailed(BundleFailedEvent),
+    ManagerStatusChanged(ManagerStatusChangedEvent),
+    ManagerAuthorityProposed(ManagerAuthorityProposedEvent),
+    ManagerAuthorityChanged(ManagerAuthorityChangedEvent),
+    ManagerStatsReset(ManagerStatsResetEvent),
+    InstructionAdded(InstructionAddedEvent),
+}
+
+impl BundleEvent {
+    /// Logs the event as one `sol_log_data` field
+    pub fn emit(&self) -> ProgramResult {
+        sol_log_data(&[&self.try_to_vec()?]);
+        Ok(())
+    }
+}
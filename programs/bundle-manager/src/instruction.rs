@@ -0,0 +1,774 @@
+//! The instruction set and the argument types its variants carry.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::{CreateIfMissing, DelegatePermissions, FundWallets, InstructionAccountMeta, ManagerConfig, SpendLimits};
+
+/// Instructions accepted by the program.
+///
+/// Borsh encodes a variant by its position, so the order of variants is the
+/// instruction ABI: deployed clients depend on it. New variants are only ever
+/// appended at the end; never insert, reorder or remove one. `abi_index`
+/// spells out the expected position of every variant.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub enum BundleInstruction {
+    /// Initialize a new bundle manager
+    /// 0. `[writable]` The bundle manager PDA, seeds `[MANAGER_SEED, authority, manager_index (LE)]`
+    /// 1. `[signer, writable]` The authority account, paying rent
+    /// 2. `[]` System program
+    ///
+    /// `fee_lamports` and `fee_collector` set the protocol fee, see SetFee.
+    /// `tip_account` receives ExecuteBundle's `jito_tip_lamports`, see SetTipAccount.
+    ///
+    /// `authorities` and `threshold` make ExecuteBundle require `threshold`
+    /// of up to `MAX_APPROVAL_AUTHORITIES` distinct keys to sign alongside
+    /// the bundle authority. No authorities and a threshold of 1 leave the
+    /// bundle authority's signature as the only approval. They cannot be
+    /// changed later, so no single key can lower them. Building a bundle
+    /// moves no funds, so CreateBundle and AddInstruction need no approvals.
+    Initialize {
+        manager_index: u16,
+        config: ManagerConfig,
+        fee_lamports: u64,
+        fee_collector: Pubkey,
+        tip_account: Pubkey,
+        authorities: Vec<Pubkey>,
+        threshold: u8,
+    },
+    
+    /// Create a new bundle
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[writable]` The bundle PDA to create, seeds
+    ///    `[BUNDLE_SEED, manager, authority, client_nonce (LE)]`
+    ///    (see `find_bundle_address`)
+    /// 2. `[signer, writable]` The authority account that will own this bundle, paying rent
+    /// 3. `[]` System program
+    /// 4. `[signer, writable]` Optional: a delegate with `can_create`, creating
+    ///    the bundle for the manager authority at 2 and paying its rent in
+    ///    its place; 2 then need not sign (see `client::with_create_delegate`)
+    ///
+    /// `client_nonce` is any value the caller picks per logical bundle. A
+    /// retried CreateBundle with the same nonce and the same bundle finds the
+    /// account it already created and succeeds without creating another or
+    /// advancing `bundle_seed`; the same nonce with a different bundle fails
+    /// with `AccountAlreadyInitialized`. Past the manager's
+    /// `max_bundles_per_slot` for the current slot, CreateBundle fails with
+    /// `SlotBundleLimitReached`; a retry that finds its bundle is not counted.
+    ///
+    /// Each wallet appears once in `wallet_indexes` (`DuplicateWallet`), with
+    /// its planned instruction count at the same position of
+    /// `instructions_per_wallet`.
+    ///
+    /// `category` is stored as given and not checked: the `CATEGORY_*`
+    /// constants name the common strategies, and any other value is the
+    /// operator's own, for filtering at `Bundle::CATEGORY_OFFSET`.
+    /// `on_failure` is an optional (program id, instruction data) hook invoked
+    /// when the bundle fails; see `invoke_failure_hook`. Its program must be on
+    /// the manager's `allowed_programs` when it has any (`ProgramNotAllowed`),
+    /// and a hook taken off the list later is skipped.
+    /// `funding` optionally funds wallet PDAs before the bundle's instructions run.
+    /// `execute_after` optionally holds the bundle until that Unix timestamp:
+    /// ExecuteBundle fails with `BundleNotYetExecutable` before it, and the
+    /// manager's `expiry_seconds` count from it into the bundle's `expires_at`.
+    /// `max_retries` optionally caps how often the bundle may run again once
+    /// it has failed (see RetryBundle); None leaves it uncapped.
+    /// `spend_limits` optionally caps what each ExecuteBundle call may take
+    /// out of the bundle's wallets, with at most `MAX_TOKEN_SPEND_CAPS` mints
+    /// (`TooManySpendCaps`).
+    CreateBundle {
+        wallet_indexes: Vec<u8>,
+        instructions_per_wallet: Vec<u8>,
+        category: u8,
+        on_failure: Option<(Pubkey, Vec<u8>)>,
+        funding: Option<FundWallets>,
+        client_nonce: u64,
+        execute_after: Option<i64>,
+        max_retries: Option<u8>,
+        spend_limits: Option<SpendLimits>,
+    },
+    
+    /// Add an instruction to a bundle
+    /// 0. `[]` The bundle manager account
+    /// 1. `[writable]` The bundle account
+    /// 2. `[writable]` The instruction record PDA to create, seeds
+    ///    `[INSTRUCTION_RECORD_SEED, bundle, wallet_index, next_record_index (LE)]`
+    ///    (see `find_instruction_record_address`)
+    /// 3. `[signer, writable]` The bundle authority, paying rent
+    /// 4. `[]` System program
+    ///
+    /// `wallet_index` must be one of the bundle's `wallet_indexes` with room
+    /// left in its `instructions_per_wallet` entry. `program_id` is the
+    /// program the instruction invokes when the bundle executes, and must be
+    /// on the manager's `allowed_programs` when it has any.
+    /// `instruction_data` and `accounts` must fit the manager's
+    /// `max_instruction_data_len` and `max_accounts_per_instruction`.
+    /// A bundle takes at most `MAX_BUNDLE_INSTRUCTIONS` records over its
+    /// life, removed ones included (`TooManyInstructions`).
+    /// See `InstructionOptions` for how the instruction is stored and run.
+    /// There is no per-instruction retry: an instruction that fails when the
+    /// bundle executes aborts the whole ExecuteBundle transaction, and the
+    /// client resends it.
+    AddInstruction {
+        wallet_index: u8,
+        program_id: Pubkey,
+        instruction_data: Vec<u8>,
+        accounts: Vec<InstructionAccountMeta>,
+        options: InstructionOptions,
+    },
+    
+    /// Execute a bundle
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[writable]` The bundle account
+    /// 2. `[]` Recent blockhash info
+    /// 3. `[signer, writable]` The bundle authority, or a delegate with
+    ///    `can_execute` for a bundle of the manager authority
+    /// 4. `[]` System program
+    /// 5. `[writable]` The manager's execution log, or this program's id to skip logging
+    /// 6. `[writable]` The bundle's N instruction records, in the order they
+    ///    were added (see `find_instruction_record_address`), then every
+    ///    account and program the instructions, funding phase and
+    ///    on_failure hook reference, the manager's `fee_collector` when it
+    ///    charges a protocol fee, its `tip_account` when tipping, its
+    ///    `WalletRegistry` once it has one, and `threshold` of its approval
+    ///    `authorities` as signers
+    ///
+    /// Records run wallet by wallet, in the order of the bundle's
+    /// `wallet_indexes`, and each wallet's records in the order they were
+    /// added. `start_index` and resuming below count records in that order.
+    ///
+    /// Once every instruction has run, the manager's `fee_lamports` is
+    /// transferred from the authority to its `fee_collector`. Failed attempts
+    /// are not charged.
+    ///
+    /// A non-zero `jito_tip_lamports` is transferred from the authority to the
+    /// manager's `tip_account` as the call's last transfer, so the transaction
+    /// can land through a Jito block engine. The tip only helps if
+    /// `tip_account` is one of Jito's published tip accounts. Every call that
+    /// runs instructions pays it, including one that stops PartiallyExecuted;
+    /// failed attempts and dry runs do not.
+    ///
+    /// A failing instruction aborts the transaction and leaves the bundle as
+    /// the previous call wrote it. Problems found before the first instruction runs (stored data
+    /// that does not decode, an authority that cannot cover the bundle's
+    /// cost) mark the bundle Failed instead, and the call succeeds so that
+    /// outcome is kept. So do failures the program sees itself while running
+    /// a record, such as its `create_if_missing` account not being passed or
+    /// an error invoke hands back: the record keeps the error in
+    /// `last_error`, and the records run before it stay `executed`. A stored instruction that references an account not
+    /// passed here, or claims signer or writable access the transaction does
+    /// not grant, is rejected before anything changes, as is a record of
+    /// another bundle, one not at its PDA or passed twice or out of order
+    /// (`InvalidRecordPda`), or a bundle whose records do not add up to
+    /// `instructions_per_wallet` for every wallet. On a manager with a wallet
+    /// registry, so is a record for a wallet index that is not registered
+    /// (`WalletNotRegistered`) or whose instruction needs a signer other than
+    /// the registered wallet and the bundle authority (`UnregisteredWalletSigner`).
+    /// With a program allowlist, so is a record invoking a program taken off
+    /// it since the record was added (`ProgramNotAllowed`).
+    ///
+    /// A bundle with `spend_limits` fails the whole call with
+    /// `SpendLimitExceeded`, keeping nothing, when its records took more out
+    /// of its wallets than the limits allow. Only the lamports of wallets, and
+    /// token accounts owned by them, passed among the accounts are counted.
+    ///
+    /// `recent_fee_micro_lamports` is the recent per-compute-unit priority
+    /// price the client observed (e.g. from `getRecentPrioritizationFees`).
+    /// Compute budget instructions only take effect at the top level of a
+    /// transaction, so the client prepends `client::compute_budget_instructions`
+    /// for the price `compute_unit_price` derives; see there for the mapping.
+    /// `max_compute_units` must be within `MAX_COMPUTE_UNIT_LIMIT`.
+    ///
+    /// Bundles too large for one transaction run over several calls. Before
+    /// each instruction, ExecuteBundle checks that `EXECUTION_COMPUTE_RESERVE`
+    /// compute units remain; if not, it stops and leaves the bundle
+    /// PartiallyExecuted, its completed records flagged `executed`. The next
+    /// call resumes at the first record not yet executed and only needs the
+    /// accounts of the records still to run. The funding phase runs once, and
+    /// the bundle ends Executed after its last record. A call that cannot run
+    /// a single instruction fails with `InvalidComputeBudget`.
+    ///
+    /// A non-zero `count` gives the client that batching explicitly: the call
+    /// runs only the `count` records from `start_index`, and only their
+    /// accounts need to be passed, although every record account still is.
+    /// The slice must start at the first record not yet executed and stay
+    /// within the bundle, otherwise the call fails with
+    /// `InvalidExecutionSlice`. The bundle ends Executed once its last record
+    /// has run, in whichever call that is. With `count` 0, `start_index` must
+    /// be 0 and the call runs every record not yet executed.
+    ///
+    /// With `dry_run` set, every check above runs, including the cost check
+    /// that would otherwise mark the bundle Failed, and the would-be priority
+    /// fee is logged, but no instruction is invoked and nothing is written.
+    /// The first failing check is returned as an error.
+    ///
+    /// Other than a dry run, every call is throttled first: within the
+    /// manager's `cooldown_slots` of its last execution call it fails with
+    /// `ExecutionCooldownActive`, and past its `max_bundles_per_slot` with
+    /// `SlotBundleLimitReached`. RetryBundle and ExecuteBundleBatch are
+    /// throttled the same way, the batch counting each of its bundles.
+    ExecuteBundle {
+        max_compute_units: u32,
+        recent_fee_micro_lamports: u64,
+        dry_run: bool,
+        jito_tip_lamports: u64,
+        start_index: u8,
+        count: u8,
+    },
+    
+    /// Set manager status (pause/unpause)
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer]` The authority account, or a delegate with `can_pause`
+    ///    when pausing
+    SetManagerStatus {
+        is_paused: bool,
+    },
+    
+    /// Create the manager's execution log, seeds `[EXECUTION_LOG_SEED, manager]`
+    /// 0. `[]` The bundle manager account
+    /// 1. `[writable]` The execution log PDA to create
+    /// 2. `[signer, writable]` The manager authority, paying rent
+    /// 3. `[]` System program
+    ///
+    /// The log grows one entry per execution attempt until it holds
+    /// `max_entries`, after which the oldest entries are overwritten.
+    InitializeExecutionLog {
+        max_entries: u32,
+    },
+    
+    /// Read stats for several managers in one call
+    /// 0..N. `[]` Bundle manager accounts, at most `MAX_STATS_MANAGERS`
+    ///
+    /// Sets return data to a Borsh `Vec<ManagerStats>` in account order.
+    GetManagersStats,
+    
+    /// Report which of a bundle's instructions have executed
+    /// 0. `[]` The bundle account
+    ///
+    /// Sets return data to a Borsh `ExecutionState`.
+    GetExecutionState,
+    
+    /// Estimate what executing a bundle would cost its authority, without
+    /// changing any state
+    /// 0. `[]` The bundle manager account
+    /// 1. `[]` The bundle account
+    ///
+    /// `max_compute_units`, `recent_fee_micro_lamports` and
+    /// `jito_tip_lamports` are those of the ExecuteBundle call to estimate.
+    /// Sets return data to a Borsh `CostEstimate`.
+    EstimateCost {
+        max_compute_units: u32,
+        recent_fee_micro_lamports: u64,
+        jito_tip_lamports: u64,
+    },
+    
+    /// Close an Executed or Failed bundle and its instruction records
+    /// 0. `[]` The bundle manager account
+    /// 1. `[writable]` The bundle account
+    /// 2. `[signer, writable]` The bundle authority
+    /// 3. `[writable]` Destination for the bundle account's lamports
+    /// 4. `[writable]` Every instruction record added to the bundle, one account each
+    ///
+    /// Record rent always goes to the bundle authority, see
+    /// `close_instruction_account`.
+    CloseBundle,
+    
+    /// Move an expired Created bundle to Failed. Permissionless: anyone may
+    /// sweep, since only bundles past their `expires_at` qualify.
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[writable]` The bundle account
+    /// 2. `[]` The on_failure hook program, if the bundle has one
+    ///
+    /// The authority can then reclaim the rent with CloseBundle, or anyone
+    /// with GarbageCollect.
+    ExpireBundle,
+    
+    /// Add up to `MAX_BATCH_ITEMS` instructions to a bundle in one call
+    /// 0. `[]` The bundle manager account
+    /// 1. `[writable]` The bundle account
+    /// 2. `[signer, writable]` The bundle authority, paying rent
+    /// 3. `[]` System program
+    /// 4. `[writable]` One instruction record PDA to create per item, in item
+    ///    order, derived with consecutive record indexes from the bundle's
+    ///    `next_record_index`
+    ///
+    /// Per-wallet budgets are checked for the whole batch before any record
+    /// is created, so either every item is added or none is.
+    BatchAddInstruction {
+        items: Vec<BatchItem>,
+    },
+    
+    /// Propose a new manager authority, which takes over once it signs
+    /// AcceptAuthority
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer]` The current authority
+    ///
+    /// The handoff is two-step so a mistyped key cannot take the manager: the
+    /// current authority stays in control until the new one accepts. A later
+    /// proposal replaces a pending one, and proposing the current authority
+    /// cancels it. The manager keeps its address, and bundles keep the
+    /// authority they were created with.
+    SetAuthority {
+        new_authority: Pubkey,
+    },
+    
+    /// Accept a manager authority proposed with SetAuthority
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer]` The pending authority
+    AcceptAuthority,
+    
+    /// Remove an instruction from a bundle that has not started executing
+    /// 0. `[]` The bundle manager account
+    /// 1. `[writable]` The bundle account
+    /// 2. `[writable]` The instruction record to remove
+    /// 3. `[signer, writable]` The bundle authority, receiving the record's rent
+    ///
+    /// Records are standalone accounts, so the record passed is what
+    /// identifies the instruction. Its wallet gets the slot back and can take
+    /// another AddInstruction; to replace an instruction, put RemoveInstruction
+    /// and AddInstruction in the same transaction.
+    RemoveInstruction,
+    
+    /// Upgrade a manager, and optionally some of its bundles, to the current
+    /// account layout versions
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer, writable]` The manager authority, paying rent for any growth
+    /// 2. `[]` System program
+    /// 3. `[writable]` Each bundle of the manager to upgrade, one account each
+    ///
+    /// Accounts already at the current version are left as they are, so the
+    /// call can be repeated to migrate bundles in batches. Every other
+    /// instruction rejects accounts at an older version with
+    /// `UnsupportedAccountVersion`.
+    MigrateManager,
+    
+    /// Change the protocol fee the manager charges per executed bundle
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer]` The manager authority
+    ///
+    /// A `fee_lamports` of 0 turns the fee off.
+    SetFee {
+        fee_lamports: u64,
+        fee_collector: Pubkey,
+    },
+    
+    /// Change the account ExecuteBundle's `jito_tip_lamports` are paid to
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer]` The manager authority
+    ///
+    /// Tips only help a transaction land if this is one of the tip accounts
+    /// Jito publishes.
+    SetTipAccount {
+        tip_account: Pubkey,
+    },
+    
+    /// Correct the manager's counters after they drift, as a break-glass tool
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer]` The manager authority
+    /// 2. `[]` Every bundle of the manager that is still active, one account each
+    ///
+    /// `active_bundles` is recomputed as the number of Created, Executing or
+    /// PartiallyExecuted bundles passed; the program cannot tell whether any
+    /// were left out. `total_bundles_executed` and `total_bundles_failed` are
+    /// zeroed. Nothing else, funds and authority included, is touched.
+    /// Emits ManagerStatsReset with the previous values.
+    ResetStats,
+    
+    /// Move a bundle left Executing or PartiallyExecuted to Failed, for when
+    /// the transaction that would finish it never lands
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[writable]` The bundle account
+    /// 2. `[signer]` The caller
+    /// 3. `[]` The on_failure hook program, if the bundle has one
+    ///
+    /// The bundle authority may fail its bundle at any time. Anyone else may
+    /// once `execution_timeout_seconds` have passed since
+    /// `execution_started_at`, and not at all on managers with the timeout
+    /// disabled or with `ephemeral_results`, which leave no start time.
+    /// Records already executed stay flagged, so a retry resumes after them.
+    FailBundle,
+    
+    /// Abandon a Created bundle before it runs, closing it and its
+    /// instruction records
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[writable]` The bundle account
+    /// 2. `[signer]` The bundle authority or the manager authority
+    /// 3. `[writable]` The bundle authority, receiving the rent
+    /// 4. `[writable]` Every instruction record added to the bundle, one account each
+    ///
+    /// All rent, the bundle account's included, goes to the bundle
+    /// authority whoever cancels, and the bundle leaves `active_bundles`.
+    CancelBundle,
+    
+    /// Execute several bundles of one authority in a single transaction, all or nothing
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[writable, signer]` The authority of every bundle
+    /// 2. `[]` The system program
+    /// 3. `[writable]` The manager's execution log, or this program's id to skip logging
+    /// 4. `[writable]` The `bundle_count` bundle accounts, in execution order
+    /// 5. `[writable]` The instruction records of each bundle in turn, in the order they were added
+    /// 6. `[]` Every account and program the bundles' instructions reference, plus
+    ///    the fee collector and tip account when they are paid and the
+    ///    manager's `WalletRegistry` once it has one
+    ///
+    /// Each bundle runs as a full ExecuteBundle with the same compute
+    /// parameters, and `jito_tip_lamports` is paid once, by the last bundle.
+    /// Every bundle must end Executed: one that is marked Failed, or stops
+    /// PartiallyExecuted for lack of compute, fails the instruction with
+    /// `BatchBundleNotExecuted`, so that nothing in the transaction is kept,
+    /// the statuses of the bundles before it included.
+    ExecuteBundleBatch {
+        bundle_count: u8,
+        max_compute_units: u32,
+        recent_fee_micro_lamports: u64,
+        jito_tip_lamports: u64,
+    },
+    
+    /// Register a wallet index in the manager's wallet registry, seeds
+    /// `[WALLET_REGISTRY_SEED, manager]`, creating the registry on first use
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[writable]` The wallet registry
+    /// 2. `[writable, signer]` The manager authority, paying the registry's rent
+    /// 3. `[]` System program
+    ///
+    /// With `wallet` None the index maps to its wallet PDA, which
+    /// ExecuteBundle signs for; with Some it maps to that keypair wallet,
+    /// which must sign ExecuteBundle itself. An index is registered once
+    /// (`WalletAlreadyRegistered`); remove it to change its wallet. The first
+    /// registration turns the registry on for the manager for good.
+    RegisterWallet {
+        wallet_index: u8,
+        wallet: Option<Pubkey>,
+    },
+    
+    /// Remove a wallet index from the manager's wallet registry
+    /// 0. `[]` The bundle manager account
+    /// 1. `[writable]` The wallet registry
+    /// 2. `[writable, signer]` The manager authority, receiving the freed rent
+    ///
+    /// Bundles still holding records for the wallet stop executing until it
+    /// is registered again.
+    RemoveWallet {
+        wallet_index: u8,
+    },
+    
+    /// Replace the manager's config
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer]` The manager authority
+    ///
+    /// Limits left at 0 take their defaults and are checked against their
+    /// ceilings, as at Initialize. Bundles and records already created keep
+    /// whatever the old limits allowed; the new fee multiplier and price cap
+    /// apply from the next ExecuteBundle.
+    UpdateManagerConfig {
+        config: ManagerConfig,
+    },
+    
+    /// Record the address lookup table the manager's bots share
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer]` The manager authority
+    /// 2. `[]` The lookup table, only when `lookup_table` is Some
+    ///
+    /// The table must be owned by the address lookup table program
+    /// (`InvalidLookupTable`). None unlinks the current table. Bots read the
+    /// address from the manager to build v0 transactions; the program itself
+    /// never uses it.
+    LinkLookupTable {
+        lookup_table: Option<Pubkey>,
+    },
+    
+    /// Let `delegate` act for the manager authority
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer]` The manager authority
+    ///
+    /// A key that already is a delegate gets `permissions` in place of its
+    /// old ones. A manager holds at most `MAX_DELEGATES` (`TooManyDelegates`).
+    /// Delegates never change the manager itself; see `DelegatePermissions`
+    /// for what each permission allows.
+    AddDelegate {
+        delegate: Pubkey,
+        permissions: DelegatePermissions,
+    },
+    
+    /// Revoke a delegate
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer]` The manager authority
+    ///
+    /// Fails with `DelegateNotFound` unless `delegate` is one.
+    RemoveDelegate {
+        delegate: Pubkey,
+    },
+    
+    /// Move tokens of one mint between the manager's wallets right away,
+    /// without building a bundle
+    /// 0. `[]` The bundle manager account
+    /// 1. `[signer, writable]` The manager authority, or a delegate with
+    ///    `can_execute`, paying rent for missing token accounts
+    /// 2. `[]` The mint
+    /// 3. `[]` The token program owning the mint, legacy or token-2022
+    /// 4. `[]` Associated token account program
+    /// 5. `[]` System program
+    /// 6. Every wallet the transfers name and its associated token account
+    ///    for the mint (writable), the manager's `WalletRegistry` once it has
+    ///    one, and `threshold` of its approval `authorities` as signers
+    ///
+    /// Each transfer is a TransferChecked of `amount` at `decimals` from the
+    /// source wallet's associated token account to the destination wallet's,
+    /// which is created first when missing. A wallet is the manager's wallet
+    /// PDA at its index or, once the manager has a wallet registry, the
+    /// address registered for it (`WalletNotRegistered` when there is none).
+    /// The program signs for PDA sources; a keypair source must sign the
+    /// transaction. `client::token_transfer_items` stores the same transfers
+    /// in a bundle instead.
+    TransferTokensBundled {
+        decimals: u8,
+        transfers: Vec<TokenTransfer>,
+    },
+    
+    /// Run a Failed bundle again, from its first record not yet executed
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[writable]` The bundle account
+    /// 2. `[]` Recent blockhash info
+    /// 3. `[signer, writable]` The bundle authority, or a delegate with
+    ///    `can_execute` for a bundle of the manager authority
+    /// 4. `[]` System program
+    /// 5. `[writable]` The manager's execution log, or this program's id to skip logging
+    /// 6. `[writable]` The bundle's instruction records and the other
+    ///    accounts ExecuteBundle takes; records already executed need no
+    ///    instruction accounts
+    ///
+    /// A full ExecuteBundle, with its checks, fees and tip, for bundles that
+    /// are Failed; anything else fails with `InvalidStateTransition`. Records
+    /// flagged `executed` are skipped, so a bundle that failed halfway
+    /// replays only what it has not done. The manager's
+    /// `retry_cooldown_slots` must have passed since the last attempt, and
+    /// the retry counts against the bundle's `max_retries`
+    /// (`RetryLimitReached` once they are used up). ExecuteBundle on a
+    /// Failed bundle is the same retry.
+    RetryBundle {
+        max_compute_units: u32,
+        recent_fee_micro_lamports: u64,
+        jito_tip_lamports: u64,
+    },
+    
+    /// Add lamports to the manager's vault, creating it on the first deposit
+    /// 0. `[]` The bundle manager account
+    /// 1. `[writable]` The manager's vault PDA
+    /// 2. `[signer, writable]` The depositor, paying the vault's rent when it
+    ///    is created
+    /// 3. `[]` System program
+    ///
+    /// Anyone may deposit, also while the manager is paused. Tokens are
+    /// deposited by transferring them to the vault's associated token account
+    /// for their mint; no instruction of this program is needed for that.
+    Deposit {
+        lamports: u64,
+    },
+    
+    /// Pay `amount` of `asset` out of the manager's vault to one of its wallets
+    /// 0. `[]` The bundle manager account
+    /// 1. `[writable]` The manager's vault PDA
+    /// 2. `[signer, writable]` The manager authority, paying rent for missing
+    ///    token accounts
+    /// 3. `[]` System program
+    /// 4. The wallet (writable for SOL), and for a token asset the mint, its
+    ///    token program, the associated token account program and the vault's
+    ///    and wallet's associated token accounts (writable); the manager's
+    ///    `WalletRegistry` once it has one, and `threshold` of its approval
+    ///    `authorities` as signers
+    ///
+    /// Wallets resolve as in TransferTokensBundled. SOL payouts leave the
+    /// vault rent-exempt (`InsufficientVaultFunds` otherwise); the wallet's
+    /// associated token account is created first when missing. Fails with
+    /// `ManagerPaused` while the manager is paused.
+    WithdrawToWallet {
+        wallet_index: u8,
+        amount: u64,
+        asset: VaultAsset,
+    },
+    
+    /// Split `total_amount` of `asset` in the manager's vault evenly between
+    /// `wallet_indexes`, with WithdrawToWallet's accounts for every wallet
+    ///
+    /// Each wallet receives `total_amount / wallet_indexes.len()`; the
+    /// remainder stays in the vault. A wallet named twice is paid twice. Fails
+    /// with `InvalidInstructionData` when the share would be zero.
+    DistributeEqually {
+        wallet_indexes: Vec<u8>,
+        total_amount: u64,
+        asset: VaultAsset,
+    },
+    
+    /// Drain the manager's wallets into a treasury after a run
+    /// 0. `[]` The bundle manager account
+    /// 1. `[signer, writable]` The manager authority, paying rent for the
+    ///    treasury's missing token accounts
+    /// 2. `[writable]` The treasury receiving the funds
+    /// 3. `[]` System program
+    /// 4. Every wallet (writable), and for each mint the mint, its token
+    ///    program, the associated token account program and the wallets'
+    ///    and treasury's associated token accounts for it (writable); the
+    ///    manager's `WalletRegistry` once it has one, and `threshold` of its
+    ///    approval `authorities` as signers
+    ///
+    /// Each wallet's whole balance of every mint in `mints` moves to the
+    /// treasury's associated token account, created first when missing;
+    /// wallets without a token account for a mint are skipped. Then every
+    /// lamport above the wallet's rent-exempt minimum goes to the treasury.
+    /// Wallets resolve as in TransferTokensBundled; the program signs for
+    /// wallet PDAs, keypair wallets must sign the transaction. Allowed while
+    /// the manager is paused, so funds can be recovered after a circuit
+    /// breaker trip. The wallets' token accounts are left open.
+    SweepWallets {
+        wallet_indexes: Vec<u8>,
+        mints: Vec<Pubkey>,
+    },
+    
+    /// Close an expired bundle and its instruction records. Permissionless:
+    /// whoever collects is paid `GARBAGE_COLLECT_BOUNTY_LAMPORTS` of the
+    /// bundle's rent, the rest goes to the bundle authority.
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[writable]` The bundle account
+    /// 2. `[signer, writable]` The collector, receiving the bounty
+    /// 3. `[writable]` The bundle authority, receiving the remaining rent
+    /// 4. `[writable]` Every instruction record added to the bundle, one account each
+    /// 5. `[]` The on_failure hook program, if the bundle has one
+    ///
+    /// Qualifies once the bundle is past its `expires_at`, while Created or
+    /// Failed without any instruction executed. A Created bundle is expired
+    /// first, as with ExpireBundle, and leaves `active_bundles`.
+    GarbageCollect,
+    
+    /// Let the manager's bundles invoke `program_id`
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer]` The manager authority
+    ///
+    /// While the allowlist is empty bundles may invoke any program; once it
+    /// holds one, AddInstruction and ExecuteBundle fail with
+    /// `ProgramNotAllowed` for instructions invoking any other. Adding a
+    /// listed program is a no-op. A manager lists at most
+    /// `MAX_ALLOWED_PROGRAMS` (`TooManyAllowedPrograms`).
+    AddAllowedProgram {
+        program_id: Pubkey,
+    },
+    
+    /// Take `program_id` off the manager's allowlist
+    /// 0. `[writable]` The bundle manager account
+    /// 1. `[signer]` The manager authority
+    ///
+    /// Fails with `AllowedProgramNotFound` unless it is listed. Removing the
+    /// last program allows every program again.
+    RemoveAllowedProgram {
+        program_id: Pubkey,
+    },
+    
+    /// Upgrade a manager's wallet registry, vault, execution log and
+    /// instruction records from before they carried a version byte
+    /// 0. `[]` The bundle manager account, already at the current version
+    /// 1. `[signer, writable]` The manager authority, paying rent for the growth
+    /// 2. `[]` System program
+    /// 3. `[writable]` Each account to upgrade, one account each, plus `[]` the
+    ///    bundle of every record among them, in any order
+    ///
+    /// Each account grows by the version byte, the authority topping up the
+    /// rent it then falls short of. A vault holding more than its rent is not
+    /// topped up, so its withdrawable SOL drops by the byte's rent.
+    /// Accounts already at their current version are left as they are, so
+    /// the call can be repeated in batches. The manager itself and its
+    /// bundles are upgraded by MigrateManager.
+    MigrateState,
+}
+
+impl BundleInstruction {
+    /// The variant's Borsh discriminant, i.e. the first byte of its encoding
+    pub const fn abi_index(&self) -> u8 {
+        match self {
+            BundleInstruction::Initialize { .. } => 0,
+            BundleInstruction::CreateBundle { .. } => 1,
+            BundleInstruction::AddInstruction { .. } => 2,
+            BundleInstruction::ExecuteBundle { .. } => 3,
+            BundleInstruction::SetManagerStatus { .. } => 4,
+            BundleInstruction::InitializeExecutionLog { .. } => 5,
+            BundleInstruction::GetManagersStats => 6,
+            BundleInstruction::GetExecutionState => 7,
+            BundleInstruction::EstimateCost { .. } => 8,
+            BundleInstruction::CloseBundle => 9,
+            BundleInstruction::ExpireBundle => 10,
+            BundleInstruction::BatchAddInstruction { .. } => 11,
+            BundleInstruction::SetAuthority { .. } => 12,
+            BundleInstruction::AcceptAuthority => 13,
+            BundleInstruction::RemoveInstruction => 14,
+            BundleInstruction::MigrateManager => 15,
+            BundleInstruction::SetFee { .. } => 16,
+            BundleInstruction::SetTipAccount { .. } => 17,
+            BundleInstruction::ResetStats => 18,
+            BundleInstruction::FailBundle => 19,
+            BundleInstruction::CancelBundle => 20,
+            BundleInstruction::ExecuteBundleBatch { .. } => 21,
+            BundleInstruction::RegisterWallet { .. } => 22,
+            BundleInstruction::RemoveWallet { .. } => 23,
+            BundleInstruction::UpdateManagerConfig { .. } => 24,
+            BundleInstruction::LinkLookupTable { .. } => 25,
+            BundleInstruction::AddDelegate { .. } => 26,
+            BundleInstruction::RemoveDelegate { .. } => 27,
+            BundleInstruction::TransferTokensBundled { .. } => 28,
+            BundleInstruction::RetryBundle { .. } => 29,
+            BundleInstruction::Deposit { .. } => 30,
+            BundleInstruction::WithdrawToWallet { .. } => 31,
+            BundleInstruction::DistributeEqually { .. } => 32,
+            BundleInstruction::SweepWallets { .. } => 33,
+            BundleInstruction::GarbageCollect => 34,
+            BundleInstruction::AddAllowedProgram { .. } => 35,
+            BundleInstruction::RemoveAllowedProgram { .. } => 36,
+            BundleInstruction::MigrateState => 37,
+        }
+    }
+}
+
+/// Per-instruction storage and execution options for AddInstruction
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default)]
+pub struct InstructionOptions {
+    /// Store instruction_data run-length encoded when that makes it smaller
+    /// (see the `compression` module)
+    pub compress: bool,
+    /// An account ExecuteBundle creates right before invoking this
+    /// instruction if it does not exist yet
+    pub create_if_missing: Option<CreateIfMissing>,
+}
+
+/// One transfer of TransferTokensBundled, between two of the manager's wallets
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenTransfer {
+    pub source_wallet: u8,
+    pub destination_wallet: u8,
+    pub amount: u64,
+}
+
+/// What WithdrawToWallet and DistributeEqually pay out of a manager's vault
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VaultAsset {
+    /// Lamports of the vault account
+    Sol,
+    /// Tokens of `mint`, at `decimals`, from the vault's associated token
+    /// account for it under `token_program`
+    Token {
+        mint: Pubkey,
+        token_program: Pubkey,
+        decimals: u8,
+    },
+}
+
+/// One instruction of a BatchAddInstruction, with AddInstruction's arguments
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct BatchItem {
+    pub wallet_index: u8,
+    pub program_id: Pubkey,
+    pub instruction_data: Vec<u8>,
+    pub accounts: Vec<InstructionAccountMeta>,
+    pub options: InstructionOptions,
+}
+
+/// Most items one BatchAddInstruction takes. Each item needs its own record
+/// account key and signature (96 bytes) on top of its data, so larger batches
+/// would not fit the 1232-byte transaction limit.
+pub const MAX_BATCH_ITEMS: usize = 8;
@@ -1,330 +1,210 @@
 use solana_program::{
-    account_info::{next_account_info, AccountInfo},
-    entrypoint,
-    entrypoint::ProgramResult,
-    msg,
+    instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     pubkey::Pubkey,
-    system_instruction,
-    program::{invoke, invoke_signed},
-    sysvar::{rent::Rent, Sysvar},
-    clock::Clock,
+    system_program,
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 
-// Define the program ID
-solana_program::declare_id!("Replace_with_your_program_id_after_deployment");
+#[cfg(feature = "client")]
+pub mod client;
+pub mod compression;
+pub mod error;
+pub mod events;
+pub mod instruction;
+mod math;
+mod migration;
+pub mod processor;
+pub mod state;
+mod validation;
+
+pub use error::*;
+pub use instruction::*;
+pub use processor::process_instruction;
+pub use state::*;
+
+use math::{checked_add, checked_mul};
+
+// Define the program ID. This is a placeholder: replace it with the address
+// of the deploy keypair (`solana address -k target/deploy/bundle_manager-keypair.json`)
+solana_program::declare_id!("ArD4uLcEVc5xrXQREnuMdeFpERfzqpkxjFDXXTcG9srF");
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub enum BundleInstruction {
-    /// Initialize a new bundle manager
-    /// 0. `[signer]` The authority account
-    /// 1. `[writable]` The bundle manager account to initialize
-    /// 2. `[]` System program
-    Initialize {
-        bundle_size: u8,
-        priority_fee_multiplier: u8,
-    },
-    
-    /// Create a new bundle
-    /// 0. `[]` The bundle manager account
-    /// 1. `[writable]` The bundle account to create
-    /// 2. `[signer]` The authority account that will own this bundle
-    /// 3. `[]` System program
-    CreateBundle {
-        wallet_indexes: Vec<u8>,
-        instructions_per_wallet: Vec<u8>,
-    },
-    
-    /// Add an instruction to a bundle
-    /// 0. `[writable]` The bundle account
-    /// 1. `[writable]` The instruction account to create
-    /// 2. `[signer]` The authority account
-    /// 3. `[]` System program
-    AddInstruction {
-        wallet_index: u8,
-        instruction_data: Vec<u8>,
-        accounts: Vec<InstructionAccountMeta>,
-    },
-    
-    /// Execute a bundle
-    /// 0. `[writable]` The bundle manager account
-    /// 1. `[writable]` The bundle account
-    /// 2. `[]` Recent blockhash info
-    /// 3. `[signer]` The authority account
-    /// 4. `[]` System program
-    ExecuteBundle {
-        max_compute_units: u32,
-    },
-    
-    /// Set manager status (pause/unpause)
-    /// 0. `[writable]` The bundle manager account
-    /// 1. `[signer]` The authority account
-    SetManagerStatus {
-        is_paused: bool,
-    },
-}
-
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-pub struct InstructionAccountMeta {
-    pub pubkey: Pubkey,
-    pub is_signer: bool,
-    pub is_writable: bool,
-}
-
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub enum BundleStatus {
-    Created,
-    Executing,
-    Executed,
-    Failed,
+// Entry point is the function called when the program is invoked
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+/// Lamports the authority spends when a bundle executes, as returned by
+/// EstimateCost. The base signature fee is outside the program's control and
+/// not included.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// Paid to wallet PDAs by the bundle's funding phase
+    pub funding_lamports: u64,
+    /// Paid to the manager's `fee_collector` once the bundle has run
+    pub protocol_fee_lamports: u64,
+    /// Charged by the runtime for the priority price `compute_unit_price`
+    /// derives, over the call's `max_compute_units`
+    pub priority_fee_lamports: u64,
+    /// Paid to the manager's `tip_account`
+    pub jito_tip_lamports: u64,
+    pub total_lamports: u64,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub struct BundleManager {
-    pub authority: Pubkey,
-    pub bundle_size: u8,
-    pub priority_fee_multiplier: u8,
-    pub active_bundles: u16,
-    pub total_bundles_executed: u32,
-    pub is_paused: bool,
-    pub bundle_seed: u32,
+/// Computes a bundle's execution cost for an ExecuteBundle call with the
+/// same `max_compute_units`, `recent_fee_micro_lamports` and
+/// `jito_tip_lamports`. ExecuteBundle charges from the same formulas, so an
+/// EstimateCost result matches the real spend.
+pub fn estimate_cost(
+    bundle: &Bundle,
+    bundle_manager: &BundleManager,
+    max_compute_units: u32,
+    recent_fee_micro_lamports: u64,
+    jito_tip_lamports: u64,
+) -> Result<CostEstimate, ProgramError> {
+    let funding_lamports = match &bundle.funding {
+        Some(funding) => funding.total_lamports()?,
+        None => 0,
+    };
+    let protocol_fee_lamports = bundle_manager.fee_lamports;
+    let price = compute_unit_price(&bundle_manager.config, recent_fee_micro_lamports)?;
+    let priority_fee_lamports = priority_fee_lamports(price, max_compute_units);
+    
+    Ok(CostEstimate {
+        funding_lamports,
+        protocol_fee_lamports,
+        priority_fee_lamports,
+        jito_tip_lamports,
+        total_lamports: [protocol_fee_lamports, priority_fee_lamports, jito_tip_lamports]
+            .into_iter()
+            .try_fold(funding_lamports, checked_add)?,
+    })
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub struct Bundle {
-    pub manager: Pubkey,
-    pub authority: Pubkey,
-    pub bundle_id: u32,
-    pub created_at: i64,
-    pub execution_started_at: i64,
-    pub execution_completed_at: i64,
-    pub wallet_count: u8,
-    pub wallet_indexes: Vec<u8>,
-    pub instructions_per_wallet: Vec<u8>,
-    pub status: BundleStatus,
-    pub priority_fee: u16,
+pub const COMPUTE_BUDGET_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("ComputeBudget111111111111111111111111111111");
+
+/// Most compute units a transaction can request with SetComputeUnitLimit
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Compute units ExecuteBundle keeps back for writing the bundle's progress
+/// and finishing the call; it does not start another instruction below this
+pub const EXECUTION_COMPUTE_RESERVE: u64 = 30_000;
+
+/// Per-compute-unit priority price, in micro-lamports, a manager pays on top
+/// of the recent price: `recent_fee_micro_lamports * priority_fee_multiplier / 10`.
+/// A multiplier of 10 matches the recent price, 15 pays 1.5x, 30 pays 3x and
+/// 0 opts out of priority fees. A non-zero `max_compute_unit_price` caps the
+/// result. Fails with `ArithmeticOverflow` for a recent price too large to
+/// multiply.
+pub fn compute_unit_price(config: &ManagerConfig, recent_fee_micro_lamports: u64) -> Result<u64, ProgramError> {
+    let price = checked_mul(recent_fee_micro_lamports, config.priority_fee_multiplier as u64)? / 10;
+    Ok(match config.max_compute_unit_price {
+        0 => price,
+        cap => price.min(cap),
+    })
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub struct BundleInstruction {
-    pub bundle: Pubkey,
-    pub wallet_index: u8,
-    pub instruction_data: Vec<u8>,
-    pub accounts: Vec<InstructionAccountMeta>,
-    pub executed: bool,
+/// Lamports a priority price costs over `compute_units`, rounded up the way
+/// the runtime charges it
+pub fn priority_fee_lamports(price_micro_lamports: u64, compute_units: u32) -> u64 {
+    let micro_lamports = price_micro_lamports as u128 * compute_units as u128;
+    u64::try_from(micro_lamports.div_ceil(1_000_000)).unwrap_or(u64::MAX)
 }
 
-// Entry point is the function called when the program is invoked
-entrypoint!(process_instruction);
+/// Allowed gap between `expected_net_lamports` and the authority's measured
+/// delta before ExecuteBundle reports a discrepancy
+pub const LAMPORT_DELTA_TOLERANCE: u64 = 0;
 
-// Program entrypoint's implementation
-pub fn process_instruction(
+/// Lamport effect on `authority` of a system-program transfer, or 0 for any
+/// other instruction. Transfers moving lamports out of the authority count
+/// negative, transfers into it positive.
+pub fn transfer_lamport_delta(
     program_id: &Pubkey,
-    accounts: &[AccountInfo],
     instruction_data: &[u8],
-) -> ProgramResult {
-    let instruction = BundleInstruction::try_from_slice(instruction_data)
-        .map_err(|_| ProgramError::InvalidInstructionData)?;
-
-    match instruction {
-        BundleInstruction::Initialize { bundle_size, priority_fee_multiplier } => {
-            process_initialize(program_id, accounts, bundle_size, priority_fee_multiplier)
-        },
-        BundleInstruction::CreateBundle { wallet_indexes, instructions_per_wallet } => {
-            process_create_bundle(program_id, accounts, wallet_indexes, instructions_per_wallet)
-        },
-        BundleInstruction::AddInstruction { wallet_index, instruction_data, accounts: instr_accounts } => {
-            process_add_instruction(program_id, accounts, wallet_index, instruction_data, instr_accounts)
-        },
-        BundleInstruction::ExecuteBundle { max_compute_units } => {
-            process_execute_bundle(program_id, accounts, max_compute_units)
-        },
-        BundleInstruction::SetManagerStatus { is_paused } => {
-            process_set_manager_status(program_id, accounts, is_paused)
-        },
+    accounts: &[InstructionAccountMeta],
+    authority: &Pubkey,
+) -> i64 {
+    // SystemInstruction::Transfer is bincode: u32 tag 2, then u64 lamports
+    if *program_id != system_program::ID || instruction_data.len() != 12 || instruction_data[..4] != [2, 0, 0, 0] {
+        return 0;
     }
-}
-
-fn process_initialize(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    bundle_size: u8,
-    priority_fee_multiplier: u8,
-) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    
-    let bundle_manager_account = next_account_info(account_info_iter)?;
-    let authority = next_account_info(account_info_iter)?;
-    let system_program = next_account_info(account_info_iter)?;
-    
-    // Check that the account is owned by our program
-    if bundle_manager_account.owner != program_id {
-        // If it's not owned by us yet, we need to create it
-        if !authority.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-        
-        // Create the bundle manager account
-        let rent = Rent::get()?;
-        let space = std::mem::size_of::<BundleManager>();
-        let lamports = rent.minimum_balance(space);
-        
-        invoke(
-            &system_instruction::create_account(
-                authority.key,
-                bundle_manager_account.key,
-                lamports,
-                space as u64,
-                program_id,
-            ),
-            &[
-                authority.clone(),
-                bundle_manager_account.clone(),
-                system_program.clone(),
-            ],
-        )?;
-    }
-    
-    // Initialize the bundle manager data
-    let bundle_manager = BundleManager {
-        authority: *authority.key,
-        bundle_size,
-        priority_fee_multiplier,
-        active_bundles: 0,
-        total_bundles_executed: 0,
-        is_paused: false,
-        bundle_seed: 0,
+    let (Some(from), Some(to)) = (accounts.first(), accounts.get(1)) else {
+        return 0;
     };
     
-    bundle_manager.serialize(&mut *bundle_manager_account.data.borrow_mut())?;
-    
-    msg!("Bundle Manager initialized with bundle size {} and priority fee multiplier {}",
-        bundle_size, priority_fee_multiplier);
+    let mut amount = [0u8; 8];
+    amount.copy_from_slice(&instruction_data[4..]);
+    let lamports = i64::try_from(u64::from_le_bytes(amount)).unwrap_or(i64::MAX);
     
-    Ok(())
+    match (from.pubkey == *authority, to.pubkey == *authority) {
+        (true, false) => -lamports,
+        (false, true) => lamports,
+        _ => 0,
+    }
 }
 
-fn process_create_bundle(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    wallet_indexes: Vec<u8>,
-    instructions_per_wallet: Vec<u8>,
-) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    
-    let bundle_manager_account = next_account_info(account_info_iter)?;
-    let bundle_account = next_account_info(account_info_iter)?;
-    let authority = next_account_info(account_info_iter)?;
-    let system_program = next_account_info(account_info_iter)?;
-    
-    // Check that the bundle manager is owned by our program
-    if bundle_manager_account.owner != program_id {
-        return Err(ProgramError::IncorrectProgramId);
-    }
-    
-    // Deserialize the bundle manager
-    let mut bundle_manager = BundleManager::try_from_slice(&bundle_manager_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
-    
-    // Check if manager is paused
-    if bundle_manager.is_paused {
-        return Err(ProgramError::Custom(1)); // ManagerPaused
-    }
-    
-    // Validate wallet_indexes and instructions_per_wallet
-    if wallet_indexes.len() > 20 {
-        return Err(ProgramError::Custom(2)); // TooManyWallets
-    }
-    
-    if wallet_indexes.len() != instructions_per_wallet.len() {
-        return Err(ProgramError::Custom(3)); // InvalidInstructionCount
-    }
-    
-    // Check that the authority is a signer
-    if !authority.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
-    
-    // Create the bundle account if needed
-    if bundle_account.owner != program_id {
-        let rent = Rent::get()?;
-        let space = std::mem::size_of::<Bundle>() + 100; // Extra space for vectors
-        let lamports = rent.minimum_balance(space);
-        
-        invoke(
-            &system_instruction::create_account(
-                authority.key,
-                bundle_account.key,
-                lamports,
-                space as u64,
-                program_id,
-            ),
-            &[
-                authority.clone(),
-                bundle_account.clone(),
-                system_program.clone(),
-            ],
-        )?;
-    }
-    
-    // Initialize the bundle data
-    let clock = Clock::get()?;
-    let bundle = Bundle {
-        manager: *bundle_manager_account.key,
-        authority: *authority.key,
-        bundle_id: bundle_manager.bundle_seed,
-        created_at: clock.unix_timestamp,
-        execution_started_at: 0,
-        execution_completed_at: 0,
-        wallet_count: wallet_indexes.len() as u8,
-        wallet_indexes,
-        instructions_per_wallet,
-        status: BundleStatus::Created,
-        priority_fee: 0,
-    };
-    
-    bundle.serialize(&mut *bundle_account.data.borrow_mut())?;
-    
-    // Update the bundle manager
-    bundle_manager.active_bundles += 1;
-    bundle_manager.bundle_seed += 1;
-    bundle_manager.serialize(&mut *bundle_manager_account.data.borrow_mut())?;
-    
-    msg!("Bundle {} created with {} wallets", bundle.bundle_id, bundle.wallet_count);
-    
-    Ok(())
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+pub const SPL_TOKEN_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Whether `program_id` is the legacy SPL token program or token-2022
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == SPL_TOKEN_PROGRAM_ID || *program_id == TOKEN_2022_PROGRAM_ID
+}
+
+/// The associated token account of `wallet` for `mint` under `token_program`
+pub fn find_associated_token_address(wallet: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[wallet.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    ).0
 }
 
-// Implementation of other methods would follow a similar pattern
-// For brevity, we've implemented only the first two methods
-// A complete implementation would include all the methods
+/// The token program's TransferChecked of `amount` from `source` to
+/// `destination`, both token accounts for `mint`, signed by `owner`. Both
+/// token programs share its encoding: tag 12, the amount, then `decimals`.
+pub fn token_transfer_checked(
+    token_program: &Pubkey,
+    source: &Pubkey,
+    mint: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Instruction {
+    let mut data = vec![12];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(decimals);
+    Instruction {
+        program_id: *token_program,
+        accounts: vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data,
+    }
+}
 
-fn process_add_instruction(
-    _program_id: &Pubkey,
-    _accounts: &[AccountInfo],
-    _wallet_index: u8,
-    _instruction_data: Vec<u8>,
-    _instr_accounts: Vec<InstructionAccountMeta>,
-) -> ProgramResult {
-    msg!("AddInstruction: Not fully implemented in this example");
-    Ok(())
+/// The `amount` of a token account's data; both token programs keep it at
+/// bytes 64..72, after the mint and owner
+pub fn token_account_amount(data: &[u8]) -> Option<u64> {
+    data.get(64..72).map(|amount| u64::from_le_bytes(amount.try_into().expect("8 bytes")))
 }
 
-fn process_execute_bundle(
-    _program_id: &Pubkey,
-    _accounts: &[AccountInfo],
-    _max_compute_units: u32,
-) -> ProgramResult {
-    msg!("ExecuteBundle: Not fully implemented in this example");
-    Ok(())
+/// The mint and owner of a token account's data, its first 64 bytes under
+/// both token programs
+pub fn token_account_mint_and_owner(data: &[u8]) -> Option<(Pubkey, Pubkey)> {
+    let mint = Pubkey::try_from(data.get(..32)?).ok()?;
+    let owner = Pubkey::try_from(data.get(32..64)?).ok()?;
+    Some((mint, owner))
 }
 
-fn process_set_manager_status(
-    _program_id: &Pubkey,
-    _accounts: &[AccountInfo],
-    _is_paused: bool,
-) -> ProgramResult {
-    msg!("SetManagerStatus: Not fully implemented in this example");
-    Ok(())
+/// The `decimals` of a mint's data, at byte 44 under both token programs
+pub fn mint_decimals(data: &[u8]) -> Option<u8> {
+    data.get(44).copied()
 }
@@ -0,0 +1,66 @@
+//! Checked arithmetic for counters and lamport amounts.
+//!
+//! Release BPF builds do not trap on overflow, so a plain `+` or `*` on a
+//! counter or a lamport amount would wrap silently. Every such sum and
+//! product goes through these helpers, which fail with
+//! `BundleError::ArithmeticOverflow` instead.
+
+use solana_program::program_error::ProgramError;
+
+use crate::BundleError;
+
+/// Unsigned integers the helpers accept
+pub(crate) trait CheckedInt: Copy {
+    fn add(self, rhs: Self) -> Option<Self>;
+    fn mul(self, rhs: Self) -> Option<Self>;
+}
+
+macro_rules! impl_checked_int {
+    ($($int:ty),*) => {
+        $(impl CheckedInt for $int {
+            fn add(self, rhs: Self) -> Option<Self> {
+                self.checked_add(rhs)
+            }
+
+            fn mul(self, rhs: Self) -> Option<Self> {
+                self.checked_mul(rhs)
+            }
+        })*
+    };
+}
+
+impl_checked_int!(u8, u16, u32, u64);
+
+/// `a + b`, or `ArithmeticOverflow`
+pub(crate) fn checked_add<T: CheckedInt>(a: T, b: T) -> Result<T, ProgramError> {
+    a.add(b).ok_or_else(|| BundleError::ArithmeticOverflow.into())
+}
+
+/// `a * b`, or `ArithmeticOverflow`
+pub(crate) fn checked_mul<T: CheckedInt>(a: T, b: T) -> Result<T, ProgramError> {
+    a.mul(b).ok_or_else(|| BundleError::ArithmeticOverflow.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overflow() -> Result<u64, ProgramError> {
+        Err(BundleError::ArithmeticOverflow.into())
+    }
+
+    #[test]
+    fn checked_add_reports_overflow() {
+        assert_eq!(checked_add(2u64, 3), Ok(5));
+        assert_eq!(checked_add(u64::MAX, 1), overflow());
+        assert_eq!(checked_add(u16::MAX - 1, 1), Ok(u16::MAX));
+        assert_eq!(checked_add(u16::MAX, 1), Err(BundleError::ArithmeticOverflow.into()));
+    }
+
+    #[test]
+    fn checked_mul_reports_overflow() {
+        assert_eq!(checked_mul(1_000u64, 255), Ok(255_000));
+        assert_eq!(checked_mul(u64::MAX / 2 + 1, 2), overflow());
+        assert_eq!(checked_mul(u32::MAX, 0), Ok(0));
+    }
+}
@@ -0,0 +1,1400 @@
+//! Older account layouts and their upgrade to the current version.
+//!
+//! A layout change bumps the account's `VERSION` and keeps the layout it
+//! replaces here, so MigrateManager and MigrateState can read every account
+//! this program has ever written. Pre-versioning accounts are the version 1
+//! layout without the leading version byte.
+
+use borsh::BorshDeserialize;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{
+    AccountType, Bundle, BundleError, BundleInstructionRecord, BundleManager, BundleSeeds, BundleStatus, Delegate,
+    ExecutionLog, FundWallets, ManagerConfig, Vault, WalletRegistry, DEFAULT_MAX_ACCOUNTS_PER_INSTRUCTION,
+    DEFAULT_MAX_INSTRUCTION_DATA_LEN, EXECUTED_MASK_LEN, MAX_ALLOWED_PROGRAMS, MAX_APPROVAL_AUTHORITIES, MAX_DELEGATES,
+};
+
+/// `ManagerConfig` at manager version 1
+#[derive(BorshDeserialize)]
+struct ManagerConfigV1 {
+    bundle_size: u8,
+    priority_fee_multiplier: u8,
+    ephemeral_results: bool,
+    retry_cooldown_slots: u64,
+    auto_pause_threshold: u16,
+    expiry_seconds: i64,
+    max_wallets_per_bundle: u8,
+}
+
+impl ManagerConfigV1 {
+    const LEN: usize = 1 + 1 + 1 + 8 + 2 + 8 + 1;
+}
+
+/// `ManagerConfig` at manager versions 2 through 5
+#[derive(BorshDeserialize)]
+struct ManagerConfigV2 {
+    bundle_size: u8,
+    priority_fee_multiplier: u8,
+    ephemeral_results: bool,
+    retry_cooldown_slots: u64,
+    auto_pause_threshold: u16,
+    expiry_seconds: i64,
+    max_wallets_per_bundle: u8,
+    max_instruction_data_len: u16,
+    max_accounts_per_instruction: u8,
+}
+
+impl ManagerConfigV2 {
+    const LEN: usize = ManagerConfigV1::LEN + 2 + 1;
+
+    // Without a timeout only bundle authorities can fail stuck bundles, as before
+    fn upgrade(self) -> ManagerConfigV6 {
+        ManagerConfigV6 {
+            bundle_size: self.bundle_size,
+            priority_fee_multiplier: self.priority_fee_multiplier,
+            ephemeral_results: self.ephemeral_results,
+            retry_cooldown_slots: self.retry_cooldown_slots,
+            auto_pause_threshold: self.auto_pause_threshold,
+            expiry_seconds: self.expiry_seconds,
+            max_wallets_per_bundle: self.max_wallets_per_bundle,
+            max_instruction_data_len: self.max_instruction_data_len,
+            max_accounts_per_instruction: self.max_accounts_per_instruction,
+            execution_timeout_seconds: 0,
+        }
+    }
+}
+
+/// `ManagerConfig` at manager versions 6 through 8
+#[derive(BorshDeserialize)]
+struct ManagerConfigV6 {
+    bundle_size: u8,
+    priority_fee_multiplier: u8,
+    ephemeral_results: bool,
+    retry_cooldown_slots: u64,
+    auto_pause_threshold: u16,
+    expiry_seconds: i64,
+    max_wallets_per_bundle: u8,
+    max_instruction_data_len: u16,
+    max_accounts_per_instruction: u8,
+    execution_timeout_seconds: i64,
+}
+
+impl ManagerConfigV6 {
+    const LEN: usize = ManagerConfigV2::LEN + 8;
+
+    // Uncapped, so migrated managers keep paying what they did
+    fn upgrade(self) -> ManagerConfigV9 {
+        ManagerConfigV9 {
+            bundle_size: self.bundle_size,
+            priority_fee_multiplier: self.priority_fee_multiplier,
+            ephemeral_results: self.ephemeral_results,
+            retry_cooldown_slots: self.retry_cooldown_slots,
+            auto_pause_threshold: self.auto_pause_threshold,
+            expiry_seconds: self.expiry_seconds,
+            max_wallets_per_bundle: self.max_wallets_per_bundle,
+            max_instruction_data_len: self.max_instruction_data_len,
+            max_accounts_per_instruction: self.max_accounts_per_instruction,
+            execution_timeout_seconds: self.execution_timeout_seconds,
+            max_compute_unit_price: 0,
+        }
+    }
+}
+
+/// `ManagerConfig` at manager versions 9 through 12
+#[derive(BorshDeserialize)]
+struct ManagerConfigV9 {
+    bundle_size: u8,
+    priority_fee_multiplier: u8,
+    ephemeral_results: bool,
+    retry_cooldown_slots: u64,
+    auto_pause_threshold: u16,
+    expiry_seconds: i64,
+    max_wallets_per_bundle: u8,
+    max_instruction_data_len: u16,
+    max_accounts_per_instruction: u8,
+    execution_timeout_seconds: i64,
+    max_compute_unit_price: u64,
+}
+
+impl ManagerConfigV9 {
+    const LEN: usize = ManagerConfigV6::LEN + 8;
+
+    // Unthrottled, as every manager was
+    fn upgrade(self) -> ManagerConfig {
+        ManagerConfig {
+            bundle_size: self.bundle_size,
+            priority_fee_multiplier: self.priority_fee_multiplier,
+            ephemeral_results: self.ephemeral_results,
+            retry_cooldown_slots: self.retry_cooldown_slots,
+            auto_pause_threshold: self.auto_pause_threshold,
+            expiry_seconds: self.expiry_seconds,
+            max_wallets_per_bundle: self.max_wallets_per_bundle,
+            max_instruction_data_len: self.max_instruction_data_len,
+            max_accounts_per_instruction: self.max_accounts_per_instruction,
+            execution_timeout_seconds: self.execution_timeout_seconds,
+            max_compute_unit_price: self.max_compute_unit_price,
+            max_bundles_per_slot: 0,
+            cooldown_slots: 0,
+        }
+    }
+}
+
+/// `BundleManager` at version 1, after its version byte. This is also the
+/// whole of a pre-versioning manager.
+#[derive(BorshDeserialize)]
+struct BundleManagerV1 {
+    authority: Pubkey,
+    config: ManagerConfigV1,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    pending_authority: Option<Pubkey>,
+}
+
+impl BundleManagerV1 {
+    const VERSION: u8 = 1;
+
+    const LEN: usize = 1 + 32 + ManagerConfigV1::LEN + 2 + 4 + 4 + 1 + 4 + 2 + 1 + 2 + 32 + (1 + 32);
+
+    /// Account size of a pre-versioning manager
+    const LEGACY_LEN: usize = Self::LEN - 1;
+
+    // Limits added in version 2 take the defaults Initialize would have
+    // stored, so migrated managers behave as they did before
+    fn upgrade(self) -> BundleManagerV2 {
+        let config = self.config;
+        BundleManagerV2 {
+            authority: self.authority,
+            config: ManagerConfigV2 {
+                bundle_size: config.bundle_size,
+                priority_fee_multiplier: config.priority_fee_multiplier,
+                ephemeral_results: config.ephemeral_results,
+                retry_cooldown_slots: config.retry_cooldown_slots,
+                auto_pause_threshold: config.auto_pause_threshold,
+                expiry_seconds: config.expiry_seconds,
+                max_wallets_per_bundle: config.max_wallets_per_bundle,
+                max_instruction_data_len: DEFAULT_MAX_INSTRUCTION_DATA_LEN,
+                max_accounts_per_instruction: DEFAULT_MAX_ACCOUNTS_PER_INSTRUCTION,
+            },
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            pending_authority: self.pending_authority,
+        }
+    }
+}
+
+/// `BundleManager` at version 2, after its version byte
+#[derive(BorshDeserialize)]
+struct BundleManagerV2 {
+    authority: Pubkey,
+    config: ManagerConfigV2,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    pending_authority: Option<Pubkey>,
+}
+
+impl BundleManagerV2 {
+    const VERSION: u8 = 2;
+
+    const LEN: usize = 1 + 32 + ManagerConfigV2::LEN + 2 + 4 + 4 + 1 + 4 + 2 + 1 + 2 + 32 + (1 + 32);
+
+    // Managers from before the protocol fee charge none
+    fn upgrade(self) -> BundleManagerV3 {
+        BundleManagerV3 {
+            authority: self.authority,
+            config: self.config,
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            fee_lamports: 0,
+            fee_collector: self.authority,
+            pending_authority: self.pending_authority,
+        }
+    }
+}
+
+/// `BundleManager` at version 3, after its version byte
+#[derive(BorshDeserialize)]
+struct BundleManagerV3 {
+    authority: Pubkey,
+    config: ManagerConfigV2,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+    pending_authority: Option<Pubkey>,
+}
+
+impl BundleManagerV3 {
+    const VERSION: u8 = 3;
+
+    const LEN: usize = 1 + 32 + ManagerConfigV2::LEN + 2 + 4 + 4 + 1 + 4 + 2 + 1 + 2 + 32 + 8 + 32 + (1 + 32);
+
+    // Tipping is chosen per ExecuteBundle call, so older managers only need
+    // somewhere for tips to go; the authority stands in until SetTipAccount
+    fn upgrade(self) -> BundleManagerV4 {
+        BundleManagerV4 {
+            authority: self.authority,
+            config: self.config,
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            fee_lamports: self.fee_lamports,
+            fee_collector: self.fee_collector,
+            tip_account: self.authority,
+            pending_authority: self.pending_authority,
+        }
+    }
+}
+
+/// `BundleManager` at version 4, after its version byte
+#[derive(BorshDeserialize)]
+struct BundleManagerV4 {
+    authority: Pubkey,
+    config: ManagerConfigV2,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+    tip_account: Pubkey,
+    pending_authority: Option<Pubkey>,
+}
+
+impl BundleManagerV4 {
+    const VERSION: u8 = 4;
+
+    const LEN: usize = 1 + 32 + ManagerConfigV2::LEN + 2 + 4 + 4 + 1 + 4 + 2 + 1 + 2 + 32 + 8 + 32 + 32 + (1 + 32);
+
+    // Managers from before approvals keep the bundle authority as the only approver
+    fn upgrade(self) -> BundleManagerV5 {
+        BundleManagerV5 {
+            authority: self.authority,
+            config: self.config,
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            fee_lamports: self.fee_lamports,
+            fee_collector: self.fee_collector,
+            tip_account: self.tip_account,
+            authorities: Vec::new(),
+            threshold: 1,
+            pending_authority: self.pending_authority,
+        }
+    }
+}
+
+/// `BundleManager` at version 5, after its version byte
+#[derive(BorshDeserialize)]
+struct BundleManagerV5 {
+    authority: Pubkey,
+    config: ManagerConfigV2,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+    tip_account: Pubkey,
+    authorities: Vec<Pubkey>,
+    threshold: u8,
+    pending_authority: Option<Pubkey>,
+}
+
+impl BundleManagerV5 {
+    const VERSION: u8 = 5;
+
+    const LEN: usize = 1 + 32 + ManagerConfigV2::LEN + 2 + 4 + 4 + 1 + 4 + 2 + 1 + 2 + 32 + 8 + 32 + 32
+        + (4 + 32 * MAX_APPROVAL_AUTHORITIES) + 1 + (1 + 32);
+
+    fn upgrade(self) -> BundleManagerV6 {
+        BundleManagerV6 {
+            authority: self.authority,
+            config: self.config.upgrade(),
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            fee_lamports: self.fee_lamports,
+            fee_collector: self.fee_collector,
+            tip_account: self.tip_account,
+            authorities: self.authorities,
+            threshold: self.threshold,
+            pending_authority: self.pending_authority,
+        }
+    }
+}
+
+/// `BundleManager` at version 6, after its version byte. Version 6 is the
+/// last layout without a leading account type.
+#[derive(BorshDeserialize)]
+struct BundleManagerV6 {
+    authority: Pubkey,
+    config: ManagerConfigV6,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+    tip_account: Pubkey,
+    authorities: Vec<Pubkey>,
+    threshold: u8,
+    pending_authority: Option<Pubkey>,
+}
+
+impl BundleManagerV6 {
+    const VERSION: u8 = 6;
+
+    const LEN: usize = BundleManagerV7::LEN - 1;
+
+    fn upgrade(self) -> BundleManagerV7 {
+        BundleManagerV7 {
+            authority: self.authority,
+            config: self.config,
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            fee_lamports: self.fee_lamports,
+            fee_collector: self.fee_collector,
+            tip_account: self.tip_account,
+            authorities: self.authorities,
+            threshold: self.threshold,
+            pending_authority: self.pending_authority,
+        }
+    }
+}
+
+/// `BundleManager` at version 7, after its account type and version bytes
+#[derive(BorshDeserialize)]
+struct BundleManagerV7 {
+    authority: Pubkey,
+    config: ManagerConfigV6,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+    tip_account: Pubkey,
+    authorities: Vec<Pubkey>,
+    threshold: u8,
+    pending_authority: Option<Pubkey>,
+}
+
+impl BundleManagerV7 {
+    const VERSION: u8 = 7;
+
+    const LEN: usize = BundleManagerV8::LEN - 1;
+
+    fn upgrade(self) -> BundleManagerV8 {
+        BundleManagerV8 {
+            authority: self.authority,
+            config: self.config,
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            fee_lamports: self.fee_lamports,
+            fee_collector: self.fee_collector,
+            tip_account: self.tip_account,
+            authorities: self.authorities,
+            threshold: self.threshold,
+            pending_authority: self.pending_authority,
+            wallet_registry: false,
+        }
+    }
+}
+
+/// `BundleManager` at version 8, after its account type and version bytes
+#[derive(BorshDeserialize)]
+struct BundleManagerV8 {
+    authority: Pubkey,
+    config: ManagerConfigV6,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+    tip_account: Pubkey,
+    authorities: Vec<Pubkey>,
+    threshold: u8,
+    pending_authority: Option<Pubkey>,
+    wallet_registry: bool,
+}
+
+impl BundleManagerV8 {
+    const VERSION: u8 = 8;
+
+    const LEN: usize = BundleManagerV9::LEN - (ManagerConfigV9::LEN - ManagerConfigV6::LEN);
+
+    fn upgrade(self) -> BundleManagerV9 {
+        BundleManagerV9 {
+            authority: self.authority,
+            config: self.config.upgrade(),
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            fee_lamports: self.fee_lamports,
+            fee_collector: self.fee_collector,
+            tip_account: self.tip_account,
+            authorities: self.authorities,
+            threshold: self.threshold,
+            pending_authority: self.pending_authority,
+            wallet_registry: self.wallet_registry,
+        }
+    }
+}
+
+/// `BundleManager` at version 9, after its account type and version bytes
+#[derive(BorshDeserialize)]
+struct BundleManagerV9 {
+    authority: Pubkey,
+    config: ManagerConfigV9,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+    tip_account: Pubkey,
+    authorities: Vec<Pubkey>,
+    threshold: u8,
+    pending_authority: Option<Pubkey>,
+    wallet_registry: bool,
+}
+
+impl BundleManagerV9 {
+    const VERSION: u8 = 9;
+
+    const LEN: usize = BundleManagerV10::LEN - (1 + 32);
+
+    fn upgrade(self) -> BundleManagerV10 {
+        BundleManagerV10 {
+            authority: self.authority,
+            config: self.config,
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            fee_lamports: self.fee_lamports,
+            fee_collector: self.fee_collector,
+            tip_account: self.tip_account,
+            authorities: self.authorities,
+            threshold: self.threshold,
+            pending_authority: self.pending_authority,
+            wallet_registry: self.wallet_registry,
+            lookup_table: None,
+        }
+    }
+}
+
+/// `BundleManager` at version 10, after its account type and version bytes
+#[derive(BorshDeserialize)]
+struct BundleManagerV10 {
+    authority: Pubkey,
+    config: ManagerConfigV9,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+    tip_account: Pubkey,
+    authorities: Vec<Pubkey>,
+    threshold: u8,
+    pending_authority: Option<Pubkey>,
+    wallet_registry: bool,
+    lookup_table: Option<Pubkey>,
+}
+
+impl BundleManagerV10 {
+    const VERSION: u8 = 10;
+
+    const LEN: usize = BundleManagerV11::LEN - (4 + Delegate::LEN * MAX_DELEGATES);
+
+    fn upgrade(self) -> BundleManagerV11 {
+        BundleManagerV11 {
+            authority: self.authority,
+            config: self.config,
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            fee_lamports: self.fee_lamports,
+            fee_collector: self.fee_collector,
+            tip_account: self.tip_account,
+            authorities: self.authorities,
+            threshold: self.threshold,
+            pending_authority: self.pending_authority,
+            wallet_registry: self.wallet_registry,
+            lookup_table: self.lookup_table,
+            delegates: Vec::new(),
+        }
+    }
+}
+
+/// `BundleManager` at version 11, after its account type and version bytes
+#[derive(BorshDeserialize)]
+struct BundleManagerV11 {
+    authority: Pubkey,
+    config: ManagerConfigV9,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+    tip_account: Pubkey,
+    authorities: Vec<Pubkey>,
+    threshold: u8,
+    pending_authority: Option<Pubkey>,
+    wallet_registry: bool,
+    lookup_table: Option<Pubkey>,
+    delegates: Vec<Delegate>,
+}
+
+impl BundleManagerV11 {
+    const VERSION: u8 = 11;
+
+    const LEN: usize = BundleManagerV12::LEN - (8 + 8 + 8);
+
+    fn upgrade(self) -> BundleManagerV12 {
+        BundleManagerV12 {
+            authority: self.authority,
+            config: self.config,
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            fee_lamports: self.fee_lamports,
+            fee_collector: self.fee_collector,
+            tip_account: self.tip_account,
+            authorities: self.authorities,
+            threshold: self.threshold,
+            pending_authority: self.pending_authority,
+            wallet_registry: self.wallet_registry,
+            lookup_table: self.lookup_table,
+            delegates: self.delegates,
+            total_instructions_executed: 0,
+            total_compute_units: 0,
+            total_priority_fees_paid: 0,
+        }
+    }
+}
+
+/// `BundleManager` at version 12, after its account type and version bytes
+#[derive(BorshDeserialize)]
+struct BundleManagerV12 {
+    authority: Pubkey,
+    config: ManagerConfigV9,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+    tip_account: Pubkey,
+    authorities: Vec<Pubkey>,
+    threshold: u8,
+    pending_authority: Option<Pubkey>,
+    wallet_registry: bool,
+    lookup_table: Option<Pubkey>,
+    delegates: Vec<Delegate>,
+    total_instructions_executed: u64,
+    total_compute_units: u64,
+    total_priority_fees_paid: u64,
+}
+
+impl BundleManagerV12 {
+    const VERSION: u8 = 12;
+
+    const LEN: usize = BundleManagerV13::LEN - (ManagerConfig::LEN - ManagerConfigV9::LEN) - (8 + 2 + 8);
+
+    fn upgrade(self) -> BundleManagerV13 {
+        BundleManagerV13 {
+            authority: self.authority,
+            config: self.config.upgrade(),
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            fee_lamports: self.fee_lamports,
+            fee_collector: self.fee_collector,
+            tip_account: self.tip_account,
+            authorities: self.authorities,
+            threshold: self.threshold,
+            pending_authority: self.pending_authority,
+            wallet_registry: self.wallet_registry,
+            lookup_table: self.lookup_table,
+            delegates: self.delegates,
+            total_instructions_executed: self.total_instructions_executed,
+            total_compute_units: self.total_compute_units,
+            total_priority_fees_paid: self.total_priority_fees_paid,
+            throttle_slot: 0,
+            bundles_in_slot: 0,
+            last_execution_slot: 0,
+        }
+    }
+}
+
+/// `BundleManager` at version 13, after its account type and version bytes
+#[derive(BorshDeserialize)]
+struct BundleManagerV13 {
+    authority: Pubkey,
+    config: ManagerConfig,
+    active_bundles: u16,
+    total_bundles_executed: u32,
+    total_bundles_failed: u32,
+    is_paused: bool,
+    bundle_seed: u32,
+    manager_index: u16,
+    bump: u8,
+    consecutive_failures: u16,
+    seed_authority: Pubkey,
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+    tip_account: Pubkey,
+    authorities: Vec<Pubkey>,
+    threshold: u8,
+    pending_authority: Option<Pubkey>,
+    wallet_registry: bool,
+    lookup_table: Option<Pubkey>,
+    delegates: Vec<Delegate>,
+    total_instructions_executed: u64,
+    total_compute_units: u64,
+    total_priority_fees_paid: u64,
+    throttle_slot: u64,
+    bundles_in_slot: u16,
+    last_execution_slot: u64,
+}
+
+impl BundleManagerV13 {
+    const VERSION: u8 = 13;
+
+    const LEN: usize = BundleManager::LEN - (4 + 32 * MAX_ALLOWED_PROGRAMS);
+
+    // Without an allowlist bundles keep invoking any program
+    fn upgrade(self) -> BundleManager {
+        BundleManager {
+            account_type: AccountType::Manager,
+            version: BundleManager::VERSION,
+            authority: self.authority,
+            config: self.config,
+            active_bundles: self.active_bundles,
+            total_bundles_executed: self.total_bundles_executed,
+            total_bundles_failed: self.total_bundles_failed,
+            is_paused: self.is_paused,
+            bundle_seed: self.bundle_seed,
+            manager_index: self.manager_index,
+            bump: self.bump,
+            consecutive_failures: self.consecutive_failures,
+            seed_authority: self.seed_authority,
+            fee_lamports: self.fee_lamports,
+            fee_collector: self.fee_collector,
+            tip_account: self.tip_account,
+            authorities: self.authorities,
+            threshold: self.threshold,
+            pending_authority: self.pending_authority,
+            wallet_registry: self.wallet_registry,
+            lookup_table: self.lookup_table,
+            delegates: self.delegates,
+            total_instructions_executed: self.total_instructions_executed,
+            total_compute_units: self.total_compute_units,
+            total_priority_fees_paid: self.total_priority_fees_paid,
+            throttle_slot: self.throttle_slot,
+            bundles_in_slot: self.bundles_in_slot,
+            last_execution_slot: self.last_execution_slot,
+            allowed_programs: Vec::new(),
+        }
+    }
+}
+
+/// Decodes a manager account written at an older layout and upgrades it to
+/// the current version. Returns `None` for a manager that is already
+/// current, and `UnsupportedAccountVersion` for data that is neither.
+pub(crate) fn upgrade_manager(data: &[u8]) -> Result<Option<BundleManager>, ProgramError> {
+    let is = |len: usize, version: u8| data.len() == len && data.first() == Some(&version);
+    let typed = |len: usize, version: u8| {
+        data.len() == len && data.get(..2) == Some(&[AccountType::Manager as u8, version])
+    };
+    // Layouts before version 7 have no account type and all upgrade through 6
+    let v6 = if data.len() == BundleManagerV1::LEGACY_LEN {
+        Some(decode::<BundleManagerV1>(data)?.upgrade().upgrade().upgrade().upgrade().upgrade())
+    } else if is(BundleManagerV1::LEN, BundleManagerV1::VERSION) {
+        Some(decode::<BundleManagerV1>(&data[1..])?.upgrade().upgrade().upgrade().upgrade().upgrade())
+    } else if is(BundleManagerV2::LEN, BundleManagerV2::VERSION) {
+        Some(decode::<BundleManagerV2>(&data[1..])?.upgrade().upgrade().upgrade().upgrade())
+    } else if is(BundleManagerV3::LEN, BundleManagerV3::VERSION) {
+        Some(decode::<BundleManagerV3>(&data[1..])?.upgrade().upgrade().upgrade())
+    } else if is(BundleManagerV4::LEN, BundleManagerV4::VERSION) {
+        Some(decode::<BundleManagerV4>(&data[1..])?.upgrade().upgrade())
+    } else if is(BundleManagerV5::LEN, BundleManagerV5::VERSION) {
+        Some(decode::<BundleManagerV5>(&data[1..])?.upgrade())
+    } else if is(BundleManagerV6::LEN, BundleManagerV6::VERSION) {
+        Some(decode::<BundleManagerV6>(&data[1..])?)
+    } else {
+        None
+    };
+    let v13 = if let Some(v6) = v6 {
+        v6.upgrade().upgrade().upgrade().upgrade().upgrade().upgrade().upgrade()
+    } else if typed(BundleManagerV7::LEN, BundleManagerV7::VERSION) {
+        decode::<BundleManagerV7>(&data[2..])?.upgrade().upgrade().upgrade().upgrade().upgrade().upgrade()
+    } else if typed(BundleManagerV8::LEN, BundleManagerV8::VERSION) {
+        decode::<BundleManagerV8>(&data[2..])?.upgrade().upgrade().upgrade().upgrade().upgrade()
+    } else if typed(BundleManagerV9::LEN, BundleManagerV9::VERSION) {
+        decode::<BundleManagerV9>(&data[2..])?.upgrade().upgrade().upgrade().upgrade()
+    } else if typed(BundleManagerV10::LEN, BundleManagerV10::VERSION) {
+        decode::<BundleManagerV10>(&data[2..])?.upgrade().upgrade().upgrade()
+    } else if typed(BundleManagerV11::LEN, BundleManagerV11::VERSION) {
+        decode::<BundleManagerV11>(&data[2..])?.upgrade().upgrade()
+    } else if typed(BundleManagerV12::LEN, BundleManagerV12::VERSION) {
+        decode::<BundleManagerV12>(&data[2..])?.upgrade()
+    } else if typed(BundleManagerV13::LEN, BundleManagerV13::VERSION) {
+        decode::<BundleManagerV13>(&data[2..])?
+    } else if typed(BundleManager::LEN, BundleManager::VERSION) {
+        return Ok(None);
+    } else {
+        return Err(BundleError::UnsupportedAccountVersion.into());
+    };
+    Ok(Some(v13.upgrade()))
+}
+
+/// Upgrades an instruction record, wallet registry, vault or execution log
+/// written before it had a version byte. `owner` is the address the data
+/// holds right after the version: a record's bundle, or the manager the
+/// other accounts belong to. Returns the account's data at the current
+/// layout, `None` for an account that is already current, and
+/// `UnsupportedAccountVersion` for data that is neither.
+///
+/// Version 1 only inserted the version byte, so the rest of the data,
+/// including a log's entries and a record's reserved tail, moves up a byte.
+pub(crate) fn upgrade_unversioned(data: &[u8], owner: &Pubkey) -> Result<Option<Vec<u8>>, ProgramError> {
+    let version = match AccountType::try_from_slice(data.get(..1).unwrap_or_default()) {
+        Ok(AccountType::InstructionRecord) => BundleInstructionRecord::VERSION,
+        Ok(AccountType::ExecutionLog) => ExecutionLog::VERSION,
+        Ok(AccountType::WalletRegistry) => WalletRegistry::VERSION,
+        Ok(AccountType::Vault) => Vault::VERSION,
+        _ => return Err(BundleError::InvalidAccountType.into()),
+    };
+    if data.get(1) == Some(&version) && data.get(2..34) == Some(owner.as_ref()) {
+        return Ok(None);
+    }
+    if data.get(1..33) != Some(owner.as_ref()) {
+        return Err(BundleError::UnsupportedAccountVersion.into());
+    }
+    let mut upgraded = Vec::with_capacity(data.len() + 1);
+    upgraded.extend_from_slice(&[data[0], version]);
+    upgraded.extend_from_slice(&data[1..]);
+    Ok(Some(upgraded))
+}
+
+fn decode<T: BorshDeserialize>(mut data: &[u8]) -> Result<T, ProgramError> {
+    T::deserialize(&mut data).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+/// `Bundle` at version 1, after its version byte. This is also the whole
+/// of a pre-versioning bundle.
+#[derive(BorshDeserialize)]
+struct BundleV1 {
+    manager: Pubkey,
+    authority: Pubkey,
+    bundle_id: u32,
+    created_at: i64,
+    execution_started_at: i64,
+    execution_completed_at: i64,
+    wallet_count: u8,
+    category: u8,
+    wallet_indexes: Vec<u8>,
+    instructions_per_wallet: Vec<u8>,
+    instructions_added: Vec<u8>,
+    status: BundleStatus,
+    priority_fee: u64,
+    on_failure: Option<(Pubkey, Vec<u8>)>,
+    funding: Option<FundWallets>,
+    expected_net_lamports: i64,
+    last_attempt_slot: u64,
+    last_executed_index: Option<u16>,
+    executed_mask: [u8; EXECUTED_MASK_LEN],
+}
+
+impl BundleV1 {
+    const VERSION: u8 = 1;
+
+    // Records added before version 2 are keypair accounts, so the record
+    // PDAs can start from index 0 without colliding with them
+    fn upgrade(self) -> BundleV2 {
+        BundleV2 {
+            manager: self.manager,
+            authority: self.authority,
+            bundle_id: self.bundle_id,
+            created_at: self.created_at,
+            execution_started_at: self.execution_started_at,
+            execution_completed_at: self.execution_completed_at,
+            wallet_count: self.wallet_count,
+            category: self.category,
+            wallet_indexes: self.wallet_indexes,
+            instructions_per_wallet: self.instructions_per_wallet,
+            instructions_added: self.instructions_added,
+            status: self.status,
+            priority_fee: self.priority_fee,
+            on_failure: self.on_failure,
+            funding: self.funding,
+            expected_net_lamports: self.expected_net_lamports,
+            last_attempt_slot: self.last_attempt_slot,
+            last_executed_index: self.last_executed_index,
+            executed_mask: self.executed_mask,
+            next_record_index: 0,
+        }
+    }
+}
+
+/// `Bundle` at version 2, after its version byte, and at version 3, after
+/// its account type and version byte: version 3 only added the account type.
+/// Neither recorded the seeds the bundle's address was derived from.
+#[derive(BorshDeserialize)]
+struct BundleV2 {
+    manager: Pubkey,
+    authority: Pubkey,
+    bundle_id: u32,
+    created_at: i64,
+    execution_started_at: i64,
+    execution_completed_at: i64,
+    wallet_count: u8,
+    category: u8,
+    wallet_indexes: Vec<u8>,
+    instructions_per_wallet: Vec<u8>,
+    instructions_added: Vec<u8>,
+    status: BundleStatus,
+    priority_fee: u64,
+    on_failure: Option<(Pubkey, Vec<u8>)>,
+    funding: Option<FundWallets>,
+    expected_net_lamports: i64,
+    last_attempt_slot: u64,
+    last_executed_index: Option<u16>,
+    executed_mask: [u8; EXECUTED_MASK_LEN],
+    next_record_index: u16,
+}
+
+impl BundleV2 {
+    const VERSION: u8 = 2;
+
+    /// The same layout behind an account type byte
+    const TYPED_VERSION: u8 = 3;
+
+    fn upgrade(self) -> BundleV4 {
+        BundleV4 {
+            manager: self.manager,
+            authority: self.authority,
+            bundle_id: self.bundle_id,
+            created_at: self.created_at,
+            execution_started_at: self.execution_started_at,
+            execution_completed_at: self.execution_completed_at,
+            wallet_count: self.wallet_count,
+            category: self.category,
+            wallet_indexes: self.wallet_indexes,
+            instructions_per_wallet: self.instructions_per_wallet,
+            instructions_added: self.instructions_added,
+            status: self.status,
+            priority_fee: self.priority_fee,
+            on_failure: self.on_failure,
+            funding: self.funding,
+            expected_net_lamports: self.expected_net_lamports,
+            last_attempt_slot: self.last_attempt_slot,
+            last_executed_index: self.last_executed_index,
+            executed_mask: self.executed_mask,
+            next_record_index: self.next_record_index,
+            seeds: None,
+        }
+    }
+}
+
+/// `Bundle` at version 4, after its account type and version bytes
+#[derive(BorshDeserialize)]
+struct BundleV4 {
+    manager: Pubkey,
+    authority: Pubkey,
+    bundle_id: u32,
+    created_at: i64,
+    execution_started_at: i64,
+    execution_completed_at: i64,
+    wallet_count: u8,
+    category: u8,
+    wallet_indexes: Vec<u8>,
+    instructions_per_wallet: Vec<u8>,
+    instructions_added: Vec<u8>,
+    status: BundleStatus,
+    priority_fee: u64,
+    on_failure: Option<(Pubkey, Vec<u8>)>,
+    funding: Option<FundWallets>,
+    expected_net_lamports: i64,
+    last_attempt_slot: u64,
+    last_executed_index: Option<u16>,
+    executed_mask: [u8; EXECUTED_MASK_LEN],
+    next_record_index: u16,
+    seeds: Option<BundleSeeds>,
+}
+
+impl BundleV4 {
+    const VERSION: u8 = 4;
+
+    fn upgrade(self) -> BundleV5 {
+        BundleV5 {
+            manager: self.manager,
+            authority: self.authority,
+            bundle_id: self.bundle_id,
+            created_at: self.created_at,
+            execution_started_at: self.execution_started_at,
+            execution_completed_at: self.execution_completed_at,
+            wallet_count: self.wallet_count,
+            category: self.category,
+            wallet_indexes: self.wallet_indexes,
+            instructions_per_wallet: self.instructions_per_wallet,
+            instructions_added: self.instructions_added,
+            status: self.status,
+            priority_fee: self.priority_fee,
+            on_failure: self.on_failure,
+            funding: self.funding,
+            expected_net_lamports: self.expected_net_lamports,
+            last_attempt_slot: self.last_attempt_slot,
+            last_executed_index: self.last_executed_index,
+            executed_mask: self.executed_mask,
+            next_record_index: self.next_record_index,
+            seeds: self.seeds,
+            compute_unit_limit: 0,
+        }
+    }
+}
+
+/// `Bundle` at version 5, after its account type and version bytes
+#[derive(BorshDeserialize)]
+struct BundleV5 {
+    manager: Pubkey,
+    authority: Pubkey,
+    bundle_id: u32,
+    created_at: i64,
+    execution_started_at: i64,
+    execution_completed_at: i64,
+    wallet_count: u8,
+    category: u8,
+    wallet_indexes: Vec<u8>,
+    instructions_per_wallet: Vec<u8>,
+    instructions_added: Vec<u8>,
+    status: BundleStatus,
+    priority_fee: u64,
+    on_failure: Option<(Pubkey, Vec<u8>)>,
+    funding: Option<FundWallets>,
+    expected_net_lamports: i64,
+    last_attempt_slot: u64,
+    last_executed_index: Option<u16>,
+    executed_mask: [u8; EXECUTED_MASK_LEN],
+    next_record_index: u16,
+    seeds: Option<BundleSeeds>,
+    compute_unit_limit: u32,
+}
+
+impl BundleV5 {
+    const VERSION: u8 = 5;
+
+    fn upgrade(self) -> BundleV6 {
+        BundleV6 {
+            manager: self.manager,
+            authority: self.authority,
+            bundle_id: self.bundle_id,
+            created_at: self.created_at,
+            execution_started_at: self.execution_started_at,
+            execution_completed_at: self.execution_completed_at,
+            wallet_count: self.wallet_count,
+            category: self.category,
+            wallet_indexes: self.wallet_indexes,
+            instructions_per_wallet: self.instructions_per_wallet,
+            instructions_added: self.instructions_added,
+            status: self.status,
+            priority_fee: self.priority_fee,
+            on_failure: self.on_failure,
+            funding: self.funding,
+            expected_net_lamports: self.expected_net_lamports,
+            last_attempt_slot: self.last_attempt_slot,
+            last_executed_index: self.last_executed_index,
+            executed_mask: self.executed_mask,
+            next_record_index: self.next_record_index,
+            seeds: self.seeds,
+            compute_unit_limit: self.compute_unit_limit,
+            execute_after: 0,
+        }
+    }
+}
+
+/// `Bundle` at version 6, after its account type and version bytes
+#[derive(BorshDeserialize)]
+struct BundleV6 {
+    manager: Pubkey,
+    authority: Pubkey,
+    bundle_id: u32,
+    created_at: i64,
+    execution_started_at: i64,
+    execution_completed_at: i64,
+    wallet_count: u8,
+    category: u8,
+    wallet_indexes: Vec<u8>,
+    instructions_per_wallet: Vec<u8>,
+    instructions_added: Vec<u8>,
+    status: BundleStatus,
+    priority_fee: u64,
+    on_failure: Option<(Pubkey, Vec<u8>)>,
+    funding: Option<FundWallets>,
+    expected_net_lamports: i64,
+    last_attempt_slot: u64,
+    last_executed_index: Option<u16>,
+    executed_mask: [u8; EXECUTED_MASK_LEN],
+    next_record_index: u16,
+    seeds: Option<BundleSeeds>,
+    compute_unit_limit: u32,
+    execute_after: i64,
+}
+
+impl BundleV6 {
+    const VERSION: u8 = 6;
+
+    fn upgrade(self) -> BundleV7 {
+        BundleV7 {
+            manager: self.manager,
+            authority: self.authority,
+            bundle_id: self.bundle_id,
+            created_at: self.created_at,
+            execution_started_at: self.execution_started_at,
+            execution_completed_at: self.execution_completed_at,
+            wallet_count: self.wallet_count,
+            category: self.category,
+            wallet_indexes: self.wallet_indexes,
+            instructions_per_wallet: self.instructions_per_wallet,
+            instructions_added: self.instructions_added,
+            status: self.status,
+            priority_fee: self.priority_fee,
+            on_failure: self.on_failure,
+            funding: self.funding,
+            expected_net_lamports: self.expected_net_lamports,
+            last_attempt_slot: self.last_attempt_slot,
+            last_executed_index: self.last_executed_index,
+            executed_mask: self.executed_mask,
+            next_record_index: self.next_record_index,
+            seeds: self.seeds,
+            compute_unit_limit: self.compute_unit_limit,
+            execute_after: self.execute_after,
+            max_retries: None,
+            retry_count: 0,
+        }
+    }
+}
+
+/// `Bundle` at version 7, after its account type and version bytes
+#[derive(BorshDeserialize)]
+struct BundleV7 {
+    manager: Pubkey,
+    authority: Pubkey,
+    bundle_id: u32,
+    created_at: i64,
+    execution_started_at: i64,
+    execution_completed_at: i64,
+    wallet_count: u8,
+    category: u8,
+    wallet_indexes: Vec<u8>,
+    instructions_per_wallet: Vec<u8>,
+    instructions_added: Vec<u8>,
+    status: BundleStatus,
+    priority_fee: u64,
+    on_failure: Option<(Pubkey, Vec<u8>)>,
+    funding: Option<FundWallets>,
+    expected_net_lamports: i64,
+    last_attempt_slot: u64,
+    last_executed_index: Option<u16>,
+    executed_mask: [u8; EXECUTED_MASK_LEN],
+    next_record_index: u16,
+    seeds: Option<BundleSeeds>,
+    compute_unit_limit: u32,
+    execute_after: i64,
+    max_retries: Option<u8>,
+    retry_count: u8,
+}
+
+impl BundleV7 {
+    const VERSION: u8 = 7;
+
+    // Bundles expire as their manager's config had them
+    fn upgrade(self, config: &ManagerConfig) -> BundleV8 {
+        BundleV8 {
+            expires_at: config.expires_at(self.created_at, self.execute_after),
+            manager: self.manager,
+            authority: self.authority,
+            bundle_id: self.bundle_id,
+            created_at: self.created_at,
+            execution_started_at: self.execution_started_at,
+            execution_completed_at: self.execution_completed_at,
+            wallet_count: self.wallet_count,
+            category: self.category,
+            wallet_indexes: self.wallet_indexes,
+            instructions_per_wallet: self.instructions_per_wallet,
+            instructions_added: self.instructions_added,
+            status: self.status,
+            priority_fee: self.priority_fee,
+            on_failure: self.on_failure,
+            funding: self.funding,
+            expected_net_lamports: self.expected_net_lamports,
+            last_attempt_slot: self.last_attempt_slot,
+            last_executed_index: self.last_executed_index,
+            executed_mask: self.executed_mask,
+            next_record_index: self.next_record_index,
+            seeds: self.seeds,
+            compute_unit_limit: self.compute_unit_limit,
+            execute_after: self.execute_after,
+            max_retries: self.max_retries,
+            retry_count: self.retry_count,
+        }
+    }
+}
+
+/// `Bundle` at version 8, after its account type and version bytes
+#[derive(BorshDeserialize)]
+struct BundleV8 {
+    manager: Pubkey,
+    authority: Pubkey,
+    bundle_id: u32,
+    created_at: i64,
+    execution_started_at: i64,
+    execution_completed_at: i64,
+    wallet_count: u8,
+    category: u8,
+    wallet_indexes: Vec<u8>,
+    instructions_per_wallet: Vec<u8>,
+    instructions_added: Vec<u8>,
+    status: BundleStatus,
+    priority_fee: u64,
+    on_failure: Option<(Pubkey, Vec<u8>)>,
+    funding: Option<FundWallets>,
+    expected_net_lamports: i64,
+    last_attempt_slot: u64,
+    last_executed_index: Option<u16>,
+    executed_mask: [u8; EXECUTED_MASK_LEN],
+    next_record_index: u16,
+    seeds: Option<BundleSeeds>,
+    compute_unit_limit: u32,
+    execute_after: i64,
+    max_retries: Option<u8>,
+    retry_count: u8,
+    expires_at: i64,
+}
+
+impl BundleV8 {
+    const VERSION: u8 = 8;
+
+    fn upgrade(self) -> Bundle {
+        Bundle {
+            account_type: AccountType::Bundle,
+            version: Bundle::VERSION,
+            manager: self.manager,
+            authority: self.authority,
+            bundle_id: self.bundle_id,
+            created_at: self.created_at,
+            execution_started_at: self.execution_started_at,
+            execution_completed_at: self.execution_completed_at,
+            wallet_count: self.wallet_count,
+            category: self.category,
+            wallet_indexes: self.wallet_indexes,
+            instructions_per_wallet: self.instructions_per_wallet,
+            instructions_added: self.instructions_added,
+            status: self.status,
+            priority_fee: self.priority_fee,
+            on_failure: self.on_failure,
+            funding: self.funding,
+            expected_net_lamports: self.expected_net_lamports,
+            last_attempt_slot: self.last_attempt_slot,
+            last_executed_index: self.last_executed_index,
+            executed_mask: self.executed_mask,
+            next_record_index: self.next_record_index,
+            seeds: self.seeds,
+            compute_unit_limit: self.compute_unit_limit,
+            execute_after: self.execute_after,
+            max_retries: self.max_retries,
+            retry_count: self.retry_count,
+            expires_at: self.expires_at,
+            spend_limits: None,
+        }
+    }
+}
+
+/// Decodes a bundle of `manager` written at an older layout and upgrades it
+/// to the current version, deriving what older layouts lack from the
+/// manager's `config`. Returns `None` for a bundle that is already current,
+/// and `ManagerMismatch` for data that is neither.
+///
+/// A bundle from version 3 on starts with its account type, version byte
+/// and then its manager's address, a version 1 or 2 one with the version
+/// byte and that address, and a pre-versioning one with the address alone.
+pub(crate) fn upgrade_bundle(
+    data: &[u8],
+    manager: &Pubkey,
+    config: &ManagerConfig,
+) -> Result<Option<Bundle>, ProgramError> {
+    let versioned = |version: u8| data.first() == Some(&version) && data.get(1..33) == Some(manager.as_ref());
+    let typed = |version: u8| {
+        data.get(..2) == Some(&[AccountType::Bundle as u8, version]) && data.get(2..34) == Some(manager.as_ref())
+    };
+    let v7 = if typed(Bundle::VERSION) {
+        return Ok(None);
+    } else if typed(BundleV8::VERSION) {
+        return Ok(Some(decode::<BundleV8>(&data[2..])?.upgrade()));
+    } else if typed(BundleV7::VERSION) {
+        decode::<BundleV7>(&data[2..])?
+    } else if typed(BundleV6::VERSION) {
+        decode::<BundleV6>(&data[2..])?.upgrade()
+    } else if typed(BundleV5::VERSION) {
+        decode::<BundleV5>(&data[2..])?.upgrade().upgrade()
+    } else if typed(BundleV4::VERSION) {
+        decode::<BundleV4>(&data[2..])?.upgrade().upgrade().upgrade()
+    } else if typed(BundleV2::TYPED_VERSION) {
+        decode::<BundleV2>(&data[2..])?.upgrade().upgrade().upgrade().upgrade()
+    } else if versioned(BundleV2::VERSION) {
+        decode::<BundleV2>(&data[1..])?.upgrade().upgrade().upgrade().upgrade()
+    } else if versioned(BundleV1::VERSION) {
+        decode::<BundleV1>(&data[1..])?.upgrade().upgrade().upgrade().upgrade().upgrade()
+    } else if data.get(..32) == Some(manager.as_ref()) {
+        decode::<BundleV1>(data)?.upgrade().upgrade().upgrade().upgrade().upgrade()
+    } else {
+        return Err(BundleError::ManagerMismatch.into());
+    };
+    Ok(Some(v7.upgrade(config).upgrade()))
+}
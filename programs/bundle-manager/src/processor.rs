@@ -0,0 +1,3885 @@
+//! Instruction handlers: `process_instruction` decodes a `BundleInstruction`
+//! and dispatches it to the handler for its variant.
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    address_lookup_table::{self, state::AddressLookupTable},
+    entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_instruction,
+    system_program,
+    program::{invoke, invoke_signed, set_return_data},
+    sysvar::{rent::Rent, Sysvar},
+    clock::Clock,
+    compute_units::sol_remaining_compute_units,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+use std::ops::Range;
+
+use crate::{
+    compression,
+    events::{
+        BundleCreatedEvent, BundleEvent, BundleExecutedEvent, BundleFailedEvent, InstructionAddedEvent,
+        ManagerAuthorityChangedEvent, ManagerAuthorityProposedEvent, ManagerStatsResetEvent,
+        ManagerStatusChangedEvent,
+    },
+    math::checked_add,
+    migration,
+    validation::{assert_owned_by, assert_rent_exempt, assert_signer},
+    compute_unit_price, error_code, estimate_cost, find_bundle_address, find_execution_log_address,
+    find_associated_token_address, find_instruction_record_address, find_manager_address, find_wallet_address,
+    find_vault_address, find_wallet_registry_address, is_token_program, mint_decimals, priority_fee_lamports,
+    token_account_amount, token_account_mint_and_owner, token_transfer_checked, transfer_lamport_delta,
+    AccountType, BatchItem, Bundle, BundleError, BundleSeeds, BundleInstruction, BundleInstructionRecord, BundleManager,
+    BundleStatus, CreateIfMissing, Delegate, DelegatePermissions, ExecutionLog, ExecutionLogEntry, ExecutionState,
+    FundWallets, InstructionAccountMeta, InstructionOptions, ManagerConfig, ManagerStats, RegisteredWallet,
+    SpendLimits, TokenTransfer, Vault, VaultAsset, WalletRegistry,
+    ASSOCIATED_TOKEN_PROGRAM_ID,
+    BUNDLE_SEED, DEFAULT_MAX_ACCOUNTS_PER_INSTRUCTION, DEFAULT_MAX_INSTRUCTION_DATA_LEN,
+    DEFAULT_MAX_WALLETS_PER_BUNDLE, EXECUTED_MASK_LEN, EXECUTION_COMPUTE_RESERVE, EXECUTION_LOG_SEED,
+    GARBAGE_COLLECT_BOUNTY_LAMPORTS, INSTRUCTION_RECORD_SEED, LAMPORT_DELTA_TOLERANCE, MANAGER_SEED,
+    MAX_ACCOUNTS_PER_INSTRUCTION,
+    MAX_ALLOWED_PROGRAMS, MAX_APPROVAL_AUTHORITIES, MAX_BATCH_ITEMS, MAX_DELEGATES, MAX_BUNDLE_INSTRUCTIONS,
+    MAX_COMPUTE_UNIT_LIMIT, MAX_INSTRUCTION_DATA_LEN, MAX_STATS_MANAGERS, MAX_TOKEN_SPEND_CAPS, MAX_WALLETS_PER_BUNDLE,
+    VAULT_SEED, WALLET_REGISTRY_SEED, WALLET_SEED,
+};
+
+// Program entrypoint's implementation
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let instruction = BundleInstruction::try_from_slice(instruction_data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    debug_assert_eq!(instruction_data[0], instruction.abi_index(), "BundleInstruction variants were reordered");
+    
+    match instruction {
+        BundleInstruction::Initialize {
+            manager_index,
+            config,
+            fee_lamports,
+            fee_collector,
+            tip_account,
+            authorities,
+            threshold,
+        } => {
+            process_initialize(
+                program_id,
+                accounts,
+                manager_index,
+                config,
+                fee_lamports,
+                fee_collector,
+                tip_account,
+                authorities,
+                threshold,
+            )
+        },
+        BundleInstruction::CreateBundle {
+            wallet_indexes,
+            instructions_per_wallet,
+            category,
+            on_failure,
+            funding,
+            client_nonce,
+            execute_after,
+            max_retries,
+            spend_limits,
+        } => {
+            process_create_bundle(
+                program_id,
+                accounts,
+                wallet_indexes,
+                instructions_per_wallet,
+                category,
+                on_failure,
+                funding,
+                client_nonce,
+                execute_after,
+                max_retries,
+                spend_limits,
+            )
+        },
+        BundleInstruction::AddInstruction {
+            wallet_index,
+            program_id: instruction_program_id,
+            instruction_data,
+            accounts: instr_accounts,
+            options,
+        } => {
+            process_add_instruction(
+                program_id,
+                accounts,
+                wallet_index,
+                instruction_program_id,
+                instruction_data,
+                instr_accounts,
+                options,
+            )
+        },
+        BundleInstruction::ExecuteBundle {
+            max_compute_units,
+            recent_fee_micro_lamports,
+            dry_run,
+            jito_tip_lamports,
+            start_index,
+            count,
+        } => {
+            if !dry_run {
+                throttle_execution(program_id, accounts, 1)?;
+            }
+            process_execute_bundle(
+                program_id,
+                accounts,
+                max_compute_units,
+                recent_fee_micro_lamports,
+                dry_run,
+                jito_tip_lamports,
+                start_index,
+                count,
+            )?;
+            if dry_run {
+                return Ok(());
+            }
+            record_priority_fee(accounts, max_compute_units, recent_fee_micro_lamports)
+        },
+        BundleInstruction::SetManagerStatus { is_paused } => {
+            process_set_manager_status(program_id, accounts, is_paused)
+        },
+        BundleInstruction::InitializeExecutionLog { max_entries } => {
+            process_initialize_execution_log(program_id, accounts, max_entries)
+        },
+        BundleInstruction::GetManagersStats => {
+            process_get_managers_stats(program_id, accounts)
+        },
+        BundleInstruction::GetExecutionState => {
+            process_get_execution_state(program_id, accounts)
+        },
+        BundleInstruction::EstimateCost { max_compute_units, recent_fee_micro_lamports, jito_tip_lamports } => {
+            process_estimate_cost(program_id, accounts, max_compute_units, recent_fee_micro_lamports, jito_tip_lamports)
+        },
+        BundleInstruction::CloseBundle => {
+            process_close_bundle(program_id, accounts)
+        },
+        BundleInstruction::ExpireBundle => {
+            process_expire_bundle(program_id, accounts)
+        },
+        BundleInstruction::BatchAddInstruction { items } => {
+            process_batch_add_instruction(program_id, accounts, items)
+        },
+        BundleInstruction::SetAuthority { new_authority } => {
+            process_set_authority(program_id, accounts, new_authority)
+        },
+        BundleInstruction::AcceptAuthority => {
+            process_accept_authority(program_id, accounts)
+        },
+        BundleInstruction::RemoveInstruction => {
+            process_remove_instruction(program_id, accounts)
+        },
+        BundleInstruction::MigrateManager => {
+            process_migrate_manager(program_id, accounts)
+        },
+        BundleInstruction::SetFee { fee_lamports, fee_collector } => {
+            process_set_fee(program_id, accounts, fee_lamports, fee_collector)
+        },
+        BundleInstruction::SetTipAccount { tip_account } => {
+            process_set_tip_account(program_id, accounts, tip_account)
+        },
+        BundleInstruction::ResetStats => {
+            process_reset_stats(program_id, accounts)
+        },
+        BundleInstruction::FailBundle => {
+            process_fail_bundle(program_id, accounts)
+        },
+        BundleInstruction::CancelBundle => {
+            process_cancel_bundle(program_id, accounts)
+        },
+        BundleInstruction::ExecuteBundleBatch {
+            bundle_count,
+            max_compute_units,
+            recent_fee_micro_lamports,
+            jito_tip_lamports,
+        } => {
+            throttle_execution(program_id, accounts, u16::from(bundle_count))?;
+            process_execute_bundle_batch(
+                program_id,
+                accounts,
+                bundle_count,
+                max_compute_units,
+                recent_fee_micro_lamports,
+                jito_tip_lamports,
+            )?;
+            record_priority_fee(accounts, max_compute_units, recent_fee_micro_lamports)
+        },
+        BundleInstruction::RegisterWallet { wallet_index, wallet } => {
+            process_register_wallet(program_id, accounts, wallet_index, wallet)
+        },
+        BundleInstruction::RemoveWallet { wallet_index } => {
+            process_remove_wallet(program_id, accounts, wallet_index)
+        },
+        BundleInstruction::UpdateManagerConfig { config } => {
+            process_update_manager_config(program_id, accounts, config)
+        },
+        BundleInstruction::LinkLookupTable { lookup_table } => {
+            process_link_lookup_table(program_id, accounts, lookup_table)
+        },
+        BundleInstruction::AddDelegate { delegate, permissions } => {
+            process_add_delegate(program_id, accounts, delegate, permissions)
+        },
+        BundleInstruction::RemoveDelegate { delegate } => {
+            process_remove_delegate(program_id, accounts, delegate)
+        },
+        BundleInstruction::TransferTokensBundled { decimals, transfers } => {
+            process_transfer_tokens_bundled(program_id, accounts, decimals, transfers)
+        },
+        BundleInstruction::RetryBundle { max_compute_units, recent_fee_micro_lamports, jito_tip_lamports } => {
+            throttle_execution(program_id, accounts, 1)?;
+            process_retry_bundle(
+                program_id,
+                accounts,
+                max_compute_units,
+                recent_fee_micro_lamports,
+                jito_tip_lamports,
+            )?;
+            record_priority_fee(accounts, max_compute_units, recent_fee_micro_lamports)
+        },
+        BundleInstruction::Deposit { lamports } => {
+            process_deposit(program_id, accounts, lamports)
+        },
+        BundleInstruction::WithdrawToWallet { wallet_index, amount, asset } => {
+            process_vault_payout(program_id, accounts, &[(wallet_index, amount)], &asset)
+        },
+        BundleInstruction::DistributeEqually { wallet_indexes, total_amount, asset } => {
+            process_distribute_equally(program_id, accounts, &wallet_indexes, total_amount, &asset)
+        },
+        BundleInstruction::SweepWallets { wallet_indexes, mints } => {
+            process_sweep_wallets(program_id, accounts, &wallet_indexes, &mints)
+        },
+        BundleInstruction::GarbageCollect => {
+            process_garbage_collect(program_id, accounts)
+        },
+        BundleInstruction::AddAllowedProgram { program_id: allowed_program } => {
+            process_add_allowed_program(program_id, accounts, allowed_program)
+        },
+        BundleInstruction::RemoveAllowedProgram { program_id: allowed_program } => {
+            process_remove_allowed_program(program_id, accounts, allowed_program)
+        },
+        BundleInstruction::MigrateState => {
+            process_migrate_state(program_id, accounts)
+        },
+    }
+}
+
+// Fails early with a readable error instead of letting create_account fail
+// inside the system program. Transaction fees are charged before the program
+// runs, so the payer's balance already excludes them.
+fn check_rent_funds(payer: &AccountInfo, required_lamports: u64) -> ProgramResult {
+    let available = payer.lamports();
+    if available < required_lamports {
+        msg!("Insufficient funds for rent: {} needs {} lamports but holds {} (short by {})",
+            payer.key, required_lamports, available, required_lamports - available);
+        return Err(BundleError::InsufficientFundsForRent.into());
+    }
+    Ok(())
+}
+
+// A program-owned account that merely deserializes as a manager is not
+// enough: its address must also be the PDA of the authority, index and bump
+// recorded inside it.
+fn verify_manager_pda(
+    program_id: &Pubkey,
+    bundle_manager_account: &AccountInfo,
+    bundle_manager: &BundleManager,
+) -> ProgramResult {
+    let expected = Pubkey::create_program_address(
+        &[
+            MANAGER_SEED,
+            bundle_manager.seed_authority.as_ref(),
+            &bundle_manager.manager_index.to_le_bytes(),
+            &[bundle_manager.bump],
+        ],
+        program_id,
+    )
+    .map_err(|_| BundleError::InvalidManagerPda)?;
+    
+    if expected != *bundle_manager_account.key {
+        return Err(BundleError::InvalidManagerPda.into());
+    }
+    Ok(())
+}
+
+// Same for bundles: the address must be the PDA of the manager, authority,
+// nonce and bump recorded inside. Bundles migrated from layouts that did
+// not record their seeds cannot be re-derived and are trusted as migrated.
+fn verify_bundle_pda(program_id: &Pubkey, bundle_account: &AccountInfo, bundle: &Bundle) -> ProgramResult {
+    let Some(seeds) = bundle.seeds else {
+        return Ok(());
+    };
+    let expected = Pubkey::create_program_address(
+        &[
+            BUNDLE_SEED,
+            bundle.manager.as_ref(),
+            bundle.authority.as_ref(),
+            &seeds.client_nonce.to_le_bytes(),
+            &[seeds.bump],
+        ],
+        program_id,
+    )
+    .map_err(|_| BundleError::InvalidBundlePda)?;
+    
+    if expected != *bundle_account.key {
+        return Err(BundleError::InvalidBundlePda.into());
+    }
+    Ok(())
+}
+
+// The record index of each record passed to ExecuteBundle. Records must be
+// passed in the order they were added, each at its PDA: a record passed twice
+// could otherwise stand in for one of the bundle's other records. Removed
+// records leave gaps in the indexes, so the walk skips over them. Bundles
+// migrated from before their seeds were recorded may hold records added as
+// keypair accounts, which have no index; those only have to be distinct and
+// are indexed by position.
+fn record_indexes(
+    program_id: &Pubkey,
+    bundle_account: &AccountInfo,
+    bundle: &Bundle,
+    record_accounts: &[AccountInfo],
+    records: &[BundleInstructionRecord],
+) -> Result<Vec<u16>, ProgramError> {
+    if bundle.seeds.is_none() {
+        for (position, record_account) in record_accounts.iter().enumerate() {
+            if record_accounts[..position].iter().any(|earlier| earlier.key == record_account.key) {
+                msg!("Record {} of bundle {} is passed twice", record_account.key, bundle.bundle_id);
+                return Err(BundleError::InvalidRecordPda.into());
+            }
+        }
+        return Ok((0..records.len() as u16).collect());
+    }
+    
+    let mut indexes = Vec::with_capacity(records.len());
+    let mut next_index = 0;
+    for (record_account, record) in record_accounts.iter().zip(records) {
+        let record_index = loop {
+            if next_index >= bundle.next_record_index {
+                msg!("Record {} is not at the PDA of a record index of bundle {} after {}",
+                    record_account.key, bundle.bundle_id, next_index);
+                return Err(BundleError::InvalidRecordPda.into());
+            }
+            let candidate = next_index;
+            next_index += 1;
+            let (address, _) =
+                find_instruction_record_address(program_id, bundle_account.key, record.wallet_index, candidate);
+            if address == *record_account.key {
+                break candidate;
+            }
+        };
+        indexes.push(record_index);
+    }
+    Ok(indexes)
+}
+
+// Notifies the bundle's on_failure program, passing the bundle account
+// read-only. Errors returned by invoke are logged and swallowed so the hook
+// never replaces the bundle's own failure. A hook program that itself aborts
+// still fails the whole transaction, so hook programs should log and succeed.
+// A hook program taken off the manager's allowlist since CreateBundle is
+// skipped.
+fn invoke_failure_hook<'a>(
+    bundle_manager: &BundleManager,
+    bundle: &Bundle,
+    bundle_account: &AccountInfo<'a>,
+    accounts: &[AccountInfo<'a>],
+) {
+    let Some((hook_program_id, hook_data)) = &bundle.on_failure else {
+        return;
+    };
+    
+    if check_allowed_program(bundle_manager, hook_program_id).is_err() {
+        msg!("on_failure hook program {} is not allowed, skipping", hook_program_id);
+        return;
+    }
+    
+    let Some(hook_program) = accounts.iter().find(|a| a.key == hook_program_id) else {
+        msg!("on_failure hook program {} not supplied, skipping", hook_program_id);
+        return;
+    };
+    
+    let hook = Instruction {
+        program_id: *hook_program_id,
+        accounts: vec![AccountMeta::new_readonly(*bundle_account.key, false)],
+        data: hook_data.clone(),
+    };
+    
+    if let Err(err) = invoke(&hook, &[bundle_account.clone(), hook_program.clone()]) {
+        msg!("on_failure hook for bundle {} failed: {:?}", bundle.bundle_id, err);
+    }
+}
+
+// Invariant every bundle-mutating handler must hold before writing a bundle
+// back: the per-wallet vectors line up with each other and with wallet_count,
+// and no wallet has more instructions added than planned.
+fn assert_bundle_consistent(bundle: &Bundle) -> ProgramResult {
+    let wallet_count = bundle.wallet_count as usize;
+    if bundle.wallet_indexes.len() != wallet_count
+        || bundle.instructions_per_wallet.len() != wallet_count
+        || bundle.instructions_added.len() != wallet_count
+    {
+        msg!("Bundle {} inconsistent: wallet_count {}, wallet_indexes {}, instructions_per_wallet {}, instructions_added {}",
+            bundle.bundle_id, wallet_count, bundle.wallet_indexes.len(),
+            bundle.instructions_per_wallet.len(), bundle.instructions_added.len());
+        return Err(BundleError::InconsistentBundleState.into());
+    }
+    
+    let over_budget = bundle.instructions_added.iter()
+        .zip(&bundle.instructions_per_wallet)
+        .any(|(added, planned)| added > planned);
+    if over_budget {
+        msg!("Bundle {} inconsistent: more instructions added than planned", bundle.bundle_id);
+        return Err(BundleError::InconsistentBundleState.into());
+    }
+    Ok(())
+}
+
+// Checks that every account a stored instruction references, and the program
+// it invokes, was passed to ExecuteBundle, and that no meta claims more than
+// the outer transaction grants: writable only if passed writable, signer only
+// if it signed or is the record's wallet PDA, which this program signs for.
+fn validate_instruction_accounts(
+    record: &BundleInstructionRecord,
+    wallet_address: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let find = |key: &Pubkey| {
+        accounts.iter().find(|info| info.key == key).ok_or_else(|| {
+            msg!("Account {} used by an instruction for wallet {} was not passed", key, record.wallet_index);
+            ProgramError::from(BundleError::MissingAccountForInstruction)
+        })
+    };
+    
+    find(&record.program_id)?;
+    for meta in &record.accounts {
+        let info = find(&meta.pubkey)?;
+        let signer_ok = !meta.is_signer || info.is_signer || meta.pubkey == *wallet_address;
+        let writable_ok = !meta.is_writable || info.is_writable;
+        if !signer_ok || !writable_ok {
+            msg!("Instruction for wallet {} escalates the privileges of {}", record.wallet_index, meta.pubkey);
+            return Err(BundleError::AccountPrivilegeEscalation.into());
+        }
+    }
+    Ok(())
+}
+
+// Rejects instructions, and on_failure hooks, invoking a program off the
+// manager's allowlist. Run when an instruction or hook is added and again
+// before it is invoked, since the list can change in between.
+fn check_allowed_program(bundle_manager: &BundleManager, instruction_program_id: &Pubkey) -> ProgramResult {
+    if !bundle_manager.allows_program(instruction_program_id) {
+        msg!("Program {} is not on the manager's allowlist", instruction_program_id);
+        return Err(BundleError::ProgramNotAllowed.into());
+    }
+    Ok(())
+}
+
+// What a bundle's wallets hold among the accounts passed to ExecuteBundle:
+// their lamports, and for each capped mint the amount in the token accounts
+// they own
+struct WalletHoldings {
+    lamports: u64,
+    tokens: Vec<u64>,
+}
+
+fn wallet_holdings(wallets: &[Pubkey], limits: &SpendLimits, accounts: &[AccountInfo]) -> WalletHoldings {
+    let mut holdings = WalletHoldings { lamports: 0, tokens: vec![0; limits.token_caps.len()] };
+    for (position, info) in accounts.iter().enumerate() {
+        // An account passed twice is counted once
+        if accounts[..position].iter().any(|earlier| earlier.key == info.key) {
+            continue;
+        }
+        if wallets.contains(info.key) {
+            holdings.lamports = holdings.lamports.saturating_add(info.lamports());
+        }
+        if !is_token_program(info.owner) {
+            continue;
+        }
+        let data = info.data.borrow();
+        let (Some((mint, owner)), Some(amount)) = (token_account_mint_and_owner(&data), token_account_amount(&data))
+        else {
+            continue;
+        };
+        if !wallets.contains(&owner) {
+            continue;
+        }
+        for (cap, held) in limits.token_caps.iter().zip(&mut holdings.tokens) {
+            if cap.mint == mint {
+                *held = held.saturating_add(amount);
+            }
+        }
+    }
+    holdings
+}
+
+// Fails the call when the bundle's wallets ended up with less than `before`
+// by more than its spend limits allow. Only outflows count: a wallet that
+// receives more than it spends has spent nothing.
+fn check_spend_limits(
+    bundle: &Bundle,
+    limits: &SpendLimits,
+    before: &WalletHoldings,
+    after: &WalletHoldings,
+) -> ProgramResult {
+    let lamports_out = before.lamports.saturating_sub(after.lamports);
+    if let Some(max_lamports_out) = limits.max_lamports_out {
+        if lamports_out > max_lamports_out {
+            msg!("Bundle {} took {} lamports out of its wallets, its limit is {}",
+                bundle.bundle_id, lamports_out, max_lamports_out);
+            return Err(BundleError::SpendLimitExceeded.into());
+        }
+    }
+    for ((cap, held_before), held_after) in limits.token_caps.iter().zip(&before.tokens).zip(&after.tokens) {
+        let tokens_out = held_before.saturating_sub(*held_after);
+        if tokens_out > cap.max_amount {
+            msg!("Bundle {} took {} of mint {} out of its wallets, its limit is {}",
+                bundle.bundle_id, tokens_out, cap.mint, cap.max_amount);
+            return Err(BundleError::SpendLimitExceeded.into());
+        }
+    }
+    Ok(())
+}
+
+// Reads the manager's wallet registry from the accounts passed to ExecuteBundle
+fn find_wallet_registry(
+    program_id: &Pubkey,
+    manager: &Pubkey,
+    accounts: &[AccountInfo],
+) -> Result<WalletRegistry, ProgramError> {
+    let (registry_address, _) = find_wallet_registry_address(program_id, manager);
+    let Some(registry_account) = accounts.iter().find(|a| *a.key == registry_address) else {
+        msg!("Wallet registry {} was not passed", registry_address);
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    assert_owned_by(registry_account, program_id)?;
+    WalletRegistry::unpack(&registry_account.data.borrow())
+}
+
+// The manager's wallet at `wallet_index`: the address registered for it once
+// the manager has a registry, its wallet PDA before that
+fn wallet_address(
+    program_id: &Pubkey,
+    manager: &Pubkey,
+    registry: Option<&WalletRegistry>,
+    wallet_index: u8,
+) -> Result<Pubkey, ProgramError> {
+    match registry {
+        Some(registry) => match registry.get(wallet_index) {
+            Some(wallet) => Ok(wallet.address),
+            None => {
+                msg!("Wallet {} is not registered", wallet_index);
+                Err(BundleError::WalletNotRegistered.into())
+            },
+        },
+        None => Ok(find_wallet_address(program_id, manager, wallet_index).0),
+    }
+}
+
+// A record runs only for a registered wallet, and every signer its
+// instruction needs is that wallet or the bundle authority, so a record
+// cannot spend from a wallet the manager authority did not register for it.
+fn check_registered_wallet(
+    record: &BundleInstructionRecord,
+    bundle_authority: &Pubkey,
+    registry: &WalletRegistry,
+) -> ProgramResult {
+    let Some(wallet) = registry.get(record.wallet_index) else {
+        msg!("Wallet {} is not registered", record.wallet_index);
+        return Err(BundleError::WalletNotRegistered.into());
+    };
+    for meta in &record.accounts {
+        if meta.is_signer && meta.pubkey != wallet.address && meta.pubkey != *bundle_authority {
+            msg!("Instruction for wallet {} needs signer {}, which is not its registered wallet",
+                record.wallet_index, meta.pubkey);
+            return Err(BundleError::UnregisteredWalletSigner.into());
+        }
+    }
+    Ok(())
+}
+
+// Puts records passed in the order they were added into the order ExecuteBundle
+// runs them: wallet by wallet, as the bundle lists its `wallet_indexes`, each
+// wallet's records in the order they were added. The sort is stable, which
+// keeps that order within a wallet.
+fn in_wallet_order<'a, 'b>(
+    bundle: &Bundle,
+    record_accounts: &'a [AccountInfo<'b>],
+    records: Vec<BundleInstructionRecord>,
+    record_indexes: Vec<u16>,
+) -> (Vec<&'a AccountInfo<'b>>, Vec<BundleInstructionRecord>, Vec<u16>) {
+    let mut loaded: Vec<_> = record_accounts.iter().zip(records).zip(record_indexes).collect();
+    loaded.sort_by_key(|((_, record), _)| {
+        bundle.wallet_indexes.iter().position(|&index| index == record.wallet_index)
+    });
+    let ((record_accounts, records), record_indexes): ((Vec<_>, Vec<_>), Vec<_>) = loaded.into_iter().unzip();
+    (record_accounts, records, record_indexes)
+}
+
+// Checks that every instruction the bundle plans was added, so a half-built
+// bundle is refused before any of its records is read
+fn check_bundle_complete(bundle: &Bundle) -> ProgramResult {
+    for (position, &wallet_index) in bundle.wallet_indexes.iter().enumerate() {
+        let planned = bundle.instructions_per_wallet[position];
+        let added = bundle.instructions_added[position];
+        if added != planned {
+            msg!("Wallet {} of bundle {} plans {} instructions, {} added",
+                wallet_index, bundle.bundle_id, planned, added);
+            return Err(BundleError::IncompleteBundle.into());
+        }
+    }
+    Ok(())
+}
+
+// Checks that the records passed to ExecuteBundle carry out the bundle's
+// plan: grouping them by wallet gives exactly `instructions_per_wallet` for
+// each wallet. Only tallies records `record_indexes` has verified, which are
+// distinct, so a record passed twice cannot fill in for a missing one.
+fn check_wallet_counts(bundle: &Bundle, records: &[BundleInstructionRecord]) -> ProgramResult {
+    let mut counts = vec![0u16; bundle.wallet_indexes.len()];
+    for record in records {
+        let position = bundle.wallet_indexes.iter()
+            .position(|&index| index == record.wallet_index)
+            .ok_or(BundleError::WalletNotInBundle)?;
+        counts[position] += 1;
+    }
+    
+    for (position, &wallet_index) in bundle.wallet_indexes.iter().enumerate() {
+        let planned = bundle.instructions_per_wallet[position];
+        if counts[position] != planned as u16 {
+            msg!("Wallet {} of bundle {} plans {} instructions, {} passed",
+                wallet_index, bundle.bundle_id, planned, counts[position]);
+            return Err(BundleError::IncompleteBundle.into());
+        }
+    }
+    Ok(())
+}
+
+// Grows a program-owned account to `required_len` if it is shorter, with
+// `payer` topping up the rent so it stays rent-exempt. New bytes are zeroed.
+// The runtime caps growth at MAX_PERMITTED_DATA_INCREASE per instruction, so
+// larger jumps are rejected up front with a readable error.
+fn grow_account<'a>(
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    required_len: usize,
+) -> ProgramResult {
+    let current_len = account.data_len();
+    if required_len <= current_len {
+        return Ok(());
+    }
+    
+    if required_len - current_len > MAX_PERMITTED_DATA_INCREASE {
+        msg!("Growing {} from {} to {} bytes exceeds the {} byte realloc limit",
+            account.key, current_len, required_len, MAX_PERMITTED_DATA_INCREASE);
+        return Err(BundleError::ReallocTooLarge.into());
+    }
+    
+    let rent = Rent::get()?;
+    let required_lamports = rent.minimum_balance(required_len);
+    let shortfall = required_lamports.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        check_rent_funds(payer, shortfall)?;
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, shortfall),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    account.realloc(required_len, true)?;
+    assert_rent_exempt(account, &rent)
+}
+
+// Writes `entry` into the log's next slot. While the log is below
+// max_entries the account is grown by one slot, with `payer` topping up the
+// rent; once full, the oldest slot is overwritten.
+fn append_execution_log<'a>(
+    program_id: &Pubkey,
+    bundle_manager_account: &AccountInfo<'a>,
+    log_account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    entry: &ExecutionLogEntry,
+) -> ProgramResult {
+    assert_owned_by(log_account, program_id)?;
+    
+    let mut log = ExecutionLog::unpack(&log_account.data.borrow())?;
+    if log.manager != *bundle_manager_account.key {
+        return Err(BundleError::InvalidExecutionLog.into());
+    }
+    
+    let offset = ExecutionLog::entry_offset(log.next_index);
+    let required_len = ExecutionLog::space(log.next_index + 1);
+    grow_account(log_account, payer, system_program, required_len)?;
+    
+    let mut data = log_account.data.borrow_mut();
+    entry.serialize(&mut &mut data[offset..required_len])?;
+    
+    log.next_index = (log.next_index + 1) % log.max_entries;
+    log.total_appended = log.total_appended.saturating_add(1);
+    log.serialize(&mut &mut data[..ExecutionLog::HEADER_LEN])?;
+    
+    Ok(())
+}
+
+// Transfers funding.lamports_each from the authority to every wallet PDA in
+// 0..funding.wallet_count, checking up front that the authority can cover
+// the whole phase so no wallet is funded unless all of them can be.
+fn fund_wallets<'a>(
+    program_id: &Pubkey,
+    bundle_manager_account: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    accounts: &[AccountInfo<'a>],
+    funding: &FundWallets,
+) -> ProgramResult {
+    let total = funding.total_lamports()?;
+    if authority.lamports() < total {
+        msg!("Funding {} wallets needs {} lamports but authority holds {}",
+            funding.wallet_count, total, authority.lamports());
+        return Err(BundleError::InsufficientFundsForFunding.into());
+    }
+    
+    for wallet_index in 0..funding.wallet_count {
+        let (wallet_address, _) = find_wallet_address(program_id, bundle_manager_account.key, wallet_index);
+        let wallet = accounts.iter()
+            .find(|a| *a.key == wallet_address)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        
+        invoke(
+            &system_instruction::transfer(authority.key, wallet.key, funding.lamports_each),
+            &[authority.clone(), wallet.clone(), system_program.clone()],
+        )?;
+    }
+    
+    msg!("Funded {} wallets with {} lamports each", funding.wallet_count, funding.lamports_each);
+    Ok(())
+}
+
+// The account `lamports` go to among the accounts passed to ExecuteBundle,
+// or None when nothing is paid. `role` names the payee in the log.
+fn find_payee<'a, 'b>(
+    accounts: &'b [AccountInfo<'a>],
+    payee: &Pubkey,
+    lamports: u64,
+    role: &str,
+) -> Result<Option<&'b AccountInfo<'a>>, ProgramError> {
+    if lamports == 0 {
+        return Ok(None);
+    }
+    match accounts.iter().find(|a| a.key == payee) {
+        Some(account) => Ok(Some(account)),
+        None => {
+            msg!("{} {} was not passed", role, payee);
+            Err(ProgramError::NotEnoughAccountKeys)
+        },
+    }
+}
+
+// Creates the account described by `spec` unless it already exists, paying
+// from the authority. The account passed in must be the spec's address.
+fn create_missing_account<'a>(
+    spec: &CreateIfMissing,
+    authority: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    accounts: &[AccountInfo<'a>],
+) -> ProgramResult {
+    let address = spec.address();
+    let target = accounts.iter()
+        .find(|a| *a.key == address)
+        .ok_or(BundleError::CreatedAccountMismatch)?;
+    
+    if target.lamports() > 0 {
+        return Ok(());
+    }
+    
+    match spec {
+        CreateIfMissing::SystemAccount { space, owner, .. } => {
+            let lamports = Rent::get()?.minimum_balance(*space as usize);
+            check_rent_funds(authority, lamports)?;
+            invoke(
+                &system_instruction::create_account(authority.key, target.key, lamports, *space, owner),
+                &[authority.clone(), target.clone(), system_program.clone()],
+            )?;
+        },
+        CreateIfMissing::AssociatedTokenAccount { wallet, mint, token_program } => {
+            let find = |key: &Pubkey| accounts.iter()
+                .find(|a| a.key == key)
+                .ok_or(ProgramError::NotEnoughAccountKeys);
+            let wallet_info = find(wallet)?;
+            let mint_info = find(mint)?;
+            let token_program_info = find(token_program)?;
+            let ata_program_info = find(&ASSOCIATED_TOKEN_PROGRAM_ID)?;
+            assert_token_program(token_program, mint_info)?;
+            
+            // AssociatedTokenAccountInstruction::CreateIdempotent
+            let create_ata = Instruction {
+                program_id: ASSOCIATED_TOKEN_PROGRAM_ID,
+                accounts: vec![
+                    AccountMeta::new(*authority.key, true),
+                    AccountMeta::new(address, false),
+                    AccountMeta::new_readonly(*wallet, false),
+                    AccountMeta::new_readonly(*mint, false),
+                    AccountMeta::new_readonly(system_program::ID, false),
+                    AccountMeta::new_readonly(*token_program, false),
+                ],
+                data: vec![1],
+            };
+            invoke(
+                &create_ata,
+                &[
+                    authority.clone(),
+                    target.clone(),
+                    wallet_info.clone(),
+                    mint_info.clone(),
+                    system_program.clone(),
+                    token_program_info.clone(),
+                    ata_program_info.clone(),
+                ],
+            )?;
+        },
+    }
+    
+    msg!("Created missing account {}", address);
+    Ok(())
+}
+
+// Token accounts and mints are owned by the token program that manages them,
+// so a legacy mint paired with token-2022 (or the reverse) is caught here
+// instead of failing deep inside the token CPI.
+fn assert_token_program(token_program: &Pubkey, token_account: &AccountInfo) -> ProgramResult {
+    if !is_token_program(token_program) || token_account.owner != token_program {
+        msg!("Token program {} does not match {} (owned by {})",
+            token_program, token_account.key, token_account.owner);
+        return Err(BundleError::InvalidTokenProgram.into());
+    }
+    Ok(())
+}
+
+// A Failed bundle may only be attempted again once the manager's
+// retry_cooldown_slots have passed since its last attempt.
+fn check_retry_cooldown(bundle: &Bundle, bundle_manager: &BundleManager, current_slot: u64) -> ProgramResult {
+    if bundle.status != BundleStatus::Failed {
+        return Ok(());
+    }
+    
+    let ready_at = bundle.last_attempt_slot.saturating_add(bundle_manager.config.retry_cooldown_slots);
+    if current_slot < ready_at {
+        msg!("Bundle {} can be retried at slot {} (current slot {})",
+            bundle.bundle_id, ready_at, current_slot);
+        return Err(BundleError::RetryCooldownActive.into());
+    }
+    Ok(())
+}
+
+// A Failed bundle with max_retries set may only run again that many times
+fn check_retry_limit(bundle: &Bundle) -> ProgramResult {
+    match bundle.max_retries {
+        Some(max_retries) if bundle.retry_count >= max_retries => {
+            msg!("Bundle {} has already been retried {} of {} times",
+                bundle.bundle_id, bundle.retry_count, max_retries);
+            Err(BundleError::RetryLimitReached.into())
+        },
+        _ => Ok(()),
+    }
+}
+
+// ExecuteBundle needs `threshold` of the manager's approval authorities to
+// have signed. A manager without authorities only needs the bundle
+// authority's signature, which is checked separately.
+fn check_approvals(bundle_manager: &BundleManager, accounts: &[AccountInfo]) -> ProgramResult {
+    if bundle_manager.authorities.is_empty() {
+        return Ok(());
+    }
+    
+    let approvals = bundle_manager.authorities.iter()
+        .filter(|key| accounts.iter().any(|account| account.is_signer && account.key == *key))
+        .count();
+    if approvals < bundle_manager.threshold as usize {
+        msg!("{} of {} approval authorities signed, {} required",
+            approvals, bundle_manager.authorities.len(), bundle_manager.threshold);
+        return Err(BundleError::InsufficientApprovals.into());
+    }
+    Ok(())
+}
+
+// Whether `key` may act for the manager authority where `allowed` picks the
+// permission that needs: it is the authority itself or a delegate holding it.
+fn acts_for_authority(
+    bundle_manager: &BundleManager,
+    key: &Pubkey,
+    allowed: fn(DelegatePermissions) -> bool,
+) -> bool {
+    bundle_manager.authority == *key || bundle_manager.delegate(key).is_some_and(allowed)
+}
+
+// Moves lamports between accounts by editing balances directly, which is how
+// a program pays out of accounts it owns (the system program can only debit
+// system-owned accounts). Checked so a bad amount errors instead of wrapping,
+// and refused between an account and itself, which would mint lamports.
+fn safe_transfer(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+    if from.key == to.key {
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    let from_balance = from.lamports()
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    let to_balance = checked_add(to.lamports(), lamports)?;
+    
+    **from.try_borrow_mut_lamports()? = from_balance;
+    **to.try_borrow_mut_lamports()? = to_balance;
+    Ok(())
+}
+
+// Rent rule for instruction-record accounts: whichever path closes one
+// (remove, compact, close or cancel), its lamports go to the bundle
+// authority, never the caller or the bundle account. Every such path closes
+// records through this function so the rule lives in one place.
+fn close_instruction_account(
+    record_account: &AccountInfo,
+    authority_account: &AccountInfo,
+    bundle: &Bundle,
+) -> ProgramResult {
+    if *authority_account.key != bundle.authority {
+        return Err(BundleError::InvalidRentDestination.into());
+    }
+    
+    safe_transfer(record_account, authority_account, record_account.lamports())?;
+    record_account.data.borrow_mut().fill(0);
+    Ok(())
+}
+
+// Circuit breaker: count consecutive failed bundles and pause the manager
+// once config.auto_pause_threshold is reached. Any success resets the count.
+// Returns whether this outcome paused the manager.
+fn record_execution_outcome(bundle_manager: &mut BundleManager, succeeded: bool) -> bool {
+    if succeeded {
+        bundle_manager.consecutive_failures = 0;
+        return false;
+    }
+    
+    bundle_manager.consecutive_failures = bundle_manager.consecutive_failures.saturating_add(1);
+    let threshold = bundle_manager.config.auto_pause_threshold;
+    if threshold > 0 && bundle_manager.consecutive_failures >= threshold && !bundle_manager.is_paused {
+        bundle_manager.is_paused = true;
+        msg!("AutoPaused: {} consecutive bundle failures reached the threshold of {}",
+            bundle_manager.consecutive_failures, threshold);
+        return true;
+    }
+    false
+}
+
+// Adds what one ExecuteBundle call did for a bundle to the manager's
+// execution totals, whichever way the call ended
+fn record_execution_totals(bundle_manager: &mut BundleManager, instructions_run: u64, compute_at_start: u64) {
+    let compute_units = compute_at_start.saturating_sub(sol_remaining_compute_units());
+    bundle_manager.total_instructions_executed =
+        bundle_manager.total_instructions_executed.saturating_add(instructions_run);
+    bundle_manager.total_compute_units = bundle_manager.total_compute_units.saturating_add(compute_units);
+}
+
+// Adds the priority fee of an ExecuteBundle or ExecuteBundleBatch call to
+// the manager it ran under, the first account of both. Counted once per
+// call rather than per bundle, since a batch runs all of its bundles in the
+// one transaction that pays the fee.
+fn record_priority_fee(
+    accounts: &[AccountInfo],
+    max_compute_units: u32,
+    recent_fee_micro_lamports: u64,
+) -> ProgramResult {
+    let bundle_manager_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    let price = compute_unit_price(&bundle_manager.config, recent_fee_micro_lamports)?;
+    bundle_manager.total_priority_fees_paid =
+        bundle_manager.total_priority_fees_paid.saturating_add(priority_fee_lamports(price, max_compute_units));
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+// Rate limit: counts `bundles` more bundles created or executed by the
+// manager in `slot`, failing once that passes config.max_bundles_per_slot.
+// The count starts over in every new slot.
+fn count_slot_bundles(bundle_manager: &mut BundleManager, slot: u64, bundles: u16) -> ProgramResult {
+    if bundle_manager.throttle_slot != slot {
+        bundle_manager.throttle_slot = slot;
+        bundle_manager.bundles_in_slot = 0;
+    }
+    let count = bundle_manager.bundles_in_slot.saturating_add(bundles);
+    let limit = bundle_manager.config.max_bundles_per_slot;
+    if limit > 0 && count > limit {
+        msg!("Manager already handled {} bundles in slot {}, its limit is {}",
+            bundle_manager.bundles_in_slot, slot, limit);
+        return Err(BundleError::SlotBundleLimitReached.into());
+    }
+    bundle_manager.bundles_in_slot = count;
+    Ok(())
+}
+
+// Throttle of the execution instructions, applied before they run so a
+// throttled call fails before spending compute on its bundles: at least
+// config.cooldown_slots since the manager's last execution call, and room
+// for `bundles` in the slot's count. Written back right away; the execution
+// reads the manager afresh, and any failure discards both writes.
+fn throttle_execution(program_id: &Pubkey, accounts: &[AccountInfo], bundles: u16) -> ProgramResult {
+    let bundle_manager_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    assert_owned_by(bundle_manager_account, program_id)?;
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    
+    let slot = Clock::get()?.slot;
+    let cooldown = bundle_manager.config.cooldown_slots;
+    if cooldown > 0 && bundle_manager.last_execution_slot > 0 {
+        let ready_at = bundle_manager.last_execution_slot.saturating_add(cooldown);
+        if slot < ready_at {
+            msg!("Manager can execute again at slot {} (current slot {})", ready_at, slot);
+            return Err(BundleError::ExecutionCooldownActive.into());
+        }
+    }
+    count_slot_bundles(&mut bundle_manager, slot, bundles)?;
+    bundle_manager.last_execution_slot = slot;
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+// Accounts ExecuteBundle hands to its bookkeeping helpers
+struct ExecuteContext<'a, 'b> {
+    program_id: &'b Pubkey,
+    bundle_manager_account: &'b AccountInfo<'a>,
+    bundle_account: &'b AccountInfo<'a>,
+    authority: &'b AccountInfo<'a>,
+    system_program: &'b AccountInfo<'a>,
+    execution_log: Option<&'b AccountInfo<'a>>,
+    accounts: &'b [AccountInfo<'a>],
+}
+
+// The records an ExecuteBundle call runs: `count` records from `start_index`,
+// or every record when `count` is 0, in which case executed ones are skipped.
+// An explicit slice must pick up exactly where the executed records end.
+fn execution_slice(
+    bundle: &Bundle,
+    records: &[BundleInstructionRecord],
+    start_index: u8,
+    count: u8,
+) -> Result<Range<usize>, ProgramError> {
+    if count == 0 {
+        if start_index != 0 {
+            msg!("Bundle {}: start_index {} needs a count", bundle.bundle_id, start_index);
+            return Err(BundleError::InvalidExecutionSlice.into());
+        }
+        return Ok(0..records.len());
+    }
+    
+    let slice = start_index as usize..start_index as usize + count as usize;
+    if slice.end > records.len() {
+        msg!("Bundle {}: records {:?} run past its {} records", bundle.bundle_id, slice, records.len());
+        return Err(BundleError::InvalidExecutionSlice.into());
+    }
+    let next = records.iter().position(|record| !record.executed).unwrap_or(records.len());
+    if slice.start != next || records[slice.clone()].iter().any(|record| record.executed) {
+        msg!("Bundle {}: records {:?} do not start at its next record {}", bundle.bundle_id, slice, next);
+        return Err(BundleError::InvalidExecutionSlice.into());
+    }
+    Ok(slice)
+}
+
+// Everything ExecuteBundle can check about the stored bundle before the
+// first CPI, returning the instructions to invoke. An error here means the
+// bundle cannot run as stored, so the caller commits it as Failed.
+fn prepare_execution(
+    bundle: &Bundle,
+    bundle_manager: &BundleManager,
+    authority: &AccountInfo,
+    records: &[BundleInstructionRecord],
+    resuming: bool,
+    jito_tip_lamports: u64,
+) -> Result<Vec<Instruction>, ProgramError> {
+    // The runtime takes the priority fee before the program runs, so only
+    // what the call transfers itself is checked
+    let cost = estimate_cost(bundle, bundle_manager, 0, 0, jito_tip_lamports)?;
+    // A resumed bundle funded its wallets in its first call
+    let required = if resuming { cost.total_lamports - cost.funding_lamports } else { cost.total_lamports };
+    if authority.lamports() < required {
+        msg!("Bundle {} costs {} lamports but authority holds {}",
+            bundle.bundle_id, required, authority.lamports());
+        return Err(BundleError::InsufficientFundsForFunding.into());
+    }
+    
+    records.iter().map(BundleInstructionRecord::instruction).collect()
+}
+
+// How an execution attempt ended
+enum ExecutionOutcome {
+    Succeeded { measured_net_lamports: i64, jito_tip: u64 },
+    Failed { error: ProgramError },
+}
+
+// Ends an execution attempt: sets the final status, updates the manager's
+// counters and breaker, writes both accounts back, appends to the execution
+// log, emits the outcome's events and, for a failure, notifies the
+// on_failure hook.
+fn finish_execution(
+    ctx: &ExecuteContext,
+    bundle_manager: &mut BundleManager,
+    bundle: &mut Bundle,
+    clock: &Clock,
+    outcome: ExecutionOutcome,
+    compute_at_start: u64,
+) -> ProgramResult {
+    let succeeded = matches!(outcome, ExecutionOutcome::Succeeded { .. });
+    bundle.transition_to(if succeeded { BundleStatus::Executed } else { BundleStatus::Failed })?;
+    if !bundle_manager.config.ephemeral_results {
+        bundle.execution_completed_at = clock.unix_timestamp;
+    }
+    
+    // active_bundles counts Created, Executing and PartiallyExecuted bundles
+    let auto_paused = record_execution_outcome(bundle_manager, succeeded);
+    bundle_manager.active_bundles = bundle_manager.active_bundles.saturating_sub(1);
+    if succeeded {
+        bundle_manager.total_bundles_executed = checked_add(bundle_manager.total_bundles_executed, 1)?;
+    } else {
+        bundle_manager.total_bundles_failed = checked_add(bundle_manager.total_bundles_failed, 1)?;
+    }
+    
+    assert_bundle_consistent(bundle)?;
+    bundle.serialize(&mut &mut ctx.bundle_account.data.borrow_mut()[..])?;
+    bundle_manager.serialize(&mut &mut ctx.bundle_manager_account.data.borrow_mut()[..])?;
+    
+    let compute_units = compute_at_start.saturating_sub(sol_remaining_compute_units());
+    if let Some(log_account) = ctx.execution_log {
+        let entry = ExecutionLogEntry {
+            executor: *ctx.authority.key,
+            bundle_id: bundle.bundle_id,
+            slot: clock.slot,
+            status: bundle.status,
+            compute_units,
+        };
+        append_execution_log(
+            ctx.program_id,
+            ctx.bundle_manager_account,
+            log_account,
+            ctx.authority,
+            ctx.system_program,
+            &entry,
+        )?;
+    }
+    
+    // Ephemeral managers do not store timestamps, but their events still carry them
+    let execution_started_at = if bundle_manager.config.ephemeral_results {
+        clock.unix_timestamp
+    } else {
+        bundle.execution_started_at
+    };
+    let event = match outcome {
+        ExecutionOutcome::Succeeded { measured_net_lamports, jito_tip } => BundleEvent::BundleExecuted(BundleExecutedEvent {
+            manager: bundle.manager,
+            bundle_id: bundle.bundle_id,
+            authority: bundle.authority,
+            wallet_count: bundle.wallet_count,
+            execution_started_at,
+            execution_completed_at: clock.unix_timestamp,
+            priority_fee: bundle.priority_fee,
+            expected_net_lamports: bundle.expected_net_lamports,
+            measured_net_lamports,
+            protocol_fee: bundle_manager.fee_lamports,
+            jito_tip,
+            compute_units,
+        }),
+        ExecutionOutcome::Failed { error } => BundleEvent::BundleFailed(BundleFailedEvent {
+            manager: bundle.manager,
+            bundle_id: bundle.bundle_id,
+            authority: bundle.authority,
+            wallet_count: bundle.wallet_count,
+            execution_started_at,
+            execution_completed_at: clock.unix_timestamp,
+            error: u64::from(error),
+            compute_units,
+        }),
+    };
+    event.emit()?;
+    
+    if auto_paused {
+        BundleEvent::ManagerStatusChanged(ManagerStatusChangedEvent {
+            manager: *ctx.bundle_manager_account.key,
+            authority: bundle_manager.authority,
+            is_paused: true,
+            automatic: true,
+        })
+        .emit()?;
+    }
+    
+    if !succeeded {
+        invoke_failure_hook(bundle_manager, bundle, ctx.bundle_account, ctx.accounts);
+    }
+    
+    msg!("Bundle {} {:?}", bundle.bundle_id, bundle.status);
+    Ok(())
+}
+
+// Fills limits left at 0 with their defaults and rejects any above its
+// ceiling. Shared by Initialize and UpdateManagerConfig.
+fn normalize_config(config: &mut ManagerConfig) -> ProgramResult {
+    if config.max_wallets_per_bundle == 0 {
+        config.max_wallets_per_bundle = DEFAULT_MAX_WALLETS_PER_BUNDLE;
+    }
+    if config.max_wallets_per_bundle > MAX_WALLETS_PER_BUNDLE {
+        msg!("max_wallets_per_bundle {} exceeds the ceiling of {}",
+            config.max_wallets_per_bundle, MAX_WALLETS_PER_BUNDLE);
+        return Err(BundleError::TooManyWallets.into());
+    }
+    if config.max_instruction_data_len == 0 {
+        config.max_instruction_data_len = DEFAULT_MAX_INSTRUCTION_DATA_LEN;
+    }
+    if config.max_instruction_data_len > MAX_INSTRUCTION_DATA_LEN {
+        msg!("max_instruction_data_len {} exceeds the ceiling of {}",
+            config.max_instruction_data_len, MAX_INSTRUCTION_DATA_LEN);
+        return Err(BundleError::InstructionDataTooLarge.into());
+    }
+    if config.max_accounts_per_instruction == 0 {
+        config.max_accounts_per_instruction = DEFAULT_MAX_ACCOUNTS_PER_INSTRUCTION;
+    }
+    if config.max_accounts_per_instruction > MAX_ACCOUNTS_PER_INSTRUCTION {
+        msg!("max_accounts_per_instruction {} exceeds the ceiling of {}",
+            config.max_accounts_per_instruction, MAX_ACCOUNTS_PER_INSTRUCTION);
+        return Err(BundleError::TooManyInstructionAccounts.into());
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_initialize(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    manager_index: u16,
+    mut config: ManagerConfig,
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+    tip_account: Pubkey,
+    authorities: Vec<Pubkey>,
+    threshold: u8,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    
+    normalize_config(&mut config)?;
+    
+    let required_approvals = if authorities.is_empty() { 1 } else { authorities.len() };
+    let repeated = authorities.iter().enumerate().any(|(i, key)| authorities[..i].contains(key));
+    if threshold == 0 || threshold as usize > required_approvals
+        || authorities.len() > MAX_APPROVAL_AUTHORITIES || repeated
+    {
+        msg!("Approval threshold {} over {} authorities is not valid", threshold, authorities.len());
+        return Err(BundleError::InvalidApprovalThreshold.into());
+    }
+    
+    let (manager_address, bump) = find_manager_address(program_id, authority.key, manager_index);
+    if manager_address != *bundle_manager_account.key {
+        return Err(BundleError::InvalidManagerPda.into());
+    }
+    
+    // An existing manager is never re-initialized: that would reset its
+    // counters, config and pause state
+    if bundle_manager_account.owner == program_id {
+        return match BundleManager::unpack(&bundle_manager_account.data.borrow()) {
+            Err(ProgramError::InvalidAccountData) => Err(ProgramError::InvalidAccountData),
+            _ => Err(BundleError::AlreadyInitialized.into()),
+        };
+    }
+    
+    assert_signer(authority)?;
+    
+    // Create the bundle manager account
+    let rent = Rent::get()?;
+    let space = BundleManager::LEN;
+    let lamports = rent.minimum_balance(space);
+    check_rent_funds(authority, lamports)?;
+    
+    invoke_signed(
+        &system_instruction::create_account(
+            authority.key,
+            bundle_manager_account.key,
+            lamports,
+            space as u64,
+            program_id,
+        ),
+        &[
+            authority.clone(),
+            bundle_manager_account.clone(),
+            system_program.clone(),
+        ],
+        &[&[MANAGER_SEED, authority.key.as_ref(), &manager_index.to_le_bytes(), &[bump]]],
+    )?;
+    assert_rent_exempt(bundle_manager_account, &rent)?;
+    
+    msg!("Bundle Manager initialized with bundle size {} and priority fee multiplier {}",
+        config.bundle_size, config.priority_fee_multiplier);
+    
+    // Initialize the bundle manager data
+    let bundle_manager = BundleManager {
+        account_type: AccountType::Manager,
+        version: BundleManager::VERSION,
+        authority: *authority.key,
+        config,
+        active_bundles: 0,
+        total_bundles_executed: 0,
+        total_bundles_failed: 0,
+        is_paused: false,
+        bundle_seed: 0,
+        manager_index,
+        bump,
+        consecutive_failures: 0,
+        seed_authority: *authority.key,
+        fee_lamports,
+        fee_collector,
+        tip_account,
+        authorities,
+        threshold,
+        pending_authority: None,
+        wallet_registry: false,
+        lookup_table: None,
+        delegates: Vec::new(),
+        total_instructions_executed: 0,
+        total_compute_units: 0,
+        total_priority_fees_paid: 0,
+        throttle_slot: 0,
+        bundles_in_slot: 0,
+        last_execution_slot: 0,
+        allowed_programs: Vec::new(),
+    };
+    
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_create_bundle(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    wallet_indexes: Vec<u8>,
+    instructions_per_wallet: Vec<u8>,
+    category: u8,
+    on_failure: Option<(Pubkey, Vec<u8>)>,
+    funding: Option<FundWallets>,
+    client_nonce: u64,
+    execute_after: Option<i64>,
+    max_retries: Option<u8>,
+    spend_limits: Option<SpendLimits>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let bundle_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    
+    // Check that the bundle manager is owned by our program
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    // Deserialize the bundle manager
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    // Check if manager is paused
+    if bundle_manager.is_paused {
+        return Err(BundleError::ManagerPaused.into());
+    }
+    
+    // Validate wallet_indexes and instructions_per_wallet
+    let max_wallets = bundle_manager.config.max_wallets_per_bundle.min(MAX_WALLETS_PER_BUNDLE);
+    if wallet_indexes.len() > max_wallets as usize {
+        msg!("Bundle spans {} wallets, the manager allows {}", wallet_indexes.len(), max_wallets);
+        return Err(BundleError::TooManyWallets.into());
+    }
+    
+    if wallet_indexes.len() != instructions_per_wallet.len() {
+        return Err(BundleError::InvalidInstructionCount.into());
+    }
+    
+    // Records and their budget are looked up by wallet index, so each wallet is listed once
+    if wallet_indexes.iter().enumerate().any(|(position, index)| wallet_indexes[..position].contains(index)) {
+        msg!("Bundle lists a wallet index more than once: {:?}", wallet_indexes);
+        return Err(BundleError::DuplicateWallet.into());
+    }
+    
+    let total_instructions: usize = instructions_per_wallet.iter().map(|&n| n as usize).sum();
+    if total_instructions > MAX_BUNDLE_INSTRUCTIONS {
+        return Err(BundleError::TooManyInstructions.into());
+    }
+    
+    // The hook is invoked with no bundled instruction around it, so it has to
+    // pass the allowlist on its own
+    if let Some((hook_program_id, _)) = &on_failure {
+        check_allowed_program(&bundle_manager, hook_program_id)?;
+    }
+    
+    if let Some(limits) = &spend_limits {
+        if limits.token_caps.len() > MAX_TOKEN_SPEND_CAPS {
+            msg!("Spend limits cap {} mints, a bundle holds at most {}", limits.token_caps.len(), MAX_TOKEN_SPEND_CAPS);
+            return Err(BundleError::TooManySpendCaps.into());
+        }
+    }
+    
+    // A delegate creating the bundle for the manager authority signs and pays
+    // in the authority's place
+    let payer = match account_info_iter.next() {
+        Some(delegate) => {
+            assert_signer(delegate)?;
+            if bundle_manager.authority != *authority.key
+                || !acts_for_authority(&bundle_manager, delegate.key, |permissions| permissions.can_create)
+            {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            delegate
+        },
+        None => {
+            assert_signer(authority)?;
+            authority
+        },
+    };
+    
+    let (bundle_address, bump) =
+        find_bundle_address(program_id, bundle_manager_account.key, authority.key, client_nonce);
+    if bundle_address != *bundle_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    // A retry of a CreateBundle that already landed: the address pins the
+    // manager, authority and nonce, the stored plan must match as well
+    if bundle_account.owner == program_id {
+        let existing = Bundle::unpack(&bundle_account.data.borrow())?;
+        let same_bundle = existing.wallet_indexes == wallet_indexes
+            && existing.instructions_per_wallet == instructions_per_wallet
+            && existing.category == category
+            && existing.on_failure == on_failure
+            && existing.funding == funding
+            && existing.execute_after == execute_after.unwrap_or(0)
+            && existing.max_retries == max_retries
+            && existing.spend_limits == spend_limits;
+        if !same_bundle {
+            msg!("Nonce {} already created bundle {} with a different plan", client_nonce, existing.bundle_id);
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        msg!("Bundle {} already exists for nonce {}", existing.bundle_id, client_nonce);
+        return Ok(());
+    }
+    
+    // Checked before anything is created so a full manager fails cleanly
+    let next_bundle_seed = checked_add(bundle_manager.bundle_seed, 1)
+        .map_err(|_| BundleError::SeedExhausted)?;
+    let active_bundles = checked_add(bundle_manager.active_bundles, 1)?;
+    let clock = Clock::get()?;
+    count_slot_bundles(&mut bundle_manager, clock.slot, 1)?;
+    
+    // Create the bundle PDA
+    let rent = Rent::get()?;
+    let space = Bundle::space(wallet_indexes.len(), &on_failure);
+    let lamports = rent.minimum_balance(space);
+    check_rent_funds(payer, lamports)?;
+    
+    invoke_signed(
+        &system_instruction::create_account(
+            payer.key,
+            bundle_account.key,
+            lamports,
+            space as u64,
+            program_id,
+        ),
+        &[
+            payer.clone(),
+            bundle_account.clone(),
+            system_program.clone(),
+        ],
+        &[&[
+            BUNDLE_SEED,
+            bundle_manager_account.key.as_ref(),
+            authority.key.as_ref(),
+            &client_nonce.to_le_bytes(),
+            &[bump],
+        ]],
+    )?;
+    assert_rent_exempt(bundle_account, &rent)?;
+    
+    // Initialize the bundle data
+    let instructions_added = vec![0; wallet_indexes.len()];
+    let bundle = Bundle {
+        account_type: AccountType::Bundle,
+        version: Bundle::VERSION,
+        manager: *bundle_manager_account.key,
+        authority: *authority.key,
+        bundle_id: bundle_manager.bundle_seed,
+        created_at: clock.unix_timestamp,
+        execution_started_at: 0,
+        execution_completed_at: 0,
+        wallet_count: wallet_indexes.len() as u8,
+        category,
+        wallet_indexes,
+        instructions_per_wallet,
+        instructions_added,
+        status: BundleStatus::Created,
+        priority_fee: 0,
+        on_failure,
+        funding,
+        expected_net_lamports: 0,
+        last_attempt_slot: 0,
+        last_executed_index: None,
+        executed_mask: [0; EXECUTED_MASK_LEN],
+        next_record_index: 0,
+        seeds: Some(BundleSeeds { client_nonce, bump }),
+        compute_unit_limit: 0,
+        execute_after: execute_after.unwrap_or(0),
+        max_retries,
+        retry_count: 0,
+        expires_at: bundle_manager.config.expires_at(clock.unix_timestamp, execute_after.unwrap_or(0)),
+        spend_limits,
+    };
+    
+    assert_bundle_consistent(&bundle)?;
+    bundle.serialize(&mut &mut bundle_account.data.borrow_mut()[..])?;
+    
+    // Update the bundle manager
+    bundle_manager.active_bundles = active_bundles;
+    bundle_manager.bundle_seed = next_bundle_seed;
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Bundle {} created with {} wallets in category {}",
+        bundle.bundle_id, bundle.wallet_count, bundle.category);
+    
+    BundleEvent::BundleCreated(BundleCreatedEvent {
+        manager: bundle.manager,
+        bundle_id: bundle.bundle_id,
+        authority: bundle.authority,
+        wallet_count: bundle.wallet_count,
+        category: bundle.category,
+        created_at: bundle.created_at,
+    })
+    .emit()?;
+    
+    Ok(())
+}
+
+fn process_add_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    wallet_index: u8,
+    instruction_program_id: Pubkey,
+    instruction_data: Vec<u8>,
+    instr_accounts: Vec<InstructionAccountMeta>,
+    options: InstructionOptions,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let bundle_account = next_account_info(account_info_iter)?;
+    let instruction_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    
+    let item = BatchItem {
+        wallet_index,
+        program_id: instruction_program_id,
+        instruction_data,
+        accounts: instr_accounts,
+        options,
+    };
+    add_instructions(
+        program_id,
+        bundle_manager_account,
+        bundle_account,
+        authority,
+        system_program,
+        std::slice::from_ref(instruction_account),
+        vec![item],
+    )
+}
+
+fn process_batch_add_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    items: Vec<BatchItem>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let bundle_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let record_accounts = account_info_iter.as_slice();
+    
+    if items.is_empty() || items.len() > MAX_BATCH_ITEMS {
+        msg!("Batch of {} instructions is outside 1..={}", items.len(), MAX_BATCH_ITEMS);
+        return Err(BundleError::BatchTooLarge.into());
+    }
+    if record_accounts.len() != items.len() {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    
+    add_instructions(
+        program_id,
+        bundle_manager_account,
+        bundle_account,
+        authority,
+        system_program,
+        record_accounts,
+        items,
+    )
+}
+
+// Shared by AddInstruction and BatchAddInstruction. Every item is checked
+// against the manager, the bundle and the per-wallet budgets before any
+// record is created, then each item is stored in its record account (paired
+// by position) and the bundle is written once.
+fn add_instructions<'a>(
+    program_id: &Pubkey,
+    bundle_manager_account: &AccountInfo<'a>,
+    bundle_account: &AccountInfo<'a>,
+    authority: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    record_accounts: &[AccountInfo<'a>],
+    items: Vec<BatchItem>,
+) -> ProgramResult {
+    assert_owned_by(bundle_manager_account, program_id)?;
+    assert_owned_by(bundle_account, program_id)?;
+    
+    let bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    if bundle_manager.is_paused {
+        return Err(BundleError::ManagerPaused.into());
+    }
+    
+    // The manager's limits only guard its own bundles, and only the bundle's
+    // authority may add to it
+    let mut bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+    if bundle.manager != *bundle_manager_account.key {
+        return Err(BundleError::ManagerMismatch.into());
+    }
+    verify_bundle_pda(program_id, bundle_account, &bundle)?;
+    
+    assert_signer(authority)?;
+    if bundle.authority != *authority.key {
+        return Err(BundleError::Unauthorized.into());
+    }
+    
+    if bundle.status != BundleStatus::Created {
+        return Err(BundleError::InvalidStateTransition.into());
+    }
+    
+    // Budgets are tracked per position in wallet_indexes
+    let config = &bundle_manager.config;
+    let mut positions = Vec::with_capacity(items.len());
+    for item in &items {
+        if item.instruction_data.len() > usize::from(config.max_instruction_data_len) {
+            msg!("Instruction data is {} bytes, the manager allows {}",
+                item.instruction_data.len(), config.max_instruction_data_len);
+            return Err(BundleError::InstructionDataTooLarge.into());
+        }
+        if item.accounts.len() > usize::from(config.max_accounts_per_instruction) {
+            msg!("Instruction references {} accounts, the manager allows {}",
+                item.accounts.len(), config.max_accounts_per_instruction);
+            return Err(BundleError::TooManyInstructionAccounts.into());
+        }
+        check_allowed_program(&bundle_manager, &item.program_id)?;
+        let position = bundle.wallet_indexes.iter()
+            .position(|&index| index == item.wallet_index)
+            .ok_or(BundleError::WalletNotInBundle)?;
+        if bundle.instructions_added[position] >= bundle.instructions_per_wallet[position] {
+            msg!("Wallet {} has no room left for another instruction", item.wallet_index);
+            return Err(BundleError::WalletInstructionLimitReached.into());
+        }
+        bundle.instructions_added[position] += 1;
+        positions.push(position);
+    }
+    
+    // Records take consecutive indexes from next_record_index, in item order
+    let mut record_seeds = Vec::with_capacity(items.len());
+    for (record_account, item) in record_accounts.iter().zip(&items) {
+        let record_index = bundle.next_record_index;
+        // Every record index needs a bit of executed_mask
+        if record_index as usize >= MAX_BUNDLE_INSTRUCTIONS {
+            msg!("Bundle {} has had {} records added, removed ones included, the most it can",
+                bundle.bundle_id, record_index);
+            return Err(BundleError::TooManyInstructions.into());
+        }
+        let (record_address, bump) =
+            find_instruction_record_address(program_id, bundle_account.key, item.wallet_index, record_index);
+        if record_address != *record_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if record_account.owner == program_id {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        bundle.next_record_index = checked_add(record_index, 1)?;
+        record_seeds.push((record_index.to_le_bytes(), bump));
+    }
+    
+    let rent = Rent::get()?;
+    for ((record_account, item), (record_index, bump)) in record_accounts.iter().zip(items).zip(record_seeds) {
+        // Measured on the raw data, before it is possibly compressed
+        let lamport_delta = transfer_lamport_delta(
+            &item.program_id,
+            &item.instruction_data,
+            &item.accounts,
+            &bundle.authority,
+        );
+        bundle.expected_net_lamports = bundle.expected_net_lamports.saturating_add(lamport_delta);
+        
+        let (instruction_data, compressed) = if item.options.compress {
+            compression::compress_if_smaller(item.instruction_data)
+        } else {
+            (item.instruction_data, false)
+        };
+        
+        let record = BundleInstructionRecord {
+            account_type: AccountType::InstructionRecord,
+            version: BundleInstructionRecord::VERSION,
+            bundle: *bundle_account.key,
+            wallet_index: item.wallet_index,
+            program_id: item.program_id,
+            instruction_data,
+            accounts: item.accounts,
+            executed: false,
+            compressed,
+            reserved: 0,
+            create_if_missing: item.options.create_if_missing,
+            last_error: None,
+        };
+        let record_data = record.try_to_vec()?;
+        
+        let space = BundleInstructionRecord::space(record.instruction_data.len(), record.accounts.len());
+        let lamports = rent.minimum_balance(space);
+        check_rent_funds(authority, lamports)?;
+        
+        invoke_signed(
+            &system_instruction::create_account(
+                authority.key,
+                record_account.key,
+                lamports,
+                space as u64,
+                program_id,
+            ),
+            &[
+                authority.clone(),
+                record_account.clone(),
+                system_program.clone(),
+            ],
+            &[&[
+                INSTRUCTION_RECORD_SEED,
+                bundle_account.key.as_ref(),
+                &[item.wallet_index],
+                &record_index,
+                &[bump],
+            ]],
+        )?;
+        assert_rent_exempt(record_account, &rent)?;
+        record_account.data.borrow_mut()[..record_data.len()].copy_from_slice(&record_data);
+        
+        BundleEvent::InstructionAdded(InstructionAddedEvent {
+            manager: *bundle_manager_account.key,
+            bundle: *bundle_account.key,
+            bundle_id: bundle.bundle_id,
+            record: *record_account.key,
+            record_index: u16::from_le_bytes(record_index),
+            wallet_index: record.wallet_index,
+            program_id: record.program_id,
+        })
+        .emit()?;
+    }
+    
+    assert_bundle_consistent(&bundle)?;
+    
+    // Bundles created before a layout change can be shorter than the
+    // current encoding, so grow the account instead of failing the write
+    let bundle_data = bundle.try_to_vec()?;
+    grow_account(bundle_account, authority, system_program, bundle_data.len())?;
+    bundle_account.data.borrow_mut()[..bundle_data.len()].copy_from_slice(&bundle_data);
+    
+    for position in positions {
+        msg!("Instruction {} of {} added for wallet {} in bundle {}",
+            bundle.instructions_added[position], bundle.instructions_per_wallet[position],
+            bundle.wallet_indexes[position], bundle.bundle_id);
+    }
+    
+    Ok(())
+}
+
+fn process_remove_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let bundle_account = next_account_info(account_info_iter)?;
+    let record_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    assert_owned_by(bundle_account, program_id)?;
+    assert_owned_by(record_account, program_id)?;
+    
+    let bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    let mut bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+    if bundle.manager != *bundle_manager_account.key {
+        return Err(BundleError::ManagerMismatch.into());
+    }
+    verify_bundle_pda(program_id, bundle_account, &bundle)?;
+    
+    assert_signer(authority)?;
+    if bundle.authority != *authority.key {
+        return Err(BundleError::Unauthorized.into());
+    }
+    
+    // Once execution has started the records are the bundle's history
+    if bundle.status != BundleStatus::Created {
+        msg!("Bundle {} is {:?}, its instructions can no longer be removed", bundle.bundle_id, bundle.status);
+        return Err(BundleError::InvalidStateTransition.into());
+    }
+    
+    let record = BundleInstructionRecord::unpack(&record_account.data.borrow())?;
+    if record.bundle != *bundle_account.key {
+        return Err(BundleError::InstructionBundleMismatch.into());
+    }
+    
+    let position = bundle.wallet_indexes.iter()
+        .position(|&index| index == record.wallet_index)
+        .ok_or(BundleError::WalletNotInBundle)?;
+    bundle.instructions_added[position] = bundle.instructions_added[position]
+        .checked_sub(1)
+        .ok_or(BundleError::InconsistentBundleState)?;
+    
+    // Undo what add_instructions accumulated, measured on the raw data
+    let instruction_data = if record.compressed {
+        compression::decompress(&record.instruction_data)?
+    } else {
+        record.instruction_data
+    };
+    let lamport_delta = transfer_lamport_delta(
+        &record.program_id,
+        &instruction_data,
+        &record.accounts,
+        &bundle.authority,
+    );
+    bundle.expected_net_lamports = bundle.expected_net_lamports.saturating_sub(lamport_delta);
+    
+    close_instruction_account(record_account, authority, &bundle)?;
+    
+    assert_bundle_consistent(&bundle)?;
+    bundle.serialize(&mut &mut bundle_account.data.borrow_mut()[..])?;
+    
+    msg!("Instruction removed for wallet {} in bundle {}, {} of {} added",
+        record.wallet_index, bundle.bundle_id,
+        bundle.instructions_added[position], bundle.instructions_per_wallet[position]);
+    
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_execute_bundle(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_compute_units: u32,
+    recent_fee_micro_lamports: u64,
+    dry_run: bool,
+    jito_tip_lamports: u64,
+    start_index: u8,
+    count: u8,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let bundle_account = next_account_info(account_info_iter)?;
+    let _recent_blockhashes = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let execution_log = next_account_info(account_info_iter)?;
+    let remaining = account_info_iter.as_slice();
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    assert_owned_by(bundle_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    if bundle_manager.is_paused {
+        return Err(BundleError::ManagerPaused.into());
+    }
+    
+    let mut bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+    if bundle.manager != *bundle_manager_account.key {
+        return Err(BundleError::ManagerMismatch.into());
+    }
+    verify_bundle_pda(program_id, bundle_account, &bundle)?;
+    
+    assert_signer(authority)?;
+    let executes_for_authority = bundle.authority == bundle_manager.authority
+        && acts_for_authority(&bundle_manager, authority.key, |permissions| permissions.can_execute);
+    if bundle.authority != *authority.key && !executes_for_authority {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_approvals(&bundle_manager, accounts)?;
+    
+    let clock = Clock::get()?;
+    if !bundle.status.can_transition_to(BundleStatus::Executing) {
+        msg!("Bundle {} is {:?} and cannot be executed", bundle.bundle_id, bundle.status);
+        return Err(BundleError::InvalidStateTransition.into());
+    }
+    if bundle.status == BundleStatus::Failed {
+        check_retry_cooldown(&bundle, &bundle_manager, clock.slot)?;
+        check_retry_limit(&bundle)?;
+        bundle.retry_count = bundle.retry_count.saturating_add(1);
+        // A retried bundle is active again until this attempt finishes
+        bundle_manager.active_bundles = checked_add(bundle_manager.active_bundles, 1)?;
+    }
+    
+    // A bundle that has started running is finished even past its expiry
+    let resuming = bundle.last_executed_index.is_some();
+    if !resuming && bundle.is_expired(clock.unix_timestamp) {
+        return Err(BundleError::BundleExpired.into());
+    }
+    if bundle.execute_after > clock.unix_timestamp {
+        msg!("Bundle {} is scheduled for {}, now is {}",
+            bundle.bundle_id, bundle.execute_after, clock.unix_timestamp);
+        return Err(BundleError::BundleNotYetExecutable.into());
+    }
+    
+    if max_compute_units == 0 || max_compute_units > MAX_COMPUTE_UNIT_LIMIT {
+        msg!("max_compute_units {} is outside 1..={}", max_compute_units, MAX_COMPUTE_UNIT_LIMIT);
+        return Err(BundleError::InvalidComputeBudget.into());
+    }
+    
+    check_bundle_complete(&bundle)?;
+    let instruction_count = bundle.total_instructions() as usize;
+    if remaining.len() < instruction_count {
+        return Err(BundleError::MissingInstructionRecords.into());
+    }
+    let record_accounts = &remaining[..instruction_count];
+    
+    let mut records = Vec::with_capacity(instruction_count);
+    for record_account in record_accounts {
+        assert_owned_by(record_account, program_id)?;
+        let record = BundleInstructionRecord::unpack(&record_account.data.borrow())?;
+        // Records spliced in from another bundle would run under this one's authority
+        if record.bundle != *bundle_account.key {
+            return Err(BundleError::InstructionBundleMismatch.into());
+        }
+        records.push(record);
+    }
+    let record_indexes = record_indexes(program_id, bundle_account, &bundle, record_accounts, &records)?;
+    check_wallet_counts(&bundle, &records)?;
+    let (record_accounts, mut records, record_indexes) =
+        in_wallet_order(&bundle, record_accounts, records, record_indexes);
+    let slice = execution_slice(&bundle, &records, start_index, count)?;
+    let registry = if bundle_manager.wallet_registry {
+        Some(find_wallet_registry(program_id, bundle_manager_account.key, accounts)?)
+    } else {
+        None
+    };
+    for record in &records[slice.clone()] {
+        if !record.executed {
+            let (wallet_address, _) =
+                find_wallet_address(program_id, bundle_manager_account.key, record.wallet_index);
+            check_allowed_program(&bundle_manager, &record.program_id)?;
+            validate_instruction_accounts(record, &wallet_address, accounts)?;
+            if let Some(registry) = &registry {
+                check_registered_wallet(record, &bundle.authority, registry)?;
+            }
+        }
+    }
+    let fee_collector = find_payee(accounts, &bundle_manager.fee_collector, bundle_manager.fee_lamports, "Fee collector")?;
+    let tip_account = find_payee(accounts, &bundle_manager.tip_account, jito_tip_lamports, "Tip account")?;
+    
+    if dry_run {
+        // Reported as an error here instead of being committed as Failed
+        prepare_execution(&bundle, &bundle_manager, authority, &records, resuming, jito_tip_lamports)?;
+        let price = compute_unit_price(&bundle_manager.config, recent_fee_micro_lamports)?;
+        msg!("Dry run of bundle {} passed: {} instructions, priority fee {} lamports at {} micro-lamports per CU, protocol fee {} lamports, tip {} lamports",
+            bundle.bundle_id, records.len(), priority_fee_lamports(price, max_compute_units), price,
+            bundle_manager.fee_lamports, jito_tip_lamports);
+        return Ok(());
+    }
+    
+    let ctx = ExecuteContext {
+        program_id,
+        bundle_manager_account,
+        bundle_account,
+        authority,
+        system_program,
+        execution_log: (execution_log.key != program_id).then_some(execution_log),
+        accounts,
+    };
+    let compute_at_start = sol_remaining_compute_units();
+    
+    // Written before any CPI so a nested ExecuteBundle on this bundle sees Executing
+    bundle.transition_to(BundleStatus::Executing)?;
+    bundle.last_attempt_slot = clock.slot;
+    if !bundle_manager.config.ephemeral_results {
+        let price = compute_unit_price(&bundle_manager.config, recent_fee_micro_lamports)?;
+        if !resuming {
+            bundle.execution_started_at = clock.unix_timestamp;
+        }
+        bundle.priority_fee = priority_fee_lamports(price, max_compute_units);
+        bundle.compute_unit_limit = max_compute_units;
+    }
+    bundle.serialize(&mut &mut bundle_account.data.borrow_mut()[..])?;
+    
+    let instructions = match prepare_execution(&bundle, &bundle_manager, authority, &records, resuming, jito_tip_lamports) {
+        Ok(instructions) => instructions,
+        Err(err) => {
+            msg!("Bundle {} cannot run: {:?}", bundle.bundle_id, err);
+            record_execution_totals(&mut bundle_manager, 0, compute_at_start);
+            let outcome = ExecutionOutcome::Failed { error: err };
+            return finish_execution(&ctx, &mut bundle_manager, &mut bundle, &clock, outcome, compute_at_start);
+        },
+    };
+    
+    if let (Some(funding), false) = (&bundle.funding, resuming) {
+        fund_wallets(program_id, bundle_manager_account, authority, system_program, accounts, funding)?;
+    }
+    
+    // Measured after funding, which is not the bundle spending
+    let wallets: Vec<Pubkey> = bundle.wallet_indexes.iter()
+        .filter_map(|&wallet_index| {
+            wallet_address(program_id, bundle_manager_account.key, registry.as_ref(), wallet_index).ok()
+        })
+        .collect();
+    let holdings_before = bundle.spend_limits.as_ref()
+        .map(|limits| wallet_holdings(&wallets, limits, accounts));
+    let check_spending = |bundle: &Bundle| match (&bundle.spend_limits, &holdings_before) {
+        (Some(limits), Some(before)) => {
+            check_spend_limits(bundle, limits, before, &wallet_holdings(&wallets, limits, accounts))
+        },
+        _ => Ok(()),
+    };
+    
+    let mut measured_net_lamports: i64 = 0;
+    let mut executed_now = 0;
+    let pending = record_accounts.iter().zip(records.iter_mut()).zip(&instructions).enumerate();
+    for (index, ((record_account, record), instruction)) in pending {
+        if record.executed || !slice.contains(&index) {
+            continue;
+        }
+        if sol_remaining_compute_units() < EXECUTION_COMPUTE_RESERVE {
+            break;
+        }
+        
+        let created = match &record.create_if_missing {
+            Some(spec) => create_missing_account(spec, authority, system_program, accounts),
+            None => Ok(()),
+        };
+    
+        // Sign for the record's wallet PDA when the instruction expects it to
+        let (wallet_address, wallet_bump) =
+            find_wallet_address(program_id, bundle_manager_account.key, record.wallet_index);
+        let wallet_index_seed = [record.wallet_index];
+        let wallet_bump_seed = [wallet_bump];
+        let wallet_seeds: &[&[u8]] = &[
+            WALLET_SEED,
+            bundle_manager_account.key.as_ref(),
+            &wallet_index_seed,
+            &wallet_bump_seed,
+        ];
+        let wallet_signs = instruction.accounts.iter()
+            .any(|meta| meta.is_signer && meta.pubkey == wallet_address);
+        let signers_seeds: &[&[&[u8]]] = if wallet_signs { &[wallet_seeds] } else { &[] };
+    
+        let lamports_before = authority.lamports();
+        // A callee that returns an error aborts the whole transaction, so it
+        // cannot be retried here; the client resends ExecuteBundle instead
+        let result = created.and_then(|()| invoke_signed(instruction, accounts, signers_seeds));
+        if let Err(err) = result {
+            // Nothing but this call's funding would be kept, and a retry funds again
+            if bundle.funding.is_some() && !resuming && executed_now == 0 {
+                return Err(err);
+            }
+            // What ran before the failure is kept, so it is held to the limits too
+            check_spending(&bundle)?;
+            msg!("Instruction {} of bundle {} failed: {:?}", record_indexes[index], bundle.bundle_id, err);
+            record.last_error = Some(error_code(&err));
+            record.serialize(&mut &mut record_account.data.borrow_mut()[..])?;
+            record_execution_totals(&mut bundle_manager, executed_now, compute_at_start);
+            let outcome = ExecutionOutcome::Failed { error: err };
+            return finish_execution(&ctx, &mut bundle_manager, &mut bundle, &clock, outcome, compute_at_start);
+        }
+        measured_net_lamports = measured_net_lamports
+            .saturating_add(authority.lamports() as i64 - lamports_before as i64);
+    
+        record.executed = true;
+        record.serialize(&mut &mut record_account.data.borrow_mut()[..])?;
+        bundle.mark_executed(record_indexes[index]);
+        executed_now += 1;
+    }
+    
+    check_spending(&bundle)?;
+    
+    let left = records.iter().filter(|record| !record.executed).count();
+    if left > 0 && executed_now == 0 {
+        msg!("Bundle {} needs more than {} compute units to run its next instruction",
+            bundle.bundle_id, sol_remaining_compute_units());
+        return Err(BundleError::InvalidComputeBudget.into());
+    }
+    
+    if left == 0 {
+        // Earlier calls of a resumed bundle are not part of the measurement
+        if !resuming && measured_net_lamports.abs_diff(bundle.expected_net_lamports) > LAMPORT_DELTA_TOLERANCE {
+            msg!("Bundle {} lamport discrepancy: expected {} net, measured {}",
+                bundle.bundle_id, bundle.expected_net_lamports, measured_net_lamports);
+        }
+    
+        // Charged after the bundle's own instructions, so failed attempts pay no fee
+        if let Some(fee_collector) = fee_collector {
+            invoke(
+                &system_instruction::transfer(authority.key, fee_collector.key, bundle_manager.fee_lamports),
+                &[authority.clone(), fee_collector.clone(), system_program.clone()],
+            )?;
+        }
+    }
+    
+    // Jito expects the tip last in the transaction
+    if let Some(tip_account) = tip_account {
+        invoke(
+            &system_instruction::transfer(authority.key, tip_account.key, jito_tip_lamports),
+            &[authority.clone(), tip_account.clone(), system_program.clone()],
+        )?;
+    }
+    
+    record_execution_totals(&mut bundle_manager, executed_now, compute_at_start);
+    if left > 0 {
+        // Still counted in active_bundles; the manager is written for a retry's increment
+        bundle.transition_to(BundleStatus::PartiallyExecuted)?;
+        assert_bundle_consistent(&bundle)?;
+        bundle.serialize(&mut &mut bundle_account.data.borrow_mut()[..])?;
+        bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+        msg!("Bundle {} ran {} instructions and stopped with {} left; ExecuteBundle resumes it",
+            bundle.bundle_id, executed_now, left);
+        return Ok(());
+    }
+    
+    let outcome = ExecutionOutcome::Succeeded { measured_net_lamports, jito_tip: jito_tip_lamports };
+    finish_execution(&ctx, &mut bundle_manager, &mut bundle, &clock, outcome, compute_at_start)
+}
+
+// ExecuteBundle restricted to Failed bundles, for bots that must not start a
+// bundle that has not run yet when they mean to retry one
+fn process_retry_bundle(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_compute_units: u32,
+    recent_fee_micro_lamports: u64,
+    jito_tip_lamports: u64,
+) -> ProgramResult {
+    let bundle_account = accounts.get(1).ok_or(ProgramError::NotEnoughAccountKeys)?;
+    assert_owned_by(bundle_account, program_id)?;
+    let bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+    if bundle.status != BundleStatus::Failed {
+        msg!("Bundle {} is {:?}, only Failed bundles are retried", bundle.bundle_id, bundle.status);
+        return Err(BundleError::InvalidStateTransition.into());
+    }
+    
+    process_execute_bundle(
+        program_id,
+        accounts,
+        max_compute_units,
+        recent_fee_micro_lamports,
+        false,
+        jito_tip_lamports,
+        0,
+        0,
+    )
+}
+
+// Runs each bundle through process_execute_bundle on an account list of its
+// own. ExecuteBundle keeps a Failed or PartiallyExecuted outcome by
+// succeeding, so the batch checks every status afterwards and fails the
+// instruction instead, which discards every write of the transaction.
+fn process_execute_bundle_batch(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    bundle_count: u8,
+    max_compute_units: u32,
+    recent_fee_micro_lamports: u64,
+    jito_tip_lamports: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let execution_log = next_account_info(account_info_iter)?;
+    let remaining = account_info_iter.as_slice();
+    
+    if bundle_count == 0 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let bundle_count = bundle_count as usize;
+    if remaining.len() < bundle_count {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let (bundle_accounts, remaining) = remaining.split_at(bundle_count);
+    
+    let mut instruction_counts = Vec::with_capacity(bundle_count);
+    for bundle_account in bundle_accounts {
+        assert_owned_by(bundle_account, program_id)?;
+        let bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+        instruction_counts.push(bundle.total_instructions() as usize);
+    }
+    let record_count = instruction_counts.iter().sum::<usize>();
+    if remaining.len() < record_count {
+        return Err(BundleError::MissingInstructionRecords.into());
+    }
+    let (record_accounts, shared) = remaining.split_at(record_count);
+    
+    let mut records_start = 0;
+    for (index, (bundle_account, instruction_count)) in bundle_accounts.iter().zip(instruction_counts).enumerate() {
+        let records = &record_accounts[records_start..records_start + instruction_count];
+        records_start += instruction_count;
+        
+        // The system program fills the unused recent blockhashes slot
+        let mut bundle_accounts = vec![
+            bundle_manager_account.clone(),
+            bundle_account.clone(),
+            system_program.clone(),
+            authority.clone(),
+            system_program.clone(),
+            execution_log.clone(),
+        ];
+        bundle_accounts.extend_from_slice(records);
+        bundle_accounts.extend_from_slice(shared);
+        
+        // Jito expects the tip last, so only the final bundle pays it
+        let tip = if index + 1 == bundle_count { jito_tip_lamports } else { 0 };
+        process_execute_bundle(program_id, &bundle_accounts, max_compute_units, recent_fee_micro_lamports, false, tip, 0, 0)?;
+        
+        let bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+        if bundle.status != BundleStatus::Executed {
+            msg!("Bundle {} ended {:?}, aborting the batch", bundle.bundle_id, bundle.status);
+            return Err(BundleError::BatchBundleNotExecuted.into());
+        }
+    }
+    
+    Ok(())
+}
+
+fn process_set_manager_status(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    is_paused: bool,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    let permitted = if is_paused {
+        acts_for_authority(&bundle_manager, authority.key, |permissions| permissions.can_pause)
+    } else {
+        bundle_manager.authority == *authority.key
+    };
+    if !permitted {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Re-applying the current status is a no-op: return before any write so
+    // repeated pause calls leave every other manager field untouched
+    if bundle_manager.is_paused == is_paused {
+        msg!("SetManagerStatus: NoChange, manager is already {}",
+            if is_paused { "paused" } else { "active" });
+        return Ok(());
+    }
+    
+    bundle_manager.is_paused = is_paused;
+    if !is_paused {
+        // The operator has dealt with whatever tripped the breaker; start counting afresh
+        bundle_manager.consecutive_failures = 0;
+    }
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Manager {} {}", bundle_manager_account.key, if is_paused { "paused" } else { "unpaused" });
+    
+    BundleEvent::ManagerStatusChanged(ManagerStatusChangedEvent {
+        manager: *bundle_manager_account.key,
+        authority: bundle_manager.authority,
+        is_paused,
+        automatic: false,
+    })
+    .emit()?;
+    Ok(())
+}
+
+fn process_set_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_authority: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // Proposing the current authority withdraws a pending proposal
+    bundle_manager.pending_authority = (new_authority != bundle_manager.authority).then_some(new_authority);
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    match bundle_manager.pending_authority {
+        Some(pending) => msg!("Manager {} authority handoff to {} proposed", bundle_manager_account.key, pending),
+        None => msg!("Manager {} authority handoff cancelled", bundle_manager_account.key),
+    }
+    
+    BundleEvent::ManagerAuthorityProposed(ManagerAuthorityProposedEvent {
+        manager: *bundle_manager_account.key,
+        authority: bundle_manager.authority,
+        pending_authority: bundle_manager.pending_authority,
+    })
+    .emit()?;
+    Ok(())
+}
+
+fn process_accept_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let new_authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    let pending = bundle_manager.pending_authority.ok_or(BundleError::NoPendingAuthority)?;
+    assert_signer(new_authority)?;
+    if pending != *new_authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let previous_authority = bundle_manager.authority;
+    bundle_manager.authority = pending;
+    bundle_manager.pending_authority = None;
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Manager {} authority changed from {} to {}",
+        bundle_manager_account.key, previous_authority, bundle_manager.authority);
+    
+    BundleEvent::ManagerAuthorityChanged(ManagerAuthorityChangedEvent {
+        manager: *bundle_manager_account.key,
+        previous_authority,
+        authority: bundle_manager.authority,
+    })
+    .emit()?;
+    Ok(())
+}
+
+fn process_set_fee(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    fee_lamports: u64,
+    fee_collector: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    bundle_manager.fee_lamports = fee_lamports;
+    bundle_manager.fee_collector = fee_collector;
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Manager {} protocol fee set to {} lamports, collected by {}",
+        bundle_manager_account.key, fee_lamports, fee_collector);
+    Ok(())
+}
+
+fn process_set_tip_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    tip_account: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    bundle_manager.tip_account = tip_account;
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Manager {} tips go to {}", bundle_manager_account.key, tip_account);
+    Ok(())
+}
+
+fn process_update_manager_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mut config: ManagerConfig,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    normalize_config(&mut config)?;
+    bundle_manager.config = config;
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Manager {} config updated: priority fee multiplier {}, compute unit price cap {}",
+        bundle_manager_account.key, bundle_manager.config.priority_fee_multiplier,
+        bundle_manager.config.max_compute_unit_price);
+    Ok(())
+}
+
+fn process_link_lookup_table(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    lookup_table: Option<Pubkey>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if let Some(lookup_table) = lookup_table {
+        let lookup_table_account = next_account_info(account_info_iter)?;
+        if *lookup_table_account.key != lookup_table
+            || *lookup_table_account.owner != address_lookup_table::program::id()
+            || AddressLookupTable::deserialize(&lookup_table_account.data.borrow()).is_err()
+        {
+            msg!("{} is not an address lookup table", lookup_table_account.key);
+            return Err(BundleError::InvalidLookupTable.into());
+        }
+    }
+    
+    bundle_manager.lookup_table = lookup_table;
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Manager {} lookup table set to {:?}", bundle_manager_account.key, lookup_table);
+    Ok(())
+}
+
+fn process_add_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate: Pubkey,
+    permissions: DelegatePermissions,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    match bundle_manager.delegates.iter_mut().find(|existing| existing.key == delegate) {
+        Some(existing) => existing.permissions = permissions,
+        None => {
+            if bundle_manager.delegates.len() >= MAX_DELEGATES {
+                msg!("Manager already holds {} delegates", MAX_DELEGATES);
+                return Err(BundleError::TooManyDelegates.into());
+            }
+            bundle_manager.delegates.push(Delegate { key: delegate, permissions });
+        },
+    }
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Manager {} delegate {} may create: {}, execute: {}, pause: {}",
+        bundle_manager_account.key, delegate, permissions.can_create, permissions.can_execute, permissions.can_pause);
+    Ok(())
+}
+
+fn process_remove_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    delegate: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let Some(position) = bundle_manager.delegates.iter().position(|existing| existing.key == delegate) else {
+        msg!("{} is not a delegate of manager {}", delegate, bundle_manager_account.key);
+        return Err(BundleError::DelegateNotFound.into());
+    };
+    bundle_manager.delegates.remove(position);
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Manager {} delegate {} removed", bundle_manager_account.key, delegate);
+    Ok(())
+}
+
+fn process_add_allowed_program(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    allowed_program: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if !bundle_manager.allowed_programs.contains(&allowed_program) {
+        if bundle_manager.allowed_programs.len() >= MAX_ALLOWED_PROGRAMS {
+            msg!("Manager already allows {} programs", MAX_ALLOWED_PROGRAMS);
+            return Err(BundleError::TooManyAllowedPrograms.into());
+        }
+        bundle_manager.allowed_programs.push(allowed_program);
+    }
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Manager {} allows program {}", bundle_manager_account.key, allowed_program);
+    Ok(())
+}
+
+fn process_remove_allowed_program(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    allowed_program: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let Some(position) = bundle_manager.allowed_programs.iter().position(|existing| *existing == allowed_program) else {
+        msg!("{} is not on the allowlist of manager {}", allowed_program, bundle_manager_account.key);
+        return Err(BundleError::AllowedProgramNotFound.into());
+    };
+    bundle_manager.allowed_programs.remove(position);
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Manager {} no longer allows program {}", bundle_manager_account.key, allowed_program);
+    Ok(())
+}
+
+fn process_transfer_tokens_bundled(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    decimals: u8,
+    transfers: Vec<TokenTransfer>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let mint = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let ata_program = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    
+    if transfers.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    if bundle_manager.is_paused {
+        return Err(BundleError::ManagerPaused.into());
+    }
+    
+    // Moves funds like ExecuteBundle, so it takes the same approvals
+    assert_signer(authority)?;
+    if !acts_for_authority(&bundle_manager, authority.key, |permissions| permissions.can_execute) {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_approvals(&bundle_manager, accounts)?;
+    
+    assert_token_program(token_program.key, mint)?;
+    if *ata_program.key != ASSOCIATED_TOKEN_PROGRAM_ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    
+    let registry = if bundle_manager.wallet_registry {
+        Some(find_wallet_registry(program_id, bundle_manager_account.key, accounts)?)
+    } else {
+        None
+    };
+    let find = |key: &Pubkey| accounts.iter()
+        .find(|a| a.key == key)
+        .ok_or(ProgramError::NotEnoughAccountKeys);
+    
+    for transfer in &transfers {
+        let manager_key = bundle_manager_account.key;
+        let source = wallet_address(program_id, manager_key, registry.as_ref(), transfer.source_wallet)?;
+        let destination = wallet_address(program_id, manager_key, registry.as_ref(), transfer.destination_wallet)?;
+        
+        let destination_account = CreateIfMissing::AssociatedTokenAccount {
+            wallet: destination,
+            mint: *mint.key,
+            token_program: *token_program.key,
+        };
+        create_missing_account(&destination_account, authority, system_program, accounts)?;
+        
+        let source_token = find_associated_token_address(&source, mint.key, token_program.key);
+        let destination_token = destination_account.address();
+        let instruction = token_transfer_checked(
+            token_program.key,
+            &source_token,
+            mint.key,
+            &destination_token,
+            &source,
+            transfer.amount,
+            decimals,
+        );
+        // A keypair source signs the transaction itself and leaves the
+        // wallet PDA's seeds unused
+        let (_, bump) = find_wallet_address(program_id, manager_key, transfer.source_wallet);
+        invoke_signed(
+            &instruction,
+            &[
+                find(&source_token)?.clone(),
+                mint.clone(),
+                find(&destination_token)?.clone(),
+                find(&source)?.clone(),
+                token_program.clone(),
+            ],
+            &[&[WALLET_SEED, manager_key.as_ref(), &[transfer.source_wallet], &[bump]]],
+        )?;
+    }
+    
+    msg!("Manager {} ran {} transfers of mint {}", bundle_manager_account.key, transfers.len(), mint.key);
+    Ok(())
+}
+
+fn process_deposit(program_id: &Pubkey, accounts: &[AccountInfo], lamports: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let depositor = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    
+    if lamports == 0 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(depositor)?;
+    
+    let (vault_address, bump) = find_vault_address(program_id, bundle_manager_account.key);
+    if vault_address != *vault_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let mut vault = if vault_account.owner == program_id {
+        Vault::unpack(&vault_account.data.borrow())?
+    } else {
+        let rent = Rent::get()?;
+        let rent_lamports = rent.minimum_balance(Vault::LEN);
+        check_rent_funds(depositor, checked_add(rent_lamports, lamports)?)?;
+        
+        invoke_signed(
+            &system_instruction::create_account(
+                depositor.key,
+                vault_account.key,
+                rent_lamports,
+                Vault::LEN as u64,
+                program_id,
+            ),
+            &[
+                depositor.clone(),
+                vault_account.clone(),
+                system_program.clone(),
+            ],
+            &[&[VAULT_SEED, bundle_manager_account.key.as_ref(), &[bump]]],
+        )?;
+        assert_rent_exempt(vault_account, &rent)?;
+        
+        Vault {
+            account_type: AccountType::Vault,
+            version: Vault::VERSION,
+            manager: *bundle_manager_account.key,
+            bump,
+            total_deposited: 0,
+            total_withdrawn: 0,
+        }
+    };
+    
+    invoke(
+        &system_instruction::transfer(depositor.key, vault_account.key, lamports),
+        &[
+            depositor.clone(),
+            vault_account.clone(),
+            system_program.clone(),
+        ],
+    )?;
+    
+    vault.total_deposited = checked_add(vault.total_deposited, lamports)?;
+    vault.serialize(&mut &mut vault_account.data.borrow_mut()[..])?;
+    
+    msg!("Deposited {} lamports into the vault of manager {}", lamports, bundle_manager_account.key);
+    Ok(())
+}
+
+fn process_distribute_equally(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    wallet_indexes: &[u8],
+    total_amount: u64,
+    asset: &VaultAsset,
+) -> ProgramResult {
+    let share = total_amount.checked_div(wallet_indexes.len() as u64).unwrap_or(0);
+    if share == 0 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    
+    let payouts: Vec<(u8, u64)> = wallet_indexes.iter().map(|&wallet_index| (wallet_index, share)).collect();
+    process_vault_payout(program_id, accounts, &payouts, asset)
+}
+
+// WithdrawToWallet and DistributeEqually: pays each (wallet_index, amount)
+// out of the manager's vault, signing as the vault PDA
+fn process_vault_payout(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    payouts: &[(u8, u64)],
+    asset: &VaultAsset,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    
+    if payouts.iter().any(|&(_, amount)| amount == 0) {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    if bundle_manager.is_paused {
+        return Err(BundleError::ManagerPaused.into());
+    }
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_approvals(&bundle_manager, accounts)?;
+    
+    assert_owned_by(vault_account, program_id)?;
+    let mut vault = Vault::unpack(&vault_account.data.borrow())?;
+    let (vault_address, bump) = find_vault_address(program_id, bundle_manager_account.key);
+    if vault_address != *vault_account.key || vault.manager != *bundle_manager_account.key {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    
+    let registry = if bundle_manager.wallet_registry {
+        Some(find_wallet_registry(program_id, bundle_manager_account.key, accounts)?)
+    } else {
+        None
+    };
+    let find = |key: &Pubkey| accounts.iter()
+        .find(|a| a.key == key)
+        .ok_or(ProgramError::NotEnoughAccountKeys);
+    let manager_key = bundle_manager_account.key;
+    
+    let total = payouts.iter().try_fold(0u64, |total, &(_, amount)| checked_add(total, amount))?;
+    match *asset {
+        VaultAsset::Sol => {
+            let available = vault_account.lamports()
+                .saturating_sub(Rent::get()?.minimum_balance(vault_account.data_len()));
+            if total > available {
+                msg!("Vault holds {} lamports above rent, {} requested", available, total);
+                return Err(BundleError::InsufficientVaultFunds.into());
+            }
+            
+            for &(wallet_index, amount) in payouts {
+                let wallet = wallet_address(program_id, manager_key, registry.as_ref(), wallet_index)?;
+                safe_transfer(vault_account, find(&wallet)?, amount)?;
+            }
+            
+            vault.total_withdrawn = checked_add(vault.total_withdrawn, total)?;
+            vault.serialize(&mut &mut vault_account.data.borrow_mut()[..])?;
+        },
+        VaultAsset::Token { mint, token_program, decimals } => {
+            let mint_account = find(&mint)?;
+            assert_token_program(&token_program, mint_account)?;
+            let token_program_account = find(&token_program)?;
+            find(&ASSOCIATED_TOKEN_PROGRAM_ID)?;
+            
+            let vault_token = find_associated_token_address(vault_account.key, &mint, &token_program);
+            for &(wallet_index, amount) in payouts {
+                let wallet = wallet_address(program_id, manager_key, registry.as_ref(), wallet_index)?;
+                let wallet_token = CreateIfMissing::AssociatedTokenAccount { wallet, mint, token_program };
+                create_missing_account(&wallet_token, authority, system_program, accounts)?;
+                
+                let destination = wallet_token.address();
+                let instruction = token_transfer_checked(
+                    &token_program,
+                    &vault_token,
+                    &mint,
+                    &destination,
+                    vault_account.key,
+                    amount,
+                    decimals,
+                );
+                invoke_signed(
+                    &instruction,
+                    &[
+                        find(&vault_token)?.clone(),
+                        mint_account.clone(),
+                        find(&destination)?.clone(),
+                        vault_account.clone(),
+                        token_program_account.clone(),
+                    ],
+                    &[&[VAULT_SEED, manager_key.as_ref(), &[bump]]],
+                )?;
+            }
+        },
+    }
+    
+    msg!("Manager {} paid {} out of its vault to {} wallets", manager_key, total, payouts.len());
+    Ok(())
+}
+
+fn process_sweep_wallets(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    wallet_indexes: &[u8],
+    mints: &[Pubkey],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let treasury = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    
+    if wallet_indexes.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_approvals(&bundle_manager, accounts)?;
+    
+    let registry = if bundle_manager.wallet_registry {
+        Some(find_wallet_registry(program_id, bundle_manager_account.key, accounts)?)
+    } else {
+        None
+    };
+    let find = |key: &Pubkey| accounts.iter()
+        .find(|a| a.key == key)
+        .ok_or(ProgramError::NotEnoughAccountKeys);
+    let manager_key = bundle_manager_account.key;
+    let rent = Rent::get()?;
+    
+    let mut swept_lamports = 0u64;
+    for &wallet_index in wallet_indexes {
+        let wallet = find(&wallet_address(program_id, manager_key, registry.as_ref(), wallet_index)?)?;
+        if wallet.key == treasury.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let (_, bump) = find_wallet_address(program_id, manager_key, wallet_index);
+        let wallet_seeds: &[&[u8]] = &[WALLET_SEED, manager_key.as_ref(), &[wallet_index], &[bump]];
+        
+        for mint in mints {
+            let mint_account = find(mint)?;
+            let token_program = *mint_account.owner;
+            if !is_token_program(&token_program) {
+                msg!("Mint {} is not owned by a token program", mint);
+                return Err(BundleError::InvalidTokenProgram.into());
+            }
+            let wallet_token = find(&find_associated_token_address(wallet.key, mint, &token_program))?;
+            if wallet_token.owner != &token_program {
+                continue;
+            }
+            let amount = token_account_amount(&wallet_token.data.borrow()).ok_or(ProgramError::InvalidAccountData)?;
+            if amount == 0 {
+                continue;
+            }
+            let decimals = mint_decimals(&mint_account.data.borrow()).ok_or(ProgramError::InvalidAccountData)?;
+            
+            let treasury_token = CreateIfMissing::AssociatedTokenAccount {
+                wallet: *treasury.key,
+                mint: *mint,
+                token_program,
+            };
+            create_missing_account(&treasury_token, authority, system_program, accounts)?;
+            let destination = treasury_token.address();
+            invoke_signed(
+                &token_transfer_checked(
+                    &token_program,
+                    wallet_token.key,
+                    mint,
+                    &destination,
+                    wallet.key,
+                    amount,
+                    decimals,
+                ),
+                &[
+                    wallet_token.clone(),
+                    mint_account.clone(),
+                    find(&destination)?.clone(),
+                    wallet.clone(),
+                    find(&token_program)?.clone(),
+                ],
+                &[wallet_seeds],
+            )?;
+        }
+        
+        let lamports = wallet.lamports().saturating_sub(rent.minimum_balance(wallet.data_len()));
+        if lamports > 0 {
+            invoke_signed(
+                &system_instruction::transfer(wallet.key, treasury.key, lamports),
+                &[
+                    wallet.clone(),
+                    treasury.clone(),
+                    system_program.clone(),
+                ],
+                &[wallet_seeds],
+            )?;
+            swept_lamports = checked_add(swept_lamports, lamports)?;
+        }
+    }
+    
+    msg!("Manager {} swept {} wallets into {}, {} lamports",
+        manager_key, wallet_indexes.len(), treasury.key, swept_lamports);
+    Ok(())
+}
+
+fn process_reset_stats(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let bundle_accounts = account_info_iter.as_slice();
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let mut active_bundles: u16 = 0;
+    for (i, bundle_account) in bundle_accounts.iter().enumerate() {
+        assert_owned_by(bundle_account, program_id)?;
+        // Counting a bundle twice would inflate the gauge all over again
+        if bundle_accounts[..i].iter().any(|other| other.key == bundle_account.key) {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+        if bundle.manager != *bundle_manager_account.key {
+            return Err(BundleError::ManagerMismatch.into());
+        }
+        verify_bundle_pda(program_id, bundle_account, &bundle)?;
+        if bundle.status.is_active() {
+            active_bundles = checked_add(active_bundles, 1)?;
+        }
+    }
+    
+    let event = ManagerStatsResetEvent {
+        manager: *bundle_manager_account.key,
+        authority: bundle_manager.authority,
+        previous_active_bundles: bundle_manager.active_bundles,
+        previous_total_bundles_executed: bundle_manager.total_bundles_executed,
+        previous_total_bundles_failed: bundle_manager.total_bundles_failed,
+        active_bundles,
+    };
+    bundle_manager.active_bundles = active_bundles;
+    bundle_manager.total_bundles_executed = 0;
+    bundle_manager.total_bundles_failed = 0;
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Manager {} stats reset: {} active bundles, was {}; executed and failed totals were {} and {}",
+        bundle_manager_account.key, active_bundles, event.previous_active_bundles,
+        event.previous_total_bundles_executed, event.previous_total_bundles_failed);
+    
+    BundleEvent::ManagerStatsReset(event).emit()?;
+    Ok(())
+}
+
+fn process_fail_bundle(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let bundle_account = next_account_info(account_info_iter)?;
+    let caller = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    assert_owned_by(bundle_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    let mut bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+    if bundle.manager != *bundle_manager_account.key {
+        return Err(BundleError::ManagerMismatch.into());
+    }
+    verify_bundle_pda(program_id, bundle_account, &bundle)?;
+    
+    if !matches!(bundle.status, BundleStatus::Executing | BundleStatus::PartiallyExecuted) {
+        msg!("Bundle {} is {:?}, not mid-execution", bundle.bundle_id, bundle.status);
+        return Err(BundleError::InvalidStateTransition.into());
+    }
+    
+    assert_signer(caller)?;
+    let clock = Clock::get()?;
+    if bundle.authority != *caller.key
+        && !bundle_manager.config.is_execution_timed_out(&bundle, clock.unix_timestamp)
+    {
+        return Err(BundleError::ExecutionNotTimedOut.into());
+    }
+    
+    // Like expiry, the breaker is left alone: the attempt never reported a failure
+    bundle.transition_to(BundleStatus::Failed)?;
+    bundle.execution_completed_at = clock.unix_timestamp;
+    bundle_manager.active_bundles = bundle_manager.active_bundles.saturating_sub(1);
+    bundle_manager.total_bundles_failed = checked_add(bundle_manager.total_bundles_failed, 1)?;
+    
+    assert_bundle_consistent(&bundle)?;
+    bundle.serialize(&mut &mut bundle_account.data.borrow_mut()[..])?;
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Bundle {} failed by {} after being left mid-execution", bundle.bundle_id, caller.key);
+    
+    BundleEvent::BundleFailed(BundleFailedEvent {
+        manager: bundle.manager,
+        bundle_id: bundle.bundle_id,
+        authority: bundle.authority,
+        wallet_count: bundle.wallet_count,
+        execution_started_at: bundle.execution_started_at,
+        execution_completed_at: bundle.execution_completed_at,
+        error: u64::from(ProgramError::from(BundleError::ExecutionAbandoned)),
+        compute_units: 0,
+    })
+    .emit()?;
+    
+    invoke_failure_hook(&bundle_manager, &bundle, bundle_account, accounts);
+    
+    Ok(())
+}
+
+fn process_cancel_bundle(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let bundle_account = next_account_info(account_info_iter)?;
+    let caller = next_account_info(account_info_iter)?;
+    let rent_destination = next_account_info(account_info_iter)?;
+    let record_accounts = account_info_iter.as_slice();
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    assert_owned_by(bundle_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    let bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+    if bundle.manager != *bundle_manager_account.key {
+        return Err(BundleError::ManagerMismatch.into());
+    }
+    verify_bundle_pda(program_id, bundle_account, &bundle)?;
+    
+    assert_signer(caller)?;
+    if bundle.authority != *caller.key && bundle_manager.authority != *caller.key {
+        return Err(BundleError::Unauthorized.into());
+    }
+    
+    // Once execution starts, FailBundle and CloseBundle take over
+    if bundle.status != BundleStatus::Created {
+        msg!("Bundle {} is {:?} and cannot be cancelled", bundle.bundle_id, bundle.status);
+        return Err(BundleError::InvalidStateTransition.into());
+    }
+    
+    if *rent_destination.key != bundle.authority {
+        return Err(BundleError::InvalidRentDestination.into());
+    }
+    
+    if record_accounts.len() != bundle.added_instructions() as usize {
+        return Err(BundleError::MissingInstructionRecords.into());
+    }
+    
+    for record_account in record_accounts {
+        assert_owned_by(record_account, program_id)?;
+        let record = BundleInstructionRecord::unpack(&record_account.data.borrow())?;
+        if record.bundle != *bundle_account.key {
+            return Err(BundleError::InstructionBundleMismatch.into());
+        }
+        
+        close_instruction_account(record_account, rent_destination, &bundle)?;
+    }
+    
+    safe_transfer(bundle_account, rent_destination, bundle_account.lamports())?;
+    bundle_account.data.borrow_mut().fill(0);
+    
+    bundle_manager.active_bundles = bundle_manager.active_bundles.saturating_sub(1);
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Bundle {} cancelled by {} with {} instruction records",
+        bundle.bundle_id, caller.key, record_accounts.len());
+    
+    Ok(())
+}
+
+// Overwrites an older account with `upgraded`, its encoding at the current
+// version, growing the account to fit.
+fn migrate_account<'a>(
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    upgraded: &[u8],
+) -> ProgramResult {
+    grow_account(account, payer, system_program, upgraded.len())?;
+    account.data.borrow_mut()[..upgraded.len()].copy_from_slice(upgraded);
+    Ok(())
+}
+
+fn process_migrate_manager(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let bundle_accounts = account_info_iter.as_slice();
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let (bundle_manager, upgraded_manager) = match migration::upgrade_manager(&bundle_manager_account.data.borrow())? {
+        Some(upgraded) => (upgraded, true),
+        None => (BundleManager::unpack(&bundle_manager_account.data.borrow())?, false),
+    };
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if upgraded_manager {
+        // Sized to LEN whether or not a pending authority is set
+        let mut manager_data = bundle_manager.try_to_vec()?;
+        manager_data.resize(BundleManager::LEN, 0);
+        migrate_account(bundle_manager_account, authority, system_program, &manager_data)?;
+        msg!("Manager {} migrated to version {}", bundle_manager_account.key, BundleManager::VERSION);
+    }
+    
+    let mut migrated_bundles = 0;
+    for bundle_account in bundle_accounts {
+        assert_owned_by(bundle_account, program_id)?;
+        
+        let data = bundle_account.data.borrow().to_vec();
+        let Some(bundle) = migration::upgrade_bundle(&data, bundle_manager_account.key, &bundle_manager.config)? else {
+            continue;
+        };
+        assert_bundle_consistent(&bundle)?;
+        
+        // Sized like a bundle CreateBundle would allocate today
+        let mut upgraded = bundle.try_to_vec()?;
+        upgraded.resize(Bundle::space(bundle.wallet_indexes.len(), &bundle.on_failure), 0);
+        migrate_account(bundle_account, authority, system_program, &upgraded)?;
+        migrated_bundles += 1;
+    }
+    
+    msg!("{} of {} bundles migrated to version {}", migrated_bundles, bundle_accounts.len(), Bundle::VERSION);
+    
+    Ok(())
+}
+
+fn process_migrate_state(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let state_accounts = account_info_iter.as_slice();
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    // The manager's bundles passed along, which records must belong to
+    let bundles: Vec<&Pubkey> = state_accounts.iter()
+        .filter(|account| account.owner == program_id)
+        .filter(|account| {
+            Bundle::unpack(&account.data.borrow()).is_ok_and(|bundle| bundle.manager == *bundle_manager_account.key)
+        })
+        .map(|account| account.key)
+        .collect();
+    let manager_accounts = [
+        find_wallet_registry_address(program_id, bundle_manager_account.key).0,
+        find_vault_address(program_id, bundle_manager_account.key).0,
+        find_execution_log_address(program_id, bundle_manager_account.key).0,
+    ];
+    
+    let mut migrated = 0;
+    for account in state_accounts {
+        if bundles.contains(&account.key) {
+            continue;
+        }
+        assert_owned_by(account, program_id)?;
+        
+        let data = account.data.borrow().to_vec();
+        let owner = if manager_accounts.contains(account.key) {
+            bundle_manager_account.key
+        } else {
+            // Only records are left, naming their bundle right after the type or the version
+            AccountType::InstructionRecord.check(&data)?;
+            let Some(bundle) = bundles.iter().copied()
+                .find(|bundle| [data.get(2..34), data.get(1..33)].contains(&Some(bundle.as_ref())))
+            else {
+                msg!("Record {} needs its bundle passed along", account.key);
+                return Err(BundleError::InstructionBundleMismatch.into());
+            };
+            bundle
+        };
+        let Some(upgraded) = migration::upgrade_unversioned(&data, owner)? else {
+            continue;
+        };
+        
+        migrate_account(account, authority, system_program, &upgraded)?;
+        migrated += 1;
+    }
+    
+    msg!("{} of {} accounts migrated", migrated, state_accounts.len() - bundles.len());
+    
+    Ok(())
+}
+
+fn process_initialize_execution_log(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_entries: u32,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let log_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if max_entries == 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+    
+    let (log_address, bump) = find_execution_log_address(program_id, bundle_manager_account.key);
+    if log_address != *log_account.key {
+        return Err(BundleError::InvalidExecutionLog.into());
+    }
+    
+    if log_account.owner == program_id {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    
+    // Start with just the header; entries are added by append_execution_log
+    let rent = Rent::get()?;
+    let space = ExecutionLog::space(0);
+    let lamports = rent.minimum_balance(space);
+    check_rent_funds(authority, lamports)?;
+    
+    invoke_signed(
+        &system_instruction::create_account(
+            authority.key,
+            log_account.key,
+            lamports,
+            space as u64,
+            program_id,
+        ),
+        &[
+            authority.clone(),
+            log_account.clone(),
+            system_program.clone(),
+        ],
+        &[&[EXECUTION_LOG_SEED, bundle_manager_account.key.as_ref(), &[bump]]],
+    )?;
+    assert_rent_exempt(log_account, &rent)?;
+    
+    let log = ExecutionLog {
+        account_type: AccountType::ExecutionLog,
+        version: ExecutionLog::VERSION,
+        manager: *bundle_manager_account.key,
+        max_entries,
+        next_index: 0,
+        total_appended: 0,
+    };
+    log.serialize(&mut &mut log_account.data.borrow_mut()[..])?;
+    
+    msg!("Execution log created for manager {} with capacity {}",
+        bundle_manager_account.key, max_entries);
+    
+    Ok(())
+}
+
+fn process_register_wallet(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    wallet_index: u8,
+    wallet: Option<Pubkey>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let registry_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let (registry_address, bump) = find_wallet_registry_address(program_id, bundle_manager_account.key);
+    if registry_address != *registry_account.key {
+        return Err(BundleError::InvalidWalletRegistry.into());
+    }
+    
+    let mut registry = if registry_account.owner == program_id {
+        WalletRegistry::unpack(&registry_account.data.borrow())?
+    } else {
+        // Created empty; the entry below grows it like every later one
+        let rent = Rent::get()?;
+        let space = WalletRegistry::space(0);
+        let lamports = rent.minimum_balance(space);
+        check_rent_funds(authority, lamports)?;
+        
+        invoke_signed(
+            &system_instruction::create_account(
+                authority.key,
+                registry_account.key,
+                lamports,
+                space as u64,
+                program_id,
+            ),
+            &[
+                authority.clone(),
+                registry_account.clone(),
+                system_program.clone(),
+            ],
+            &[&[WALLET_REGISTRY_SEED, bundle_manager_account.key.as_ref(), &[bump]]],
+        )?;
+        assert_rent_exempt(registry_account, &rent)?;
+        
+        WalletRegistry {
+            account_type: AccountType::WalletRegistry,
+            version: WalletRegistry::VERSION,
+            manager: *bundle_manager_account.key,
+            bump,
+            wallets: Vec::new(),
+        }
+    };
+    
+    let position = match registry.wallets.binary_search_by_key(&wallet_index, |wallet| wallet.wallet_index) {
+        Ok(_) => {
+            msg!("Wallet {} is already registered for manager {}", wallet_index, bundle_manager_account.key);
+            return Err(BundleError::WalletAlreadyRegistered.into());
+        },
+        Err(position) => position,
+    };
+    let registered = match wallet {
+        Some(address) => RegisteredWallet { wallet_index, address, is_pda: false },
+        None => {
+            let (address, _) = find_wallet_address(program_id, bundle_manager_account.key, wallet_index);
+            RegisteredWallet { wallet_index, address, is_pda: true }
+        },
+    };
+    registry.wallets.insert(position, registered);
+    
+    grow_account(registry_account, authority, system_program, WalletRegistry::space(registry.wallets.len()))?;
+    registry.serialize(&mut &mut registry_account.data.borrow_mut()[..])?;
+    
+    if !bundle_manager.wallet_registry {
+        bundle_manager.wallet_registry = true;
+        bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    }
+    
+    msg!("Wallet {} of manager {} registered as {}{}", wallet_index, bundle_manager_account.key,
+        registered.address, if registered.is_pda { " (PDA)" } else { "" });
+    Ok(())
+}
+
+fn process_remove_wallet(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    wallet_index: u8,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let registry_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    assert_owned_by(registry_account, program_id)?;
+    
+    let bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    assert_signer(authority)?;
+    if bundle_manager.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    let (registry_address, _) = find_wallet_registry_address(program_id, bundle_manager_account.key);
+    if registry_address != *registry_account.key {
+        return Err(BundleError::InvalidWalletRegistry.into());
+    }
+    
+    let mut registry = WalletRegistry::unpack(&registry_account.data.borrow())?;
+    let Ok(position) = registry.wallets.binary_search_by_key(&wallet_index, |wallet| wallet.wallet_index) else {
+        msg!("Wallet {} is not registered for manager {}", wallet_index, bundle_manager_account.key);
+        return Err(BundleError::WalletNotRegistered.into());
+    };
+    registry.wallets.remove(position);
+    
+    // Shrink to the remaining entries and return the rent they no longer need
+    let space = WalletRegistry::space(registry.wallets.len());
+    registry.serialize(&mut &mut registry_account.data.borrow_mut()[..])?;
+    registry_account.realloc(space, false)?;
+    let excess = registry_account.lamports().saturating_sub(Rent::get()?.minimum_balance(space));
+    safe_transfer(registry_account, authority, excess)?;
+    
+    msg!("Wallet {} of manager {} removed", wallet_index, bundle_manager_account.key);
+    Ok(())
+}
+
+fn process_get_managers_stats(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if accounts.len() > MAX_STATS_MANAGERS {
+        return Err(BundleError::TooManyManagers.into());
+    }
+    
+    let mut stats = Vec::with_capacity(accounts.len());
+    for bundle_manager_account in accounts {
+        assert_owned_by(bundle_manager_account, program_id)?;
+        
+        let bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+        verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+        
+        stats.push(ManagerStats {
+            version: bundle_manager.version,
+            active_bundles: bundle_manager.active_bundles,
+            total_bundles_executed: bundle_manager.total_bundles_executed,
+            total_bundles_failed: bundle_manager.total_bundles_failed,
+            is_paused: bundle_manager.is_paused,
+        });
+    }
+    
+    set_return_data(&stats.try_to_vec()?);
+    
+    Ok(())
+}
+
+fn process_get_execution_state(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_account = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_account, program_id)?;
+    
+    let bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+    verify_bundle_pda(program_id, bundle_account, &bundle)?;
+    
+    let state = ExecutionState {
+        last_executed_index: bundle.last_executed_index,
+        total_instructions: bundle.total_instructions(),
+        executed_mask: bundle.executed_mask,
+        next_record_index: bundle.next_record_index,
+    };
+    set_return_data(&state.try_to_vec()?);
+    
+    Ok(())
+}
+
+fn process_estimate_cost(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_compute_units: u32,
+    recent_fee_micro_lamports: u64,
+    jito_tip_lamports: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let bundle_account = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    assert_owned_by(bundle_account, program_id)?;
+    
+    let bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    let bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+    if bundle.manager != *bundle_manager_account.key {
+        return Err(BundleError::ManagerMismatch.into());
+    }
+    verify_bundle_pda(program_id, bundle_account, &bundle)?;
+    
+    let estimate =
+        estimate_cost(&bundle, &bundle_manager, max_compute_units, recent_fee_micro_lamports, jito_tip_lamports)?;
+    set_return_data(&estimate.try_to_vec()?);
+    
+    Ok(())
+}
+
+fn process_close_bundle(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let bundle_account = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let destination = next_account_info(account_info_iter)?;
+    let record_accounts = account_info_iter.as_slice();
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    assert_owned_by(bundle_account, program_id)?;
+    
+    let bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    let bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+    if bundle.manager != *bundle_manager_account.key {
+        return Err(BundleError::ManagerMismatch.into());
+    }
+    verify_bundle_pda(program_id, bundle_account, &bundle)?;
+    
+    assert_signer(authority)?;
+    if bundle.authority != *authority.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if !matches!(bundle.status, BundleStatus::Executed | BundleStatus::Failed) {
+        msg!("Bundle {} is {:?} and cannot be closed", bundle.bundle_id, bundle.status);
+        return Err(BundleError::InvalidStateTransition.into());
+    }
+    
+    // Every record must go with the bundle: once it is closed, a leftover
+    // record could never be closed and its rent would be stranded
+    if record_accounts.len() != bundle.added_instructions() as usize {
+        return Err(BundleError::MissingInstructionRecords.into());
+    }
+    
+    for record_account in record_accounts {
+        assert_owned_by(record_account, program_id)?;
+        let record = BundleInstructionRecord::unpack(&record_account.data.borrow())?;
+        if record.bundle != *bundle_account.key {
+            return Err(BundleError::InstructionBundleMismatch.into());
+        }
+        
+        close_instruction_account(record_account, authority, &bundle)?;
+    }
+    
+    // Finished bundles already left active_bundles when they reached
+    // Executed or Failed, so the manager's counters are unchanged
+    safe_transfer(bundle_account, destination, bundle_account.lamports())?;
+    bundle_account.data.borrow_mut().fill(0);
+    
+    msg!("Bundle {} closed with {} instruction records", bundle.bundle_id, record_accounts.len());
+    
+    Ok(())
+}
+
+// Moves an expired Created bundle to Failed and emits its BundleFailed
+fn fail_expired_bundle(bundle_manager: &mut BundleManager, bundle: &mut Bundle, now: i64) -> ProgramResult {
+    // Expiry is not an execution failure, so the auto-pause breaker is untouched
+    bundle.transition_to(BundleStatus::Failed)?;
+    bundle.execution_completed_at = now;
+    bundle_manager.active_bundles = bundle_manager.active_bundles.saturating_sub(1);
+    bundle_manager.total_bundles_failed = checked_add(bundle_manager.total_bundles_failed, 1)?;
+    
+    BundleEvent::BundleFailed(BundleFailedEvent {
+        manager: bundle.manager,
+        bundle_id: bundle.bundle_id,
+        authority: bundle.authority,
+        wallet_count: bundle.wallet_count,
+        execution_started_at: bundle.execution_started_at,
+        execution_completed_at: bundle.execution_completed_at,
+        error: u64::from(ProgramError::from(BundleError::BundleExpired)),
+        compute_units: 0,
+    })
+    .emit()
+}
+
+fn process_expire_bundle(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let bundle_account = next_account_info(account_info_iter)?;
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    assert_owned_by(bundle_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    let mut bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+    if bundle.manager != *bundle_manager_account.key {
+        return Err(BundleError::ManagerMismatch.into());
+    }
+    verify_bundle_pda(program_id, bundle_account, &bundle)?;
+    
+    if bundle.status != BundleStatus::Created {
+        return Err(BundleError::InvalidStateTransition.into());
+    }
+    
+    let clock = Clock::get()?;
+    if !bundle.is_expired(clock.unix_timestamp) {
+        return Err(BundleError::BundleNotExpired.into());
+    }
+    
+    fail_expired_bundle(&mut bundle_manager, &mut bundle, clock.unix_timestamp)?;
+    
+    assert_bundle_consistent(&bundle)?;
+    bundle.serialize(&mut &mut bundle_account.data.borrow_mut()[..])?;
+    bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+    
+    msg!("Bundle {} expired", bundle.bundle_id);
+    
+    invoke_failure_hook(&bundle_manager, &bundle, bundle_account, accounts);
+    
+    Ok(())
+}
+
+fn process_garbage_collect(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    let bundle_manager_account = next_account_info(account_info_iter)?;
+    let bundle_account = next_account_info(account_info_iter)?;
+    let collector = next_account_info(account_info_iter)?;
+    let rent_destination = next_account_info(account_info_iter)?;
+    let remaining_accounts = account_info_iter.as_slice();
+    
+    assert_owned_by(bundle_manager_account, program_id)?;
+    assert_owned_by(bundle_account, program_id)?;
+    
+    let mut bundle_manager = BundleManager::unpack(&bundle_manager_account.data.borrow())?;
+    verify_manager_pda(program_id, bundle_manager_account, &bundle_manager)?;
+    
+    let mut bundle = Bundle::unpack(&bundle_account.data.borrow())?;
+    if bundle.manager != *bundle_manager_account.key {
+        return Err(BundleError::ManagerMismatch.into());
+    }
+    verify_bundle_pda(program_id, bundle_account, &bundle)?;
+    
+    assert_signer(collector)?;
+    
+    // Only bundles that can never run again: Created ones, and Failed ones
+    // that never got past their first instruction, which a retry would
+    // have to start over past their expiry
+    if !matches!(bundle.status, BundleStatus::Created | BundleStatus::Failed) || bundle.last_executed_index.is_some() {
+        msg!("Bundle {} is {:?} and cannot be garbage collected", bundle.bundle_id, bundle.status);
+        return Err(BundleError::InvalidStateTransition.into());
+    }
+    
+    let clock = Clock::get()?;
+    if !bundle.is_expired(clock.unix_timestamp) {
+        return Err(BundleError::BundleNotExpired.into());
+    }
+    
+    if *rent_destination.key != bundle.authority {
+        return Err(BundleError::InvalidRentDestination.into());
+    }
+    
+    let record_count = bundle.added_instructions() as usize;
+    if remaining_accounts.len() < record_count {
+        return Err(BundleError::MissingInstructionRecords.into());
+    }
+    let record_accounts = &remaining_accounts[..record_count];
+    
+    if bundle.status == BundleStatus::Created {
+        fail_expired_bundle(&mut bundle_manager, &mut bundle, clock.unix_timestamp)?;
+        assert_bundle_consistent(&bundle)?;
+        bundle.serialize(&mut &mut bundle_account.data.borrow_mut()[..])?;
+        bundle_manager.serialize(&mut &mut bundle_manager_account.data.borrow_mut()[..])?;
+        invoke_failure_hook(&bundle_manager, &bundle, bundle_account, remaining_accounts);
+    }
+    
+    for record_account in record_accounts {
+        assert_owned_by(record_account, program_id)?;
+        let record = BundleInstructionRecord::unpack(&record_account.data.borrow())?;
+        if record.bundle != *bundle_account.key {
+            return Err(BundleError::InstructionBundleMismatch.into());
+        }
+        
+        close_instruction_account(record_account, rent_destination, &bundle)?;
+    }
+    
+    let bounty = GARBAGE_COLLECT_BOUNTY_LAMPORTS.min(bundle_account.lamports());
+    safe_transfer(bundle_account, collector, bounty)?;
+    safe_transfer(bundle_account, rent_destination, bundle_account.lamports())?;
+    bundle_account.data.borrow_mut().fill(0);
+    
+    msg!("Bundle {} garbage collected by {} with {} instruction records, bounty {} lamports",
+        bundle.bundle_id, collector.key, record_count, bounty);
+    
+    Ok(())
+}
@@ -0,0 +1,931 @@
+//! Accounts the program owns, the data it returns, and the PDAs it derives
+//! them at.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::MAX_RETURN_DATA,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{compression, find_associated_token_address, math::checked_mul, BundleError};
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct InstructionAccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl InstructionAccountMeta {
+    /// Borsh length of an account meta
+    pub const LEN: usize = 32 + 1 + 1;
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleStatus {
+    Created,
+    Executing,
+    Executed,
+    Failed,
+    /// ExecuteBundle stopped short of its compute limit with instructions
+    /// left to run; the next ExecuteBundle resumes it
+    PartiallyExecuted,
+}
+
+impl BundleStatus {
+    /// The bundle lifecycle. A Created bundle starts Executing, which ends
+    /// Executed or Failed, or PartiallyExecuted when the call runs low on
+    /// compute; a PartiallyExecuted bundle starts Executing again to resume.
+    /// A Created bundle can also expire to Failed. Executed is final, and a
+    /// Failed bundle may only start Executing again as a retry (see
+    /// `check_retry_cooldown`). Executing is never re-entered, so a nested
+    /// ExecuteBundle on a bundle mid-execution is rejected.
+    pub fn can_transition_to(self, next: BundleStatus) -> bool {
+        use BundleStatus::*;
+        matches!(
+            (self, next),
+            (Created, Executing)
+                | (Created, Failed)
+                | (Executing, Executed)
+                | (Executing, Failed)
+                | (Executing, PartiallyExecuted)
+                | (Failed, Executing)
+                | (PartiallyExecuted, Executing)
+                | (PartiallyExecuted, Failed)
+        )
+    }
+    
+    /// Whether the bundle still counts toward the manager's `active_bundles`
+    pub fn is_active(self) -> bool {
+        matches!(self, BundleStatus::Created | BundleStatus::Executing | BundleStatus::PartiallyExecuted)
+    }
+}
+
+/// Operator-chosen manager settings, set at Initialize and replaced with
+/// UpdateManagerConfig
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default)]
+pub struct ManagerConfig {
+    pub bundle_size: u8,
+    /// Scales the recent priority price in tenths, see `compute_unit_price`
+    pub priority_fee_multiplier: u8,
+    /// When set, ExecuteBundle only writes the bundle's `status` back and
+    /// skips the per-bundle result fields (`execution_started_at`,
+    /// `execution_completed_at`, `priority_fee`), saving the extra writes for
+    /// bots that keep no on-chain history. Manager counters are still updated.
+    pub ephemeral_results: bool,
+    /// Slots a Failed bundle must wait after its last attempt before it can be retried
+    pub retry_cooldown_slots: u64,
+    /// Consecutive failed bundles after which the manager pauses itself; 0 disables
+    pub auto_pause_threshold: u16,
+    /// Seconds after `created_at` at which a bundle expires: it can no longer
+    /// execute and anyone may move it to Failed with ExpireBundle or close it
+    /// with GarbageCollect; 0 disables. Fixed into each bundle's `expires_at`
+    /// at creation, so a change only affects bundles created afterwards.
+    pub expiry_seconds: i64,
+    /// Most wallets a bundle of this manager may span, at most
+    /// `MAX_WALLETS_PER_BUNDLE`. Initialize stores
+    /// `DEFAULT_MAX_WALLETS_PER_BUNDLE` when this is 0.
+    pub max_wallets_per_bundle: u8,
+    /// Longest `instruction_data` AddInstruction accepts, measured before
+    /// compression and at most `MAX_INSTRUCTION_DATA_LEN`. Initialize stores
+    /// `DEFAULT_MAX_INSTRUCTION_DATA_LEN` when this is 0.
+    pub max_instruction_data_len: u16,
+    /// Most accounts a bundled instruction may reference, at most
+    /// `MAX_ACCOUNTS_PER_INSTRUCTION`. Initialize stores
+    /// `DEFAULT_MAX_ACCOUNTS_PER_INSTRUCTION` when this is 0.
+    pub max_accounts_per_instruction: u8,
+    /// Seconds after `execution_started_at` at which anyone, not only the
+    /// bundle authority, may fail a bundle stuck mid-execution with
+    /// FailBundle; 0 disables
+    pub execution_timeout_seconds: i64,
+    /// Highest per-compute-unit price, in micro-lamports, `compute_unit_price`
+    /// returns however high recent fees run; 0 leaves the price uncapped
+    pub max_compute_unit_price: u64,
+    /// Most bundles the manager may create plus start executing in one slot;
+    /// 0 disables. An ExecuteBundleBatch counts each of its bundles.
+    pub max_bundles_per_slot: u16,
+    /// Slots that must pass between two ExecuteBundle, RetryBundle or
+    /// ExecuteBundleBatch calls of the manager; 0 disables
+    pub cooldown_slots: u64,
+}
+
+/// Wallet limit of managers initialized without one
+pub const DEFAULT_MAX_WALLETS_PER_BUNDLE: u8 = 20;
+
+/// Hard ceiling on `ManagerConfig::max_wallets_per_bundle`. ExecuteBundle
+/// touches every wallet of a bundle in one transaction, which can lock at
+/// most 64 accounts.
+pub const MAX_WALLETS_PER_BUNDLE: u8 = 64;
+
+/// Instruction data limit of managers initialized without one, the size of
+/// a whole transaction packet
+pub const DEFAULT_MAX_INSTRUCTION_DATA_LEN: u16 = 1232;
+
+/// Hard ceiling on `ManagerConfig::max_instruction_data_len`. Compressed
+/// records are inflated into a buffer of at most
+/// `compression::MAX_DECOMPRESSED_LEN` bytes at execution.
+pub const MAX_INSTRUCTION_DATA_LEN: u16 = compression::MAX_DECOMPRESSED_LEN as u16;
+
+/// Account limit of managers initialized without one
+pub const DEFAULT_MAX_ACCOUNTS_PER_INSTRUCTION: u8 = 32;
+
+/// Hard ceiling on `ManagerConfig::max_accounts_per_instruction`, the most
+/// accounts a transaction can lock
+pub const MAX_ACCOUNTS_PER_INSTRUCTION: u8 = 64;
+
+/// Most approval authorities a manager can require signatures from
+pub const MAX_APPROVAL_AUTHORITIES: usize = 8;
+
+/// Most delegates a manager can hold
+pub const MAX_DELEGATES: usize = 8;
+
+/// Most programs a manager's allowlist can hold
+pub const MAX_ALLOWED_PROGRAMS: usize = 16;
+
+impl ManagerConfig {
+    /// Borsh length of the config
+    pub const LEN: usize = 1 + 1 + 1 + 8 + 2 + 8 + 1 + 2 + 1 + 8 + 8 + 2 + 8;
+    
+    /// `expires_at` of a bundle created at `created_at` and held until
+    /// `execute_after`: `expiry_seconds` counted from when it becomes
+    /// executable, so a scheduled bundle gets the full window. 0 when expiry
+    /// is disabled.
+    pub fn expires_at(&self, created_at: i64, execute_after: i64) -> i64 {
+        if self.expiry_seconds <= 0 {
+            return 0;
+        }
+        created_at.max(execute_after).saturating_add(self.expiry_seconds)
+    }
+    
+    /// Whether `bundle`'s execution has outlived `execution_timeout_seconds`
+    /// at `now`. Never true without a recorded start time.
+    pub fn is_execution_timed_out(&self, bundle: &Bundle, now: i64) -> bool {
+        self.execution_timeout_seconds > 0
+            && bundle.execution_started_at > 0
+            && bundle.execution_started_at.saturating_add(self.execution_timeout_seconds) < now
+    }
+}
+
+/// Type of a program-owned account, stored as its first byte so that no
+/// account can be read as another type. Closed accounts are zeroed and read
+/// as `Uninitialized`. New types are only ever appended.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountType {
+    Uninitialized,
+    Manager,
+    Bundle,
+    InstructionRecord,
+    ExecutionLog,
+    WalletRegistry,
+    Vault,
+}
+
+impl AccountType {
+    /// Fails with `InvalidAccountType` unless `data` starts with this type
+    pub fn check(self, data: &[u8]) -> ProgramResult {
+        if data.first() != Some(&(self as u8)) {
+            msg!("Expected a {:?} account", self);
+            return Err(BundleError::InvalidAccountType.into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct BundleManager {
+    /// Always `AccountType::Manager`
+    pub account_type: AccountType,
+    /// Layout version, always `BundleManager::VERSION` for accounts this
+    /// program writes
+    pub version: u8,
+    pub authority: Pubkey,
+    pub config: ManagerConfig,
+    pub active_bundles: u16,
+    pub total_bundles_executed: u32,
+    /// Bundles that ended Failed, whether an attempt failed or they expired
+    pub total_bundles_failed: u32,
+    pub is_paused: bool,
+    pub bundle_seed: u32,
+    pub manager_index: u16,
+    pub bump: u8,
+    /// Failed bundles since the last successful one, for the auto-pause breaker
+    pub consecutive_failures: u16,
+    /// The authority the manager PDA was derived from. It stays fixed when
+    /// `authority` is handed off, so the manager keeps its address.
+    pub seed_authority: Pubkey,
+    /// Protocol fee charged to the authority per executed bundle; 0 disables
+    pub fee_lamports: u64,
+    /// Receives `fee_lamports` for every executed bundle
+    pub fee_collector: Pubkey,
+    /// Receives ExecuteBundle's `jito_tip_lamports`, normally a Jito tip account
+    pub tip_account: Pubkey,
+    /// Keys that approve ExecuteBundle by signing it, fixed at Initialize;
+    /// empty when the bundle authority alone approves
+    pub authorities: Vec<Pubkey>,
+    /// How many of `authorities` must sign ExecuteBundle; 1 with no authorities
+    pub threshold: u8,
+    /// Proposed by SetAuthority, becomes `authority` once it signs AcceptAuthority
+    pub pending_authority: Option<Pubkey>,
+    /// Set by the first RegisterWallet. From then on ExecuteBundle needs the
+    /// manager's `WalletRegistry` and only runs records of registered wallets.
+    pub wallet_registry: bool,
+    /// Address lookup table the manager's bots share for v0 transactions, set
+    /// with LinkLookupTable. The program never reads it.
+    pub lookup_table: Option<Pubkey>,
+    /// Keys acting for `authority` in the operations their permissions allow,
+    /// managed with AddDelegate and RemoveDelegate; at most `MAX_DELEGATES`
+    pub delegates: Vec<Delegate>,
+    /// Instructions ExecuteBundle has run for the manager's bundles
+    pub total_instructions_executed: u64,
+    /// Compute units ExecuteBundle calls used, as the program measures them
+    pub total_compute_units: u64,
+    /// Lamports of priority fees ExecuteBundle calls paid: the price from
+    /// `compute_unit_price` over the compute units each call requested
+    pub total_priority_fees_paid: u64,
+    /// Slot `bundles_in_slot` counts for, the last slot the manager created
+    /// or executed a bundle in
+    pub throttle_slot: u64,
+    /// Bundles created and executed in `throttle_slot`, for `max_bundles_per_slot`
+    pub bundles_in_slot: u16,
+    /// Slot of the manager's last execution call, for `cooldown_slots`; 0 before any
+    pub last_execution_slot: u64,
+    /// Programs bundled instructions may invoke, managed with
+    /// AddAllowedProgram and RemoveAllowedProgram; at most
+    /// `MAX_ALLOWED_PROGRAMS`. Empty allows any program.
+    pub allowed_programs: Vec<Pubkey>,
+}
+
+impl BundleManager {
+    /// Current manager layout version
+    ///
+    /// 2 added `max_instruction_data_len` and `max_accounts_per_instruction`
+    /// to the config, 3 the protocol fee, 4 `tip_account`, 5 the approval
+    /// `authorities` and `threshold`, 6 `execution_timeout_seconds` to the
+    /// config, 7 the leading `account_type`, 8 `wallet_registry`, 9
+    /// `max_compute_unit_price` to the config, 10 `lookup_table`, 11
+    /// `delegates`, 12 the execution totals, 13 the per-slot throttle to the
+    /// config and its state, 14 `allowed_programs`; the older layouts live in
+    /// the `migration` module.
+    pub const VERSION: u8 = 14;
+    
+    /// Borsh length of a manager with every approval authority, delegate and
+    /// allowed program, `pending_authority` and `lookup_table` set, which is
+    /// also its account size
+    pub const LEN: usize = 1 + 1 + 32 + ManagerConfig::LEN + 2 + 4 + 4 + 1 + 4 + 2 + 1 + 2 + 32 + 8 + 32 + 32
+        + (4 + 32 * MAX_APPROVAL_AUTHORITIES) + 1 + (1 + 32) + 1 + (1 + 32) + (4 + Delegate::LEN * MAX_DELEGATES)
+        + 8 + 8 + 8 + 8 + 2 + 8 + (4 + 32 * MAX_ALLOWED_PROGRAMS);
+    
+    /// Reads a manager account, branching on its version. Only `VERSION` is
+    /// readable; older managers must be upgraded with MigrateManager first.
+    /// Every version has its own size and managers are always allocated at
+    /// exactly their version's `LEN`, so a size mismatch also means an older
+    /// layout. Layouts before version 7 have no account type and mostly fail
+    /// the type check instead.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        AccountType::Manager.check(data)?;
+        if data.len() != Self::LEN || data.get(1) != Some(&Self::VERSION) {
+            return Err(BundleError::UnsupportedAccountVersion.into());
+        }
+        Self::deserialize(&mut &data[..]).map_err(|_| ProgramError::InvalidAccountData)
+    }
+    
+    /// The permissions `key` holds as a delegate, if it is one
+    pub fn delegate(&self, key: &Pubkey) -> Option<DelegatePermissions> {
+        self.delegates.iter().find(|delegate| delegate.key == *key).map(|delegate| delegate.permissions)
+    }
+    
+    /// Whether bundled instructions may invoke `program_id`: any program
+    /// while `allowed_programs` is empty, only those it lists otherwise
+    pub fn allows_program(&self, program_id: &Pubkey) -> bool {
+        self.allowed_programs.is_empty() || self.allowed_programs.contains(program_id)
+    }
+}
+
+/// What a delegate may do for the manager authority
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DelegatePermissions {
+    /// Create bundles owned by the manager authority, paying their rent
+    pub can_create: bool,
+    /// Execute bundles owned by the manager authority
+    pub can_execute: bool,
+    /// Pause the manager; unpausing stays with the authority
+    pub can_pause: bool,
+}
+
+/// A key added with AddDelegate
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delegate {
+    pub key: Pubkey,
+    pub permissions: DelegatePermissions,
+}
+
+impl Delegate {
+    pub const LEN: usize = 32 + 1 + 1 + 1;
+}
+
+/// Compact per-manager stats returned by GetManagersStats
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManagerStats {
+    /// Layout version of the manager account, see `BundleManager::VERSION`
+    pub version: u8,
+    pub active_bundles: u16,
+    pub total_bundles_executed: u32,
+    pub total_bundles_failed: u32,
+    pub is_paused: bool,
+}
+
+impl ManagerStats {
+    pub const LEN: usize = 1 + 2 + 4 + 4 + 1;
+}
+
+/// Most managers one GetManagersStats call reports on, so that the
+/// length-prefixed stats vector fits in the return data limit
+pub const MAX_STATS_MANAGERS: usize = (MAX_RETURN_DATA - 4) / ManagerStats::LEN;
+
+/// Seed prefix for manager PDAs, derived from `[MANAGER_SEED, authority, manager_index (LE)]`
+pub const MANAGER_SEED: &[u8] = b"manager";
+
+/// Derives the manager PDA for an authority and manager index
+pub fn find_manager_address(program_id: &Pubkey, authority: &Pubkey, manager_index: u16) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[MANAGER_SEED, authority.as_ref(), &manager_index.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Seed prefix for bundle PDAs, derived from
+/// `[BUNDLE_SEED, manager, authority, client_nonce (LE)]`
+pub const BUNDLE_SEED: &[u8] = b"bundle";
+
+/// Derives the address of the bundle `authority` creates on `manager` with
+/// `client_nonce`. The authority is part of the seeds so one caller cannot
+/// claim another's nonce first.
+pub fn find_bundle_address(program_id: &Pubkey, manager: &Pubkey, authority: &Pubkey, client_nonce: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[BUNDLE_SEED, manager.as_ref(), authority.as_ref(), &client_nonce.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Seed prefix for instruction record PDAs, derived from
+/// `[INSTRUCTION_RECORD_SEED, bundle, wallet_index, record_index (LE)]`
+pub const INSTRUCTION_RECORD_SEED: &[u8] = b"instruction";
+
+/// Derives the address of the instruction record added to `bundle` for
+/// `wallet_index` when the bundle's `next_record_index` is `record_index`
+pub fn find_instruction_record_address(
+    program_id: &Pubkey,
+    bundle: &Pubkey,
+    wallet_index: u8,
+    record_index: u16,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[INSTRUCTION_RECORD_SEED, bundle.as_ref(), &[wallet_index], &record_index.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Seed prefix for wallet PDAs, derived from `[WALLET_SEED, manager, wallet_index]`
+pub const WALLET_SEED: &[u8] = b"wallet";
+
+/// Derives the program-controlled wallet PDA for a manager and wallet index.
+///
+/// ExecuteBundle signs with `[WALLET_SEED, manager, &[wallet_index], &[bump]]`
+/// for a record's own wallet only. A stored meta marking any other key a
+/// signer, including another wallet's PDA, must be a signer of the
+/// ExecuteBundle transaction itself or the bundle is rejected with
+/// `AccountPrivilegeEscalation`.
+pub fn find_wallet_address(program_id: &Pubkey, manager: &Pubkey, wallet_index: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[WALLET_SEED, manager.as_ref(), &[wallet_index]], program_id)
+}
+
+/// Seed prefix for a manager's wallet registry PDA, `[WALLET_REGISTRY_SEED, manager]`
+pub const WALLET_REGISTRY_SEED: &[u8] = b"wallet_registry";
+
+/// Derives the wallet registry PDA of a manager
+pub fn find_wallet_registry_address(program_id: &Pubkey, manager: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[WALLET_REGISTRY_SEED, manager.as_ref()], program_id)
+}
+
+/// A wallet index registered with RegisterWallet
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisteredWallet {
+    pub wallet_index: u8,
+    /// The wallet PDA at `wallet_index`, or a keypair wallet's own address
+    pub address: Pubkey,
+    /// ExecuteBundle signs for PDA wallets; keypair wallets must sign the
+    /// ExecuteBundle transaction themselves
+    pub is_pda: bool,
+}
+
+impl RegisteredWallet {
+    pub const LEN: usize = 1 + 32 + 1;
+}
+
+/// The wallets a manager's bundles may run instructions for. Once a manager
+/// has one, every signer of a record's instruction other than the bundle
+/// authority must be the address registered for the record's wallet index.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct WalletRegistry {
+    /// Always `AccountType::WalletRegistry`
+    pub account_type: AccountType,
+    /// Layout version, always `WalletRegistry::VERSION` for accounts this program writes
+    pub version: u8,
+    pub manager: Pubkey,
+    pub bump: u8,
+    /// Sorted by `wallet_index`, at most one entry per index
+    pub wallets: Vec<RegisteredWallet>,
+}
+
+impl WalletRegistry {
+    /// Layout version. Registries from before it have no version byte and
+    /// must be upgraded with MigrateState.
+    pub const VERSION: u8 = 1;
+    
+    pub const HEADER_LEN: usize = 1 + 1 + 32 + 1 + 4;
+    
+    /// Reads a registry account
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        AccountType::WalletRegistry.check(data)?;
+        if data.get(1) != Some(&Self::VERSION) {
+            return Err(BundleError::UnsupportedAccountVersion.into());
+        }
+        Self::deserialize(&mut &data[..]).map_err(|_| ProgramError::InvalidAccountData)
+    }
+    
+    /// Account space for a registry of `wallets` entries
+    pub fn space(wallets: usize) -> usize {
+        Self::HEADER_LEN + wallets * RegisteredWallet::LEN
+    }
+    
+    pub fn get(&self, wallet_index: u8) -> Option<&RegisteredWallet> {
+        self.wallets.binary_search_by_key(&wallet_index, |wallet| wallet.wallet_index)
+            .ok()
+            .map(|position| &self.wallets[position])
+    }
+}
+
+/// Seed prefix for a manager's vault PDA, `[VAULT_SEED, manager]`
+pub const VAULT_SEED: &[u8] = b"vault";
+
+/// Derives the vault PDA of a manager
+pub fn find_vault_address(program_id: &Pubkey, manager: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, manager.as_ref()], program_id)
+}
+
+/// Funds a manager keeps for its wallets, created by the first Deposit. Its
+/// SOL is the account's lamports above the rent-exempt minimum; its tokens
+/// sit in the vault's associated token accounts.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct Vault {
+    /// Always `AccountType::Vault`
+    pub account_type: AccountType,
+    /// Layout version, always `Vault::VERSION` for accounts this program writes
+    pub version: u8,
+    pub manager: Pubkey,
+    pub bump: u8,
+    /// Lamports received through Deposit
+    pub total_deposited: u64,
+    /// Lamports paid out to wallets
+    pub total_withdrawn: u64,
+}
+
+impl Vault {
+    /// Layout version. Vaults from before it have no version byte and must
+    /// be upgraded with MigrateState.
+    pub const VERSION: u8 = 1;
+    
+    pub const LEN: usize = 1 + 1 + 32 + 1 + 8 + 8;
+    
+    /// Reads a vault account
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        AccountType::Vault.check(data)?;
+        if data.get(1) != Some(&Self::VERSION) {
+            return Err(BundleError::UnsupportedAccountVersion.into());
+        }
+        Self::deserialize(&mut &data[..]).map_err(|_| ProgramError::InvalidAccountData)
+    }
+}
+
+/// Funding phase run at the start of ExecuteBundle: the authority sends
+/// `lamports_each` to the wallet PDAs for indexes `0..wallet_count`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FundWallets {
+    pub lamports_each: u64,
+    pub wallet_count: u8,
+}
+
+impl FundWallets {
+    pub fn total_lamports(&self) -> Result<u64, ProgramError> {
+        checked_mul(self.lamports_each, self.wallet_count as u64)
+    }
+}
+
+/// Most mints a bundle's `SpendLimits` can cap
+pub const MAX_TOKEN_SPEND_CAPS: usize = 4;
+
+/// Caps on what a bundle's instructions may take out of its wallets during
+/// one ExecuteBundle call, measured as the drop in the wallets' balances
+/// from before the first instruction of the call to after its last. The
+/// funding phase runs before, so funded lamports do not count against it.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpendLimits {
+    /// Most lamports the wallets may lose; None leaves SOL uncapped
+    pub max_lamports_out: Option<u64>,
+    /// Per-mint caps on what the wallets' token accounts may lose, at most
+    /// `MAX_TOKEN_SPEND_CAPS`
+    pub token_caps: Vec<TokenSpendCap>,
+}
+
+impl SpendLimits {
+    /// Borsh length with every token cap used
+    pub const MAX_LEN: usize = (1 + 8) + 4 + TokenSpendCap::LEN * MAX_TOKEN_SPEND_CAPS;
+}
+
+/// Most of `mint`, in base units, a bundle's wallets may lose in one call
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenSpendCap {
+    pub mint: Pubkey,
+    pub max_amount: u64,
+}
+
+impl TokenSpendCap {
+    pub const LEN: usize = 32 + 8;
+}
+
+// Strategy categories for Bundle.category
+pub const CATEGORY_ARBITRAGE: u8 = 0;
+pub const CATEGORY_LIQUIDATION: u8 = 1;
+pub const CATEGORY_SNIPE: u8 = 2;
+pub const CATEGORY_CUSTOM: u8 = 255;
+
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct Bundle {
+    /// Always `AccountType::Bundle`
+    pub account_type: AccountType,
+    /// Layout version, always `Bundle::VERSION` for accounts this program writes
+    pub version: u8,
+    pub manager: Pubkey,
+    pub authority: Pubkey,
+    pub bundle_id: u32,
+    pub created_at: i64,
+    /// Unix time the most recent execution attempt started, 0 before the
+    /// first one. ExecuteBundle leaves both timestamps untouched on managers
+    /// with `ephemeral_results`.
+    pub execution_started_at: i64,
+    /// Unix time the bundle last reached Executed or Failed, whether an
+    /// attempt ended or ExpireBundle swept it
+    pub execution_completed_at: i64,
+    pub wallet_count: u8,
+    /// Kept ahead of the vectors so it sits at a fixed offset for memcmp filters
+    pub category: u8,
+    pub wallet_indexes: Vec<u8>,
+    pub instructions_per_wallet: Vec<u8>,
+    /// Instructions added so far for each entry of `wallet_indexes`
+    pub instructions_added: Vec<u8>,
+    pub status: BundleStatus,
+    /// Lamports of priority fee the last execution's price costs over its
+    /// `max_compute_units`, see `priority_fee_lamports`
+    pub priority_fee: u64,
+    pub on_failure: Option<(Pubkey, Vec<u8>)>,
+    pub funding: Option<FundWallets>,
+    /// Net lamports the authority should gain (negative: spend) from the
+    /// bundle's system transfers, summed from `transfer_lamport_delta`
+    pub expected_net_lamports: i64,
+    /// Slot of the most recent ExecuteBundle attempt, 0 if never attempted
+    pub last_attempt_slot: u64,
+    /// Record index of the most recently executed instruction
+    pub last_executed_index: Option<u16>,
+    /// Bit `i % 8` of byte `i / 8` is set once the record at record index `i`
+    /// (see `find_instruction_record_address`) has executed
+    pub executed_mask: [u8; EXECUTED_MASK_LEN],
+    /// Record index the next added instruction's PDA is derived with. It
+    /// counts every record ever added, removed ones included, so no two
+    /// records of a bundle share an address; at most `MAX_BUNDLE_INSTRUCTIONS`
+    /// records are ever added, so each has a bit of `executed_mask`.
+    pub next_record_index: u16,
+    /// What the bundle's address was derived from, so handlers can check it.
+    /// None for bundles migrated from version 3 or earlier, which did not
+    /// record it.
+    pub seeds: Option<BundleSeeds>,
+    /// SetComputeUnitLimit the last execution requested, its
+    /// `max_compute_units`; `priority_fee` is the price over these units
+    pub compute_unit_limit: u32,
+    /// Unix timestamp before which ExecuteBundle refuses the bundle, set at
+    /// CreateBundle; 0 when it may run at once
+    pub execute_after: i64,
+    /// Times the bundle may run again after failing, set at CreateBundle;
+    /// None leaves retries unlimited
+    pub max_retries: Option<u8>,
+    /// Executions started after the bundle failed, counted against
+    /// `max_retries`
+    pub retry_count: u8,
+    /// Unix timestamp after which the bundle can no longer start executing
+    /// and may be garbage collected; 0 never expires
+    pub expires_at: i64,
+    /// Caps on what an ExecuteBundle call may take out of the bundle's
+    /// wallets; None leaves them uncapped
+    pub spend_limits: Option<SpendLimits>,
+}
+
+/// The CreateBundle arguments a bundle PDA is derived from besides its
+/// manager and authority, see `find_bundle_address`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BundleSeeds {
+    pub client_nonce: u64,
+    pub bump: u8,
+}
+
+/// Most instructions a bundle can plan, bounded by the executed bitmask
+pub const MAX_BUNDLE_INSTRUCTIONS: usize = 256;
+pub const EXECUTED_MASK_LEN: usize = MAX_BUNDLE_INSTRUCTIONS / 8;
+
+/// Lamports of an expired bundle's rent GarbageCollect pays whoever closes it
+pub const GARBAGE_COLLECT_BOUNTY_LAMPORTS: u64 = 10_000;
+
+impl Bundle {
+    /// Current bundle layout version
+    ///
+    /// 2 added `next_record_index`, 3 the leading `account_type`, 4 `seeds`,
+    /// 5 `compute_unit_limit`, 6 `execute_after`, 7 `max_retries` and
+    /// `retry_count`, 8 `expires_at`, 9 `spend_limits`; the older layouts
+    /// live in the `migration` module.
+    pub const VERSION: u8 = 9;
+    
+    /// Byte offset of `category`, for `getProgramAccounts` memcmp filters
+    /// such as `{ offset: CATEGORY_OFFSET, bytes: [CATEGORY_SNIPE] }`.
+    /// account type + version + manager + authority + bundle_id + three
+    /// timestamps + wallet_count.
+    pub const CATEGORY_OFFSET: usize = 1 + 1 + 32 + 32 + 4 + 8 + 8 + 8 + 1;
+    
+    /// Reads a bundle account, branching on its version. Only `VERSION` is
+    /// readable; bundles created before versioning start with their manager's
+    /// address instead of a version byte and must be upgraded with
+    /// MigrateManager first. The unused tail `serialized_len` reserves is ignored.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        AccountType::Bundle.check(data)?;
+        if data.get(1) != Some(&Self::VERSION) {
+            return Err(BundleError::UnsupportedAccountVersion.into());
+        }
+        Self::deserialize(&mut &data[..]).map_err(|_| ProgramError::InvalidAccountData)
+    }
+    
+    /// Account space for a bundle with `wallet_count` wallets and no
+    /// on_failure hook (see `on_failure_len`). Optional fields are counted at
+    /// their largest so that later writes always fit; bundles are therefore
+    /// read with `Bundle::unpack`, which ignores the unused tail.
+    pub fn serialized_len(wallet_count: usize) -> usize {
+        1 + 1                            // account_type, version
+            + 32 + 32 + 4                // manager, authority, bundle_id
+            + 8 + 8 + 8                  // timestamps
+            + 1 + 1                      // wallet_count, category
+            + 3 * (4 + wallet_count)     // the three per-wallet vectors
+            + 1 + 8                      // status, priority_fee
+            + 1                          // on_failure tag
+            + 1 + 8 + 1                  // funding
+            + 8 + 8                      // expected_net_lamports, last_attempt_slot
+            + 1 + 2                      // last_executed_index
+            + EXECUTED_MASK_LEN
+            + 2                          // next_record_index
+            + 1 + 8 + 1                  // seeds
+            + 4                          // compute_unit_limit
+            + 8                          // execute_after
+            + 1 + 1 + 1                  // max_retries, retry_count
+            + 8                          // expires_at
+            + 1 + SpendLimits::MAX_LEN   // spend_limits
+    }
+    
+    /// Extra account space an on_failure hook takes on top of `serialized_len`
+    pub fn on_failure_len(on_failure: &Option<(Pubkey, Vec<u8>)>) -> usize {
+        on_failure.as_ref().map_or(0, |(_, data)| 32 + 4 + data.len())
+    }
+    
+    /// Account space CreateBundle allocates for a bundle with `wallet_count`
+    /// wallets and `on_failure`
+    pub fn space(wallet_count: usize, on_failure: &Option<(Pubkey, Vec<u8>)>) -> usize {
+        Self::serialized_len(wallet_count) + Self::on_failure_len(on_failure)
+    }
+    
+    /// Whether the bundle is past its `expires_at` at `now`
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at > 0 && self.expires_at < now
+    }
+    
+    /// Instructions added so far across all wallets
+    pub fn added_instructions(&self) -> u16 {
+        self.instructions_added.iter().map(|&n| n as u16).sum()
+    }
+    
+    /// Instructions planned across all wallets
+    pub fn total_instructions(&self) -> u16 {
+        self.instructions_per_wallet.iter().map(|&n| n as u16).sum()
+    }
+    
+    /// Moves the bundle to `next` if `BundleStatus::can_transition_to` allows it
+    pub fn transition_to(&mut self, next: BundleStatus) -> ProgramResult {
+        if !self.status.can_transition_to(next) {
+            msg!("Bundle {} cannot move from {:?} to {:?}", self.bundle_id, self.status, next);
+            return Err(BundleError::InvalidStateTransition.into());
+        }
+        self.status = next;
+        Ok(())
+    }
+    
+    pub fn is_executed(&self, index: u16) -> bool {
+        let index = index as usize;
+        index < MAX_BUNDLE_INSTRUCTIONS && self.executed_mask[index / 8] & (1 << (index % 8)) != 0
+    }
+    
+    pub fn mark_executed(&mut self, index: u16) {
+        let index = index as usize;
+        if index < MAX_BUNDLE_INSTRUCTIONS {
+            self.executed_mask[index / 8] |= 1 << (index % 8);
+            self.last_executed_index = Some(index as u16);
+        }
+    }
+}
+
+/// Execution progress returned by GetExecutionState. `executed_mask` and
+/// `last_executed_index` are by record index, as in `Bundle::executed_mask`,
+/// and only the first `ceil(next_record_index / 8)` bytes of the mask are
+/// meaningful. Removed records leave holes below `next_record_index` with
+/// their bits clear, so the records still to run are those with a clear bit
+/// whose PDA (see `find_instruction_record_address`, for one of the bundle's
+/// wallets) still exists.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionState {
+    pub last_executed_index: Option<u16>,
+    pub total_instructions: u16,
+    pub executed_mask: [u8; EXECUTED_MASK_LEN],
+    /// The bundle's `next_record_index`: every record ever added has a lower index
+    pub next_record_index: u16,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct BundleInstructionRecord {
+    /// Always `AccountType::InstructionRecord`
+    pub account_type: AccountType,
+    /// Layout version, always `BundleInstructionRecord::VERSION` for accounts this program writes
+    pub version: u8,
+    pub bundle: Pubkey,
+    pub wallet_index: u8,
+    /// Program the stored instruction invokes
+    pub program_id: Pubkey,
+    pub instruction_data: Vec<u8>,
+    pub accounts: Vec<InstructionAccountMeta>,
+    pub executed: bool,
+    /// instruction_data is run-length encoded and must be decompressed before invoking
+    pub compressed: bool,
+    /// Always 0. Holds the byte of a retry count ExecuteBundle could never
+    /// act on, since a failing callee aborts the transaction, so the record
+    /// layout stays the same.
+    pub reserved: u8,
+    pub create_if_missing: Option<CreateIfMissing>,
+    /// Error code of the last attempt to run the instruction that failed, see
+    /// `error_code`; kept once a retry runs it
+    pub last_error: Option<u32>,
+}
+
+impl BundleInstructionRecord {
+    /// Layout version. Records from before it have no version byte and must
+    /// be upgraded with MigrateState.
+    pub const VERSION: u8 = 1;
+    
+    /// Reads a record account, ignoring the unused tail `space` reserves
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        AccountType::InstructionRecord.check(data)?;
+        if data.get(1) != Some(&Self::VERSION) {
+            return Err(BundleError::UnsupportedAccountVersion.into());
+        }
+        Self::deserialize(&mut &data[..]).map_err(|_| ProgramError::InvalidAccountData)
+    }
+    
+    /// The stored instruction as ExecuteBundle invokes it, its data
+    /// decompressed
+    pub fn instruction(&self) -> Result<Instruction, ProgramError> {
+        let data = if self.compressed {
+            compression::decompress(&self.instruction_data)?
+        } else {
+            self.instruction_data.clone()
+        };
+        let accounts = self.accounts.iter()
+            .map(|meta| AccountMeta { pubkey: meta.pubkey, is_signer: meta.is_signer, is_writable: meta.is_writable })
+            .collect();
+        Ok(Instruction { program_id: self.program_id, accounts, data })
+    }
+    
+    /// Account space for a record holding `data_len` bytes of instruction
+    /// data and `account_count` account metas. `create_if_missing` is counted
+    /// at its largest variant whether or not it is set.
+    pub fn space(data_len: usize, account_count: usize) -> usize {
+        1 + 1 + 32 + 1 + 32                            // account_type, version, bundle, wallet_index, program_id
+            + 4 + data_len                             // instruction_data
+            + 4 + account_count * InstructionAccountMeta::LEN
+            + 1 + 1 + 1                                // executed, compressed, reserved
+            + 1 + CreateIfMissing::MAX_LEN             // create_if_missing
+            + 1 + 4                                    // last_error
+    }
+}
+
+/// An account a bundled instruction needs that may not exist yet
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub enum CreateIfMissing {
+    /// Created with system create_account, funded by the bundle authority. A
+    /// non-PDA `address` must sign the ExecuteBundle transaction.
+    SystemAccount {
+        address: Pubkey,
+        space: u64,
+        owner: Pubkey,
+    },
+    /// The associated token account of `wallet` for `mint`, created through
+    /// the associated-token-account program's idempotent create.
+    /// `token_program` is the legacy token program or token-2022 and must
+    /// own `mint`.
+    AssociatedTokenAccount {
+        wallet: Pubkey,
+        mint: Pubkey,
+        token_program: Pubkey,
+    },
+}
+
+impl CreateIfMissing {
+    /// Borsh length of the largest variant, AssociatedTokenAccount
+    pub const MAX_LEN: usize = 1 + 32 + 32 + 32;
+    
+    /// Address of the account this spec creates
+    pub fn address(&self) -> Pubkey {
+        match self {
+            CreateIfMissing::SystemAccount { address, .. } => *address,
+            CreateIfMissing::AssociatedTokenAccount { wallet, mint, token_program } => {
+                find_associated_token_address(wallet, mint, token_program)
+            },
+        }
+    }
+}
+
+/// Seed prefix for a manager's execution log PDA
+pub const EXECUTION_LOG_SEED: &[u8] = b"execution_log";
+
+/// Derives the execution log PDA of a manager
+pub fn find_execution_log_address(program_id: &Pubkey, manager: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[EXECUTION_LOG_SEED, manager.as_ref()], program_id)
+}
+
+/// Header of the append-only execution log. The account holds the header
+/// followed by up to `max_entries` fixed-size `ExecutionLogEntry` slots used
+/// as a ring buffer: `next_index` is the slot the next attempt is written to.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct ExecutionLog {
+    /// Always `AccountType::ExecutionLog`
+    pub account_type: AccountType,
+    /// Layout version, always `ExecutionLog::VERSION` for accounts this program writes
+    pub version: u8,
+    pub manager: Pubkey,
+    pub max_entries: u32,
+    pub next_index: u32,
+    pub total_appended: u64,
+}
+
+impl ExecutionLog {
+    /// Layout version. Logs from before it have no version byte and must be
+    /// upgraded with MigrateState.
+    pub const VERSION: u8 = 1;
+    
+    pub const HEADER_LEN: usize = 1 + 1 + 32 + 4 + 4 + 8;
+    
+    /// Reads a log's header
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        AccountType::ExecutionLog.check(data)?;
+        if data.get(1) != Some(&Self::VERSION) {
+            return Err(BundleError::UnsupportedAccountVersion.into());
+        }
+        Self::deserialize(&mut &data[..]).map_err(|_| ProgramError::InvalidAccountData)
+    }
+    
+    /// Account space for a log holding `entries` entry slots
+    pub fn space(entries: u32) -> usize {
+        Self::entry_offset(entries)
+    }
+    
+    /// Byte offset of the entry slot at `index`
+    pub fn entry_offset(index: u32) -> usize {
+        Self::HEADER_LEN + index as usize * ExecutionLogEntry::LEN
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct ExecutionLogEntry {
+    pub executor: Pubkey,
+    pub bundle_id: u32,
+    pub slot: u64,
+    pub status: BundleStatus,
+    pub compute_units: u64,
+}
+
+impl ExecutionLogEntry {
+    pub const LEN: usize = 32 + 4 + 8 + 1 + 8;
+}
@@ -0,0 +1,97 @@
+//! Account checks shared by the instruction processors.
+//!
+//! Each helper returns the `ProgramError` a processor would return for the
+//! failed check, so processors can use them with `?` and keep their error
+//! codes unchanged.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+};
+
+/// Fails with `IncorrectProgramId` unless `account` is owned by `owner`
+pub(crate) fn assert_owned_by(account: &AccountInfo, owner: &Pubkey) -> ProgramResult {
+    if account.owner != owner {
+        msg!("Account {} is owned by {}, expected {}", account.key, account.owner, owner);
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Fails with `MissingRequiredSignature` unless `account` signed the transaction
+pub(crate) fn assert_signer(account: &AccountInfo) -> ProgramResult {
+    if !account.is_signer {
+        msg!("Account {} must sign", account.key);
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+/// Fails with `AccountNotRentExempt` unless `account` holds at least the
+/// rent-exempt minimum for its current data length
+pub(crate) fn assert_rent_exempt(account: &AccountInfo, rent: &Rent) -> ProgramResult {
+    let required = rent.minimum_balance(account.data_len());
+    if account.lamports() < required {
+        msg!("Account {} holds {} lamports, {} needed for rent exemption",
+            account.key, account.lamports(), required);
+        return Err(ProgramError::AccountNotRentExempt);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_account<T>(
+        owner: Pubkey,
+        is_signer: bool,
+        lamports: u64,
+        data_len: usize,
+        f: impl FnOnce(&AccountInfo) -> T,
+    ) -> T {
+        let key = Pubkey::new_unique();
+        let mut lamports = lamports;
+        let mut data = vec![0; data_len];
+        let account = AccountInfo::new(&key, is_signer, true, &mut lamports, &mut data, &owner, false, 0);
+        f(&account)
+    }
+
+    #[test]
+    fn assert_owned_by_rejects_other_owners() {
+        let owner = Pubkey::new_unique();
+        with_account(owner, false, 0, 0, |account| {
+            assert_eq!(assert_owned_by(account, &owner), Ok(()));
+            assert_eq!(
+                assert_owned_by(account, &Pubkey::new_unique()),
+                Err(ProgramError::IncorrectProgramId),
+            );
+        });
+    }
+
+    #[test]
+    fn assert_signer_rejects_non_signers() {
+        with_account(Pubkey::new_unique(), true, 0, 0, |account| {
+            assert_eq!(assert_signer(account), Ok(()));
+        });
+        with_account(Pubkey::new_unique(), false, 0, 0, |account| {
+            assert_eq!(assert_signer(account), Err(ProgramError::MissingRequiredSignature));
+        });
+    }
+
+    #[test]
+    fn assert_rent_exempt_rejects_underfunded_accounts() {
+        let rent = Rent::default();
+        let minimum = rent.minimum_balance(100);
+        with_account(Pubkey::new_unique(), false, minimum, 100, |account| {
+            assert_eq!(assert_rent_exempt(account, &rent), Ok(()));
+        });
+        with_account(Pubkey::new_unique(), false, minimum - 1, 100, |account| {
+            assert_eq!(assert_rent_exempt(account, &rent), Err(ProgramError::AccountNotRentExempt));
+        });
+    }
+}
@@ -0,0 +1,175 @@
+//! The instruction ABI: every variant keeps its Borsh position, the client
+//! builders encode the variant they are named after, and error codes decode
+//! back to the `BundleError` that raised them.
+
+use borsh::BorshDeserialize;
+use bundle_manager::{
+    client, error_code, BundleError, BundleInstruction, ManagerConfig, VaultAsset, BUNDLE_ERROR_BASE,
+    SPL_TOKEN_PROGRAM_ID,
+};
+use solana_program::{instruction::Instruction, program_error::ProgramError, pubkey::Pubkey, system_instruction};
+
+fn decode(instruction: &Instruction) -> BundleInstruction {
+    assert_eq!(instruction.program_id, bundle_manager::id());
+    BundleInstruction::try_from_slice(&instruction.data).expect("builder data decodes")
+}
+
+#[test]
+fn variants_keep_their_abi_index() {
+    let manager = Pubkey::new_unique();
+    let bundle = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let record = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let transfer = system_instruction::transfer(&authority, &Pubkey::new_unique(), 1);
+
+    let builders = [
+        client::initialize(&authority, 0, ManagerConfig::default()),
+        client::create_bundle(&manager, 0, &authority, &[(0, 1)], 0, None, None),
+        client::add_instruction(&manager, &bundle, &record, &authority, 0, &transfer, Default::default()),
+        client::execute_bundle(&manager, &bundle, &authority, true, &[], 200_000, 0),
+        client::set_manager_status(&manager, &authority, true),
+        client::initialize_execution_log(&manager, &authority, 16),
+        client::get_managers_stats(&[manager]),
+        client::get_execution_state(&bundle),
+        client::estimate_cost(&manager, &bundle, 200_000, 0, 0),
+        client::close_bundle(&manager, &bundle, &authority, &authority, &[record]),
+        client::expire_bundle(&manager, &bundle, None),
+        client::batch_add_instruction(&manager, &bundle, &authority, vec![
+            (record, client::batch_item(0, &transfer, Default::default())),
+        ]),
+        client::set_authority(&manager, &authority, Pubkey::new_unique()),
+        client::accept_authority(&manager, &authority),
+        client::remove_instruction(&manager, &bundle, &record, &authority),
+        client::migrate_manager(&manager, &authority, &[bundle]),
+        client::set_fee(&manager, &authority, 5_000, &Pubkey::new_unique()),
+        client::set_tip_account(&manager, &authority, &Pubkey::new_unique()),
+        client::reset_stats(&manager, &authority, &[bundle]),
+        client::fail_bundle(&manager, &bundle, &authority, None),
+        client::cancel_bundle(&manager, &bundle, &authority, &authority, &[record]),
+        client::execute_bundle_batch(&manager, &[bundle], &authority, false, &[], 200_000, 0),
+        client::register_wallet(&manager, &authority, 0, None),
+        client::remove_wallet(&manager, &authority, 0),
+        client::update_manager_config(&manager, &authority, ManagerConfig::default()),
+        client::link_lookup_table(&manager, &authority, Some(&Pubkey::new_unique())),
+        client::add_delegate(&manager, &authority, &Pubkey::new_unique(), Default::default()),
+        client::remove_delegate(&manager, &authority, &Pubkey::new_unique()),
+        client::transfer_tokens_bundled(&manager, &authority, &mint, &SPL_TOKEN_PROGRAM_ID, 6, &[], None),
+        client::retry_bundle(&manager, &bundle, &authority, false, &[], 200_000, 0),
+        client::deposit(&manager, &authority, 1),
+        client::withdraw_to_wallet(&manager, &authority, 0, 1, VaultAsset::Sol, None),
+        client::distribute_equally(&manager, &authority, &[0, 1], 2, VaultAsset::Sol, None),
+        client::sweep_wallets(&manager, &authority, &Pubkey::new_unique(), &[0], &[], None),
+        client::garbage_collect(&manager, &bundle, &authority, &authority, &[], None),
+        client::add_allowed_program(&manager, &authority, &Pubkey::new_unique()),
+        client::remove_allowed_program(&manager, &authority, &Pubkey::new_unique()),
+        client::migrate_state(&manager, &authority, &[], &[]),
+    ];
+
+    for (index, instruction) in builders.iter().enumerate() {
+        assert_eq!(instruction.data[0] as usize, index, "builder {index} encodes a different variant");
+        assert_eq!(decode(instruction).abi_index() as usize, index);
+    }
+}
+
+#[test]
+fn builders_encode_their_arguments() {
+    let manager = Pubkey::new_unique();
+    let bundle = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+    let record = bundle_manager::find_instruction_record_address(&bundle_manager::id(), &bundle, 3, 0).0;
+    let recipient = Pubkey::new_unique();
+    let transfer = system_instruction::transfer(&authority, &recipient, 42);
+
+    let create = client::create_bundle(&manager, 7, &authority, &[(0, 2), (3, 1)], 2, None, None);
+    match decode(&create) {
+        BundleInstruction::CreateBundle { wallet_indexes, instructions_per_wallet, category, client_nonce, .. } => {
+            assert_eq!(wallet_indexes, vec![0, 3]);
+            assert_eq!(instructions_per_wallet, vec![2, 1]);
+            assert_eq!(category, 2);
+            assert_eq!(client_nonce, 7);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    assert_eq!(
+        create.accounts[1].pubkey,
+        bundle_manager::find_bundle_address(&bundle_manager::id(), &manager, &authority, 7).0,
+    );
+
+    let add = client::add_instruction(&manager, &bundle, &record, &authority, 3, &transfer, Default::default());
+    match decode(&add) {
+        BundleInstruction::AddInstruction { wallet_index, program_id, instruction_data, accounts, .. } => {
+            assert_eq!(wallet_index, 3);
+            assert_eq!(program_id, transfer.program_id);
+            assert_eq!(instruction_data, transfer.data);
+            let keys: Vec<Pubkey> = accounts.iter().map(|meta| meta.pubkey).collect();
+            assert_eq!(keys, vec![authority, recipient]);
+            assert!(accounts[0].is_signer && accounts[0].is_writable);
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    assert_eq!(add.accounts[2].pubkey, record);
+    assert!(!add.accounts[2].is_signer, "the program signs for the record PDA");
+
+    let execute = client::execute_bundle(&manager, &bundle, &authority, false, &[], 150_000, 9);
+    match decode(&execute) {
+        BundleInstruction::ExecuteBundle {
+            max_compute_units,
+            recent_fee_micro_lamports,
+            dry_run,
+            jito_tip_lamports,
+            start_index,
+            count,
+        } => {
+            assert_eq!(max_compute_units, 150_000);
+            assert_eq!(recent_fee_micro_lamports, 9);
+            assert!(!dry_run);
+            assert_eq!(jito_tip_lamports, 0);
+            assert_eq!((start_index, count), (0, 0));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    let tip_account = Pubkey::new_unique();
+    let tipped = client::with_jito_tip(execute.clone(), &tip_account, 10_000);
+    assert!(matches!(
+        decode(&tipped),
+        BundleInstruction::ExecuteBundle { max_compute_units: 150_000, dry_run: false, jito_tip_lamports: 10_000, .. },
+    ));
+    assert_eq!(tipped.accounts.last().map(|meta| meta.pubkey), Some(tip_account));
+    let sliced = client::with_slice(tipped, 2, 3);
+    assert!(matches!(
+        decode(&sliced),
+        BundleInstruction::ExecuteBundle { jito_tip_lamports: 10_000, start_index: 2, count: 3, .. },
+    ));
+
+    // The multiplier is in tenths, so 20 doubles the recent fee
+    let config = ManagerConfig { priority_fee_multiplier: 20, ..Default::default() };
+    let budgeted = client::with_compute_budget(&config, execute.clone()).unwrap();
+    assert_eq!(budgeted[..2], client::compute_budget_instructions(150_000, 18));
+    assert_eq!(budgeted[2], execute);
+    let simulate = client::simulate_bundle(&manager, &bundle, &authority, false, &[], 150_000, 9);
+    assert!(matches!(decode(&simulate), BundleInstruction::ExecuteBundle { dry_run: true, .. }));
+    assert_eq!(simulate.accounts, execute.accounts);
+    // Without a log the program id fills the execution log slot
+    assert_eq!(execute.accounts[5].pubkey, bundle_manager::id());
+}
+
+#[test]
+fn error_codes_decode_to_their_variant() {
+    // Codes are contiguous from BUNDLE_ERROR_BASE up to the newest variant
+    let errors: Vec<BundleError> = (BUNDLE_ERROR_BASE..).map_while(BundleError::from_code).collect();
+    assert_eq!(errors.first(), Some(&BundleError::ManagerPaused));
+    assert_eq!(errors.last(), Some(&BundleError::DuplicateWallet));
+    for error in errors {
+        let program_error = ProgramError::from(error);
+        assert_eq!(BundleError::from_program_error(&program_error), Some(error));
+    }
+
+    assert_eq!(BundleError::from_code(BUNDLE_ERROR_BASE - 1), None);
+    assert_eq!(BundleError::from_program_error(&ProgramError::MissingRequiredSignature), None);
+
+    // Records keep custom codes as they are and builtin errors by their index
+    assert_eq!(error_code(&BundleError::RetryLimitReached.into()), 1052);
+    assert_eq!(error_code(&ProgramError::Custom(0)), 0);
+    assert_eq!(error_code(&ProgramError::MissingRequiredSignature), 8);
+}
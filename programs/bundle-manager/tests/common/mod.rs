@@ -0,0 +1,630 @@
+//! In-process test harness.
+//!
+//! The integration tests run `process_instruction` directly against accounts
+//! kept in memory, with the syscalls the program makes routed through
+//! `program_stubs`:
+//!
+//! - Clock and Rent come from the `TestContext`.
+//! - CPIs into the system program (CreateAccount, Transfer) are emulated.
+//! - CPIs into this program, or into a mock program registered with
+//!   `TestContext::add_program`, are dispatched in-process, with the same
+//!   signer and writable privilege checks the runtime applies.
+//! - A failing callee fails the whole transaction, as on chain.
+//! - Logs, `sol_log_data` events and return data are captured for asserts.
+//! - Each transaction has `COMPUTE_UNIT_LIMIT` units, and every CPI
+//!   consumes `TestContext::cpi_compute_cost` of them.
+//!
+//! Every `process` call is a transaction: if an instruction fails, every
+//! account is rolled back to its state before the call.
+
+#![allow(dead_code)]
+
+use std::{
+    cell::{RefCell, UnsafeCell},
+    collections::HashMap,
+    slice,
+    sync::Once,
+};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use bundle_manager::{
+    client, events::BundleEvent, find_bundle_address, find_instruction_record_address, find_manager_address,
+    Bundle, BundleInstructionRecord, BundleManager, FundWallets, InstructionOptions, ManagerConfig,
+    COMPUTE_BUDGET_PROGRAM_ID,
+};
+use solana_program::{
+    account_info::AccountInfo,
+    bpf_loader_upgradeable,
+    clock::Clock,
+    entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE, SUCCESS},
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
+};
+
+pub const SOL: u64 = 1_000_000_000;
+
+/// Compute units each transaction starts with
+pub const COMPUTE_UNIT_LIMIT: u64 = 200_000;
+
+/// Entrypoint of a mock program a test registers for CPIs to reach
+pub type MockProgram = fn(&Pubkey, &[AccountInfo], &[u8]) -> ProgramResult;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Account {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+// Per-thread state the syscall stubs read and write. Tests run on their own
+// threads, so contexts in concurrent tests never see each other.
+#[derive(Default)]
+struct Runtime {
+    clock: Clock,
+    programs: HashMap<Pubkey, MockProgram>,
+    program_stack: Vec<Pubkey>,
+    return_data: Option<(Pubkey, Vec<u8>)>,
+    logs: Vec<String>,
+    events: Vec<Vec<u8>>,
+    aborted: Option<ProgramError>,
+    cpi_compute_cost: u64,
+    compute_used: u64,
+}
+
+thread_local! {
+    static RUNTIME: RefCell<Runtime> = RefCell::new(Runtime::default());
+}
+
+fn with_runtime<T>(f: impl FnOnce(&mut Runtime) -> T) -> T {
+    RUNTIME.with(|runtime| f(&mut runtime.borrow_mut()))
+}
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_log(&self, message: &str) {
+        println!("{message}");
+        with_runtime(|runtime| runtime.logs.push(message.to_string()));
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        with_runtime(|runtime| runtime.events.push(fields.concat()));
+    }
+
+    fn sol_remaining_compute_units(&self) -> u64 {
+        with_runtime(|runtime| COMPUTE_UNIT_LIMIT.saturating_sub(runtime.compute_used))
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        invoke(instruction, account_infos, signers_seeds)
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = with_runtime(|runtime| runtime.clock.clone());
+        unsafe { *(var_addr as *mut Clock) = clock };
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        SUCCESS
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        with_runtime(|runtime| runtime.return_data.clone())
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        with_runtime(|runtime| {
+            let program_id = *runtime.program_stack.last().expect("return data set outside a program");
+            runtime.return_data = (!data.is_empty()).then(|| (program_id, data.to_vec()));
+        });
+    }
+}
+
+// A CPI: checks the callee only gets privileges the caller holds or signs
+// for with its PDA seeds, then runs it on the caller's accounts.
+fn invoke(instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+    let caller = with_runtime(|runtime| {
+        runtime.compute_used += runtime.cpi_compute_cost;
+        *runtime.program_stack.last().expect("CPI outside a program")
+    });
+    let pda_signers = signers_seeds.iter()
+        .map(|seeds| Pubkey::create_program_address(seeds, &caller))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+
+    if !account_infos.iter().any(|info| *info.key == instruction.program_id) {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    let mut infos = Vec::with_capacity(instruction.accounts.len());
+    for meta in &instruction.accounts {
+        let info = account_infos.iter()
+            .find(|info| *info.key == meta.pubkey)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if meta.is_signer && !info.is_signer && !pda_signers.contains(&meta.pubkey) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if meta.is_writable && !info.is_writable {
+            return Err(ProgramError::InvalidArgument);
+        }
+        infos.push(AccountInfo {
+            is_signer: meta.is_signer,
+            is_writable: meta.is_writable,
+            ..info.clone()
+        });
+    }
+
+    let result = dispatch(&instruction.program_id, &infos, &instruction.data);
+    if let Err(err) = &result {
+        // The runtime fails the transaction as soon as a callee fails,
+        // whatever the caller does with the error
+        with_runtime(|runtime| {
+            runtime.aborted.get_or_insert(err.clone());
+        });
+    }
+    result
+}
+
+fn dispatch(program_id: &Pubkey, infos: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let mock = with_runtime(|runtime| {
+        runtime.program_stack.push(*program_id);
+        runtime.programs.get(program_id).copied()
+    });
+
+    let result = if *program_id == bundle_manager::id() {
+        bundle_manager::process_instruction(program_id, infos, data)
+    } else if *program_id == system_program::id() {
+        process_system_instruction(infos, data)
+    } else if *program_id == COMPUTE_BUDGET_PROGRAM_ID {
+        Ok(())
+    } else if let Some(mock) = mock {
+        mock(program_id, infos, data)
+    } else {
+        Err(ProgramError::IncorrectProgramId)
+    };
+
+    with_runtime(|runtime| runtime.program_stack.pop());
+    result
+}
+
+// The subset of the system program the bundle manager uses. Error codes
+// match SystemError.
+fn process_system_instruction(infos: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    const ACCOUNT_ALREADY_IN_USE: u32 = 0;
+    const RESULT_WITH_NEGATIVE_LAMPORTS: u32 = 1;
+
+    let read_u64 = |offset: usize| -> Result<u64, ProgramError> {
+        let bytes = data.get(offset..offset + 8).ok_or(ProgramError::InvalidInstructionData)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let debit = |from: &AccountInfo, to: &AccountInfo, lamports: u64| -> ProgramResult {
+        if !from.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if from.lamports() < lamports {
+            return Err(ProgramError::Custom(RESULT_WITH_NEGATIVE_LAMPORTS));
+        }
+        **from.try_borrow_mut_lamports()? -= lamports;
+        **to.try_borrow_mut_lamports()? += lamports;
+        Ok(())
+    };
+
+    let tag = data.get(..4).ok_or(ProgramError::InvalidInstructionData)?;
+    let [from, to] = infos else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    match u32::from_le_bytes(tag.try_into().unwrap()) {
+        // CreateAccount { lamports, space, owner }
+        0 => {
+            let lamports = read_u64(4)?;
+            let space = read_u64(12)?;
+            let owner = data.get(20..52)
+                .map(|bytes| Pubkey::try_from(bytes).unwrap())
+                .ok_or(ProgramError::InvalidInstructionData)?;
+            if !to.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if to.lamports() > 0 || to.data_len() > 0 || *to.owner != system_program::id() {
+                return Err(ProgramError::Custom(ACCOUNT_ALREADY_IN_USE));
+            }
+            debit(from, to, lamports)?;
+            to.realloc(space as usize, true)?;
+            to.assign(&owner);
+            Ok(())
+        },
+        // Transfer { lamports }
+        2 => {
+            if *from.owner != system_program::id() || from.data_len() > 0 {
+                return Err(ProgramError::InvalidArgument);
+            }
+            debit(from, to, read_u64(4)?)
+        },
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+// Backing memory of one AccountInfo, laid out the way the runtime serializes
+// accounts so `AccountInfo::realloc` works: the original data length sits
+// right before the key, and the data is preceded by its current length and
+// followed by MAX_PERMITTED_DATA_INCREASE spare bytes.
+#[repr(C)]
+struct KeySlot {
+    original_data_len: u32,
+    key: Pubkey,
+}
+
+struct AccountSlot {
+    key: Box<KeySlot>,
+    lamports: u64,
+    owner: Box<UnsafeCell<Pubkey>>,
+    executable: bool,
+    // u64 words keep the length prefix aligned
+    data: Vec<u64>,
+    data_len: usize,
+}
+
+impl AccountSlot {
+    fn new(key: Pubkey, account: &Account) -> Self {
+        let data_len = account.data.len();
+        let mut data = vec![0u64; 1 + (data_len + MAX_PERMITTED_DATA_INCREASE).div_ceil(8)];
+        data[0] = data_len as u64;
+        let mut slot = Self {
+            key: Box::new(KeySlot { original_data_len: data_len as u32, key }),
+            lamports: account.lamports,
+            owner: Box::new(UnsafeCell::new(account.owner)),
+            executable: account.executable,
+            data,
+            data_len,
+        };
+        slot.data_mut().copy_from_slice(&account.data);
+        slot
+    }
+
+    fn data_mut<'a>(&mut self) -> &'a mut [u8] {
+        // The slot outlives every AccountInfo made from it
+        unsafe { slice::from_raw_parts_mut((self.data.as_mut_ptr() as *mut u8).add(8), self.data_len) }
+    }
+
+    // The data as the runtime reads it back: the length from the prefix word,
+    // whatever the program did to its own `RefCell` slice
+    fn committed_data(&self) -> Vec<u8> {
+        let len = self.data[0] as usize;
+        let bytes = unsafe { slice::from_raw_parts((self.data.as_ptr() as *const u8).add(8), len) };
+        bytes.to_vec()
+    }
+
+    fn info(&mut self, is_signer: bool, is_writable: bool) -> AccountInfo<'_> {
+        let data = self.data_mut();
+        AccountInfo::new(
+            &self.key.key,
+            is_signer,
+            is_writable,
+            &mut self.lamports,
+            data,
+            unsafe { &*self.owner.get() },
+            self.executable,
+            0,
+        )
+    }
+}
+
+pub struct TestContext {
+    pub accounts: HashMap<Pubkey, Account>,
+    pub clock: Clock,
+    /// Compute units every CPI consumes, 0 unless a test sets it
+    pub cpi_compute_cost: u64,
+}
+
+impl TestContext {
+    pub fn new() -> Self {
+        static INSTALL_STUBS: Once = Once::new();
+        INSTALL_STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(Stubs));
+        });
+        with_runtime(|runtime| *runtime = Runtime::default());
+
+        let mut ctx = Self {
+            accounts: HashMap::new(),
+            clock: Clock { slot: 100, unix_timestamp: 1_700_000_000, ..Clock::default() },
+            cpi_compute_cost: 0,
+        };
+        for program_id in [system_program::id(), bundle_manager::id(), COMPUTE_BUDGET_PROGRAM_ID] {
+            ctx.add_program_account(program_id);
+        }
+        ctx
+    }
+
+    fn add_program_account(&mut self, program_id: Pubkey) {
+        self.accounts.insert(program_id, Account {
+            lamports: 1,
+            data: vec![],
+            owner: bpf_loader_upgradeable::id(),
+            executable: true,
+        });
+    }
+
+    /// Registers a mock program that CPIs and top-level instructions can call
+    pub fn add_program(&mut self, program_id: Pubkey, entry: MockProgram) {
+        self.add_program_account(program_id);
+        with_runtime(|runtime| runtime.programs.insert(program_id, entry));
+    }
+
+    /// Adds `lamports` to `key`, creating it as a system account if needed
+    pub fn fund(&mut self, key: &Pubkey, lamports: u64) {
+        self.accounts.entry(*key).or_default().lamports += lamports;
+    }
+
+    /// A fresh system account holding `lamports`
+    pub fn funded_key(&mut self, lamports: u64) -> Pubkey {
+        let key = Pubkey::new_unique();
+        self.fund(&key, lamports);
+        key
+    }
+
+    pub fn set_account(&mut self, key: Pubkey, account: Account) {
+        self.accounts.insert(key, account);
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<&Account> {
+        self.accounts.get(key)
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.accounts.get(key).map_or(0, |account| account.lamports)
+    }
+
+    pub fn data(&self, key: &Pubkey) -> &[u8] {
+        &self.accounts.get(key).expect("account exists").data
+    }
+
+    pub fn manager(&self, key: &Pubkey) -> BundleManager {
+        BundleManager::unpack(self.data(key)).unwrap()
+    }
+
+    pub fn bundle(&self, key: &Pubkey) -> Bundle {
+        Bundle::unpack(self.data(key)).unwrap()
+    }
+
+    pub fn record(&self, key: &Pubkey) -> BundleInstructionRecord {
+        BundleInstructionRecord::unpack(self.data(key)).unwrap()
+    }
+
+    /// Rewrites an account's stored state, e.g. to reach a state that would
+    /// take too many transactions to set up
+    pub fn write_state(&mut self, key: &Pubkey, state: &impl BorshSerialize) {
+        let account = self.accounts.get_mut(key).expect("account exists");
+        let bytes = state.try_to_vec().unwrap();
+        account.data[..bytes.len()].copy_from_slice(&bytes);
+    }
+
+    pub fn warp_slots(&mut self, slots: u64) {
+        self.clock.slot += slots;
+    }
+
+    pub fn advance_time(&mut self, seconds: i64) {
+        self.clock.unix_timestamp += seconds;
+    }
+
+    /// Return data set by the last transaction
+    pub fn return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        with_runtime(|runtime| runtime.return_data.clone())
+    }
+
+    /// Events emitted by the last transaction, in order
+    pub fn events(&self) -> Vec<BundleEvent> {
+        with_runtime(|runtime| {
+            runtime.events.iter()
+                .map(|data| BundleEvent::try_from_slice(data).expect("event decodes"))
+                .collect()
+        })
+    }
+
+    /// Log lines of the last transaction
+    pub fn logs(&self) -> Vec<String> {
+        with_runtime(|runtime| runtime.logs.clone())
+    }
+
+    /// Runs `instructions` as one transaction signed by `signers`
+    pub fn process(&mut self, instructions: &[Instruction], signers: &[Pubkey]) -> Result<(), ProgramError> {
+        let snapshot = self.accounts.clone();
+        let clock = self.clock.clone();
+        let cpi_compute_cost = self.cpi_compute_cost;
+        with_runtime(|runtime| {
+            runtime.clock = clock;
+            runtime.cpi_compute_cost = cpi_compute_cost;
+            runtime.compute_used = 0;
+            runtime.return_data = None;
+            runtime.logs.clear();
+            runtime.events.clear();
+            runtime.aborted = None;
+        });
+
+        for instruction in instructions {
+            if let Err(err) = self.process_instruction(instruction, signers) {
+                self.accounts = snapshot;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    fn process_instruction(&mut self, instruction: &Instruction, signers: &[Pubkey]) -> Result<(), ProgramError> {
+        // One slot per distinct key; repeated metas share it, as on chain
+        let mut keys: Vec<Pubkey> = Vec::new();
+        for key in instruction.accounts.iter().map(|meta| meta.pubkey).chain([instruction.program_id]) {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        for meta in &instruction.accounts {
+            if meta.is_signer && !signers.contains(&meta.pubkey) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+        }
+
+        let lamports_before: u64 = keys.iter().map(|key| self.lamports(key)).sum();
+        let mut slots: Vec<AccountSlot> = keys.iter()
+            .map(|key| AccountSlot::new(*key, &self.accounts.get(key).cloned().unwrap_or_default()))
+            .collect();
+        let infos: Vec<AccountInfo> = slots.iter_mut()
+            .zip(&keys)
+            .map(|(slot, key)| {
+                let metas = || instruction.accounts.iter().filter(|meta| meta.pubkey == *key);
+                slot.info(signers.contains(key), metas().any(|meta| meta.is_writable))
+            })
+            .collect();
+        let instruction_infos: Vec<AccountInfo> = instruction.accounts.iter()
+            .map(|meta| infos[keys.iter().position(|key| *key == meta.pubkey).unwrap()].clone())
+            .collect();
+
+        let result = dispatch(&instruction.program_id, &instruction_infos, &instruction.data);
+        if let Some(aborted) = with_runtime(|runtime| runtime.aborted.take()) {
+            return Err(aborted);
+        }
+        result?;
+
+        let writable: Vec<bool> = infos.iter().map(|info| info.is_writable).collect();
+        drop(instruction_infos);
+        drop(infos);
+        let updated: Vec<(Pubkey, Account, bool)> = slots.iter()
+            .zip(writable)
+            .map(|(slot, is_writable)| {
+                let account = Account {
+                    lamports: slot.lamports,
+                    data: slot.committed_data(),
+                    owner: unsafe { *slot.owner.get() },
+                    executable: slot.executable,
+                };
+                (slot.key.key, account, is_writable)
+            })
+            .collect();
+        drop(slots);
+
+        let lamports_after: u64 = updated.iter().map(|(_, account, _)| account.lamports).sum();
+        assert_eq!(lamports_before, lamports_after, "instruction created or destroyed lamports");
+        for (key, account, is_writable) in updated {
+            let unchanged = self.accounts.get(&key).cloned().unwrap_or_default() == account;
+            assert!(is_writable || unchanged, "read-only account {key} was modified");
+            // Accounts left without lamports are removed when the transaction ends
+            if account.lamports == 0 {
+                self.accounts.remove(&key);
+            } else {
+                self.accounts.insert(key, account);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A manager owned by `authority`, plus helpers for the common flows
+pub struct Fixture {
+    pub ctx: TestContext,
+    pub authority: Pubkey,
+    pub manager: Pubkey,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Self::with_config(ManagerConfig::default())
+    }
+
+    pub fn with_config(config: ManagerConfig) -> Self {
+        Self::with_initialize(|authority| client::initialize(authority, 0, config))
+    }
+
+    /// A manager created by the Initialize `initialize` builds for the
+    /// fixture's authority at manager index 0
+    pub fn with_initialize(initialize: impl FnOnce(&Pubkey) -> Instruction) -> Self {
+        let mut ctx = TestContext::new();
+        let authority = ctx.funded_key(100 * SOL);
+        let (manager, _) = find_manager_address(&bundle_manager::id(), &authority, 0);
+        ctx.process(&[initialize(&authority)], &[authority])
+            .expect("manager initializes");
+        Self { ctx, authority, manager }
+    }
+
+    /// Client nonce of the fixture's next CreateBundle: the manager's
+    /// `bundle_seed`, which is fresh for every bundle created
+    pub fn next_nonce(&self) -> u64 {
+        self.ctx.manager(&self.manager).bundle_seed as u64
+    }
+
+    /// Address the fixture's next CreateBundle creates
+    pub fn next_bundle(&self) -> Pubkey {
+        find_bundle_address(&bundle_manager::id(), &self.manager, &self.authority, self.next_nonce()).0
+    }
+
+    /// Creates a bundle where each `(wallet_index, count)` entry plans
+    /// `count` instructions for that wallet
+    pub fn create_bundle(&mut self, wallets: &[(u8, u8)]) -> Pubkey {
+        self.try_create_bundle(wallets, None).expect("bundle is created")
+    }
+
+    pub fn try_create_bundle(&mut self, wallets: &[(u8, u8)], funding: Option<FundWallets>) -> Result<Pubkey, ProgramError> {
+        let bundle = self.next_bundle();
+        let instruction = client::create_bundle(&self.manager, self.next_nonce(), &self.authority, wallets, 0, None, funding);
+        self.ctx.process(&[instruction], &[self.authority])?;
+        Ok(bundle)
+    }
+
+    /// Stores `instruction` for `wallet_index` in a new record, returning its address
+    pub fn add_instruction(&mut self, bundle: &Pubkey, wallet_index: u8, instruction: &Instruction) -> Result<Pubkey, ProgramError> {
+        self.add_instruction_with(bundle, wallet_index, instruction, InstructionOptions::default())
+    }
+
+    pub fn add_instruction_with(
+        &mut self,
+        bundle: &Pubkey,
+        wallet_index: u8,
+        instruction: &Instruction,
+        options: InstructionOptions,
+    ) -> Result<Pubkey, ProgramError> {
+        let record = self.next_record(bundle, wallet_index);
+        let add = client::add_instruction(&self.manager, bundle, &record, &self.authority, wallet_index, instruction, options);
+        self.ctx.process(&[add], &[self.authority])?;
+        Ok(record)
+    }
+
+    /// Record PDA the bundle's next added instruction for `wallet_index`
+    /// creates, or its first one if the bundle is not readable
+    pub fn next_record(&self, bundle: &Pubkey, wallet_index: u8) -> Pubkey {
+        let record_index = Bundle::unpack(self.ctx.data(bundle)).map_or(0, |bundle| bundle.next_record_index);
+        find_instruction_record_address(&bundle_manager::id(), bundle, wallet_index, record_index).0
+    }
+
+    /// A system transfer of `lamports` from the authority to `to`
+    pub fn transfer_from_authority(&self, to: &Pubkey, lamports: u64) -> Instruction {
+        system_instruction::transfer(&self.authority, to, lamports)
+    }
+
+    /// Executes `bundle` without an execution log. `accounts` follow the records.
+    pub fn execute(&mut self, bundle: &Pubkey, records: &[Pubkey], accounts: &[AccountMeta]) -> Result<(), ProgramError> {
+        let remaining = execute_accounts(records, accounts);
+        let execute = client::execute_bundle(&self.manager, bundle, &self.authority, false, &remaining, 200_000, 0);
+        self.ctx.process(&[execute], &[self.authority])
+    }
+}
+
+/// Remaining ExecuteBundle accounts: the records, then `accounts`
+pub fn execute_accounts(records: &[Pubkey], accounts: &[AccountMeta]) -> Vec<AccountMeta> {
+    records.iter()
+        .map(|record| AccountMeta::new(*record, false))
+        .chain(accounts.iter().cloned())
+        .collect()
+}
+
+pub fn bundle_error(err: bundle_manager::BundleError) -> ProgramError {
+    err.into()
+}
@@ -0,0 +1,77 @@
+//! The client decoders read back exactly what the program writes.
+
+mod common;
+
+use borsh::BorshSerialize;
+use bundle_manager::client::{self, decode_bundle, decode_manager, BundleSummary};
+use bundle_manager::{Bundle, BundleStatus, CATEGORY_ARBITRAGE, CATEGORY_CUSTOM, CATEGORY_SNIPE};
+use common::{Fixture, SOL};
+use solana_program::{instruction::AccountMeta, pubkey::Pubkey};
+
+#[test]
+fn decoders_round_trip_stored_accounts() {
+    let mut fixture = Fixture::new();
+    let bundle = fixture.create_bundle(&[(0, 1), (3, 2)]);
+
+    let data = fixture.ctx.data(&bundle).to_vec();
+    let decoded = decode_bundle(&data).unwrap();
+    let encoded = decoded.try_to_vec().unwrap();
+    assert_eq!(&data[..encoded.len()], &encoded[..]);
+    assert_eq!(decoded.wallet_indexes, vec![0, 3]);
+
+    let data = fixture.ctx.data(&fixture.manager).to_vec();
+    let decoded = decode_manager(&data).unwrap();
+    let encoded = decoded.try_to_vec().unwrap();
+    assert_eq!(&data[..encoded.len()], &encoded[..]);
+    assert_eq!(decoded.authority, fixture.authority);
+}
+
+#[test]
+fn bundles_filter_by_category_at_its_offset() {
+    let mut fixture = Fixture::new();
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    let categories = [CATEGORY_SNIPE, CATEGORY_ARBITRAGE, CATEGORY_SNIPE, CATEGORY_CUSTOM];
+    let bundles: Vec<Pubkey> = categories.iter()
+        .map(|&category| {
+            let bundle = fixture.next_bundle();
+            let create =
+                client::create_bundle(&manager, fixture.next_nonce(), &authority, &[(0, 1)], category, None, None);
+            fixture.ctx.process(&[create], &[authority]).unwrap();
+            bundle
+        })
+        .collect();
+
+    // What a `getProgramAccounts` memcmp filter on the category byte matches
+    let snipes: Vec<Pubkey> = bundles.iter()
+        .copied()
+        .filter(|bundle| fixture.ctx.data(bundle)[Bundle::CATEGORY_OFFSET] == CATEGORY_SNIPE)
+        .collect();
+    assert_eq!(snipes, vec![bundles[0], bundles[2]]);
+    for (bundle, category) in bundles.iter().zip(categories) {
+        assert_eq!(decode_bundle(fixture.ctx.data(bundle)).unwrap().category, category);
+    }
+}
+
+#[test]
+fn summary_tracks_execution_progress() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 2)]);
+
+    let summary = BundleSummary::decode(fixture.ctx.data(&bundle)).unwrap();
+    assert!(!summary.is_complete);
+    assert_eq!(summary.elapsed_ms, None);
+    assert_eq!(summary.remaining_instructions, 2);
+
+    let records = [
+        fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap(),
+        fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap(),
+    ];
+    fixture.execute(&bundle, &records, &[AccountMeta::new(recipient, false)]).unwrap();
+
+    let summary = BundleSummary::decode(fixture.ctx.data(&bundle)).unwrap();
+    assert_eq!(summary.bundle.status, BundleStatus::Executed);
+    assert!(summary.is_complete);
+    assert_eq!(summary.elapsed_ms, Some(0));
+    assert_eq!(summary.remaining_instructions, 0);
+}
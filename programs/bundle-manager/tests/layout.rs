@@ -0,0 +1,187 @@
+//! Account space helpers cover everything the program serializes into the
+//! accounts they size, with every optional field at its largest.
+
+use borsh::BorshSerialize;
+use bundle_manager::{
+    AccountType, Bundle, BundleInstructionRecord, BundleManager, BundleSeeds, BundleStatus, CreateIfMissing, Delegate,
+    ExecutionLog, ExecutionLogEntry, FundWallets, InstructionAccountMeta, ManagerConfig, RegisteredWallet, SpendLimits,
+    TokenSpendCap, Vault, WalletRegistry, EXECUTED_MASK_LEN, MAX_ALLOWED_PROGRAMS, MAX_APPROVAL_AUTHORITIES,
+    MAX_DELEGATES, MAX_TOKEN_SPEND_CAPS,
+};
+use solana_program::pubkey::Pubkey;
+
+#[test]
+fn manager_fits_its_len() {
+    let manager = BundleManager {
+        account_type: AccountType::Manager,
+        version: BundleManager::VERSION,
+        authority: Pubkey::new_unique(),
+        config: ManagerConfig {
+            max_compute_unit_price: u64::MAX,
+            max_bundles_per_slot: u16::MAX,
+            cooldown_slots: u64::MAX,
+            ..Default::default()
+        },
+        active_bundles: u16::MAX,
+        total_bundles_executed: u32::MAX,
+        total_bundles_failed: u32::MAX,
+        is_paused: true,
+        bundle_seed: u32::MAX,
+        manager_index: u16::MAX,
+        bump: 255,
+        consecutive_failures: u16::MAX,
+        seed_authority: Pubkey::new_unique(),
+        fee_lamports: u64::MAX,
+        fee_collector: Pubkey::new_unique(),
+        tip_account: Pubkey::new_unique(),
+        authorities: (0..MAX_APPROVAL_AUTHORITIES).map(|_| Pubkey::new_unique()).collect(),
+        threshold: MAX_APPROVAL_AUTHORITIES as u8,
+        pending_authority: Some(Pubkey::new_unique()),
+        wallet_registry: true,
+        lookup_table: Some(Pubkey::new_unique()),
+        delegates: (0..MAX_DELEGATES)
+            .map(|_| Delegate { key: Pubkey::new_unique(), permissions: Default::default() })
+            .collect(),
+        total_instructions_executed: u64::MAX,
+        total_compute_units: u64::MAX,
+        total_priority_fees_paid: u64::MAX,
+        throttle_slot: u64::MAX,
+        bundles_in_slot: u16::MAX,
+        last_execution_slot: u64::MAX,
+        allowed_programs: (0..MAX_ALLOWED_PROGRAMS).map(|_| Pubkey::new_unique()).collect(),
+    };
+    assert_eq!(manager.try_to_vec().unwrap().len(), BundleManager::LEN);
+}
+
+#[test]
+fn bundle_fits_its_space() {
+    for (wallet_count, hook_data) in [(1, None), (5, Some(0)), (32, Some(200))] {
+        let on_failure = hook_data.map(|len| (Pubkey::new_unique(), vec![7; len]));
+        let bundle = Bundle {
+            account_type: AccountType::Bundle,
+            version: Bundle::VERSION,
+            manager: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            bundle_id: 9,
+            created_at: 1,
+            execution_started_at: 2,
+            execution_completed_at: 3,
+            wallet_count: wallet_count as u8,
+            category: 1,
+            wallet_indexes: vec![0; wallet_count],
+            instructions_per_wallet: vec![1; wallet_count],
+            instructions_added: vec![1; wallet_count],
+            status: BundleStatus::PartiallyExecuted,
+            priority_fee: 5_000,
+            on_failure: on_failure.clone(),
+            funding: Some(FundWallets { lamports_each: 1, wallet_count: 1 }),
+            expected_net_lamports: -1,
+            last_attempt_slot: 4,
+            last_executed_index: Some(3),
+            executed_mask: [0xff; EXECUTED_MASK_LEN],
+            next_record_index: 7,
+            seeds: Some(BundleSeeds { client_nonce: u64::MAX, bump: 255 }),
+            compute_unit_limit: u32::MAX,
+            execute_after: i64::MAX,
+            max_retries: Some(u8::MAX),
+            retry_count: u8::MAX,
+            expires_at: i64::MAX,
+            spend_limits: Some(SpendLimits {
+                max_lamports_out: Some(u64::MAX),
+                token_caps: (0..MAX_TOKEN_SPEND_CAPS)
+                    .map(|_| TokenSpendCap { mint: Pubkey::new_unique(), max_amount: u64::MAX })
+                    .collect(),
+            }),
+        };
+        let space = Bundle::space(wallet_count, &on_failure);
+        assert_eq!(bundle.try_to_vec().unwrap().len(), space);
+    }
+}
+
+#[test]
+fn instruction_records_fit_their_space() {
+    let system_account = CreateIfMissing::SystemAccount {
+        address: Pubkey::new_unique(),
+        space: 165,
+        owner: Pubkey::new_unique(),
+    };
+    let token_account = CreateIfMissing::AssociatedTokenAccount {
+        wallet: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        token_program: Pubkey::new_unique(),
+    };
+
+    for (data_len, account_count, create_if_missing) in
+        [(0, 0, None), (12, 3, Some(system_account)), (1_232, 64, Some(token_account))]
+    {
+        let record = BundleInstructionRecord {
+            account_type: AccountType::InstructionRecord,
+            version: BundleInstructionRecord::VERSION,
+            bundle: Pubkey::new_unique(),
+            wallet_index: 3,
+            program_id: Pubkey::new_unique(),
+            instruction_data: vec![1; data_len],
+            accounts: (0..account_count)
+                .map(|_| InstructionAccountMeta { pubkey: Pubkey::new_unique(), is_signer: true, is_writable: true })
+                .collect(),
+            executed: true,
+            compressed: true,
+            reserved: 0,
+            create_if_missing,
+            last_error: Some(u32::MAX),
+        };
+        let space = BundleInstructionRecord::space(data_len, account_count);
+        assert!(record.try_to_vec().unwrap().len() <= space);
+    }
+}
+
+#[test]
+fn execution_log_fits_its_space() {
+    let log = ExecutionLog {
+        account_type: AccountType::ExecutionLog,
+        version: ExecutionLog::VERSION,
+        manager: Pubkey::new_unique(),
+        max_entries: 16,
+        next_index: 15,
+        total_appended: 31,
+    };
+    assert_eq!(log.try_to_vec().unwrap().len(), ExecutionLog::space(0));
+
+    let entry = ExecutionLogEntry {
+        executor: Pubkey::new_unique(),
+        bundle_id: 1,
+        slot: 2,
+        status: BundleStatus::Executed,
+        compute_units: 3,
+    };
+    assert_eq!(entry.try_to_vec().unwrap().len(), ExecutionLog::space(1) - ExecutionLog::space(0));
+}
+
+#[test]
+fn wallet_registry_fits_its_space() {
+    for wallet_count in [0, 1, 256] {
+        let registry = WalletRegistry {
+            account_type: AccountType::WalletRegistry,
+            version: WalletRegistry::VERSION,
+            manager: Pubkey::new_unique(),
+            bump: 255,
+            wallets: (0..wallet_count)
+                .map(|index| RegisteredWallet { wallet_index: index as u8, address: Pubkey::new_unique(), is_pda: true })
+                .collect(),
+        };
+        assert_eq!(registry.try_to_vec().unwrap().len(), WalletRegistry::space(wallet_count));
+    }
+}
+
+#[test]
+fn vault_fits_its_len() {
+    let vault = Vault {
+        account_type: AccountType::Vault,
+        version: Vault::VERSION,
+        manager: Pubkey::new_unique(),
+        bump: 255,
+        total_deposited: u64::MAX,
+        total_withdrawn: u64::MAX,
+    };
+    assert_eq!(vault.try_to_vec().unwrap().len(), Vault::LEN);
+}
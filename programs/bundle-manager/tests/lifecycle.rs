@@ -0,0 +1,1658 @@
+//! A bundle's full lifecycle, from Initialize to ExecuteBundle, and the
+//! checks that stop each step when the caller has no business running it.
+
+mod common;
+
+use std::cell::{Cell, RefCell};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use bundle_manager::{
+    client,
+    events::{BundleEvent, InstructionAddedEvent},
+    find_instruction_record_address, find_manager_address, find_wallet_address, find_wallet_registry_address,
+    BundleError, BundleInstruction, BundleStatus, CostEstimate, CreateIfMissing, ExecutionState, FundWallets,
+    InstructionOptions, ManagerConfig, RegisteredWallet, SpendLimits, TokenSpendCap, WalletRegistry,
+    GARBAGE_COLLECT_BOUNTY_LAMPORTS, MAX_BUNDLE_INSTRUCTIONS, MAX_TOKEN_SPEND_CAPS,
+};
+use common::{bundle_error, Account, Fixture, SOL};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+};
+
+thread_local! {
+    static FLAKY_FAILURES: Cell<u32> = const { Cell::new(0) };
+    static HOOK_CALLS: RefCell<Vec<(Pubkey, Vec<u8>)>> = const { RefCell::new(Vec::new()) };
+}
+
+// Fails with custom error 7 while `FLAKY_FAILURES` is non-zero, counting it down
+fn mock_flaky_program(_program_id: &Pubkey, _accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    FLAKY_FAILURES.with(|failures| match failures.get() {
+        0 => Ok(()),
+        left => {
+            failures.set(left - 1);
+            Err(ProgramError::Custom(7))
+        },
+    })
+}
+
+// Records the bundle account and data of every call
+fn mock_hook_program(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    HOOK_CALLS.with(|calls| calls.borrow_mut().push((*accounts[0].key, data.to_vec())));
+    Ok(())
+}
+
+// Options creating `address` right before the instruction runs. ExecuteBundle
+// fails the bundle itself when `address` is not passed.
+fn create_first(address: Pubkey) -> InstructionOptions {
+    InstructionOptions {
+        create_if_missing: Some(CreateIfMissing::SystemAccount { address, space: 0, owner: system_program::id() }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn executes_a_bundle_of_transfers() {
+    let mut fixture = Fixture::new();
+    let first = Pubkey::new_unique();
+    let second = Pubkey::new_unique();
+
+    let bundle = fixture.create_bundle(&[(0, 2)]);
+    let records = [
+        fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&first, SOL)).unwrap(),
+        fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&second, 2 * SOL)).unwrap(),
+    ];
+    assert_eq!(fixture.ctx.bundle(&bundle).expected_net_lamports, -3 * SOL as i64);
+    assert_eq!(fixture.ctx.events(), vec![BundleEvent::InstructionAdded(InstructionAddedEvent {
+        manager: fixture.manager,
+        bundle,
+        bundle_id: fixture.ctx.bundle(&bundle).bundle_id,
+        record: records[1],
+        record_index: 1,
+        wallet_index: 0,
+        program_id: system_program::id(),
+    })]);
+
+    let authority_before = fixture.ctx.lamports(&fixture.authority);
+    fixture.ctx.advance_time(5);
+    fixture.execute(&bundle, &records, &[AccountMeta::new(first, false), AccountMeta::new(second, false)])
+        .unwrap();
+
+    assert_eq!(fixture.ctx.lamports(&first), SOL);
+    assert_eq!(fixture.ctx.lamports(&second), 2 * SOL);
+    assert_eq!(fixture.ctx.lamports(&fixture.authority), authority_before - 3 * SOL);
+
+    let state = fixture.ctx.bundle(&bundle);
+    assert_eq!(state.status, BundleStatus::Executed);
+    assert!(state.execution_started_at > 0);
+    assert!(state.execution_completed_at >= state.execution_started_at);
+    assert!(state.is_executed(0) && state.is_executed(1));
+    for record in &records {
+        assert!(fixture.ctx.record(record).executed);
+    }
+
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!(manager.active_bundles, 0);
+    assert_eq!(manager.total_bundles_executed, 1);
+
+    let executed = fixture.ctx.events().into_iter().find_map(|event| match event {
+        BundleEvent::BundleExecuted(executed) => Some(executed),
+        _ => None,
+    });
+    let executed = executed.expect("BundleExecuted is emitted");
+    assert_eq!(executed.bundle_id, state.bundle_id);
+    assert_eq!(executed.measured_net_lamports, executed.expected_net_lamports);
+    assert!(!fixture.ctx.logs().iter().any(|line| line.contains("lamport discrepancy")));
+}
+
+#[test]
+fn lamport_discrepancies_are_logged() {
+    let mut fixture = Fixture::new();
+    let authority = fixture.authority;
+    // Only plain transfers are counted in the expected net, so funding a new
+    // account with CreateAccount goes unexpected
+    let created = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let create = system_instruction::create_account(&authority, &created, SOL, 0, &system_program::id());
+    let record = fixture.add_instruction(&bundle, 0, &create).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).expected_net_lamports, 0);
+
+    let remaining = common::execute_accounts(&[record], &[AccountMeta::new(created, true)]);
+    let execute = client::execute_bundle(&fixture.manager, &bundle, &authority, false, &remaining, 200_000, 0);
+    fixture.ctx.process(&[execute], &[authority, created]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+    assert_eq!(fixture.ctx.lamports(&created), SOL);
+
+    let discrepancy = format!(
+        "Bundle {} lamport discrepancy: expected 0 net, measured {}",
+        fixture.ctx.bundle(&bundle).bundle_id,
+        -(SOL as i64),
+    );
+    assert!(fixture.ctx.logs().contains(&discrepancy));
+    let executed = fixture.ctx.events().into_iter().find_map(|event| match event {
+        BundleEvent::BundleExecuted(executed) => Some(executed),
+        _ => None,
+    });
+    let executed = executed.expect("BundleExecuted is emitted");
+    assert_eq!((executed.expected_net_lamports, executed.measured_net_lamports), (0, -(SOL as i64)));
+}
+
+#[test]
+fn executed_bundles_pay_the_protocol_fee() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let collector = Pubkey::new_unique();
+
+    let stranger = fixture.ctx.funded_key(SOL);
+    let set_fee = client::set_fee(&fixture.manager, &stranger, 5_000, &collector);
+    assert_eq!(fixture.ctx.process(&[set_fee], &[stranger]), Err(ProgramError::MissingRequiredSignature));
+    let set_fee = client::set_fee(&fixture.manager, &fixture.authority, 5_000, &collector);
+    fixture.ctx.process(&[set_fee], &[fixture.authority]).unwrap();
+
+    let accounts = [AccountMeta::new(recipient, false), AccountMeta::new(collector, false)];
+    let funding = FundWallets { lamports_each: 1_000 * SOL, wallet_count: 1 };
+    let failed = fixture.try_create_bundle(&[(0, 1)], Some(funding)).unwrap();
+    let record = fixture.add_instruction(&failed, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    fixture.execute(&failed, &[record], &accounts).unwrap();
+    assert_eq!(fixture.ctx.bundle(&failed).status, BundleStatus::Failed);
+    assert_eq!(fixture.ctx.lamports(&collector), 0);
+
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    assert_eq!(
+        fixture.execute(&bundle, &[record], &accounts[..1]),
+        Err(ProgramError::NotEnoughAccountKeys),
+    );
+
+    let authority_before = fixture.ctx.lamports(&fixture.authority);
+    fixture.execute(&bundle, &[record], &accounts).unwrap();
+    assert_eq!(fixture.ctx.lamports(&collector), 5_000);
+    assert_eq!(fixture.ctx.lamports(&fixture.authority), authority_before - SOL - 5_000);
+
+    let executed = fixture.ctx.events().into_iter().find_map(|event| match event {
+        BundleEvent::BundleExecuted(executed) => Some(executed),
+        _ => None,
+    });
+    assert_eq!(executed.expect("BundleExecuted is emitted").protocol_fee, 5_000);
+}
+
+#[test]
+fn cost_estimates_match_what_execution_spends() {
+    let mut fixture = Fixture::with_config(ManagerConfig { priority_fee_multiplier: 15, ..Default::default() });
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    let recipient = Pubkey::new_unique();
+    let collector = Pubkey::new_unique();
+    let tip_account = Pubkey::new_unique();
+    let setup = [
+        client::set_fee(&manager, &authority, 5_000, &collector),
+        client::set_tip_account(&manager, &authority, &tip_account),
+    ];
+    fixture.ctx.process(&setup, &[authority]).unwrap();
+    let wallets: Vec<Pubkey> = (0..2)
+        .map(|index| find_wallet_address(&bundle_manager::id(), &manager, index).0)
+        .collect();
+    let funding = FundWallets { lamports_each: SOL, wallet_count: 2 };
+    let bundle = fixture.try_create_bundle(&[(0, 1), (1, 1)], Some(funding)).unwrap();
+    let records: Vec<Pubkey> = wallets.iter()
+        .enumerate()
+        .map(|(index, wallet)| {
+            let transfer = system_instruction::transfer(wallet, &recipient, SOL / 4);
+            fixture.add_instruction(&bundle, index as u8, &transfer).unwrap()
+        })
+        .collect();
+
+    // 2_000 micro-lamports recent, paid at 1.5x over 200_000 CU
+    fixture.ctx.process(&[client::estimate_cost(&manager, &bundle, 200_000, 2_000, 10_000)], &[]).unwrap();
+    let (program, data) = fixture.ctx.return_data().expect("the estimate is returned");
+    assert_eq!(program, bundle_manager::id());
+    let estimate = CostEstimate::try_from_slice(&data).unwrap();
+    assert_eq!(estimate, CostEstimate {
+        funding_lamports: 2 * SOL,
+        protocol_fee_lamports: 5_000,
+        priority_fee_lamports: 600,
+        jito_tip_lamports: 10_000,
+        total_lamports: 2 * SOL + 5_000 + 600 + 10_000,
+    });
+
+    let accounts: Vec<AccountMeta> = wallets.iter()
+        .chain([&recipient, &collector])
+        .map(|key| AccountMeta::new(*key, false))
+        .collect();
+    let remaining = common::execute_accounts(&records, &accounts);
+    let execute = client::execute_bundle(&manager, &bundle, &authority, false, &remaining, 200_000, 2_000);
+    let authority_before = fixture.ctx.lamports(&authority);
+    fixture.ctx.process(&[client::with_jito_tip(execute, &tip_account, 10_000)], &[authority]).unwrap();
+    let executed = fixture.ctx.bundle(&bundle);
+    assert_eq!(executed.status, BundleStatus::Executed);
+    // The runtime charges the priority fee to the fee payer, which this harness does not, so the program's record of
+    // it stands in for that part
+    assert_eq!(executed.priority_fee, estimate.priority_fee_lamports);
+    assert_eq!(fixture.ctx.manager(&manager).total_priority_fees_paid, estimate.priority_fee_lamports);
+    let spent = authority_before - fixture.ctx.lamports(&authority);
+    assert_eq!(spent + executed.priority_fee, estimate.total_lamports);
+}
+
+#[test]
+fn tips_go_to_the_manager_tip_account() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let tip_account = Pubkey::new_unique();
+    let set_tip = client::set_tip_account(&fixture.manager, &fixture.authority, &tip_account);
+    fixture.ctx.process(&[set_tip], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.manager(&fixture.manager).tip_account, tip_account);
+
+    // A zero tip needs no tip account
+    let untipped = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&untipped, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    fixture.execute(&untipped, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+    assert_eq!(fixture.ctx.lamports(&tip_account), 0);
+
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let remaining = common::execute_accounts(&[record], &[AccountMeta::new(recipient, false)]);
+    let execute = client::execute_bundle(&fixture.manager, &bundle, &fixture.authority, false, &remaining, 200_000, 0);
+    let stray = client::with_jito_tip(execute.clone(), &Pubkey::new_unique(), 10_000);
+    assert_eq!(fixture.ctx.process(&[stray], &[fixture.authority]), Err(ProgramError::NotEnoughAccountKeys));
+
+    let authority_before = fixture.ctx.lamports(&fixture.authority);
+    let tipped = client::with_jito_tip(execute, &tip_account, 10_000);
+    fixture.ctx.process(&[tipped], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.lamports(&tip_account), 10_000);
+    assert_eq!(fixture.ctx.lamports(&fixture.authority), authority_before - SOL - 10_000);
+
+    let executed = fixture.ctx.events().into_iter().find_map(|event| match event {
+        BundleEvent::BundleExecuted(executed) => Some(executed),
+        _ => None,
+    });
+    assert_eq!(executed.expect("BundleExecuted is emitted").jito_tip, 10_000);
+}
+
+#[test]
+fn ephemeral_results_skip_the_result_fields_without_costing_more() {
+    // Compute units each mode reports for the same bundle, and the bundle it leaves
+    let run = |ephemeral_results: bool| {
+        let mut fixture = Fixture::with_config(ManagerConfig { ephemeral_results, ..Default::default() });
+        fixture.ctx.cpi_compute_cost = 1_000;
+        let recipients: Vec<Pubkey> = (0..2).map(|_| Pubkey::new_unique()).collect();
+        let bundle = fixture.create_bundle(&[(0, 2)]);
+        let records: Vec<Pubkey> = recipients.iter()
+            .map(|recipient| {
+                let transfer = fixture.transfer_from_authority(recipient, SOL);
+                fixture.add_instruction(&bundle, 0, &transfer).unwrap()
+            })
+            .collect();
+        let accounts: Vec<AccountMeta> =
+            recipients.iter().map(|recipient| AccountMeta::new(*recipient, false)).collect();
+        fixture.ctx.advance_time(5);
+        fixture.execute(&bundle, &records, &accounts).unwrap();
+        let compute_units = fixture.ctx.events().into_iter().find_map(|event| match event {
+            BundleEvent::BundleExecuted(executed) => Some(executed.compute_units),
+            _ => None,
+        });
+        (compute_units.expect("BundleExecuted is emitted"), fixture.ctx.bundle(&bundle))
+    };
+
+    let (persistent_units, persistent) = run(false);
+    let (ephemeral_units, ephemeral) = run(true);
+    assert_eq!((persistent.status, ephemeral.status), (BundleStatus::Executed, BundleStatus::Executed));
+    assert!(persistent.execution_started_at > 0 && persistent.execution_completed_at > 0);
+    assert!(persistent.compute_unit_limit > 0);
+    assert_eq!((ephemeral.execution_started_at, ephemeral.execution_completed_at), (0, 0));
+    assert_eq!((ephemeral.priority_fee, ephemeral.compute_unit_limit), (0, 0));
+    // The harness meters CPIs only, and both modes make the same ones; on
+    // chain the skipped writes make ephemeral results cheaper still
+    assert!(persistent_units > 0);
+    assert!(ephemeral_units <= persistent_units);
+}
+
+#[test]
+fn scheduled_bundles_execute_once_their_time_has_come() {
+    let mut fixture = Fixture::with_config(ManagerConfig { expiry_seconds: 60, ..Default::default() });
+    let recipient = Pubkey::new_unique();
+    let execute_after = fixture.ctx.clock.unix_timestamp + 3_600;
+    let bundle = fixture.next_bundle();
+    let nonce = fixture.next_nonce();
+    let create = client::create_bundle(&fixture.manager, nonce, &fixture.authority, &[(0, 1)], 0, None, None);
+    let scheduled = client::with_execute_after(create, execute_after);
+    fixture.ctx.process(std::slice::from_ref(&scheduled), &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).execute_after, execute_after);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+
+    // Well past the expiry window, but not yet executable
+    fixture.ctx.advance_time(3_599);
+    assert_eq!(
+        fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]),
+        Err(bundle_error(BundleError::BundleNotYetExecutable)),
+    );
+    // A retry of the same CreateBundle still finds the bundle it created
+    fixture.ctx.process(&[scheduled], &[fixture.authority]).unwrap();
+
+    fixture.ctx.advance_time(60);
+    fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+    assert_eq!(fixture.ctx.lamports(&recipient), SOL);
+}
+
+#[test]
+fn failed_attempts_record_their_timing() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let funding = FundWallets { lamports_each: 1_000 * SOL, wallet_count: 1 };
+    let bundle = fixture.try_create_bundle(&[(0, 1)], Some(funding)).unwrap();
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+
+    fixture.ctx.advance_time(30);
+    fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+
+    let state = fixture.ctx.bundle(&bundle);
+    assert_eq!(state.status, BundleStatus::Failed);
+    assert!(state.execution_started_at > state.created_at);
+    assert!(state.execution_completed_at >= state.execution_started_at);
+}
+
+#[test]
+fn wallet_pdas_are_funded_and_sign_their_instructions() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let wallets: Vec<Pubkey> = (0..2)
+        .map(|index| find_wallet_address(&bundle_manager::id(), &fixture.manager, index).0)
+        .collect();
+
+    let funding = FundWallets { lamports_each: SOL, wallet_count: 2 };
+    let bundle = fixture.try_create_bundle(&[(0, 1), (1, 1)], Some(funding)).unwrap();
+    let records: Vec<Pubkey> = wallets.iter()
+        .enumerate()
+        .map(|(index, wallet)| {
+            let transfer = system_instruction::transfer(wallet, &recipient, SOL / 4);
+            fixture.add_instruction(&bundle, index as u8, &transfer).unwrap()
+        })
+        .collect();
+
+    let accounts: Vec<AccountMeta> = wallets.iter()
+        .chain([&recipient])
+        .map(|key| AccountMeta::new(*key, false))
+        .collect();
+    fixture.execute(&bundle, &records, &accounts).unwrap();
+
+    for wallet in &wallets {
+        assert_eq!(fixture.ctx.lamports(wallet), SOL - SOL / 4);
+    }
+    assert_eq!(fixture.ctx.lamports(&recipient), SOL / 2);
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+}
+
+#[test]
+fn spend_limits_cap_the_lamports_a_bundle_takes_out_of_its_wallets() {
+    let mut fixture = Fixture::new();
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    let recipient = Pubkey::new_unique();
+    let wallets: Vec<Pubkey> = (0..2)
+        .map(|index| find_wallet_address(&bundle_manager::id(), &manager, index).0)
+        .collect();
+    let accounts: Vec<AccountMeta> = wallets.iter()
+        .chain([&recipient])
+        .map(|key| AccountMeta::new(*key, false))
+        .collect();
+    let limits = SpendLimits { max_lamports_out: Some(SOL / 2), token_caps: Vec::new() };
+
+    // Funding is not spending; the limit covers what the wallets send on, together
+    let run = |fixture: &mut Fixture, each: u64| {
+        let bundle = fixture.next_bundle();
+        let funding = Some(FundWallets { lamports_each: SOL, wallet_count: 2 });
+        let nonce = fixture.next_nonce();
+        let create = client::create_bundle(&manager, nonce, &authority, &[(0, 1), (1, 1)], 0, None, funding);
+        fixture.ctx.process(&[client::with_spend_limits(create, limits.clone())], &[authority]).unwrap();
+        let records: Vec<Pubkey> = wallets.iter()
+            .enumerate()
+            .map(|(index, wallet)| {
+                let transfer = system_instruction::transfer(wallet, &recipient, each);
+                fixture.add_instruction(&bundle, index as u8, &transfer).unwrap()
+            })
+            .collect();
+        (bundle, fixture.execute(&bundle, &records, &accounts))
+    };
+
+    let (bundle, result) = run(&mut fixture, SOL / 4 + 1);
+    assert_eq!(result, Err(bundle_error(BundleError::SpendLimitExceeded)));
+    assert_eq!(fixture.ctx.lamports(&recipient), 0);
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Created);
+
+    let (bundle, result) = run(&mut fixture, SOL / 4);
+    result.unwrap();
+    assert_eq!(fixture.ctx.lamports(&recipient), SOL / 2);
+    assert_eq!(fixture.ctx.bundle(&bundle).spend_limits, Some(limits));
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+
+    let too_many = SpendLimits {
+        max_lamports_out: None,
+        token_caps: vec![TokenSpendCap { mint: Pubkey::new_unique(), max_amount: 1 }; MAX_TOKEN_SPEND_CAPS + 1],
+    };
+    let create = client::create_bundle(&manager, fixture.next_nonce(), &authority, &[(0, 1)], 0, None, None);
+    assert_eq!(
+        fixture.ctx.process(&[client::with_spend_limits(create, too_many)], &[authority]),
+        Err(bundle_error(BundleError::TooManySpendCaps)),
+    );
+}
+
+#[test]
+fn records_cannot_sign_for_another_wallet() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let wallets: Vec<Pubkey> = (0..2)
+        .map(|index| find_wallet_address(&bundle_manager::id(), &fixture.manager, index).0)
+        .collect();
+    let funding = FundWallets { lamports_each: SOL, wallet_count: 2 };
+    let bundle = fixture.try_create_bundle(&[(0, 1), (1, 1)], Some(funding)).unwrap();
+
+    // Wallet 0's record spends from wallet 1's PDA
+    let records = [
+        fixture.add_instruction(&bundle, 0, &system_instruction::transfer(&wallets[1], &recipient, SOL / 2)).unwrap(),
+        fixture.add_instruction(&bundle, 1, &system_instruction::transfer(&wallets[1], &recipient, 0)).unwrap(),
+    ];
+    let accounts: Vec<AccountMeta> = wallets.iter()
+        .chain([&recipient])
+        .map(|key| AccountMeta::new(*key, false))
+        .collect();
+
+    assert_eq!(
+        fixture.execute(&bundle, &records, &accounts),
+        Err(bundle_error(BundleError::AccountPrivilegeEscalation)),
+    );
+    assert_eq!(fixture.ctx.lamports(&recipient), 0);
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Created);
+}
+
+#[test]
+fn records_run_wallet_by_wallet() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let wallets: Vec<Pubkey> = (0..2)
+        .map(|index| find_wallet_address(&bundle_manager::id(), &fixture.manager, index).0)
+        .collect();
+    let funding = FundWallets { lamports_each: SOL, wallet_count: 2 };
+    let bundle = fixture.try_create_bundle(&[(0, 1), (1, 1)], Some(funding)).unwrap();
+
+    // Wallet 1's record is added first but can only pay once wallet 0's has topped it up
+    let spend = system_instruction::transfer(&wallets[1], &recipient, 3 * SOL / 2);
+    let top_up = system_instruction::transfer(&wallets[0], &wallets[1], SOL);
+    let records = [
+        fixture.add_instruction(&bundle, 1, &spend).unwrap(),
+        fixture.add_instruction(&bundle, 0, &top_up).unwrap(),
+    ];
+    let accounts: Vec<AccountMeta> = wallets.iter()
+        .chain([&recipient])
+        .map(|key| AccountMeta::new(*key, false))
+        .collect();
+
+    fixture.execute(&bundle, &records, &accounts).unwrap();
+    assert_eq!(fixture.ctx.lamports(&recipient), 3 * SOL / 2);
+    assert_eq!(fixture.ctx.lamports(&wallets[1]), SOL / 2);
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+}
+
+#[test]
+fn records_from_another_bundle_are_rejected() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let other = fixture.create_bundle(&[(0, 1)]);
+    let spliced = fixture.add_instruction(&other, 0, &fixture.transfer_from_authority(&recipient, 2 * SOL)).unwrap();
+
+    assert_eq!(
+        fixture.execute(&bundle, &[spliced], &[AccountMeta::new(recipient, false)]),
+        Err(bundle_error(BundleError::InstructionBundleMismatch)),
+    );
+    assert_eq!(fixture.ctx.lamports(&recipient), 0);
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Created);
+    assert!(!fixture.ctx.record(&spliced).executed);
+}
+
+#[test]
+fn records_run_once_each_in_the_order_they_were_added() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 2)]);
+    let first = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let second = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, 2 * SOL)).unwrap();
+    let accounts = [AccountMeta::new(recipient, false)];
+
+    // Passing the first record twice would run it twice and skip the second
+    for records in [[first, first], [second, first], [second, second]] {
+        assert_eq!(
+            fixture.execute(&bundle, &records, &accounts),
+            Err(bundle_error(BundleError::InvalidRecordPda)),
+        );
+    }
+    assert_eq!(fixture.ctx.lamports(&recipient), 0);
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Created);
+
+    fixture.execute(&bundle, &[first, second], &accounts).unwrap();
+    assert_eq!(fixture.ctx.lamports(&recipient), 3 * SOL);
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+}
+
+#[test]
+fn executed_records_are_marked_by_record_index() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 2)]);
+    let removed = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let second = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let remove = client::remove_instruction(&fixture.manager, &bundle, &removed, &fixture.authority);
+    fixture.ctx.process(&[remove], &[fixture.authority]).unwrap();
+    let third = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+
+    // What a client works out from GetExecutionState: live records below next_record_index with a clear bit
+    let remaining = |fixture: &mut Fixture| {
+        fixture.ctx.process(&[client::get_execution_state(&bundle)], &[]).unwrap();
+        let state = ExecutionState::try_from_slice(&fixture.ctx.return_data().unwrap().1).unwrap();
+        assert_eq!((state.next_record_index, state.total_instructions), (3, 2));
+        (0..state.next_record_index)
+            .filter(|&index| state.executed_mask[index as usize / 8] & (1 << (index % 8)) == 0)
+            .filter(|&index| {
+                let record = find_instruction_record_address(&bundle_manager::id(), &bundle, 0, index).0;
+                fixture.ctx.account(&record).is_some()
+            })
+            .collect::<Vec<u16>>()
+    };
+    assert_eq!(remaining(&mut fixture), vec![1, 2]);
+
+    // Records 1 and 2 run; the removed record 0 keeps its bit clear
+    fixture.execute(&bundle, &[second, third], &[AccountMeta::new(recipient, false)]).unwrap();
+    let state = fixture.ctx.bundle(&bundle);
+    assert_eq!(state.executed_mask[0], 0b110);
+    assert_eq!(state.last_executed_index, Some(2));
+    assert!(!state.is_executed(0) && state.is_executed(1) && state.is_executed(2));
+    assert_eq!(remaining(&mut fixture), Vec::<u16>::new());
+
+    // Past the last record index with a mask bit, nothing more can be added
+    let full = fixture.create_bundle(&[(0, 1)]);
+    let mut state = fixture.ctx.bundle(&full);
+    state.next_record_index = MAX_BUNDLE_INSTRUCTIONS as u16;
+    fixture.ctx.write_state(&full, &state);
+    assert_eq!(
+        fixture.add_instruction(&full, 0, &fixture.transfer_from_authority(&recipient, SOL)),
+        Err(bundle_error(BundleError::TooManyInstructions)),
+    );
+}
+
+#[test]
+fn accounts_are_only_read_as_their_own_type() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let invalid_type = Err(bundle_error(BundleError::InvalidAccountType));
+
+    // A bundle passed as its own record, a record as a bundle, a bundle as a manager
+    assert_eq!(fixture.execute(&bundle, &[bundle], &[AccountMeta::new(recipient, false)]), invalid_type);
+    assert_eq!(fixture.ctx.process(&[client::get_execution_state(&record)], &[]), invalid_type);
+    let pause = client::set_manager_status(&bundle, &fixture.authority, true);
+    assert_eq!(fixture.ctx.process(&[pause], &[fixture.authority]), invalid_type);
+
+    fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+    assert_eq!(fixture.ctx.lamports(&recipient), SOL);
+}
+
+#[test]
+fn bundles_are_only_read_at_their_pda() {
+    let mut fixture = Fixture::new();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let seeds = fixture.ctx.bundle(&bundle).seeds.expect("new bundles record their seeds");
+    assert_eq!(
+        bundle_manager::find_bundle_address(&bundle_manager::id(), &fixture.manager, &fixture.authority, seeds.client_nonce),
+        (bundle, seeds.bump),
+    );
+
+    // A program-owned copy at any other address is refused
+    let copy = Pubkey::new_unique();
+    fixture.ctx.set_account(copy, fixture.ctx.account(&bundle).unwrap().clone());
+    assert_eq!(
+        fixture.ctx.process(&[client::get_execution_state(&copy)], &[]),
+        Err(bundle_error(BundleError::InvalidBundlePda)),
+    );
+    assert_eq!(
+        fixture.add_instruction(&copy, 0, &fixture.transfer_from_authority(&Pubkey::new_unique(), SOL)),
+        Err(bundle_error(BundleError::InvalidBundlePda)),
+    );
+}
+
+#[test]
+fn managers_are_only_read_at_their_pda() {
+    let mut fixture = Fixture::new();
+    let authority = fixture.authority;
+    let invalid_pda = Err(bundle_error(BundleError::InvalidManagerPda));
+
+    // A program-owned copy at any other address is refused
+    let copy = Pubkey::new_unique();
+    fixture.ctx.set_account(copy, fixture.ctx.account(&fixture.manager).unwrap().clone());
+    assert_eq!(fixture.ctx.process(&[client::get_managers_stats(&[copy])], &[]), invalid_pda);
+    assert_eq!(fixture.ctx.process(&[client::set_manager_status(&copy, &authority, true)], &[authority]), invalid_pda);
+    let create = client::create_bundle(&copy, fixture.next_nonce(), &authority, &[(0, 1)], 0, None, None);
+    assert_eq!(fixture.ctx.process(&[create], &[authority]), invalid_pda);
+
+    // So is the manager itself once its stored seeds no longer derive its address
+    let mut manager = fixture.ctx.manager(&fixture.manager);
+    manager.bump = manager.bump.wrapping_sub(1);
+    fixture.ctx.write_state(&fixture.manager, &manager);
+    assert_eq!(
+        fixture.try_create_bundle(&[(0, 1)], None),
+        Err(bundle_error(BundleError::InvalidManagerPda)),
+    );
+}
+
+#[test]
+fn only_registered_wallets_execute_once_a_manager_has_a_registry() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let keypair_wallet = fixture.ctx.funded_key(SOL);
+    let registry = find_wallet_registry_address(&bundle_manager::id(), &fixture.manager).0;
+    let register = |fixture: &mut Fixture, wallet_index, wallet| {
+        let register = client::register_wallet(&fixture.manager, &fixture.authority, wallet_index, wallet);
+        fixture.ctx.process(&[register], &[fixture.authority])
+    };
+    register(&mut fixture, 0, None).unwrap();
+    register(&mut fixture, 1, Some(keypair_wallet)).unwrap();
+    assert_eq!(register(&mut fixture, 1, None), Err(bundle_error(BundleError::WalletAlreadyRegistered)));
+    assert!(fixture.ctx.manager(&fixture.manager).wallet_registry);
+
+    let execute = |fixture: &mut Fixture, bundle: &Pubkey, record: Pubkey, accounts: &[AccountMeta]| {
+        let remaining = common::execute_accounts(&[record], accounts);
+        let execute = client::execute_bundle(&fixture.manager, bundle, &fixture.authority, false, &remaining, 200_000, 0);
+        fixture.ctx.process(&[execute], &[fixture.authority, keypair_wallet])
+    };
+    let spend = system_instruction::transfer(&keypair_wallet, &recipient, SOL / 2);
+    let accounts = [
+        AccountMeta::new(keypair_wallet, true),
+        AccountMeta::new(recipient, false),
+        AccountMeta::new_readonly(registry, false),
+    ];
+
+    // Wallet 0 is registered as its PDA, so its records cannot spend from the keypair wallet
+    let misattributed = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&misattributed, 0, &spend).unwrap();
+    assert_eq!(
+        execute(&mut fixture, &misattributed, record, &accounts),
+        Err(bundle_error(BundleError::UnregisteredWalletSigner)),
+    );
+
+    let bundle = fixture.create_bundle(&[(1, 1)]);
+    let record = fixture.add_instruction(&bundle, 1, &spend).unwrap();
+    assert_eq!(execute(&mut fixture, &bundle, record, &accounts[..2]), Err(ProgramError::NotEnoughAccountKeys));
+    execute(&mut fixture, &bundle, record, &accounts).unwrap();
+    assert_eq!(fixture.ctx.lamports(&recipient), SOL / 2);
+
+    let unregistered = fixture.create_bundle(&[(2, 1)]);
+    let record = fixture.add_instruction(&unregistered, 2, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    assert_eq!(
+        execute(&mut fixture, &unregistered, record, &accounts[1..]),
+        Err(bundle_error(BundleError::WalletNotRegistered)),
+    );
+
+    let authority_before = fixture.ctx.lamports(&fixture.authority);
+    let remove = |fixture: &mut Fixture, wallet_index| {
+        let remove = client::remove_wallet(&fixture.manager, &fixture.authority, wallet_index);
+        fixture.ctx.process(&[remove], &[fixture.authority])
+    };
+    remove(&mut fixture, 1).unwrap();
+    assert_eq!(remove(&mut fixture, 1), Err(bundle_error(BundleError::WalletNotRegistered)));
+    assert!(fixture.ctx.lamports(&fixture.authority) > authority_before);
+    assert_eq!(fixture.ctx.data(&registry).len(), WalletRegistry::space(1));
+    let wallets = WalletRegistry::unpack(fixture.ctx.data(&registry)).unwrap().wallets;
+    let pda = find_wallet_address(&bundle_manager::id(), &fixture.manager, 0).0;
+    assert_eq!(wallets, vec![RegisteredWallet { wallet_index: 0, address: pda, is_pda: true }]);
+}
+
+#[test]
+fn compressed_instruction_data_is_restored_before_invoking() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+
+    // The transfer's zero-padded tag and amount compress
+    let transfer = fixture.transfer_from_authority(&recipient, SOL);
+    let options = InstructionOptions { compress: true, ..Default::default() };
+    let record = fixture.add_instruction_with(&bundle, 0, &transfer, options).unwrap();
+    let stored = fixture.ctx.record(&record);
+    assert!(stored.compressed);
+    assert!(stored.instruction_data.len() < transfer.data.len());
+
+    fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+    assert_eq!(fixture.ctx.lamports(&recipient), SOL);
+}
+
+#[test]
+fn instruction_records_are_created_at_their_pda() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1), (1, 1)]);
+    let transfer = fixture.transfer_from_authority(&recipient, SOL);
+
+    // Any other address, including the PDA of another wallet or index, is refused
+    let keypair_record = Pubkey::new_unique();
+    let wrong_wallet = find_instruction_record_address(&bundle_manager::id(), &bundle, 1, 0).0;
+    for record in [keypair_record, wrong_wallet] {
+        let add = client::add_instruction(&fixture.manager, &bundle, &record, &fixture.authority, 0, &transfer, Default::default());
+        assert_eq!(fixture.ctx.process(&[add], &[fixture.authority, record]), Err(ProgramError::InvalidSeeds));
+    }
+
+    let first = fixture.add_instruction(&bundle, 0, &transfer).unwrap();
+    let second = fixture.add_instruction(&bundle, 1, &system_instruction::transfer(&recipient, &recipient, 0)).unwrap();
+    assert_eq!(first, find_instruction_record_address(&bundle_manager::id(), &bundle, 0, 0).0);
+    assert_eq!(second, find_instruction_record_address(&bundle_manager::id(), &bundle, 1, 1).0);
+    assert_eq!(fixture.ctx.bundle(&bundle).next_record_index, 2);
+}
+
+#[test]
+fn removed_instructions_free_their_slot_until_execution() {
+    let mut fixture = Fixture::new();
+    let wrong = Pubkey::new_unique();
+    let right = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let mistake = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&wrong, SOL)).unwrap();
+    let rent = fixture.ctx.lamports(&mistake);
+
+    // Replacing is a remove and an add in one transaction
+    let authority_before = fixture.ctx.lamports(&fixture.authority);
+    let record = fixture.next_record(&bundle, 0);
+    let remove = client::remove_instruction(&fixture.manager, &bundle, &mistake, &fixture.authority);
+    let transfer = fixture.transfer_from_authority(&right, 2 * SOL);
+    let add = client::add_instruction(&fixture.manager, &bundle, &record, &fixture.authority, 0, &transfer, Default::default());
+    fixture.ctx.process(&[remove, add], &[fixture.authority]).unwrap();
+
+    assert!(fixture.ctx.account(&mistake).is_none());
+    // The replacement takes the next record index; removed indexes are not reused
+    assert_ne!(record, mistake);
+    assert_eq!(fixture.ctx.bundle(&bundle).next_record_index, 2);
+    assert_eq!(fixture.ctx.lamports(&fixture.authority), authority_before + rent - fixture.ctx.lamports(&record));
+    let state = fixture.ctx.bundle(&bundle);
+    assert_eq!(state.added_instructions(), 1);
+    assert_eq!(state.expected_net_lamports, -2 * SOL as i64);
+
+    fixture.execute(&bundle, &[record], &[AccountMeta::new(right, false)]).unwrap();
+    assert_eq!(fixture.ctx.lamports(&right), 2 * SOL);
+    let remove = client::remove_instruction(&fixture.manager, &bundle, &record, &fixture.authority);
+    assert_eq!(
+        fixture.ctx.process(&[remove], &[fixture.authority]),
+        Err(bundle_error(BundleError::InvalidStateTransition)),
+    );
+}
+
+#[test]
+fn bundles_out_of_compute_resume_where_they_stopped() {
+    let mut fixture = Fixture::new();
+    let recipients: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let bundle = fixture.create_bundle(&[(0, 4)]);
+    let records: Vec<Pubkey> = recipients.iter()
+        .map(|recipient| fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(recipient, SOL)).unwrap())
+        .collect();
+
+    // Three transfers fit the compute limit before the reserve is reached
+    fixture.ctx.cpi_compute_cost = 60_000;
+    let accounts: Vec<AccountMeta> = recipients.iter().map(|recipient| AccountMeta::new(*recipient, false)).collect();
+    fixture.execute(&bundle, &records, &accounts).unwrap();
+
+    let state = fixture.ctx.bundle(&bundle);
+    assert_eq!(state.status, BundleStatus::PartiallyExecuted);
+    assert_eq!(state.last_executed_index, Some(2));
+    let executed: Vec<bool> = records.iter().map(|record| fixture.ctx.record(record).executed).collect();
+    assert_eq!(executed, vec![true, true, true, false]);
+    assert_eq!(fixture.ctx.lamports(&recipients[3]), 0);
+    assert_eq!(fixture.ctx.manager(&fixture.manager).active_bundles, 1);
+    assert!(fixture.ctx.events().is_empty());
+
+    // Only the remaining record's accounts are needed to resume
+    fixture.ctx.advance_time(10);
+    fixture.execute(&bundle, &records, &accounts[3..]).unwrap();
+
+    let resumed = fixture.ctx.bundle(&bundle);
+    assert_eq!(resumed.status, BundleStatus::Executed);
+    assert_eq!(resumed.execution_started_at, state.execution_started_at);
+    for recipient in &recipients {
+        assert_eq!(fixture.ctx.lamports(recipient), SOL);
+    }
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!((manager.active_bundles, manager.total_bundles_executed), (0, 1));
+}
+
+#[test]
+fn bundles_left_mid_execution_can_be_failed() {
+    let mut fixture = Fixture::with_config(ManagerConfig { execution_timeout_seconds: 60, ..Default::default() });
+    let recipients: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let bundle = fixture.create_bundle(&[(0, 4)]);
+    let records: Vec<Pubkey> = recipients.iter()
+        .map(|recipient| fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(recipient, SOL)).unwrap())
+        .collect();
+    let stranger = fixture.ctx.funded_key(SOL);
+    let fail = |fixture: &Fixture, caller: &Pubkey| client::fail_bundle(&fixture.manager, &bundle, caller, None);
+
+    assert_eq!(
+        fixture.ctx.process(&[fail(&fixture, &fixture.authority)], &[fixture.authority]),
+        Err(bundle_error(BundleError::InvalidStateTransition)),
+    );
+
+    fixture.ctx.cpi_compute_cost = 60_000;
+    let accounts: Vec<AccountMeta> = recipients.iter().map(|recipient| AccountMeta::new(*recipient, false)).collect();
+    fixture.execute(&bundle, &records, &accounts).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::PartiallyExecuted);
+
+    // Others have to wait out the manager's timeout
+    fixture.ctx.advance_time(60);
+    assert_eq!(
+        fixture.ctx.process(&[fail(&fixture, &stranger)], &[stranger]),
+        Err(bundle_error(BundleError::ExecutionNotTimedOut)),
+    );
+    fixture.ctx.advance_time(1);
+    fixture.ctx.process(&[fail(&fixture, &stranger)], &[stranger]).unwrap();
+
+    let failed = fixture.ctx.bundle(&bundle);
+    assert_eq!(failed.status, BundleStatus::Failed);
+    assert!(failed.execution_completed_at > failed.execution_started_at);
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!((manager.active_bundles, manager.total_bundles_failed), (0, 1));
+    let error = fixture.ctx.events().into_iter().find_map(|event| match event {
+        BundleEvent::BundleFailed(failed) => Some(failed.error),
+        _ => None,
+    });
+    assert_eq!(error, Some(u64::from(bundle_error(BundleError::ExecutionAbandoned))));
+
+    // A retry picks up after the records that already ran
+    fixture.execute(&bundle, &records, &accounts[3..]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+    assert_eq!(fixture.ctx.lamports(&recipients[3]), SOL);
+
+    // The authority need not wait, even for a bundle stuck in Executing
+    let stuck = fixture.create_bundle(&[(0, 1)]);
+    let mut state = fixture.ctx.bundle(&stuck);
+    state.status = BundleStatus::Executing;
+    fixture.ctx.write_state(&stuck, &state);
+    let fail_stuck = client::fail_bundle(&fixture.manager, &stuck, &fixture.authority, None);
+    fixture.ctx.process(&[fail_stuck], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&stuck).status, BundleStatus::Failed);
+}
+
+#[test]
+fn failed_records_keep_their_error_and_retries_resume_after_them() {
+    let mut fixture = Fixture::new();
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    let recipient = Pubkey::new_unique();
+    let late = fixture.ctx.funded_key(SOL);
+    let create_late = |address: Pubkey| InstructionOptions {
+        create_if_missing: Some(CreateIfMissing::SystemAccount { address, space: 0, owner: system_program::id() }),
+        ..Default::default()
+    };
+    let create_capped = |fixture: &mut Fixture, max_retries: u8| {
+        let bundle = fixture.next_bundle();
+        let create = client::create_bundle(&manager, fixture.next_nonce(), &authority, &[(0, 3)], 0, None, None);
+        fixture.ctx.process(&[client::with_max_retries(create, max_retries)], &[authority]).unwrap();
+        assert_eq!(fixture.ctx.bundle(&bundle).max_retries, Some(max_retries));
+        bundle
+    };
+
+    let bundle = create_capped(&mut fixture, 1);
+    let records = [
+        fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap(),
+        fixture.add_instruction_with(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL), create_late(late))
+            .unwrap(),
+        fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap(),
+    ];
+    // The account the second record creates first is not passed, which the
+    // program catches itself
+    fixture.execute(&bundle, &records, &[AccountMeta::new(recipient, false)]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Failed);
+    let mismatch = BundleError::CreatedAccountMismatch as u32;
+    assert_eq!(fixture.ctx.record(&records[1]).last_error, Some(mismatch));
+    assert!(fixture.ctx.record(&records[0]).executed);
+    assert_eq!(fixture.ctx.lamports(&recipient), SOL);
+
+    let accounts = [AccountMeta::new(recipient, false), AccountMeta::new(late, false)];
+    let remaining = common::execute_accounts(&records, &accounts);
+    let retry = client::retry_bundle(&manager, &bundle, &authority, false, &remaining, 200_000, 0);
+    fixture.ctx.process(std::slice::from_ref(&retry), &[authority]).unwrap();
+    let retried = fixture.ctx.bundle(&bundle);
+    assert_eq!((retried.status, retried.retry_count), (BundleStatus::Executed, 1));
+    assert_eq!(fixture.ctx.lamports(&recipient), 3 * SOL);
+    assert_eq!(fixture.ctx.record(&records[1]).last_error, Some(mismatch), "the error stays for diagnosis");
+    assert_eq!(fixture.ctx.process(&[retry], &[authority]), Err(bundle_error(BundleError::InvalidStateTransition)));
+
+    // Without retries left, neither RetryBundle nor ExecuteBundle runs it again
+    let missing = Pubkey::new_unique();
+    let bundle = create_capped(&mut fixture, 0);
+    let records: Vec<Pubkey> = (0..3)
+        .map(|_| {
+            let transfer = fixture.transfer_from_authority(&recipient, SOL);
+            fixture.add_instruction_with(&bundle, 0, &transfer, create_late(missing)).unwrap()
+        })
+        .collect();
+    fixture.execute(&bundle, &records, &[AccountMeta::new(recipient, false)]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Failed);
+    let accounts = [AccountMeta::new(recipient, false), AccountMeta::new(missing, false)];
+    let remaining = common::execute_accounts(&records, &accounts);
+    let retry = client::retry_bundle(&manager, &bundle, &authority, false, &remaining, 200_000, 0);
+    assert_eq!(fixture.ctx.process(&[retry], &[authority]), Err(bundle_error(BundleError::RetryLimitReached)));
+    assert_eq!(fixture.execute(&bundle, &records, &accounts), Err(bundle_error(BundleError::RetryLimitReached)));
+}
+
+#[test]
+fn failed_bundles_notify_their_on_failure_program() {
+    let mut fixture = Fixture::new();
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    let hook = Pubkey::new_unique();
+    fixture.ctx.add_program(hook, mock_hook_program);
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.next_bundle();
+    let on_failure = Some((hook, vec![42]));
+    let create = client::create_bundle(&manager, fixture.next_nonce(), &authority, &[(0, 1)], 0, on_failure, None);
+    fixture.ctx.process(&[create], &[authority]).unwrap();
+    // The account the record creates first is not passed, which fails the bundle
+    let missing = InstructionOptions {
+        create_if_missing: Some(CreateIfMissing::SystemAccount {
+            address: Pubkey::new_unique(),
+            space: 0,
+            owner: system_program::id(),
+        }),
+        ..Default::default()
+    };
+    let record =
+        fixture.add_instruction_with(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL), missing).unwrap();
+
+    let accounts = [AccountMeta::new(recipient, false), AccountMeta::new_readonly(hook, false)];
+    fixture.execute(&bundle, &[record], &accounts).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Failed);
+    assert_eq!(HOOK_CALLS.with(|calls| calls.take()), vec![(bundle, vec![42])]);
+}
+
+#[test]
+fn failure_hooks_taken_off_the_allowlist_are_skipped() {
+    let mut fixture = Fixture::new();
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    let hook = Pubkey::new_unique();
+    fixture.ctx.add_program(hook, mock_hook_program);
+    let allow = [
+        client::add_allowed_program(&manager, &authority, &system_program::id()),
+        client::add_allowed_program(&manager, &authority, &hook),
+    ];
+    fixture.ctx.process(&allow, &[authority]).unwrap();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.next_bundle();
+    let on_failure = Some((hook, vec![42]));
+    let create = client::create_bundle(&manager, fixture.next_nonce(), &authority, &[(0, 1)], 0, on_failure, None);
+    fixture.ctx.process(&[create], &[authority]).unwrap();
+    let transfer = fixture.transfer_from_authority(&recipient, SOL);
+    let record = fixture.add_instruction_with(&bundle, 0, &transfer, create_first(Pubkey::new_unique())).unwrap();
+
+    let disallow = client::remove_allowed_program(&manager, &authority, &hook);
+    fixture.ctx.process(&[disallow], &[authority]).unwrap();
+    let accounts = [AccountMeta::new(recipient, false), AccountMeta::new_readonly(hook, false)];
+    fixture.execute(&bundle, &[record], &accounts).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Failed);
+    assert_eq!(HOOK_CALLS.with(|calls| calls.take()), Vec::new());
+    let skipped = format!("on_failure hook program {hook} is not allowed, skipping");
+    assert!(fixture.ctx.logs().iter().any(|line| line.contains(&skipped)));
+}
+
+#[test]
+fn consecutive_failures_pause_the_manager() {
+    let mut fixture = Fixture::with_config(ManagerConfig { auto_pause_threshold: 2, ..Default::default() });
+    let recipient = Pubkey::new_unique();
+    let accounts = [AccountMeta::new(recipient, false)];
+    let run = |fixture: &mut Fixture, fails: bool| {
+        let bundle = fixture.create_bundle(&[(0, 1)]);
+        let transfer = fixture.transfer_from_authority(&recipient, SOL);
+        let options = if fails { create_first(Pubkey::new_unique()) } else { InstructionOptions::default() };
+        let record = fixture.add_instruction_with(&bundle, 0, &transfer, options).unwrap();
+        fixture.execute(&bundle, &[record], &accounts)
+    };
+
+    // A success in between starts the count afresh
+    run(&mut fixture, true).unwrap();
+    run(&mut fixture, false).unwrap();
+    run(&mut fixture, true).unwrap();
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!((manager.consecutive_failures, manager.is_paused), (1, false));
+
+    run(&mut fixture, true).unwrap();
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!((manager.consecutive_failures, manager.is_paused), (2, true));
+    assert!(fixture.ctx.logs().iter().any(|line| line.contains("AutoPaused")));
+    assert_eq!(fixture.try_create_bundle(&[(0, 1)], None), Err(bundle_error(BundleError::ManagerPaused)));
+}
+
+#[test]
+fn failing_callees_abort_the_call_and_a_resend_runs_them() {
+    let mut fixture = Fixture::new();
+    let flaky = Pubkey::new_unique();
+    fixture.ctx.add_program(flaky, mock_flaky_program);
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 2)]);
+    let records = [
+        fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap(),
+        fixture.add_instruction(&bundle, 0, &Instruction::new_with_bytes(flaky, &[], Vec::new())).unwrap(),
+    ];
+    let accounts = [AccountMeta::new(recipient, false), AccountMeta::new_readonly(flaky, false)];
+
+    FLAKY_FAILURES.with(|failures| failures.set(1));
+    assert_eq!(fixture.execute(&bundle, &records, &accounts), Err(ProgramError::Custom(7)));
+    assert_eq!(fixture.ctx.lamports(&recipient), 0);
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Created);
+    assert!(!fixture.ctx.record(&records[0]).executed);
+
+    fixture.execute(&bundle, &records, &accounts).unwrap();
+    assert_eq!(fixture.ctx.lamports(&recipient), SOL);
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+}
+
+#[test]
+fn callee_errors_stay_out_of_the_bundle_error_range() {
+    let mut fixture = Fixture::new();
+    let flaky = Pubkey::new_unique();
+    fixture.ctx.add_program(flaky, mock_flaky_program);
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&bundle, 0, &Instruction::new_with_bytes(flaky, &[], Vec::new())).unwrap();
+    let accounts = [AccountMeta::new_readonly(flaky, false)];
+
+    FLAKY_FAILURES.with(|failures| failures.set(1));
+    let callee_error = fixture.execute(&bundle, &[record], &accounts);
+    assert_eq!(callee_error, Err(ProgramError::Custom(7)));
+    assert!(!BundleError::is_bundle_error_code(7));
+    assert_eq!(BundleError::from_program_error(&callee_error.unwrap_err()), None);
+
+    let pause = client::set_manager_status(&fixture.manager, &fixture.authority, true);
+    fixture.ctx.process(&[pause], &[fixture.authority]).unwrap();
+    let ProgramError::Custom(code) = fixture.execute(&bundle, &[record], &accounts).unwrap_err() else {
+        panic!("ExecuteBundle on a paused manager should fail with a BundleError");
+    };
+    assert!(BundleError::is_bundle_error_code(code));
+    assert_eq!(BundleError::from_code(code), Some(BundleError::ManagerPaused));
+}
+
+#[test]
+fn failed_bundles_wait_out_the_retry_cooldown() {
+    let mut fixture = Fixture::with_config(ManagerConfig { retry_cooldown_slots: 10, ..Default::default() });
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    let recipient = Pubkey::new_unique();
+    let late = fixture.ctx.funded_key(SOL);
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let transfer = fixture.transfer_from_authority(&recipient, SOL);
+    let record = fixture.add_instruction_with(&bundle, 0, &transfer, create_first(late)).unwrap();
+    fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Failed);
+
+    let accounts = [AccountMeta::new(recipient, false), AccountMeta::new(late, false)];
+    let remaining = common::execute_accounts(&[record], &accounts);
+    let retry = client::retry_bundle(&manager, &bundle, &authority, false, &remaining, 200_000, 0);
+    fixture.ctx.warp_slots(9);
+    assert_eq!(
+        fixture.ctx.process(std::slice::from_ref(&retry), &[authority]),
+        Err(bundle_error(BundleError::RetryCooldownActive)),
+    );
+    assert_eq!(fixture.execute(&bundle, &[record], &accounts), Err(bundle_error(BundleError::RetryCooldownActive)));
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Failed);
+
+    fixture.ctx.warp_slots(1);
+    fixture.ctx.process(&[retry], &[authority]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+    assert_eq!(fixture.ctx.lamports(&recipient), SOL);
+}
+
+#[test]
+fn created_bundles_can_be_cancelled_for_their_rent() {
+    let mut fixture = Fixture::new();
+    let manager_authority = fixture.authority;
+    let user = fixture.ctx.funded_key(10 * SOL);
+    let stranger = fixture.ctx.funded_key(SOL);
+
+    // A bundle of another user's, built while the fixture acts as them
+    fixture.authority = user;
+    let bundle = fixture.create_bundle(&[(0, 2)]);
+    let records: Vec<Pubkey> = (0..2)
+        .map(|_| fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&Pubkey::new_unique(), SOL)).unwrap())
+        .collect();
+    fixture.authority = manager_authority;
+    let rent = fixture.ctx.lamports(&bundle) + records.iter().map(|record| fixture.ctx.lamports(record)).sum::<u64>();
+    let cancel = |fixture: &Fixture, caller: &Pubkey, destination: &Pubkey, records: &[Pubkey]| {
+        client::cancel_bundle(&fixture.manager, &bundle, caller, destination, records)
+    };
+
+    assert_eq!(
+        fixture.ctx.process(&[cancel(&fixture, &stranger, &user, &records)], &[stranger]),
+        Err(bundle_error(BundleError::Unauthorized)),
+    );
+    assert_eq!(
+        fixture.ctx.process(&[cancel(&fixture, &manager_authority, &manager_authority, &records)], &[manager_authority]),
+        Err(bundle_error(BundleError::InvalidRentDestination)),
+    );
+    assert_eq!(
+        fixture.ctx.process(&[cancel(&fixture, &manager_authority, &user, &records[..1])], &[manager_authority]),
+        Err(bundle_error(BundleError::MissingInstructionRecords)),
+    );
+
+    // The manager authority may cancel, but the rent still goes to the user
+    let user_before = fixture.ctx.lamports(&user);
+    fixture.ctx.process(&[cancel(&fixture, &manager_authority, &user, &records)], &[manager_authority]).unwrap();
+    assert!(fixture.ctx.account(&bundle).is_none());
+    assert!(records.iter().all(|record| fixture.ctx.account(record).is_none()));
+    assert_eq!(fixture.ctx.lamports(&user), user_before + rent);
+    assert_eq!(fixture.ctx.manager(&fixture.manager).active_bundles, 0);
+
+    // Bundles that have started executing are failed or closed instead
+    let executed = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&executed, 0, &system_instruction::transfer(&fixture.authority, &user, 1)).unwrap();
+    fixture.execute(&executed, &[record], &[AccountMeta::new(user, false)]).unwrap();
+    let cancel_executed = client::cancel_bundle(&fixture.manager, &executed, &fixture.authority, &fixture.authority, &[record]);
+    assert_eq!(
+        fixture.ctx.process(&[cancel_executed], &[fixture.authority]),
+        Err(bundle_error(BundleError::InvalidStateTransition)),
+    );
+}
+
+#[test]
+fn closing_paths_return_exactly_the_reclaimed_rent_to_the_authority() {
+    let mut fixture = Fixture::with_config(ManagerConfig { expiry_seconds: 60, ..Default::default() });
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    let collector = fixture.ctx.funded_key(SOL);
+    let recipient = Pubkey::new_unique();
+    // A bundle of two records, and the rent of its account and of the records
+    let build = |fixture: &mut Fixture| {
+        let bundle = fixture.create_bundle(&[(0, 2)]);
+        let records: Vec<Pubkey> = (0..2)
+            .map(|_| fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, 1)).unwrap())
+            .collect();
+        let record_rent: u64 = records.iter().map(|record| fixture.ctx.lamports(record)).sum();
+        (bundle, records, fixture.ctx.lamports(&bundle), record_rent)
+    };
+    let reclaim = |fixture: &mut Fixture, instruction: Instruction, signer: Pubkey, records: &[Pubkey]| {
+        let before = fixture.ctx.lamports(&authority);
+        fixture.ctx.process(&[instruction], &[signer]).unwrap();
+        assert!(records.iter().all(|record| fixture.ctx.account(record).is_none()));
+        fixture.ctx.lamports(&authority) - before
+    };
+
+    let (bundle, records, bundle_rent, record_rent) = build(&mut fixture);
+    fixture.execute(&bundle, &records, &[AccountMeta::new(recipient, false)]).unwrap();
+    let close = client::close_bundle(&manager, &bundle, &authority, &authority, &records);
+    assert_eq!(reclaim(&mut fixture, close, authority, &records), bundle_rent + record_rent);
+
+    let (bundle, records, bundle_rent, record_rent) = build(&mut fixture);
+    let cancel = client::cancel_bundle(&manager, &bundle, &authority, &authority, &records);
+    assert_eq!(reclaim(&mut fixture, cancel, authority, &records), bundle_rent + record_rent);
+
+    // The collector's bounty comes out of the bundle account's rent, never the records'
+    let (bundle, records, bundle_rent, record_rent) = build(&mut fixture);
+    fixture.ctx.advance_time(61);
+    let collect = client::garbage_collect(&manager, &bundle, &collector, &authority, &records, None);
+    assert_eq!(
+        reclaim(&mut fixture, collect, collector, &records),
+        bundle_rent + record_rent - GARBAGE_COLLECT_BOUNTY_LAMPORTS,
+    );
+}
+
+#[test]
+fn expired_bundles_are_garbage_collected_for_a_bounty() {
+    let mut fixture = Fixture::with_config(ManagerConfig { expiry_seconds: 60, ..Default::default() });
+    let user = fixture.authority;
+    let collector = fixture.ctx.funded_key(SOL);
+    let bundle = fixture.create_bundle(&[(0, 2)]);
+    let transfer = fixture.transfer_from_authority(&Pubkey::new_unique(), SOL);
+    let records: Vec<Pubkey> = (0..2).map(|_| fixture.add_instruction(&bundle, 0, &transfer).unwrap()).collect();
+    assert_eq!(fixture.ctx.bundle(&bundle).expires_at, 1_700_000_000 + 60);
+    let rent = fixture.ctx.lamports(&bundle) + records.iter().map(|record| fixture.ctx.lamports(record)).sum::<u64>();
+    let collect = |fixture: &Fixture, destination: &Pubkey, records: &[Pubkey]| {
+        client::garbage_collect(&fixture.manager, &bundle, &collector, destination, records, None)
+    };
+
+    assert_eq!(
+        fixture.ctx.process(&[collect(&fixture, &user, &records)], &[collector]),
+        Err(bundle_error(BundleError::BundleNotExpired)),
+    );
+    fixture.ctx.advance_time(61);
+    assert_eq!(
+        fixture.ctx.process(&[collect(&fixture, &collector, &records)], &[collector]),
+        Err(bundle_error(BundleError::InvalidRentDestination)),
+    );
+    assert_eq!(
+        fixture.ctx.process(&[collect(&fixture, &user, &records[..1])], &[collector]),
+        Err(bundle_error(BundleError::MissingInstructionRecords)),
+    );
+    // Expired bundles no longer execute
+    assert_eq!(fixture.execute(&bundle, &records, &[]), Err(bundle_error(BundleError::BundleExpired)));
+
+    let user_before = fixture.ctx.lamports(&user);
+    let collector_before = fixture.ctx.lamports(&collector);
+    fixture.ctx.process(&[collect(&fixture, &user, &records)], &[collector]).unwrap();
+    assert!(fixture.ctx.account(&bundle).is_none());
+    assert!(records.iter().all(|record| fixture.ctx.account(record).is_none()));
+    assert_eq!(fixture.ctx.lamports(&collector), collector_before + GARBAGE_COLLECT_BOUNTY_LAMPORTS);
+    assert_eq!(fixture.ctx.lamports(&user), user_before + rent - GARBAGE_COLLECT_BOUNTY_LAMPORTS);
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!((manager.active_bundles, manager.total_bundles_failed), (0, 1));
+    assert!(fixture.ctx.events().iter().any(|event| matches!(event, BundleEvent::BundleFailed(_))));
+
+    // A bundle ExpireBundle already failed is collected as it stands
+    let expired = fixture.create_bundle(&[(0, 1)]);
+    fixture.ctx.advance_time(61);
+    fixture.ctx.process(&[client::expire_bundle(&fixture.manager, &expired, None)], &[]).unwrap();
+    let collect_expired = client::garbage_collect(&fixture.manager, &expired, &collector, &user, &[], None);
+    fixture.ctx.process(&[collect_expired], &[collector]).unwrap();
+    assert!(fixture.ctx.account(&expired).is_none());
+    assert_eq!(fixture.ctx.manager(&fixture.manager).total_bundles_failed, 2);
+
+    // Bundles that ran are the authority's to retry or close
+    let executed = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&executed, 0, &system_instruction::transfer(&user, &collector, 1)).unwrap();
+    fixture.execute(&executed, &[record], &[AccountMeta::new(collector, false)]).unwrap();
+    fixture.ctx.advance_time(61);
+    let collect_executed = client::garbage_collect(&fixture.manager, &executed, &collector, &user, &[record], None);
+    assert_eq!(
+        fixture.ctx.process(&[collect_executed], &[collector]),
+        Err(bundle_error(BundleError::InvalidStateTransition)),
+    );
+}
+
+#[test]
+fn clients_execute_bundles_in_explicit_slices() {
+    let mut fixture = Fixture::new();
+    let recipients: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+    let bundle = fixture.create_bundle(&[(0, 3)]);
+    let records: Vec<Pubkey> = recipients.iter()
+        .map(|recipient| fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(recipient, SOL)).unwrap())
+        .collect();
+    let accounts: Vec<AccountMeta> = recipients.iter().map(|recipient| AccountMeta::new(*recipient, false)).collect();
+    let execute = |fixture: &mut Fixture, start_index, count, accounts: &[AccountMeta]| {
+        let remaining = common::execute_accounts(&records, accounts);
+        let execute = client::execute_bundle(&fixture.manager, &bundle, &fixture.authority, false, &remaining, 200_000, 0);
+        fixture.ctx.process(&[client::with_slice(execute, start_index, count)], &[fixture.authority])
+    };
+
+    // The first slice only needs its own records' accounts
+    execute(&mut fixture, 0, 2, &accounts[..2]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::PartiallyExecuted);
+    let executed: Vec<bool> = records.iter().map(|record| fixture.ctx.record(record).executed).collect();
+    assert_eq!(executed, vec![true, true, false]);
+    assert_eq!(fixture.ctx.lamports(&recipients[2]), 0);
+
+    // GetExecutionState reports the same progress
+    fixture.ctx.process(&[client::get_execution_state(&bundle)], &[]).unwrap();
+    let (program, data) = fixture.ctx.return_data().expect("the state is returned");
+    assert_eq!(program, bundle_manager::id());
+    let state = ExecutionState::try_from_slice(&data).unwrap();
+    assert_eq!((state.last_executed_index, state.total_instructions), (Some(1), 3));
+    assert_eq!(state.executed_mask[0], 0b011);
+    assert!(state.executed_mask[1..].iter().all(|&byte| byte == 0));
+
+    let invalid_slice = Err(bundle_error(BundleError::InvalidExecutionSlice));
+    // Overlapping the executed records, skipping ahead, running past the end
+    assert_eq!(execute(&mut fixture, 1, 2, &accounts), invalid_slice);
+    assert_eq!(execute(&mut fixture, 0, 1, &accounts), invalid_slice);
+    assert_eq!(execute(&mut fixture, 2, 2, &accounts), invalid_slice);
+    assert_eq!(execute(&mut fixture, 2, 0, &accounts), invalid_slice);
+
+    execute(&mut fixture, 2, 1, &accounts[2..]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+    for recipient in &recipients {
+        assert_eq!(fixture.ctx.lamports(recipient), SOL);
+    }
+    assert_eq!(fixture.ctx.manager(&fixture.manager).total_bundles_executed, 1);
+}
+
+#[test]
+fn batches_execute_every_bundle_or_none() {
+    let mut fixture = Fixture::new();
+    let recipients = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let mut bundles = Vec::new();
+    let mut records = Vec::new();
+    for recipient in &recipients {
+        let bundle = fixture.create_bundle(&[(0, 1)]);
+        records.push(fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(recipient, SOL)).unwrap());
+        bundles.push(bundle);
+    }
+    // Cannot be funded, so ExecuteBundle alone would mark it Failed
+    let funding = FundWallets { lamports_each: 1_000 * SOL, wallet_count: 1 };
+    let unfunded = fixture.try_create_bundle(&[(0, 1)], Some(funding)).unwrap();
+    let unfunded_record = fixture.add_instruction(&unfunded, 0, &fixture.transfer_from_authority(&recipients[0], SOL))
+        .unwrap();
+    let accounts: Vec<AccountMeta> = recipients.iter().map(|recipient| AccountMeta::new(*recipient, false)).collect();
+    let batch = |fixture: &mut Fixture, bundles: &[Pubkey], records: &[Pubkey]| {
+        let remaining = common::execute_accounts(records, &accounts);
+        let batch = client::execute_bundle_batch(&fixture.manager, bundles, &fixture.authority, false, &remaining, 200_000, 0);
+        fixture.ctx.process(&[batch], &[fixture.authority])
+    };
+
+    // The first bundle would succeed on its own, but nothing of the batch is kept
+    let aborted = batch(&mut fixture, &[bundles[0], unfunded], &[records[0], unfunded_record]);
+    assert_eq!(aborted, Err(bundle_error(BundleError::BatchBundleNotExecuted)));
+    assert_eq!(fixture.ctx.bundle(&bundles[0]).status, BundleStatus::Created);
+    assert_eq!(fixture.ctx.bundle(&unfunded).status, BundleStatus::Created);
+    assert_eq!(fixture.ctx.lamports(&recipients[0]), 0);
+
+    batch(&mut fixture, &bundles, &records).unwrap();
+    for (bundle, recipient) in bundles.iter().zip(&recipients) {
+        assert_eq!(fixture.ctx.bundle(bundle).status, BundleStatus::Executed);
+        assert_eq!(fixture.ctx.lamports(recipient), SOL);
+    }
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!(manager.total_bundles_executed, 2);
+    assert_eq!(manager.active_bundles, 1);
+
+    // Executed bundles cannot run again, in a batch or otherwise
+    assert_eq!(batch(&mut fixture, &bundles[..1], &records[..1]), Err(bundle_error(BundleError::InvalidStateTransition)));
+}
+
+#[test]
+fn executions_add_up_in_the_manager_totals() {
+    let mut fixture = Fixture::with_config(ManagerConfig { priority_fee_multiplier: 10, ..Default::default() });
+    fixture.ctx.cpi_compute_cost = 1_000;
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    let recipient = Pubkey::new_unique();
+    let accounts = [AccountMeta::new(recipient, false)];
+    let run = |fixture: &mut Fixture, execute: Instruction| fixture.ctx.process(&[execute], &[authority]);
+    let totals = |fixture: &Fixture| {
+        let manager = fixture.ctx.manager(&fixture.manager);
+        (manager.total_instructions_executed, manager.total_compute_units, manager.total_priority_fees_paid)
+    };
+
+    // A failed attempt runs nothing but still pays its priority fee: 1 000
+    // micro-lamports per unit, at a multiplier of 1, over 200 000 units
+    let funding = FundWallets { lamports_each: 1_000 * SOL, wallet_count: 1 };
+    let failed = fixture.try_create_bundle(&[(0, 1)], Some(funding)).unwrap();
+    let record = fixture.add_instruction(&failed, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let remaining = common::execute_accounts(&[record], &accounts);
+    let execute = client::execute_bundle(&manager, &failed, &authority, false, &remaining, 200_000, 1_000);
+    run(&mut fixture, execute).unwrap();
+    assert_eq!(fixture.ctx.bundle(&failed).status, BundleStatus::Failed);
+    let (instructions, _, fees) = totals(&fixture);
+    assert_eq!((instructions, fees), (0, 200));
+
+    let mut bundles = Vec::new();
+    let mut records = Vec::new();
+    for _ in 0..3 {
+        let bundle = fixture.create_bundle(&[(0, 2)]);
+        for _ in 0..2 {
+            records.push(fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, 1)).unwrap());
+        }
+        bundles.push(bundle);
+    }
+    let remaining = common::execute_accounts(&records[..2], &accounts);
+    let dry_run = client::simulate_bundle(&manager, &bundles[0], &authority, false, &remaining, 200_000, 1_000);
+    run(&mut fixture, dry_run).unwrap();
+    assert_eq!(totals(&fixture).2, 200, "dry runs pay no fee of their own");
+
+    let execute = client::execute_bundle(&manager, &bundles[0], &authority, false, &remaining, 200_000, 1_000);
+    run(&mut fixture, execute).unwrap();
+    let (instructions, compute_units, fees) = totals(&fixture);
+    assert_eq!((instructions, fees), (2, 400));
+    assert!(compute_units >= 2_000, "both transfers' compute is counted, got {compute_units}");
+    let executed = fixture.ctx.events().into_iter().find_map(|event| match event {
+        BundleEvent::BundleExecuted(executed) => Some(executed.compute_units),
+        _ => None,
+    });
+    let executed = executed.expect("BundleExecuted is emitted");
+    assert!(executed >= 2_000 && executed <= compute_units, "the event carries the call's own {executed} units");
+
+    // A batch is one transaction, paying one priority fee for all its bundles
+    let remaining = common::execute_accounts(&records[2..], &accounts);
+    let batch = client::execute_bundle_batch(&manager, &bundles[1..], &authority, false, &remaining, 200_000, 1_000);
+    run(&mut fixture, batch).unwrap();
+    let (instructions, batch_compute_units, fees) = totals(&fixture);
+    assert_eq!((instructions, fees), (6, 600));
+    assert!(batch_compute_units >= compute_units + 4_000);
+}
+
+#[test]
+fn half_built_bundles_do_not_execute() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let wallet = find_wallet_address(&bundle_manager::id(), &fixture.manager, 1).0;
+    let bundle = fixture.create_bundle(&[(0, 1), (1, 1)]);
+    let first = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let accounts = [AccountMeta::new(recipient, false), AccountMeta::new(wallet, false)];
+
+    // Wallet 1 has nothing added, even though two records are passed
+    assert_eq!(
+        fixture.execute(&bundle, &[first, first], &accounts),
+        Err(bundle_error(BundleError::IncompleteBundle)),
+    );
+
+    // Once it has, the first record still cannot be counted in its place
+    let second = fixture.add_instruction(&bundle, 1, &system_instruction::transfer(&wallet, &recipient, 0)).unwrap();
+    assert_eq!(
+        fixture.execute(&bundle, &[first, first], &accounts),
+        Err(bundle_error(BundleError::InvalidRecordPda)),
+    );
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Created);
+
+    fixture.execute(&bundle, &[first, second], &accounts).unwrap();
+    assert_eq!(fixture.ctx.lamports(&recipient), SOL);
+}
+
+#[test]
+fn dry_runs_check_a_bundle_without_running_it() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let remaining = common::execute_accounts(&[record], &[AccountMeta::new(recipient, false)]);
+
+    let simulate = client::simulate_bundle(&fixture.manager, &bundle, &fixture.authority, false, &remaining, 200_000, 0);
+    let bundle_before = fixture.ctx.data(&bundle).to_vec();
+    fixture.ctx.process(&[simulate], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.data(&bundle), &bundle_before[..]);
+    assert_eq!(fixture.ctx.lamports(&recipient), 0);
+
+    let oversized = client::simulate_bundle(&fixture.manager, &bundle, &fixture.authority, false, &remaining, 1_400_001, 0);
+    assert_eq!(
+        fixture.ctx.process(&[oversized], &[fixture.authority]),
+        Err(bundle_error(BundleError::InvalidComputeBudget)),
+    );
+
+    // A cost the authority cannot cover is an error, not a Failed bundle
+    let funding = FundWallets { lamports_each: 1_000 * SOL, wallet_count: 1 };
+    let unfunded = fixture.try_create_bundle(&[(0, 1)], Some(funding)).unwrap();
+    let record = fixture.add_instruction(&unfunded, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let remaining = common::execute_accounts(&[record], &[AccountMeta::new(recipient, false)]);
+    let simulate = client::simulate_bundle(&fixture.manager, &unfunded, &fixture.authority, false, &remaining, 200_000, 0);
+    assert_eq!(
+        fixture.ctx.process(&[simulate], &[fixture.authority]),
+        Err(bundle_error(BundleError::InsufficientFundsForFunding)),
+    );
+    assert_eq!(fixture.ctx.bundle(&unfunded).status, BundleStatus::Created);
+}
+
+#[test]
+fn execution_needs_the_approval_threshold() {
+    let approvers: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+    let mut fixture = Fixture::with_initialize(|authority| {
+        client::initialize_with_approvals(authority, 0, Default::default(), &approvers, 2)
+    });
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+
+    let execute_signed_by = |fixture: &mut Fixture, signers: &[Pubkey]| {
+        let mut accounts = vec![AccountMeta::new(recipient, false)];
+        accounts.extend(signers.iter().map(|signer| AccountMeta::new_readonly(*signer, true)));
+        let remaining = common::execute_accounts(&[record], &accounts);
+        let execute = client::execute_bundle(&fixture.manager, &bundle, &fixture.authority, false, &remaining, 200_000, 0);
+        let signers: Vec<Pubkey> = signers.iter().copied().chain([fixture.authority]).collect();
+        fixture.ctx.process(&[execute], &signers)
+    };
+
+    // The bundle authority is not an approver, and outsiders do not count
+    assert_eq!(execute_signed_by(&mut fixture, &[]), Err(bundle_error(BundleError::InsufficientApprovals)));
+    assert_eq!(
+        execute_signed_by(&mut fixture, &[approvers[0], Pubkey::new_unique()]),
+        Err(bundle_error(BundleError::InsufficientApprovals)),
+    );
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Created);
+
+    execute_signed_by(&mut fixture, &[approvers[0], approvers[2]]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+    assert_eq!(fixture.ctx.lamports(&recipient), SOL);
+}
+
+#[test]
+fn paused_manager_rejects_bundle_work() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 2)]);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+
+    let pause = client::set_manager_status(&fixture.manager, &fixture.authority, true);
+    fixture.ctx.process(&[pause], &[fixture.authority]).unwrap();
+
+    assert_eq!(fixture.try_create_bundle(&[(0, 1)], None), Err(bundle_error(BundleError::ManagerPaused)));
+    assert_eq!(
+        fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)),
+        Err(bundle_error(BundleError::ManagerPaused)),
+    );
+    assert_eq!(
+        fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]),
+        Err(bundle_error(BundleError::ManagerPaused)),
+    );
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Created);
+}
+
+#[test]
+fn only_the_bundle_authority_can_add_remove_and_execute() {
+    let mut fixture = Fixture::new();
+    let intruder = fixture.ctx.funded_key(10 * SOL);
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 2)]);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+
+    let intruder_record = fixture.next_record(&bundle, 0);
+    let transfer = system_instruction::transfer(&intruder, &recipient, SOL);
+    let add = client::add_instruction(
+        &fixture.manager,
+        &bundle,
+        &intruder_record,
+        &intruder,
+        0,
+        &transfer,
+        Default::default(),
+    );
+    assert_eq!(
+        fixture.ctx.process(&[add], &[intruder]),
+        Err(bundle_error(BundleError::Unauthorized)),
+    );
+    let remove = client::remove_instruction(&fixture.manager, &bundle, &record, &intruder);
+    assert_eq!(fixture.ctx.process(&[remove], &[intruder]), Err(bundle_error(BundleError::Unauthorized)));
+
+    let remaining = common::execute_accounts(&[record], &[AccountMeta::new(recipient, false)]);
+    let execute = client::execute_bundle(&fixture.manager, &bundle, &intruder, false, &remaining, 200_000, 0);
+    assert_eq!(fixture.ctx.process(&[execute], &[intruder]), Err(ProgramError::MissingRequiredSignature));
+
+    assert_eq!(fixture.ctx.bundle(&bundle).added_instructions(), 1);
+    assert_eq!(fixture.ctx.lamports(&recipient), 0);
+}
+
+#[test]
+fn instructions_are_only_added_through_the_bundles_manager() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let initialize = client::initialize(&fixture.authority, 1, Default::default());
+    fixture.ctx.process(&[initialize], &[fixture.authority]).unwrap();
+    let other_manager = find_manager_address(&bundle_manager::id(), &fixture.authority, 1).0;
+
+    let record = fixture.next_record(&bundle, 0);
+    let transfer = fixture.transfer_from_authority(&recipient, SOL);
+    let add = client::add_instruction(&other_manager, &bundle, &record, &fixture.authority, 0, &transfer, Default::default());
+    assert_eq!(
+        fixture.ctx.process(&[add], &[fixture.authority]),
+        Err(bundle_error(BundleError::ManagerMismatch)),
+    );
+    assert_eq!(fixture.ctx.bundle(&bundle).added_instructions(), 0);
+}
+
+#[test]
+fn authorities_short_of_rent_are_refused_up_front() {
+    let mut fixture = Fixture::new();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let transfer = fixture.transfer_from_authority(&Pubkey::new_unique(), 1);
+    let authority = fixture.authority;
+    fixture.ctx.set_account(authority, Account { lamports: 1_000, ..Default::default() });
+
+    let short_of_rent = Err(bundle_error(BundleError::InsufficientFundsForRent));
+    assert_eq!(fixture.try_create_bundle(&[(0, 1)], None), short_of_rent);
+    assert_eq!(fixture.add_instruction(&bundle, 0, &transfer), short_of_rent);
+    assert!(fixture.ctx.logs().iter().any(|line| line.starts_with("Insufficient funds for rent")));
+    assert_eq!(fixture.ctx.bundle(&bundle).instructions_added, vec![0]);
+    assert_eq!(fixture.ctx.lamports(&authority), 1_000);
+}
+
+#[test]
+fn create_bundle_rejects_mismatched_wallet_vectors() {
+    let mut fixture = Fixture::new();
+    let bundle = fixture.next_bundle();
+    let bundle_seed = fixture.ctx.manager(&fixture.manager).bundle_seed;
+
+    let nonce = fixture.next_nonce();
+    let accounts = client::create_bundle(&fixture.manager, nonce, &fixture.authority, &[], 0, None, None).accounts;
+    let data = BundleInstruction::CreateBundle {
+        wallet_indexes: vec![0, 1, 2],
+        instructions_per_wallet: vec![1, 1],
+        category: 0,
+        on_failure: None,
+        funding: None,
+        client_nonce: nonce,
+        execute_after: None,
+        max_retries: None,
+        spend_limits: None,
+    };
+    let create = Instruction { program_id: bundle_manager::id(), accounts, data: data.try_to_vec().unwrap() };
+
+    assert_eq!(
+        fixture.ctx.process(&[create], &[fixture.authority]),
+        Err(bundle_error(BundleError::InvalidInstructionCount)),
+    );
+    assert!(fixture.ctx.account(&bundle).is_none());
+    assert_eq!(fixture.ctx.manager(&fixture.manager).bundle_seed, bundle_seed);
+}
+
+#[test]
+fn create_bundle_rejects_a_wallet_listed_twice() {
+    let mut fixture = Fixture::new();
+    let bundle = fixture.next_bundle();
+    assert_eq!(
+        fixture.try_create_bundle(&[(0, 1), (1, 1), (0, 2)], None),
+        Err(bundle_error(BundleError::DuplicateWallet)),
+    );
+    assert!(fixture.ctx.account(&bundle).is_none());
+
+    // Any category is stored as given
+    let nonce = fixture.next_nonce();
+    let create = client::create_bundle(&fixture.manager, nonce, &fixture.authority, &[(0, 1), (1, 1)], 200, None, None);
+    fixture.ctx.process(&[create], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).category, 200);
+}
+
+#[test]
+fn create_bundle_retries_with_the_same_nonce_create_one_bundle() {
+    let mut fixture = Fixture::new();
+    let create = client::create_bundle(&fixture.manager, 42, &fixture.authority, &[(0, 1)], 0, None, None);
+    let bundle = create.accounts[1].pubkey;
+
+    fixture.ctx.process(std::slice::from_ref(&create), &[fixture.authority]).unwrap();
+    fixture.ctx.process(std::slice::from_ref(&create), &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).bundle_id, 0);
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!((manager.bundle_seed, manager.active_bundles), (1, 1));
+
+    // The nonce is taken; a different bundle needs a new one
+    let other = client::create_bundle(&fixture.manager, 42, &fixture.authority, &[(0, 2)], 0, None, None);
+    assert_eq!(fixture.ctx.process(&[other], &[fixture.authority]), Err(ProgramError::AccountAlreadyInitialized));
+    assert_eq!(fixture.ctx.bundle(&bundle).instructions_per_wallet, vec![1]);
+
+    // Nonces are per authority, so another caller's 42 is a separate bundle
+    let stranger = fixture.ctx.funded_key(SOL);
+    let theirs = client::create_bundle(&fixture.manager, 42, &stranger, &[(0, 1)], 0, None, None);
+    assert_ne!(theirs.accounts[1].pubkey, bundle);
+    fixture.ctx.process(&[theirs], &[stranger]).unwrap();
+    assert_eq!(fixture.ctx.manager(&fixture.manager).bundle_seed, 2);
+}
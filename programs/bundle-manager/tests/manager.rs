@@ -0,0 +1,1125 @@
+//! Manager setup, limits, authority handoff and the counters it keeps across
+//! bundles.
+
+mod common;
+
+use std::slice;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use bundle_manager::{
+    client, compute_unit_price,
+    events::{BundleEvent, ManagerStatsResetEvent},
+    find_execution_log_address, find_instruction_record_address, find_manager_address, find_vault_address,
+    find_wallet_registry_address, Bundle, BundleError, BundleManager, BundleStatus, Delegate, DelegatePermissions,
+    ExecutionLog, ExecutionLogEntry, FundWallets, ManagerConfig, ManagerStats,
+    DEFAULT_MAX_ACCOUNTS_PER_INSTRUCTION, DEFAULT_MAX_INSTRUCTION_DATA_LEN, DEFAULT_MAX_WALLETS_PER_BUNDLE,
+    MAX_ACCOUNTS_PER_INSTRUCTION, MAX_ALLOWED_PROGRAMS, MAX_APPROVAL_AUTHORITIES, MAX_BATCH_ITEMS, MAX_DELEGATES,
+    MAX_INSTRUCTION_DATA_LEN, MAX_WALLETS_PER_BUNDLE,
+};
+use common::{bundle_error, execute_accounts, Account, Fixture, TestContext, SOL};
+use solana_program::{
+    address_lookup_table::{
+        self,
+        state::{AddressLookupTable, LookupTableMeta},
+    },
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
+};
+
+/// `bundle` in the version 8 layout, before `spend_limits`
+fn version_8_bundle(bundle: &Bundle) -> Vec<u8> {
+    let mut data = bundle.try_to_vec().unwrap();
+    data.truncate(data.len() - bundle.spend_limits.try_to_vec().unwrap().len());
+    data[1] = 8;
+    data
+}
+
+/// `bundle` in the version 7 layout, before `expires_at`
+fn version_7_bundle(bundle: &Bundle) -> Vec<u8> {
+    let mut data = version_8_bundle(bundle);
+    data.truncate(data.len() - 8);
+    data[1] = 7;
+    data
+}
+
+/// `bundle` in the version 6 layout, before `max_retries` and `retry_count`
+fn version_6_bundle(bundle: &Bundle) -> Vec<u8> {
+    let mut data = version_7_bundle(bundle);
+    data.truncate(data.len() - bundle.max_retries.try_to_vec().unwrap().len() - 1);
+    data[1] = 6;
+    data
+}
+
+/// `bundle` in the version 5 layout, before `execute_after`
+fn version_5_bundle(bundle: &Bundle) -> Vec<u8> {
+    let mut data = version_6_bundle(bundle);
+    data.truncate(data.len() - 8);
+    data[1] = 5;
+    data
+}
+
+/// `bundle` in the version 4 layout, before `compute_unit_limit`
+fn version_4_bundle(bundle: &Bundle) -> Vec<u8> {
+    let mut data = version_5_bundle(bundle);
+    data.truncate(data.len() - 4);
+    data[1] = 4;
+    data
+}
+
+/// `bundle` in the version 2 layout, before the account type and `seeds`
+fn version_2_bundle(bundle: &Bundle) -> Vec<u8> {
+    let mut data = version_4_bundle(bundle);
+    data.truncate(data.len() - bundle.seeds.try_to_vec().unwrap().len());
+    data.remove(0);
+    data[0] = 2;
+    data
+}
+
+/// `bundle` in the version 1 layout, before `next_record_index`, with or
+/// without the version byte pre-versioning accounts lack
+fn version_1_bundle(bundle: &Bundle, version_byte: bool) -> Vec<u8> {
+    let mut data = version_2_bundle(bundle);
+    data.truncate(data.len() - 2);
+    if !version_byte {
+        data.remove(0);
+    } else {
+        data[0] = 1;
+    }
+    data
+}
+
+/// `manager` in the version 1 layout, before the per-instruction limits,
+/// with or without the version byte pre-versioning accounts lack
+fn version_1_manager(manager: &BundleManager, version_byte: bool) -> Vec<u8> {
+    let config = &manager.config;
+    let mut data = if version_byte { vec![1] } else { Vec::new() };
+    (
+        manager.authority,
+        config.bundle_size,
+        config.priority_fee_multiplier,
+        config.ephemeral_results,
+        config.retry_cooldown_slots,
+        config.auto_pause_threshold,
+        config.expiry_seconds,
+        config.max_wallets_per_bundle,
+    )
+        .serialize(&mut data)
+        .unwrap();
+    (
+        manager.active_bundles,
+        manager.total_bundles_executed,
+        manager.total_bundles_failed,
+        manager.is_paused,
+        manager.bundle_seed,
+        manager.manager_index,
+        manager.bump,
+        manager.consecutive_failures,
+        manager.seed_authority,
+        manager.pending_authority,
+    )
+        .serialize(&mut data)
+        .unwrap();
+    // Allocated with room for a pending authority
+    data.resize(data.len() + 32 * usize::from(manager.pending_authority.is_none()), 0);
+    data
+}
+
+/// A manager account's data in the version 13 layout, before the program
+/// allowlist; a manager without one ends in zeros either way
+fn version_13_manager(data: &[u8]) -> Vec<u8> {
+    let mut data = data[..data.len() - (4 + 32 * MAX_ALLOWED_PROGRAMS)].to_vec();
+    data[1] = 13;
+    data
+}
+
+/// A manager account's data in the version 12 layout, before the per-slot
+/// throttle; an unthrottled manager that has executed nothing holds zeros
+/// in its place either way
+fn version_12_manager(data: &[u8]) -> Vec<u8> {
+    let data = version_13_manager(data);
+    let throttle = 2 + 32 + ManagerConfig::LEN - (2 + 8);
+    let mut data = [&data[..throttle], &data[throttle + 2 + 8..data.len() - (8 + 2 + 8)]].concat();
+    data[1] = 12;
+    data
+}
+
+/// A manager account's data in the version 11 layout, before the execution
+/// totals; a manager that has executed nothing ends in zeros either way
+fn version_11_manager(data: &[u8]) -> Vec<u8> {
+    let data = version_12_manager(data);
+    let mut data = data[..data.len() - (8 + 8 + 8)].to_vec();
+    data[1] = 11;
+    data
+}
+
+/// A manager account's data in the version 10 layout, before the
+/// delegates; a manager without any ends in zeros either way
+fn version_10_manager(data: &[u8]) -> Vec<u8> {
+    let data = version_11_manager(data);
+    let mut data = data[..data.len() - (4 + Delegate::LEN * MAX_DELEGATES)].to_vec();
+    data[1] = 10;
+    data
+}
+
+/// A manager account's data in the version 9 layout, before the lookup
+/// table; a manager without one ends in zeros either way
+fn version_9_manager(data: &[u8]) -> Vec<u8> {
+    let data = version_10_manager(data);
+    let mut data = data[..data.len() - 33].to_vec();
+    data[1] = 9;
+    data
+}
+
+/// A manager account's data in the version 8 layout, before the config's
+/// compute unit price cap
+fn version_8_manager(data: &[u8]) -> Vec<u8> {
+    let data = version_9_manager(data);
+    let cap = 2 + 32 + ManagerConfig::LEN - (2 + 8) - 8;
+    let mut data = [&data[..cap], &data[cap + 8..]].concat();
+    data[1] = 8;
+    data
+}
+
+fn set_data(ctx: &mut TestContext, key: Pubkey, data: Vec<u8>) {
+    let mut account = ctx.account(&key).unwrap().clone();
+    account.data = data;
+    ctx.set_account(key, account);
+}
+
+#[test]
+fn initialize_rejects_an_existing_manager() {
+    let mut fixture = Fixture::new();
+    let again = client::initialize(&fixture.authority, 0, ManagerConfig::default());
+    assert_eq!(
+        fixture.ctx.process(&[again], &[fixture.authority]),
+        Err(bundle_error(BundleError::AlreadyInitialized)),
+    );
+}
+
+#[test]
+fn create_bundle_respects_the_manager_wallet_limit() {
+    let mut fixture = Fixture::with_config(ManagerConfig { max_wallets_per_bundle: 5, ..Default::default() });
+    let five: Vec<(u8, u8)> = (0..5).map(|index| (index, 1)).collect();
+    let six: Vec<(u8, u8)> = (0..6).map(|index| (index, 1)).collect();
+
+    fixture.create_bundle(&five);
+    assert_eq!(fixture.try_create_bundle(&six, None), Err(bundle_error(BundleError::TooManyWallets)));
+}
+
+#[test]
+fn initialize_defaults_and_caps_the_instruction_limits() {
+    let fixture = Fixture::new();
+    let config = fixture.ctx.manager(&fixture.manager).config;
+    assert_eq!(config.max_instruction_data_len, DEFAULT_MAX_INSTRUCTION_DATA_LEN);
+    assert_eq!(config.max_accounts_per_instruction, DEFAULT_MAX_ACCOUNTS_PER_INSTRUCTION);
+
+    let mut fixture = Fixture::with_config(ManagerConfig {
+        max_instruction_data_len: MAX_INSTRUCTION_DATA_LEN,
+        max_accounts_per_instruction: MAX_ACCOUNTS_PER_INSTRUCTION,
+        ..Default::default()
+    });
+    let too_long = ManagerConfig { max_instruction_data_len: MAX_INSTRUCTION_DATA_LEN + 1, ..Default::default() };
+    assert_eq!(
+        fixture.ctx.process(&[client::initialize(&fixture.authority, 1, too_long)], &[fixture.authority]),
+        Err(bundle_error(BundleError::InstructionDataTooLarge)),
+    );
+    let too_many = ManagerConfig { max_accounts_per_instruction: MAX_ACCOUNTS_PER_INSTRUCTION + 1, ..Default::default() };
+    assert_eq!(
+        fixture.ctx.process(&[client::initialize(&fixture.authority, 1, too_many)], &[fixture.authority]),
+        Err(bundle_error(BundleError::TooManyInstructionAccounts)),
+    );
+}
+
+#[test]
+fn initialize_rejects_unreachable_approval_thresholds() {
+    let mut fixture = Fixture::new();
+    let approvers: Vec<Pubkey> = (0..=MAX_APPROVAL_AUTHORITIES).map(|_| Pubkey::new_unique()).collect();
+    let invalid: [(&[Pubkey], u8); 5] = [
+        (&[], 0),
+        (&[], 2),
+        (&approvers[..2], 3),
+        (&[approvers[0], approvers[0]], 2),
+        (&approvers, 1),
+    ];
+    for (authorities, threshold) in invalid {
+        let initialize = client::initialize_with_approvals(
+            &fixture.authority, 1, ManagerConfig::default(), authorities, threshold,
+        );
+        assert_eq!(
+            fixture.ctx.process(&[initialize], &[fixture.authority]),
+            Err(bundle_error(BundleError::InvalidApprovalThreshold)),
+            "{} authorities, threshold {threshold}", authorities.len(),
+        );
+    }
+
+    let default = fixture.ctx.manager(&fixture.manager);
+    assert!(default.authorities.is_empty());
+    assert_eq!(default.threshold, 1);
+}
+
+#[test]
+fn add_instruction_respects_the_manager_instruction_limits() {
+    // A system transfer is 12 bytes of data over 2 accounts, exactly the limits
+    let mut fixture = Fixture::with_config(ManagerConfig {
+        max_instruction_data_len: 12,
+        max_accounts_per_instruction: 2,
+        ..Default::default()
+    });
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 3)]);
+    let transfer = fixture.transfer_from_authority(&recipient, SOL);
+    fixture.add_instruction(&bundle, 0, &transfer).unwrap();
+
+    let long = Instruction::new_with_bytes(system_program::id(), &[0; 13], transfer.accounts.clone());
+    assert_eq!(fixture.add_instruction(&bundle, 0, &long), Err(bundle_error(BundleError::InstructionDataTooLarge)));
+
+    let mut wide = transfer;
+    wide.accounts.push(AccountMeta::new_readonly(Pubkey::new_unique(), false));
+    assert_eq!(
+        fixture.add_instruction(&bundle, 0, &wide),
+        Err(bundle_error(BundleError::TooManyInstructionAccounts)),
+    );
+    assert_eq!(fixture.ctx.bundle(&bundle).added_instructions(), 1);
+}
+
+#[test]
+fn create_bundle_stops_at_the_last_seed() {
+    let mut fixture = Fixture::new();
+    let mut manager = fixture.ctx.manager(&fixture.manager);
+    manager.bundle_seed = u32::MAX;
+    fixture.ctx.write_state(&fixture.manager, &manager);
+
+    assert_eq!(fixture.try_create_bundle(&[(0, 1)], None), Err(bundle_error(BundleError::SeedExhausted)));
+    assert_eq!(fixture.ctx.manager(&fixture.manager).active_bundles, 0);
+}
+
+#[test]
+fn batch_add_rejects_more_than_max_batch_items() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let count = MAX_BATCH_ITEMS as u8 + 1;
+    let bundle = fixture.create_bundle(&[(0, count)]);
+
+    let items: Vec<(Pubkey, _)> = (0..count as u16)
+        .map(|record_index| {
+            let record = find_instruction_record_address(&bundle_manager::id(), &bundle, 0, record_index).0;
+            let transfer = fixture.transfer_from_authority(&recipient, 1);
+            (record, client::batch_item(0, &transfer, Default::default()))
+        })
+        .collect();
+    let batch = client::batch_add_instruction(&fixture.manager, &bundle, &fixture.authority, items);
+
+    assert_eq!(fixture.ctx.process(&[batch], &[fixture.authority]), Err(bundle_error(BundleError::BatchTooLarge)));
+    assert_eq!(fixture.ctx.bundle(&bundle).added_instructions(), 0);
+}
+
+#[test]
+fn authority_handoff_takes_effect_once_accepted() {
+    let mut fixture = Fixture::new();
+    let successor = fixture.ctx.funded_key(SOL);
+
+    let propose = client::set_authority(&fixture.manager, &fixture.authority, successor);
+    fixture.ctx.process(&[propose], &[fixture.authority]).unwrap();
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!(manager.authority, fixture.authority);
+    assert_eq!(manager.pending_authority, Some(successor));
+
+    let stranger = fixture.ctx.funded_key(SOL);
+    assert_eq!(
+        fixture.ctx.process(&[client::accept_authority(&fixture.manager, &stranger)], &[stranger]),
+        Err(ProgramError::MissingRequiredSignature),
+    );
+
+    fixture.ctx.process(&[client::accept_authority(&fixture.manager, &successor)], &[successor]).unwrap();
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!(manager.authority, successor);
+    assert_eq!(manager.pending_authority, None);
+
+    // The old authority no longer controls the manager
+    let pause = client::set_manager_status(&fixture.manager, &fixture.authority, true);
+    assert!(fixture.ctx.process(&[pause], &[fixture.authority]).is_err());
+    let pause = client::set_manager_status(&fixture.manager, &successor, true);
+    fixture.ctx.process(&[pause], &[successor]).unwrap();
+    assert!(fixture.ctx.manager(&fixture.manager).is_paused);
+
+    assert_eq!(
+        fixture.ctx.process(&[client::accept_authority(&fixture.manager, &successor)], &[successor]),
+        Err(bundle_error(BundleError::NoPendingAuthority)),
+    );
+}
+
+#[test]
+fn pausing_a_paused_manager_changes_nothing() {
+    let mut fixture = Fixture::new();
+    let pause = client::set_manager_status(&fixture.manager, &fixture.authority, true);
+    fixture.ctx.process(slice::from_ref(&pause), &[fixture.authority]).unwrap();
+    let manager_before = fixture.ctx.data(&fixture.manager).to_vec();
+    let lamports_before = fixture.ctx.lamports(&fixture.manager);
+
+    fixture.ctx.process(&[pause], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.data(&fixture.manager), &manager_before[..]);
+    assert_eq!(fixture.ctx.lamports(&fixture.manager), lamports_before);
+    assert!(fixture.ctx.logs().iter().any(|line| line.contains("NoChange")));
+    assert!(fixture.ctx.manager(&fixture.manager).is_paused);
+}
+
+#[test]
+fn stats_count_executed_failed_and_expired_bundles() {
+    let mut fixture = Fixture::with_config(ManagerConfig { expiry_seconds: 60, ..Default::default() });
+    let recipient = Pubkey::new_unique();
+
+    let executed = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&executed, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    fixture.execute(&executed, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+
+    // Funding more than the authority holds fails the attempt, which is committed
+    let funding = FundWallets { lamports_each: 1_000 * SOL, wallet_count: 1 };
+    let failed = fixture.try_create_bundle(&[(0, 1)], Some(funding)).unwrap();
+    let record = fixture.add_instruction(&failed, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    fixture.execute(&failed, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&failed).status, BundleStatus::Failed);
+
+    let expired = fixture.create_bundle(&[(0, 1)]);
+    let expire = client::expire_bundle(&fixture.manager, &expired, None);
+    assert_eq!(
+        fixture.ctx.process(std::slice::from_ref(&expire), &[]),
+        Err(bundle_error(BundleError::BundleNotExpired)),
+    );
+    fixture.ctx.advance_time(61);
+    fixture.ctx.process(&[expire], &[]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&expired).status, BundleStatus::Failed);
+
+    fixture.ctx.process(&[client::get_managers_stats(&[fixture.manager])], &[]).unwrap();
+    let (program, data) = fixture.ctx.return_data().expect("stats are returned");
+    assert_eq!(program, bundle_manager::id());
+    let stats = Vec::<ManagerStats>::try_from_slice(&data).unwrap();
+    assert_eq!(stats, vec![ManagerStats {
+        version: BundleManager::VERSION,
+        active_bundles: 0,
+        total_bundles_executed: 1,
+        total_bundles_failed: 2,
+        is_paused: false,
+    }]);
+
+    // Several managers are read in one call, in account order
+    let authority = fixture.authority;
+    let (second, _) = find_manager_address(&bundle_manager::id(), &authority, 1);
+    fixture.ctx.process(&[client::initialize(&authority, 1, ManagerConfig::default())], &[authority]).unwrap();
+    fixture.ctx.process(&[client::set_manager_status(&second, &authority, true)], &[authority]).unwrap();
+    fixture.ctx.process(&[client::get_managers_stats(&[second, fixture.manager])], &[]).unwrap();
+    let stats = Vec::<ManagerStats>::try_from_slice(&fixture.ctx.return_data().unwrap().1).unwrap();
+    assert_eq!(stats, vec![
+        ManagerStats {
+            version: BundleManager::VERSION,
+            active_bundles: 0,
+            total_bundles_executed: 0,
+            total_bundles_failed: 0,
+            is_paused: true,
+        },
+        ManagerStats {
+            version: BundleManager::VERSION,
+            active_bundles: 0,
+            total_bundles_executed: 1,
+            total_bundles_failed: 2,
+            is_paused: false,
+        },
+    ]);
+}
+
+#[test]
+fn executions_append_an_entry_to_the_execution_log() {
+    let mut fixture = Fixture::new();
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    fixture.ctx.process(&[client::initialize_execution_log(&manager, &authority, 4)], &[authority]).unwrap();
+    let log = find_execution_log_address(&bundle_manager::id(), &manager).0;
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+
+    // The bundle's one transfer is the only CPI before the entry is written
+    fixture.ctx.cpi_compute_cost = 1_000;
+    let remaining = execute_accounts(&[record], &[AccountMeta::new(recipient, false)]);
+    let execute = client::execute_bundle(&manager, &bundle, &authority, true, &remaining, 200_000, 0);
+    fixture.ctx.process(&[execute], &[authority]).unwrap();
+
+    let header = ExecutionLog::unpack(fixture.ctx.data(&log)).unwrap();
+    assert_eq!((header.next_index, header.total_appended), (1, 1));
+    let offset = ExecutionLog::entry_offset(0);
+    let entry = ExecutionLogEntry::try_from_slice(&fixture.ctx.data(&log)[offset..offset + ExecutionLogEntry::LEN])
+        .unwrap();
+    assert_eq!(entry.executor, authority);
+    assert_eq!(entry.bundle_id, fixture.ctx.bundle(&bundle).bundle_id);
+    assert_eq!(entry.slot, fixture.ctx.clock.slot);
+    assert_eq!(entry.status, BundleStatus::Executed);
+    assert_eq!(entry.compute_units, 1_000);
+}
+
+#[test]
+fn reset_stats_recounts_active_bundles_and_zeroes_the_totals() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let executed = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&executed, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    fixture.execute(&executed, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+    let active = fixture.create_bundle(&[(0, 1)]);
+
+    // A gauge that has drifted from the bundles it counts
+    let mut manager = fixture.ctx.manager(&fixture.manager);
+    manager.active_bundles = 7;
+    fixture.ctx.write_state(&fixture.manager, &manager);
+
+    let stranger = fixture.ctx.funded_key(SOL);
+    let reset = client::reset_stats(&fixture.manager, &stranger, &[active]);
+    assert_eq!(fixture.ctx.process(&[reset], &[stranger]), Err(ProgramError::MissingRequiredSignature));
+    let twice = client::reset_stats(&fixture.manager, &fixture.authority, &[active, active]);
+    assert_eq!(fixture.ctx.process(&[twice], &[fixture.authority]), Err(ProgramError::InvalidArgument));
+
+    let authority_before = fixture.ctx.lamports(&fixture.authority);
+    let reset = client::reset_stats(&fixture.manager, &fixture.authority, &[executed, active]);
+    fixture.ctx.process(&[reset], &[fixture.authority]).unwrap();
+
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!((manager.active_bundles, manager.total_bundles_executed, manager.total_bundles_failed), (1, 0, 0));
+    assert_eq!(manager.authority, fixture.authority);
+    assert_eq!(fixture.ctx.lamports(&fixture.authority), authority_before);
+    assert_eq!(fixture.ctx.events(), vec![BundleEvent::ManagerStatsReset(ManagerStatsResetEvent {
+        manager: fixture.manager,
+        authority: fixture.authority,
+        previous_active_bundles: 7,
+        previous_total_bundles_executed: 1,
+        previous_total_bundles_failed: 0,
+        active_bundles: 1,
+    })]);
+}
+
+#[test]
+fn pre_versioning_accounts_are_rejected_until_migrated() {
+    let mut fixture = Fixture::new();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+
+    // Accounts written before versioning are the version 1 layout minus the version byte
+    let legacy_manager = version_1_manager(&fixture.ctx.manager(&fixture.manager), false);
+    set_data(&mut fixture.ctx, fixture.manager, legacy_manager);
+    let legacy_bundle = version_1_bundle(&fixture.ctx.bundle(&bundle), false);
+    set_data(&mut fixture.ctx, bundle, legacy_bundle);
+
+    // Without an account type byte they do not read as a manager at all
+    let recipient = Pubkey::new_unique();
+    assert_eq!(
+        fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)),
+        Err(bundle_error(BundleError::InvalidAccountType)),
+    );
+
+    let stranger = fixture.ctx.funded_key(SOL);
+    let migrate = client::migrate_manager(&fixture.manager, &stranger, &[bundle]);
+    assert_eq!(fixture.ctx.process(&[migrate], &[stranger]), Err(ProgramError::MissingRequiredSignature));
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[bundle]);
+    fixture.ctx.process(std::slice::from_ref(&migrate), &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.manager(&fixture.manager).version, BundleManager::VERSION);
+    assert_eq!(fixture.ctx.bundle(&bundle).version, Bundle::VERSION);
+    assert_eq!(fixture.ctx.data(&fixture.manager).len(), BundleManager::LEN);
+
+    // Migrating again leaves current accounts alone
+    let manager_before = fixture.ctx.data(&fixture.manager).to_vec();
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.data(&fixture.manager), &manager_before[..]);
+
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+}
+
+#[test]
+fn version_1_bundles_migrate_with_a_fresh_record_counter() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let legacy_bundle = version_1_bundle(&fixture.ctx.bundle(&bundle), true);
+    set_data(&mut fixture.ctx, bundle, legacy_bundle);
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[bundle]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    let migrated = fixture.ctx.bundle(&bundle);
+    assert_eq!((migrated.version, migrated.next_record_index), (Bundle::VERSION, 0));
+    assert_eq!(fixture.ctx.data(&bundle).len(), Bundle::space(1, &None));
+
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    assert_eq!(record, find_instruction_record_address(&bundle_manager::id(), &bundle, 0, 0).0);
+    assert_eq!(fixture.ctx.bundle(&bundle).next_record_index, 1);
+}
+
+#[test]
+fn version_1_managers_migrate_with_default_instruction_limits() {
+    let mut fixture = Fixture::with_config(ManagerConfig {
+        max_wallets_per_bundle: 5,
+        expiry_seconds: 60,
+        max_instruction_data_len: 100,
+        ..Default::default()
+    });
+    fixture.create_bundle(&[(0, 1)]);
+    let before = fixture.ctx.manager(&fixture.manager);
+    set_data(&mut fixture.ctx, fixture.manager, version_1_manager(&before, true));
+    let pause = client::set_manager_status(&fixture.manager, &fixture.authority, true);
+    assert_eq!(
+        fixture.ctx.process(&[pause], &[fixture.authority]),
+        Err(bundle_error(BundleError::UnsupportedAccountVersion)),
+    );
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.data(&fixture.manager).len(), BundleManager::LEN);
+
+    let after = fixture.ctx.manager(&fixture.manager);
+    assert_eq!(after.version, BundleManager::VERSION);
+    assert_eq!(after.config.max_wallets_per_bundle, 5);
+    assert_eq!(after.config.expiry_seconds, 60);
+    assert_eq!(after.config.max_instruction_data_len, DEFAULT_MAX_INSTRUCTION_DATA_LEN);
+    assert_eq!(after.config.max_accounts_per_instruction, DEFAULT_MAX_ACCOUNTS_PER_INSTRUCTION);
+    assert_eq!(after.fee_lamports, 0);
+    assert_eq!(after.tip_account, fixture.authority);
+    assert_eq!((after.authorities.len(), after.threshold), (0, 1));
+    assert_eq!((after.active_bundles, after.bundle_seed), (before.active_bundles, before.bundle_seed));
+    fixture.create_bundle(&[(0, 1)]);
+}
+
+#[test]
+fn accounts_without_an_account_type_migrate_to_one() {
+    let mut fixture = Fixture::new();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    // Layouts before version 13 have no throttle state, so it migrates empty
+    let mut manager = fixture.ctx.manager(&fixture.manager);
+    (manager.throttle_slot, manager.bundles_in_slot) = (0, 0);
+    fixture.ctx.write_state(&fixture.manager, &manager);
+    let manager_before = fixture.ctx.data(&fixture.manager).to_vec();
+    let mut bundle_before = fixture.ctx.bundle(&bundle);
+
+    // Manager version 6 is version 8 minus the account type and the trailing wallet_registry flag
+    let manager_v8 = version_8_manager(&manager_before);
+    let mut manager_v6 = manager_v8[1..manager_v8.len() - 1].to_vec();
+    manager_v6[0] = 6;
+    set_data(&mut fixture.ctx, fixture.manager, manager_v6);
+    set_data(&mut fixture.ctx, bundle, version_2_bundle(&bundle_before));
+    let pause = client::set_manager_status(&fixture.manager, &fixture.authority, true);
+    assert_eq!(
+        fixture.ctx.process(&[pause], &[fixture.authority]),
+        Err(bundle_error(BundleError::InvalidAccountType)),
+    );
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[bundle]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.data(&fixture.manager), &manager_before[..]);
+    // The bundle's seeds were not recorded before version 4
+    bundle_before.seeds = None;
+    let expected = bundle_before.try_to_vec().unwrap();
+    assert_eq!(&fixture.ctx.data(&bundle)[..expected.len()], &expected[..]);
+}
+
+#[test]
+fn version_7_managers_migrate_without_a_wallet_registry() {
+    let mut fixture = Fixture::new();
+    let manager_before = fixture.ctx.data(&fixture.manager).to_vec();
+    let manager_v8 = version_8_manager(&manager_before);
+    let mut manager_v7 = manager_v8[..manager_v8.len() - 1].to_vec();
+    manager_v7[1] = 7;
+    set_data(&mut fixture.ctx, fixture.manager, manager_v7);
+    let pause = client::set_manager_status(&fixture.manager, &fixture.authority, true);
+    assert_eq!(
+        fixture.ctx.process(&[pause], &[fixture.authority]),
+        Err(bundle_error(BundleError::UnsupportedAccountVersion)),
+    );
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.data(&fixture.manager), &manager_before[..]);
+    assert!(!fixture.ctx.manager(&fixture.manager).wallet_registry);
+}
+
+#[test]
+fn version_8_managers_migrate_without_a_compute_unit_price_cap() {
+    let mut fixture = Fixture::with_config(ManagerConfig { priority_fee_multiplier: 15, ..Default::default() });
+    let manager_before = fixture.ctx.data(&fixture.manager).to_vec();
+    set_data(&mut fixture.ctx, fixture.manager, version_8_manager(&manager_before));
+    let pause = client::set_manager_status(&fixture.manager, &fixture.authority, true);
+    assert_eq!(
+        fixture.ctx.process(&[pause], &[fixture.authority]),
+        Err(bundle_error(BundleError::UnsupportedAccountVersion)),
+    );
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.data(&fixture.manager), &manager_before[..]);
+    let config = fixture.ctx.manager(&fixture.manager).config;
+    assert_eq!((config.priority_fee_multiplier, config.max_compute_unit_price), (15, 0));
+}
+
+#[test]
+fn version_13_managers_migrate_allowing_every_program() {
+    let mut fixture = Fixture::with_config(ManagerConfig { max_bundles_per_slot: 4, ..Default::default() });
+    fixture.create_bundle(&[(0, 1)]);
+    let manager_before = fixture.ctx.data(&fixture.manager).to_vec();
+    set_data(&mut fixture.ctx, fixture.manager, version_13_manager(&manager_before));
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.data(&fixture.manager), &manager_before[..]);
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!((manager.bundles_in_slot, manager.allowed_programs.len()), (1, 0));
+}
+
+#[test]
+fn version_12_managers_migrate_unthrottled() {
+    let mut fixture = Fixture::new();
+    let manager_before = fixture.ctx.data(&fixture.manager).to_vec();
+    set_data(&mut fixture.ctx, fixture.manager, version_12_manager(&manager_before));
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.data(&fixture.manager), &manager_before[..]);
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!((manager.config.max_bundles_per_slot, manager.config.cooldown_slots), (0, 0));
+}
+
+#[test]
+fn throttled_managers_limit_bundles_per_slot_and_space_out_executions() {
+    let config = ManagerConfig { max_bundles_per_slot: 2, cooldown_slots: 3, ..Default::default() };
+    let mut fixture = Fixture::with_config(config);
+    let recipient = Pubkey::new_unique();
+    let accounts = [AccountMeta::new(recipient, false)];
+    let mut bundles = Vec::new();
+    for _ in 0..2 {
+        let bundle = fixture.create_bundle(&[(0, 1)]);
+        let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, 1)).unwrap();
+        bundles.push((bundle, record));
+    }
+    assert_eq!(fixture.try_create_bundle(&[(0, 1)], None), Err(bundle_error(BundleError::SlotBundleLimitReached)));
+
+    // A new slot starts a new count, and executions count too
+    fixture.ctx.warp_slots(1);
+    let (first, first_record) = bundles[0];
+    let (second, second_record) = bundles[1];
+    let remaining = execute_accounts(&[first_record], &accounts);
+    let dry_run = client::simulate_bundle(&fixture.manager, &first, &fixture.authority, false, &remaining, 200_000, 0);
+    fixture.ctx.process(&[dry_run], &[fixture.authority]).unwrap();
+    fixture.execute(&first, &[first_record], &accounts).unwrap();
+    assert_eq!(
+        fixture.execute(&second, &[second_record], &accounts),
+        Err(bundle_error(BundleError::ExecutionCooldownActive)),
+    );
+    fixture.create_bundle(&[(0, 1)]);
+    assert_eq!(fixture.try_create_bundle(&[(0, 1)], None), Err(bundle_error(BundleError::SlotBundleLimitReached)));
+
+    fixture.ctx.warp_slots(2);
+    assert_eq!(
+        fixture.execute(&second, &[second_record], &accounts),
+        Err(bundle_error(BundleError::ExecutionCooldownActive)),
+    );
+    fixture.ctx.warp_slots(1);
+    fixture.execute(&second, &[second_record], &accounts).unwrap();
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!((manager.throttle_slot, manager.bundles_in_slot, manager.last_execution_slot), (104, 1, 104));
+    assert_eq!(manager.total_bundles_executed, 2);
+}
+
+#[test]
+fn lookup_tables_link_only_when_they_are_lookup_tables() {
+    let mut fixture = Fixture::new();
+    let table = Pubkey::new_unique();
+    let addresses = vec![fixture.manager, system_program::id()];
+    let table_data = AddressLookupTable { meta: LookupTableMeta::new(fixture.authority), addresses: addresses.into() }
+        .serialize_for_tests()
+        .unwrap();
+    let account = |owner: Pubkey| Account { lamports: SOL, data: table_data.clone(), owner, executable: false };
+    fixture.ctx.set_account(table, account(system_program::id()));
+
+    let link = client::link_lookup_table(&fixture.manager, &fixture.authority, Some(&table));
+    assert_eq!(
+        fixture.ctx.process(slice::from_ref(&link), &[fixture.authority]),
+        Err(bundle_error(BundleError::InvalidLookupTable)),
+    );
+    fixture.ctx.set_account(table, account(address_lookup_table::program::id()));
+    fixture.ctx.process(&[link], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.manager(&fixture.manager).lookup_table, Some(table));
+
+    let unlink = client::link_lookup_table(&fixture.manager, &fixture.authority, None);
+    fixture.ctx.process(&[unlink], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.manager(&fixture.manager).lookup_table, None);
+}
+
+#[test]
+fn version_9_managers_migrate_without_a_lookup_table() {
+    let mut fixture = Fixture::new();
+    let manager_before = fixture.ctx.data(&fixture.manager).to_vec();
+    set_data(&mut fixture.ctx, fixture.manager, version_9_manager(&manager_before));
+    let pause = client::set_manager_status(&fixture.manager, &fixture.authority, true);
+    assert_eq!(
+        fixture.ctx.process(&[pause], &[fixture.authority]),
+        Err(bundle_error(BundleError::UnsupportedAccountVersion)),
+    );
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.data(&fixture.manager), &manager_before[..]);
+    assert_eq!(fixture.ctx.manager(&fixture.manager).lookup_table, None);
+}
+
+#[test]
+fn version_10_managers_migrate_without_delegates() {
+    let mut fixture = Fixture::new();
+    let manager_before = fixture.ctx.data(&fixture.manager).to_vec();
+    set_data(&mut fixture.ctx, fixture.manager, version_10_manager(&manager_before));
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.data(&fixture.manager), &manager_before[..]);
+    assert!(fixture.ctx.manager(&fixture.manager).delegates.is_empty());
+}
+
+#[test]
+fn version_11_managers_migrate_with_empty_execution_totals() {
+    let mut fixture = Fixture::new();
+    let bot = Pubkey::new_unique();
+    let permissions = DelegatePermissions { can_execute: true, ..Default::default() };
+    let add = client::add_delegate(&fixture.manager, &fixture.authority, &bot, permissions);
+    fixture.ctx.process(&[add], &[fixture.authority]).unwrap();
+    let manager_before = fixture.ctx.data(&fixture.manager).to_vec();
+    set_data(&mut fixture.ctx, fixture.manager, version_11_manager(&manager_before));
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.data(&fixture.manager), &manager_before[..]);
+    let manager = fixture.ctx.manager(&fixture.manager);
+    assert_eq!(manager.delegate(&bot), Some(permissions));
+    assert_eq!((manager.total_instructions_executed, manager.total_priority_fees_paid), (0, 0));
+}
+
+#[test]
+fn delegates_act_for_the_authority_within_their_permissions() {
+    let mut fixture = Fixture::new();
+    let bot = fixture.ctx.funded_key(10 * SOL);
+    let builder = DelegatePermissions { can_create: true, can_execute: true, can_pause: false };
+    let forged = client::add_delegate(&fixture.manager, &bot, &bot, builder);
+    assert_eq!(fixture.ctx.process(&[forged], &[bot]), Err(ProgramError::MissingRequiredSignature));
+    let add = client::add_delegate(&fixture.manager, &fixture.authority, &bot, builder);
+    fixture.ctx.process(&[add], &[fixture.authority]).unwrap();
+
+    // The bot creates a bundle the authority owns, paying its rent
+    let bundle = fixture.next_bundle();
+    let authority_lamports = fixture.ctx.lamports(&fixture.authority);
+    let create =
+        client::create_bundle(&fixture.manager, fixture.next_nonce(), &fixture.authority, &[(0, 1)], 0, None, None);
+    fixture.ctx.process(&[client::with_create_delegate(create, &bot)], &[bot]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).authority, fixture.authority);
+    assert_eq!(fixture.ctx.lamports(&fixture.authority), authority_lamports);
+
+    // ... and executes it once built
+    let recipient = Pubkey::new_unique();
+    let record = fixture.add_instruction(&bundle, 0, &system_instruction::transfer(&bot, &recipient, SOL)).unwrap();
+    let remaining = execute_accounts(&[record], &[AccountMeta::new(recipient, false)]);
+    let execute = client::execute_bundle(&fixture.manager, &bundle, &bot, false, &remaining, 200_000, 0);
+    fixture.ctx.process(&[execute], &[bot]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+
+    // Other users' bundles stay their own
+    let user = fixture.ctx.funded_key(SOL);
+    let create = client::create_bundle(&fixture.manager, 0, &user, &[(0, 1)], 0, None, None);
+    let user_bundle = create.accounts[1].pubkey;
+    fixture.ctx.process(&[create], &[user]).unwrap();
+    let execute = client::execute_bundle(&fixture.manager, &user_bundle, &bot, false, &[], 200_000, 0);
+    assert_eq!(fixture.ctx.process(&[execute], &[bot]), Err(ProgramError::MissingRequiredSignature));
+
+    // Pausing needs can_pause, which adding the bot again grants in place;
+    // unpausing stays with the authority
+    let pause = client::set_manager_status(&fixture.manager, &bot, true);
+    assert_eq!(fixture.ctx.process(slice::from_ref(&pause), &[bot]), Err(ProgramError::MissingRequiredSignature));
+    let pauser = DelegatePermissions { can_pause: true, ..builder };
+    let add = client::add_delegate(&fixture.manager, &fixture.authority, &bot, pauser);
+    fixture.ctx.process(&[add], &[fixture.authority]).unwrap();
+    assert_eq!(fixture.ctx.manager(&fixture.manager).delegates.len(), 1);
+    fixture.ctx.process(&[pause], &[bot]).unwrap();
+    let unpause = client::set_manager_status(&fixture.manager, &bot, false);
+    assert_eq!(fixture.ctx.process(&[unpause], &[bot]), Err(ProgramError::MissingRequiredSignature));
+    let unpause = client::set_manager_status(&fixture.manager, &fixture.authority, false);
+    fixture.ctx.process(&[unpause], &[fixture.authority]).unwrap();
+
+    let remove = client::remove_delegate(&fixture.manager, &fixture.authority, &bot);
+    fixture.ctx.process(slice::from_ref(&remove), &[fixture.authority]).unwrap();
+    assert_eq!(
+        fixture.ctx.process(&[remove], &[fixture.authority]),
+        Err(bundle_error(BundleError::DelegateNotFound)),
+    );
+    let create =
+        client::create_bundle(&fixture.manager, fixture.next_nonce(), &fixture.authority, &[(0, 1)], 0, None, None);
+    assert_eq!(
+        fixture.ctx.process(&[client::with_create_delegate(create, &bot)], &[bot]),
+        Err(ProgramError::MissingRequiredSignature),
+    );
+
+    for _ in 0..MAX_DELEGATES {
+        let add = client::add_delegate(&fixture.manager, &fixture.authority, &Pubkey::new_unique(), builder);
+        fixture.ctx.process(&[add], &[fixture.authority]).unwrap();
+    }
+    let add = client::add_delegate(&fixture.manager, &fixture.authority, &bot, builder);
+    assert_eq!(
+        fixture.ctx.process(&[add], &[fixture.authority]),
+        Err(bundle_error(BundleError::TooManyDelegates)),
+    );
+}
+
+#[test]
+fn allowlisted_managers_only_bundle_the_programs_they_list() {
+    let mut fixture = Fixture::new();
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    let recipient = Pubkey::new_unique();
+    let other_program = Pubkey::new_unique();
+    let allow = |program: &Pubkey| client::add_allowed_program(&manager, &authority, program);
+    let disallow = |program: &Pubkey| client::remove_allowed_program(&manager, &authority, program);
+    let forged = client::add_allowed_program(&manager, &recipient, &other_program);
+    assert_eq!(fixture.ctx.process(&[forged], &[recipient]), Err(ProgramError::MissingRequiredSignature));
+
+    // Listing one program shuts out every other
+    fixture.ctx.process(&[allow(&system_program::id()), allow(&system_program::id())], &[authority]).unwrap();
+    assert_eq!(fixture.ctx.manager(&manager).allowed_programs, vec![system_program::id()]);
+    let bundle = fixture.create_bundle(&[(0, 2)]);
+    let foreign = Instruction { program_id: other_program, accounts: Vec::new(), data: Vec::new() };
+    assert_eq!(fixture.add_instruction(&bundle, 0, &foreign), Err(bundle_error(BundleError::ProgramNotAllowed)));
+    let transfer = fixture.transfer_from_authority(&recipient, SOL);
+    let record = fixture.add_instruction(&bundle, 0, &transfer).unwrap();
+
+    // Records are checked again when they run
+    fixture.ctx.process(&[allow(&other_program), disallow(&system_program::id())], &[authority]).unwrap();
+    let accounts = [AccountMeta::new(recipient, false)];
+    let records = [record, fixture.add_instruction(&bundle, 0, &foreign).unwrap()];
+    assert_eq!(fixture.execute(&bundle, &records, &accounts), Err(bundle_error(BundleError::ProgramNotAllowed)));
+
+    // An empty list allows every program again
+    fixture.ctx.process(&[disallow(&other_program)], &[authority]).unwrap();
+    assert_eq!(
+        fixture.ctx.process(&[disallow(&other_program)], &[authority]),
+        Err(bundle_error(BundleError::AllowedProgramNotFound)),
+    );
+    assert!(fixture.ctx.manager(&manager).allows_program(&other_program));
+
+    let full: Vec<Instruction> = (0..MAX_ALLOWED_PROGRAMS).map(|_| allow(&Pubkey::new_unique())).collect();
+    fixture.ctx.process(&full, &[authority]).unwrap();
+    assert_eq!(
+        fixture.ctx.process(&[allow(&other_program)], &[authority]),
+        Err(bundle_error(BundleError::TooManyAllowedPrograms)),
+    );
+}
+
+#[test]
+fn allowlisted_managers_refuse_failure_hooks_they_do_not_list() {
+    let mut fixture = Fixture::new();
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    let hook = Pubkey::new_unique();
+    let allow = client::add_allowed_program(&manager, &authority, &system_program::id());
+    fixture.ctx.process(&[allow], &[authority]).unwrap();
+
+    let create = |fixture: &mut Fixture| {
+        let on_failure = Some((hook, vec![1]));
+        let create = client::create_bundle(&manager, fixture.next_nonce(), &authority, &[(0, 1)], 0, on_failure, None);
+        fixture.ctx.process(&[create], &[authority])
+    };
+    assert_eq!(create(&mut fixture), Err(bundle_error(BundleError::ProgramNotAllowed)));
+
+    let allow = client::add_allowed_program(&manager, &authority, &hook);
+    fixture.ctx.process(&[allow], &[authority]).unwrap();
+    create(&mut fixture).unwrap();
+}
+
+#[test]
+fn update_manager_config_replaces_the_config_within_its_ceilings() {
+    let mut fixture = Fixture::with_config(ManagerConfig { max_wallets_per_bundle: 5, ..Default::default() });
+    let config = ManagerConfig { priority_fee_multiplier: 20, max_compute_unit_price: 50_000, ..Default::default() };
+    let update = client::update_manager_config(&fixture.manager, &fixture.authority, config.clone());
+    let stranger = fixture.ctx.funded_key(SOL);
+    let forged = client::update_manager_config(&fixture.manager, &stranger, config.clone());
+    assert_eq!(fixture.ctx.process(&[forged], &[stranger]), Err(ProgramError::MissingRequiredSignature));
+    fixture.ctx.process(&[update], &[fixture.authority]).unwrap();
+
+    let after = fixture.ctx.manager(&fixture.manager).config;
+    assert_eq!((after.priority_fee_multiplier, after.max_compute_unit_price), (20, 50_000));
+    assert_eq!(after.max_wallets_per_bundle, DEFAULT_MAX_WALLETS_PER_BUNDLE);
+    assert_eq!(after.max_instruction_data_len, DEFAULT_MAX_INSTRUCTION_DATA_LEN);
+    assert_eq!(compute_unit_price(&after, 10_000), Ok(20_000));
+    assert_eq!(compute_unit_price(&after, 100_000), Ok(50_000));
+
+    let too_many = ManagerConfig { max_wallets_per_bundle: MAX_WALLETS_PER_BUNDLE + 1, ..config };
+    let update = client::update_manager_config(&fixture.manager, &fixture.authority, too_many);
+    assert_eq!(
+        fixture.ctx.process(&[update], &[fixture.authority]),
+        Err(bundle_error(BundleError::TooManyWallets)),
+    );
+}
+
+#[test]
+fn version_4_bundles_migrate_without_a_compute_unit_limit() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let before = fixture.ctx.bundle(&bundle);
+    set_data(&mut fixture.ctx, bundle, version_4_bundle(&before));
+    assert_eq!(
+        fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]),
+        Err(bundle_error(BundleError::UnsupportedAccountVersion)),
+    );
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[bundle]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    let migrated = fixture.ctx.bundle(&bundle);
+    assert_eq!((migrated.version, migrated.compute_unit_limit), (Bundle::VERSION, 0));
+    assert_eq!(migrated.seeds, before.seeds);
+
+    fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+    assert_eq!(fixture.ctx.bundle(&bundle).compute_unit_limit, 200_000);
+}
+
+#[test]
+fn version_5_bundles_migrate_unscheduled() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let before = fixture.ctx.bundle(&bundle);
+    set_data(&mut fixture.ctx, bundle, version_5_bundle(&before));
+    assert_eq!(
+        fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]),
+        Err(bundle_error(BundleError::UnsupportedAccountVersion)),
+    );
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[bundle]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    let migrated = fixture.ctx.bundle(&bundle);
+    assert_eq!((migrated.version, migrated.execute_after), (Bundle::VERSION, 0));
+    fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+}
+
+#[test]
+fn version_6_bundles_migrate_with_unlimited_retries() {
+    let mut fixture = Fixture::new();
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let before = fixture.ctx.bundle(&bundle);
+    set_data(&mut fixture.ctx, bundle, version_6_bundle(&before));
+    assert_eq!(
+        fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]),
+        Err(bundle_error(BundleError::UnsupportedAccountVersion)),
+    );
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[bundle]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    let migrated = fixture.ctx.bundle(&bundle);
+    assert_eq!((migrated.version, migrated.max_retries, migrated.retry_count), (Bundle::VERSION, None, 0));
+    assert_eq!(migrated.execute_after, before.execute_after);
+    fixture.execute(&bundle, &[record], &[AccountMeta::new(recipient, false)]).unwrap();
+}
+
+#[test]
+fn version_8_bundles_migrate_without_spend_limits() {
+    let mut fixture = Fixture::with_config(ManagerConfig { expiry_seconds: 60, ..Default::default() });
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let before = fixture.ctx.bundle(&bundle);
+    set_data(&mut fixture.ctx, bundle, version_8_bundle(&before));
+
+    // A stored expiry is kept as is, whatever the config says by now
+    let update = client::update_manager_config(&fixture.manager, &fixture.authority, ManagerConfig::default());
+    fixture.ctx.process(&[update], &[fixture.authority]).unwrap();
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[bundle]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    let migrated = fixture.ctx.bundle(&bundle);
+    assert_eq!((migrated.version, migrated.expires_at), (Bundle::VERSION, before.created_at + 60));
+    assert_eq!(migrated.spend_limits, None);
+}
+
+#[test]
+fn unversioned_registries_vaults_logs_and_records_migrate_in_place() {
+    let mut fixture = Fixture::new();
+    let (manager, authority) = (fixture.manager, fixture.authority);
+    let setup = [
+        client::register_wallet(&manager, &authority, 0, None),
+        client::deposit(&manager, &authority, SOL),
+        client::initialize_execution_log(&manager, &authority, 4),
+    ];
+    fixture.ctx.process(&setup, &[authority]).unwrap();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let transfer = fixture.transfer_from_authority(&Pubkey::new_unique(), 1);
+    let record = fixture.add_instruction(&bundle, 0, &transfer).unwrap();
+
+    let vault = find_vault_address(&bundle_manager::id(), &manager).0;
+    let state = [
+        find_wallet_registry_address(&bundle_manager::id(), &manager).0,
+        vault,
+        find_execution_log_address(&bundle_manager::id(), &manager).0,
+        record,
+    ];
+    let current: Vec<Vec<u8>> = state.iter().map(|key| fixture.ctx.data(key).to_vec()).collect();
+    let rent = Rent::default();
+    for (key, data) in state.iter().zip(&current) {
+        let mut unversioned = data.clone();
+        unversioned.remove(1);
+        // Rent exempt at the shorter layout, as accounts written before versioning are; the vault keeps its deposit
+        let mut account = fixture.ctx.account(key).unwrap().clone();
+        if *key != vault {
+            account.lamports = rent.minimum_balance(unversioned.len());
+        }
+        account.data = unversioned;
+        fixture.ctx.set_account(*key, account);
+    }
+    let shortfall: u64 = state.iter().zip(&current)
+        .map(|(key, data)| rent.minimum_balance(data.len()).saturating_sub(fixture.ctx.lamports(key)))
+        .sum();
+    let (vault_lamports, authority_lamports) = (fixture.ctx.lamports(&vault), fixture.ctx.lamports(&authority));
+
+    let alone = client::migrate_state(&manager, &authority, &[record], &[]);
+    assert_eq!(
+        fixture.ctx.process(&[alone], &[authority]),
+        Err(bundle_error(BundleError::InstructionBundleMismatch)),
+    );
+
+    let migrate = client::migrate_state(&manager, &authority, &state, &[bundle]);
+    fixture.ctx.process(slice::from_ref(&migrate), &[authority]).unwrap();
+    for (key, data) in state.iter().zip(&current) {
+        assert_eq!(fixture.ctx.data(key), &data[..]);
+    }
+    // The authority tops up whatever each account's extra byte leaves short of rent, once; the vault's
+    // deposit already covers its own
+    assert!(shortfall > 0);
+    assert_eq!(fixture.ctx.lamports(&authority), authority_lamports - shortfall);
+    assert_eq!(fixture.ctx.lamports(&vault), vault_lamports);
+
+    // Everything is current now, so repeating the call changes nothing
+    fixture.ctx.process(&[migrate], &[authority]).unwrap();
+    assert_eq!(fixture.ctx.lamports(&authority), authority_lamports - shortfall);
+    fixture.ctx.process(&[client::deposit(&manager, &authority, SOL)], &[authority]).unwrap();
+}
+
+#[test]
+fn version_7_bundles_migrate_expiring_as_their_manager_had_them() {
+    let mut fixture = Fixture::with_config(ManagerConfig { expiry_seconds: 60, ..Default::default() });
+    let recipient = Pubkey::new_unique();
+    let bundle = fixture.create_bundle(&[(0, 1)]);
+    let record = fixture.add_instruction(&bundle, 0, &fixture.transfer_from_authority(&recipient, SOL)).unwrap();
+    let before = fixture.ctx.bundle(&bundle);
+    set_data(&mut fixture.ctx, bundle, version_7_bundle(&before));
+
+    let migrate = client::migrate_manager(&fixture.manager, &fixture.authority, &[bundle]);
+    fixture.ctx.process(&[migrate], &[fixture.authority]).unwrap();
+    let migrated = fixture.ctx.bundle(&bundle);
+    assert_eq!((migrated.version, migrated.expires_at), (Bundle::VERSION, before.created_at + 60));
+    assert_eq!(migrated.max_retries, before.max_retries);
+
+    // Later config changes leave the stored expiry alone
+    let update = client::update_manager_config(&fixture.manager, &fixture.authority, ManagerConfig::default());
+    fixture.ctx.process(&[update], &[fixture.authority]).unwrap();
+    fixture.ctx.advance_time(61);
+    let authority = fixture.authority;
+    let collect = client::garbage_collect(&fixture.manager, &bundle, &authority, &authority, &[record], None);
+    fixture.ctx.process(&[collect], &[authority]).unwrap();
+    assert!(fixture.ctx.account(&bundle).is_none());
+}
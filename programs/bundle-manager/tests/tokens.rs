@@ -0,0 +1,296 @@
+//! Token transfers between a manager's wallets, run at once with
+//! TransferTokensBundled or stored in a bundle by the client builders, over
+//! mock token and associated token account programs.
+
+mod common;
+
+use bundle_manager::{
+    client, find_associated_token_address, find_instruction_record_address, find_vault_address, find_wallet_address,
+    BundleError, BundleStatus, SpendLimits, TokenSpendCap, TokenTransfer, VaultAsset, ASSOCIATED_TOKEN_PROGRAM_ID,
+    SPL_TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID,
+};
+use common::{bundle_error, Account, Fixture, SOL};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, instruction::Instruction, program_error::ProgramError,
+    pubkey::Pubkey, rent::Rent,
+};
+
+const TOKEN_ACCOUNT_LEN: usize = 165;
+const DECIMALS: u8 = 6;
+
+// TransferChecked over token accounts laid out as mint, owner, amount
+fn mock_token_program(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let ([12, amount @ .., decimals], [source, mint, destination, owner]) = (data, accounts) else {
+        return Err(ProgramError::InvalidInstructionData);
+    };
+    let amount = u64::from_le_bytes(amount.try_into().map_err(|_| ProgramError::InvalidInstructionData)?);
+    if !owner.is_signer || source.data.borrow()[32..64] != owner.key.to_bytes() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if mint.data.borrow()[44] != *decimals {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let balance = |account: &AccountInfo| u64::from_le_bytes(account.data.borrow()[64..72].try_into().unwrap());
+    let source_balance = balance(source).checked_sub(amount).ok_or(ProgramError::InsufficientFunds)?;
+    let destination_balance = balance(destination) + amount;
+    source.data.borrow_mut()[64..72].copy_from_slice(&source_balance.to_le_bytes());
+    destination.data.borrow_mut()[64..72].copy_from_slice(&destination_balance.to_le_bytes());
+    Ok(())
+}
+
+// CreateIdempotent, funded by the payer
+fn mock_ata_program(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let ([1], [payer, token_account, wallet, mint, _system_program, token_program]) = (data, accounts) else {
+        return Err(ProgramError::InvalidInstructionData);
+    };
+    if token_account.lamports() > 0 {
+        return Ok(());
+    }
+    let lamports = Rent::default().minimum_balance(TOKEN_ACCOUNT_LEN);
+    **payer.try_borrow_mut_lamports()? -= lamports;
+    **token_account.try_borrow_mut_lamports()? += lamports;
+    token_account.realloc(TOKEN_ACCOUNT_LEN, true)?;
+    token_account.assign(token_program.key);
+    let mut token_data = token_account.data.borrow_mut();
+    token_data[..32].copy_from_slice(mint.key.as_ref());
+    token_data[32..64].copy_from_slice(wallet.key.as_ref());
+    Ok(())
+}
+
+struct Tokens {
+    fixture: Fixture,
+    mint: Pubkey,
+}
+
+impl Tokens {
+    /// A manager whose wallet 0 holds 1 000 tokens of a fresh mint
+    fn new() -> Self {
+        let mut fixture = Fixture::new();
+        fixture.ctx.add_program(SPL_TOKEN_PROGRAM_ID, mock_token_program);
+        fixture.ctx.add_program(ASSOCIATED_TOKEN_PROGRAM_ID, mock_ata_program);
+        let mint = Pubkey::new_unique();
+        let mut mint_data = vec![0; 82];
+        mint_data[44] = DECIMALS;
+        fixture.ctx.set_account(mint, Account {
+            lamports: SOL,
+            data: mint_data,
+            owner: SPL_TOKEN_PROGRAM_ID,
+            executable: false,
+        });
+
+        let mut tokens = Self { fixture, mint };
+        let wallet = tokens.wallet(0);
+        let mut data = vec![0; TOKEN_ACCOUNT_LEN];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(wallet.as_ref());
+        data[64..72].copy_from_slice(&1_000u64.to_le_bytes());
+        let token_account = tokens.token_account(0);
+        tokens.fixture.ctx.set_account(token_account, Account {
+            lamports: SOL,
+            data,
+            owner: SPL_TOKEN_PROGRAM_ID,
+            executable: false,
+        });
+        tokens
+    }
+
+    fn wallet(&self, wallet_index: u8) -> Pubkey {
+        find_wallet_address(&bundle_manager::id(), &self.fixture.manager, wallet_index).0
+    }
+
+    fn token_account(&self, wallet_index: u8) -> Pubkey {
+        find_associated_token_address(&self.wallet(wallet_index), &self.mint, &SPL_TOKEN_PROGRAM_ID)
+    }
+
+    /// TransferTokensBundled of the mint signed by `authority`
+    fn transfer(&self, authority: &Pubkey, token_program: &Pubkey, transfers: &[TokenTransfer]) -> Instruction {
+        let manager = &self.fixture.manager;
+        client::transfer_tokens_bundled(manager, authority, &self.mint, token_program, DECIMALS, transfers, None)
+    }
+
+    fn balance(&self, wallet_index: u8) -> Option<u64> {
+        let account = self.fixture.ctx.account(&self.token_account(wallet_index))?;
+        Some(u64::from_le_bytes(account.data[64..72].try_into().unwrap()))
+    }
+}
+
+const TRANSFERS: [TokenTransfer; 2] = [
+    TokenTransfer { source_wallet: 0, destination_wallet: 1, amount: 300 },
+    TokenTransfer { source_wallet: 0, destination_wallet: 2, amount: 200 },
+];
+
+#[test]
+fn transfer_tokens_bundled_moves_tokens_between_wallets() {
+    let mut tokens = Tokens::new();
+    let authority = tokens.fixture.authority;
+    assert_eq!(tokens.balance(1), None);
+
+    let stranger = tokens.fixture.ctx.funded_key(SOL);
+    let forged = tokens.transfer(&stranger, &SPL_TOKEN_PROGRAM_ID, &TRANSFERS);
+    assert_eq!(tokens.fixture.ctx.process(&[forged], &[stranger]), Err(ProgramError::MissingRequiredSignature));
+    let token_2022 = tokens.transfer(&authority, &TOKEN_2022_PROGRAM_ID, &TRANSFERS);
+    assert_eq!(
+        tokens.fixture.ctx.process(&[token_2022], &[authority]),
+        Err(bundle_error(BundleError::InvalidTokenProgram)),
+    );
+
+    let transfer = tokens.transfer(&authority, &SPL_TOKEN_PROGRAM_ID, &TRANSFERS);
+    tokens.fixture.ctx.process(&[transfer], &[authority]).unwrap();
+    assert_eq!([tokens.balance(0), tokens.balance(1), tokens.balance(2)], [Some(500), Some(300), Some(200)]);
+
+    // Existing token accounts are reused, and the source cannot overdraw
+    let again = tokens.transfer(&authority, &SPL_TOKEN_PROGRAM_ID, &TRANSFERS[..1]);
+    tokens.fixture.ctx.process(&[again], &[authority]).unwrap();
+    assert_eq!(tokens.balance(1), Some(600));
+    let too_much = TokenTransfer { amount: 201, ..TRANSFERS[1] };
+    let overdraw = tokens.transfer(&authority, &SPL_TOKEN_PROGRAM_ID, &[too_much]);
+    assert_eq!(tokens.fixture.ctx.process(&[overdraw], &[authority]), Err(ProgramError::InsufficientFunds));
+}
+
+#[test]
+fn token_transfer_items_run_inside_a_bundle() {
+    let mut tokens = Tokens::new();
+    let manager = tokens.fixture.manager;
+    let authority = tokens.fixture.authority;
+    let bundle = tokens.fixture.create_bundle(&[(0, 2)]);
+
+    let items = client::token_transfer_items(&manager, &tokens.mint, &SPL_TOKEN_PROGRAM_ID, DECIMALS, &TRANSFERS, None);
+    let records: Vec<Pubkey> = (0..items.len() as u16)
+        .map(|index| find_instruction_record_address(&bundle_manager::id(), &bundle, 0, index).0)
+        .collect();
+    let items = records.iter().copied().zip(items).collect();
+    let add = client::batch_add_instruction(&manager, &bundle, &authority, items);
+    tokens.fixture.ctx.process(&[add], &[authority]).unwrap();
+
+    let accounts = client::token_transfer_accounts(&manager, &tokens.mint, &SPL_TOKEN_PROGRAM_ID, &TRANSFERS, None);
+    tokens.fixture.execute(&bundle, &records, &accounts).unwrap();
+    assert_eq!(tokens.fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+    assert_eq!([tokens.balance(0), tokens.balance(1), tokens.balance(2)], [Some(500), Some(300), Some(200)]);
+    // The program signs for the PDA source, so no wallet signs the transaction
+    assert!(accounts.iter().all(|meta| !meta.is_signer));
+}
+
+#[test]
+fn token_accounts_created_by_a_record_are_usable_later_in_the_bundle() {
+    let mut tokens = Tokens::new();
+    let manager = tokens.fixture.manager;
+    let authority = tokens.fixture.authority;
+    let bundle = tokens.fixture.create_bundle(&[(0, 1), (1, 1)]);
+    assert_eq!(tokens.balance(1), None);
+
+    // Wallet 1 passes on tokens from the account wallet 0's record creates for it
+    let transfers = [
+        TokenTransfer { source_wallet: 0, destination_wallet: 1, amount: 300 },
+        TokenTransfer { source_wallet: 1, destination_wallet: 2, amount: 100 },
+    ];
+    let items = client::token_transfer_items(&manager, &tokens.mint, &SPL_TOKEN_PROGRAM_ID, DECIMALS, &transfers, None);
+    let records: Vec<Pubkey> = items.iter()
+        .enumerate()
+        .map(|(index, item)| {
+            find_instruction_record_address(&bundle_manager::id(), &bundle, item.wallet_index, index as u16).0
+        })
+        .collect();
+    let items = records.iter().copied().zip(items).collect();
+    let add = client::batch_add_instruction(&manager, &bundle, &authority, items);
+    tokens.fixture.ctx.process(&[add], &[authority]).unwrap();
+
+    let accounts = client::token_transfer_accounts(&manager, &tokens.mint, &SPL_TOKEN_PROGRAM_ID, &transfers, None);
+    let authority_before = tokens.fixture.ctx.lamports(&authority);
+    tokens.fixture.execute(&bundle, &records, &accounts).unwrap();
+    assert_eq!(tokens.fixture.ctx.bundle(&bundle).status, BundleStatus::Executed);
+    assert_eq!([tokens.balance(0), tokens.balance(1), tokens.balance(2)], [Some(700), Some(200), Some(100)]);
+    let token_account_rent = Rent::default().minimum_balance(TOKEN_ACCOUNT_LEN);
+    assert_eq!(tokens.fixture.ctx.lamports(&authority), authority_before - 2 * token_account_rent);
+}
+
+#[test]
+fn token_spend_caps_limit_what_a_bundle_moves_out_of_its_wallets() {
+    let mut tokens = Tokens::new();
+    let manager = tokens.fixture.manager;
+    let authority = tokens.fixture.authority;
+    let accounts = client::token_transfer_accounts(&manager, &tokens.mint, &SPL_TOKEN_PROGRAM_ID, &TRANSFERS, None);
+
+    // Wallets 1 and 2 are not the bundle's, so all 500 tokens leave it
+    let run = |tokens: &mut Tokens, max_amount: u64| {
+        let bundle = tokens.fixture.next_bundle();
+        let nonce = tokens.fixture.next_nonce();
+        let cap = TokenSpendCap { mint: tokens.mint, max_amount };
+        let limits = SpendLimits { max_lamports_out: None, token_caps: vec![cap] };
+        let create = client::create_bundle(&manager, nonce, &authority, &[(0, 2)], 0, None, None);
+        tokens.fixture.ctx.process(&[client::with_spend_limits(create, limits)], &[authority]).unwrap();
+
+        let mint = &tokens.mint;
+        let items = client::token_transfer_items(&manager, mint, &SPL_TOKEN_PROGRAM_ID, DECIMALS, &TRANSFERS, None);
+        let records: Vec<Pubkey> = (0..items.len() as u16)
+            .map(|index| find_instruction_record_address(&bundle_manager::id(), &bundle, 0, index).0)
+            .collect();
+        let items = records.iter().copied().zip(items).collect();
+        let add = client::batch_add_instruction(&manager, &bundle, &authority, items);
+        tokens.fixture.ctx.process(&[add], &[authority]).unwrap();
+        tokens.fixture.execute(&bundle, &records, &accounts)
+    };
+
+    assert_eq!(run(&mut tokens, 499), Err(bundle_error(BundleError::SpendLimitExceeded)));
+    assert_eq!(tokens.balance(0), Some(1_000));
+    run(&mut tokens, 500).unwrap();
+    assert_eq!([tokens.balance(0), tokens.balance(1), tokens.balance(2)], [Some(500), Some(300), Some(200)]);
+}
+
+#[test]
+fn vault_tokens_are_distributed_to_wallets() {
+    let mut tokens = Tokens::new();
+    let manager = tokens.fixture.manager;
+    let authority = tokens.fixture.authority;
+    let deposit = client::deposit(&manager, &authority, SOL);
+    tokens.fixture.ctx.process(&[deposit], &[authority]).unwrap();
+
+    // Tokens reach the vault by a plain transfer to its token account
+    let vault = find_vault_address(&bundle_manager::id(), &manager).0;
+    let mut data = vec![0; TOKEN_ACCOUNT_LEN];
+    data[..32].copy_from_slice(tokens.mint.as_ref());
+    data[32..64].copy_from_slice(vault.as_ref());
+    data[64..72].copy_from_slice(&900u64.to_le_bytes());
+    let vault_token = find_associated_token_address(&vault, &tokens.mint, &SPL_TOKEN_PROGRAM_ID);
+    tokens.fixture.ctx.set_account(vault_token, Account {
+        lamports: SOL,
+        data,
+        owner: SPL_TOKEN_PROGRAM_ID,
+        executable: false,
+    });
+
+    let asset = VaultAsset::Token { mint: tokens.mint, token_program: SPL_TOKEN_PROGRAM_ID, decimals: DECIMALS };
+    let distribute = client::distribute_equally(&manager, &authority, &[0, 1, 2], 900, asset, None);
+    tokens.fixture.ctx.process(&[distribute], &[authority]).unwrap();
+    assert_eq!([tokens.balance(0), tokens.balance(1), tokens.balance(2)], [Some(1_300), Some(300), Some(300)]);
+
+    let withdraw = client::withdraw_to_wallet(&manager, &authority, 1, 1, asset, None);
+    assert_eq!(tokens.fixture.ctx.process(&[withdraw], &[authority]), Err(ProgramError::InsufficientFunds));
+}
+
+#[test]
+fn sweep_wallets_drains_tokens_and_sol_into_the_treasury() {
+    let mut tokens = Tokens::new();
+    let manager = tokens.fixture.manager;
+    let authority = tokens.fixture.authority;
+    let treasury = Pubkey::new_unique();
+    let mints = [(tokens.mint, SPL_TOKEN_PROGRAM_ID)];
+    for wallet_index in 0..2 {
+        let wallet = tokens.wallet(wallet_index);
+        tokens.fixture.ctx.fund(&wallet, 2 * SOL);
+    }
+
+    let stranger = tokens.fixture.ctx.funded_key(SOL);
+    let forged = client::sweep_wallets(&manager, &stranger, &treasury, &[0, 1], &mints, None);
+    assert_eq!(tokens.fixture.ctx.process(&[forged], &[stranger]), Err(ProgramError::MissingRequiredSignature));
+
+    // Wallet 1 has no token account, so only its SOL moves
+    let sweep = client::sweep_wallets(&manager, &authority, &treasury, &[0, 1], &mints, None);
+    tokens.fixture.ctx.process(&[sweep], &[authority]).unwrap();
+    let treasury_token = find_associated_token_address(&treasury, &tokens.mint, &SPL_TOKEN_PROGRAM_ID);
+    let treasury_balance = tokens.fixture.ctx.account(&treasury_token).map(|account| account.data[64..72].to_vec());
+    assert_eq!(treasury_balance, Some(1_000u64.to_le_bytes().to_vec()));
+    assert_eq!([tokens.balance(0), tokens.balance(1)], [Some(0), None]);
+    let wallet_rent = Rent::default().minimum_balance(0);
+    assert_eq!(tokens.fixture.ctx.lamports(&tokens.wallet(0)), wallet_rent);
+    assert_eq!(tokens.fixture.ctx.lamports(&treasury), 2 * (2 * SOL - wallet_rent));
+}
@@ -0,0 +1,81 @@
+//! The manager's vault: deposits from anyone, SOL paid out to the manager's
+//! wallets by its authority with WithdrawToWallet and DistributeEqually.
+
+mod common;
+
+use bundle_manager::{client, find_vault_address, find_wallet_address, BundleError, Vault, VaultAsset};
+use common::{bundle_error, Fixture, SOL};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey, rent::Rent};
+
+fn wallet(fixture: &Fixture, wallet_index: u8) -> Pubkey {
+    find_wallet_address(&bundle_manager::id(), &fixture.manager, wallet_index).0
+}
+
+fn read_vault(fixture: &Fixture) -> (Pubkey, Vault) {
+    let vault = find_vault_address(&bundle_manager::id(), &fixture.manager).0;
+    (vault, Vault::unpack(fixture.ctx.data(&vault)).unwrap())
+}
+
+#[test]
+fn deposits_create_the_vault_and_withdrawals_pay_wallets() {
+    let mut fixture = Fixture::new();
+    let manager = fixture.manager;
+    let authority = fixture.authority;
+    let depositor = fixture.ctx.funded_key(10 * SOL);
+
+    let deposit = client::deposit(&manager, &depositor, 3 * SOL);
+    fixture.ctx.process(&[deposit], &[depositor]).unwrap();
+    let again = client::deposit(&manager, &depositor, 2 * SOL);
+    fixture.ctx.process(&[again], &[depositor]).unwrap();
+    let (vault_key, vault) = read_vault(&fixture);
+    let rent = Rent::default().minimum_balance(Vault::LEN);
+    assert_eq!(vault.manager, manager);
+    assert_eq!(vault.total_deposited, 5 * SOL);
+    assert_eq!(fixture.ctx.lamports(&vault_key), rent + 5 * SOL);
+
+    // Only the manager authority pays out
+    let forged = client::withdraw_to_wallet(&manager, &depositor, 0, SOL, VaultAsset::Sol, None);
+    assert_eq!(fixture.ctx.process(&[forged], &[depositor]), Err(ProgramError::MissingRequiredSignature));
+
+    let withdraw = client::withdraw_to_wallet(&manager, &authority, 0, 2 * SOL, VaultAsset::Sol, None);
+    fixture.ctx.process(&[withdraw], &[authority]).unwrap();
+    assert_eq!(fixture.ctx.lamports(&wallet(&fixture, 0)), 2 * SOL);
+    assert_eq!(read_vault(&fixture).1.total_withdrawn, 2 * SOL);
+
+    // The vault's rent-exempt reserve is never paid out
+    let overdraw = client::withdraw_to_wallet(&manager, &authority, 1, 3 * SOL + 1, VaultAsset::Sol, None);
+    assert_eq!(
+        fixture.ctx.process(&[overdraw], &[authority]),
+        Err(bundle_error(BundleError::InsufficientVaultFunds)),
+    );
+
+    let pause = client::set_manager_status(&manager, &authority, true);
+    fixture.ctx.process(&[pause], &[authority]).unwrap();
+    let paused = client::withdraw_to_wallet(&manager, &authority, 1, SOL, VaultAsset::Sol, None);
+    assert_eq!(fixture.ctx.process(&[paused], &[authority]), Err(bundle_error(BundleError::ManagerPaused)));
+    assert_eq!(fixture.ctx.lamports(&vault_key), rent + 3 * SOL);
+}
+
+#[test]
+fn distribute_equally_funds_wallets_in_one_instruction() {
+    let mut fixture = Fixture::new();
+    let manager = fixture.manager;
+    let authority = fixture.authority;
+    let deposit = client::deposit(&manager, &authority, 10 * SOL);
+    fixture.ctx.process(&[deposit], &[authority]).unwrap();
+
+    let fund = client::fund_wallets_from_vault(&manager, &authority, 4, 2 * SOL, None);
+    fixture.ctx.process(&[fund], &[authority]).unwrap();
+    for wallet_index in 0..4 {
+        assert_eq!(fixture.ctx.lamports(&wallet(&fixture, wallet_index)), 2 * SOL);
+    }
+
+    // Shares round down and the remainder stays in the vault
+    let split = client::distribute_equally(&manager, &authority, &[4, 5, 6], SOL + 1, VaultAsset::Sol, None);
+    fixture.ctx.process(&[split], &[authority]).unwrap();
+    assert_eq!(fixture.ctx.lamports(&wallet(&fixture, 6)), SOL / 3);
+    assert_eq!(read_vault(&fixture).1.total_withdrawn, 8 * SOL + SOL / 3 * 3);
+
+    let dust = client::distribute_equally(&manager, &authority, &[0, 1], 1, VaultAsset::Sol, None);
+    assert_eq!(fixture.ctx.process(&[dust], &[authority]), Err(ProgramError::InvalidInstructionData));
+}